@@ -0,0 +1,448 @@
+use crate::error::{Result, RsAlgoError, RsAlgoErrorKind};
+
+use futures_util::{SinkExt, StreamExt};
+use serde_json::{json, Value};
+use std::collections::{HashMap, VecDeque};
+use std::net::{SocketAddr, TcpListener as StdTcpListener};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::{broadcast, oneshot};
+use tokio_tungstenite::{accept_async, WebSocketStream};
+use tungstenite::Message;
+
+const STREAM_SUBSCRIPTIONS: [&str; 4] = ["getCandles", "getTickPrices", "getTrades", "getBalance"];
+
+#[derive(Debug, Clone)]
+pub enum MockResponse {
+    Reply(Value),
+    Error(String, String),
+    Delayed(Duration, Box<MockResponse>),
+    Silent,
+    Close,
+}
+
+impl MockResponse {
+    pub fn reply(return_data: Value) -> Self {
+        MockResponse::Reply(json!({ "status": true, "returnData": return_data }))
+    }
+
+    pub fn error(code: &str, description: &str) -> Self {
+        MockResponse::Error(code.to_owned(), description.to_owned())
+    }
+
+    pub fn delayed(delay: Duration, response: MockResponse) -> Self {
+        MockResponse::Delayed(delay, Box::new(response))
+    }
+}
+
+#[derive(Debug, Default)]
+struct MockScript {
+    responses: HashMap<String, VecDeque<MockResponse>>,
+    stream: VecDeque<MockResponse>,
+    requests: Vec<Value>,
+    stream_requests: Vec<Value>,
+    order_id: u64,
+}
+
+impl MockScript {
+    fn next_response(&mut self, command: &str, arguments: &Value) -> MockResponse {
+        match self
+            .responses
+            .get_mut(command)
+            .and_then(|responses| responses.pop_front())
+        {
+            Some(response) => response,
+            None => self.default_response(command, arguments),
+        }
+    }
+
+    fn default_response(&mut self, command: &str, arguments: &Value) -> MockResponse {
+        let symbol = arguments["symbol"]
+            .as_str()
+            .or_else(|| arguments["info"]["symbol"].as_str())
+            .unwrap_or("EURUSD");
+
+        match command {
+            "login" => MockResponse::Reply(json!({
+                "status": true,
+                "streamSessionId": "mock-stream-session"
+            })),
+            "logout" | "ping" => MockResponse::Reply(json!({ "status": true })),
+            "getChartLastRequest" | "getChartRangeRequest" => MockResponse::reply(json!({
+                "digits": 5,
+                "rateInfos": []
+            })),
+            "getAllSymbols" | "getTrades" | "getTradesHistory" => MockResponse::reply(json!([])),
+            "getSymbol" => MockResponse::reply(json!({
+                "symbol": symbol,
+                "ask": 1.10010,
+                "bid": 1.10000,
+                "high": 1.10500,
+                "low": 1.09500,
+                "spreadRaw": 0.0001,
+                "precision": 5,
+                "pipsPrecision": 4,
+                "tickSize": 0.00001,
+                "contractSize": 100000,
+                "lotStep": 0.01,
+                "lotMin": 0.01,
+                "lotMax": 100.0,
+                "leverage": 3.33,
+                "currency": "EUR",
+                "currencyProfit": "USD",
+                "swapEnable": true,
+                "swapLong": -1.0,
+                "swapShort": -1.0,
+                "swap_rollover3days": 3
+            })),
+            "getTradingHours" => {
+                let trading: Vec<Value> = (1..=5)
+                    .map(|day| json!({ "day": day, "fromT": 0, "toT": 86400000 }))
+                    .collect();
+
+                MockResponse::reply(json!([{
+                    "symbol": symbol,
+                    "quotes": trading.clone(),
+                    "trading": trading
+                }]))
+            }
+            "getMarginLevel" => MockResponse::reply(json!({
+                "balance": 10000.0,
+                "credit": 0.0,
+                "currency": "USD",
+                "equity": 10000.0,
+                "margin": 0.0,
+                "margin_free": 10000.0,
+                "margin_level": 0.0
+            })),
+            "tradeTransaction" => {
+                self.order_id += 1;
+                MockResponse::reply(json!({ "order": self.order_id }))
+            }
+            "tradeTransactionStatus" => MockResponse::reply(json!({
+                "order": arguments["order"].as_u64().unwrap_or(self.order_id),
+                "requestStatus": 3,
+                "ask": 1.10010,
+                "bid": 1.10000,
+                "customComment": "",
+                "message": null
+            })),
+            _ => MockResponse::error("EX000", &format!("Unknown command {}", command)),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct MockXtbServer {
+    address: SocketAddr,
+    stream_address: SocketAddr,
+    script: Arc<Mutex<MockScript>>,
+    stream_sender: broadcast::Sender<Value>,
+    shutdown: Option<oneshot::Sender<()>>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl MockXtbServer {
+    pub fn new() -> MockXtbServerBuilder {
+        MockXtbServerBuilder::new()
+    }
+
+    pub fn url(&self) -> String {
+        format!("ws://{}", self.address)
+    }
+
+    pub fn stream_url(&self) -> String {
+        format!("ws://{}", self.stream_address)
+    }
+
+    pub fn respond(&self, command: &str, response: MockResponse) {
+        self.script
+            .lock()
+            .unwrap()
+            .responses
+            .entry(command.to_owned())
+            .or_default()
+            .push_back(response);
+    }
+
+    pub fn push_stream(&self, message: Value) {
+        if self.stream_sender.send(message).is_err() {
+            log::warn!("No stream client connected to the mock server");
+        }
+    }
+
+    pub fn requests(&self) -> Vec<Value> {
+        self.script.lock().unwrap().requests.clone()
+    }
+
+    pub fn stream_requests(&self) -> Vec<Value> {
+        self.script.lock().unwrap().stream_requests.clone()
+    }
+
+    pub fn stop(&mut self) {
+        if let Some(shutdown) = self.shutdown.take() {
+            shutdown.send(()).ok();
+        }
+
+        if let Some(handle) = self.handle.take() {
+            handle.join().ok();
+        }
+    }
+}
+
+impl Drop for MockXtbServer {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct MockXtbServerBuilder {
+    responses: HashMap<String, VecDeque<MockResponse>>,
+    stream: VecDeque<MockResponse>,
+}
+
+impl MockXtbServerBuilder {
+    pub fn new() -> Self {
+        Self {
+            responses: HashMap::new(),
+            stream: VecDeque::new(),
+        }
+    }
+
+    pub fn respond(mut self, command: &str, response: MockResponse) -> Self {
+        self.responses
+            .entry(command.to_owned())
+            .or_default()
+            .push_back(response);
+        self
+    }
+
+    pub fn stream(mut self, response: MockResponse) -> Self {
+        self.stream.push_back(response);
+        self
+    }
+
+    pub fn start(self) -> Result<MockXtbServer> {
+        let listener = bind_listener()?;
+        let stream_listener = bind_listener()?;
+        let address = local_address(&listener)?;
+        let stream_address = local_address(&stream_listener)?;
+
+        let script = Arc::new(Mutex::new(MockScript {
+            responses: self.responses,
+            stream: self.stream,
+            ..MockScript::default()
+        }));
+
+        let (stream_sender, _) = broadcast::channel(256);
+        let (shutdown, shutdown_receiver) = oneshot::channel();
+
+        let server_script = script.clone();
+        let server_sender = stream_sender.clone();
+
+        let handle = thread::spawn(move || {
+            let runtime = tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+                .unwrap();
+
+            runtime.block_on(serve(
+                listener,
+                stream_listener,
+                server_script,
+                server_sender,
+                shutdown_receiver,
+            ));
+        });
+
+        log::info!("Mock XTB server listening on {}", address);
+
+        Ok(MockXtbServer {
+            address,
+            stream_address,
+            script,
+            stream_sender,
+            shutdown: Some(shutdown),
+            handle: Some(handle),
+        })
+    }
+}
+
+fn bind_listener() -> Result<StdTcpListener> {
+    let listener = StdTcpListener::bind("127.0.0.1:0").map_err(|err| {
+        log::error!("Can't bind mock server: {}", err);
+        RsAlgoError {
+            err: RsAlgoErrorKind::ConnectionError,
+        }
+    })?;
+
+    listener.set_nonblocking(true).map_err(|_| RsAlgoError {
+        err: RsAlgoErrorKind::ConnectionError,
+    })?;
+
+    Ok(listener)
+}
+
+fn local_address(listener: &StdTcpListener) -> Result<SocketAddr> {
+    listener.local_addr().map_err(|_| RsAlgoError {
+        err: RsAlgoErrorKind::ConnectionError,
+    })
+}
+
+async fn serve(
+    listener: StdTcpListener,
+    stream_listener: StdTcpListener,
+    script: Arc<Mutex<MockScript>>,
+    stream_sender: broadcast::Sender<Value>,
+    mut shutdown: oneshot::Receiver<()>,
+) {
+    let listener = TcpListener::from_std(listener).unwrap();
+    let stream_listener = TcpListener::from_std(stream_listener).unwrap();
+
+    loop {
+        tokio::select! {
+            _ = &mut shutdown => break,
+            Ok((tcp, _)) = listener.accept() => {
+                tokio::spawn(handle_commands(tcp, script.clone()));
+            }
+            Ok((tcp, _)) = stream_listener.accept() => {
+                tokio::spawn(handle_stream(tcp, script.clone(), stream_sender.subscribe()));
+            }
+        }
+    }
+}
+
+async fn handle_commands(tcp: TcpStream, script: Arc<Mutex<MockScript>>) {
+    let mut socket = match accept_async(tcp).await {
+        Ok(socket) => socket,
+        Err(err) => {
+            log::error!("Mock handshake failed: {}", err);
+            return;
+        }
+    };
+
+    while let Some(Ok(msg)) = socket.next().await {
+        let txt = match msg {
+            Message::Text(txt) => txt,
+            Message::Ping(data) => {
+                socket.send(Message::Pong(data)).await.ok();
+                continue;
+            }
+            Message::Close(_) => break,
+            _ => continue,
+        };
+
+        let (response, custom_tag) = match serde_json::from_str::<Value>(&txt) {
+            Ok(request) => {
+                let mut script = script.lock().unwrap();
+                let command = request["command"].as_str().unwrap_or_default().to_owned();
+                let response = script.next_response(&command, &request["arguments"]);
+                let custom_tag = request.get("customTag").cloned();
+                script.requests.push(request);
+                (response, custom_tag)
+            }
+            Err(_) => (MockResponse::error("BE001", "Invalid JSON"), None),
+        };
+
+        if !send_response(&mut socket, response, custom_tag).await {
+            break;
+        }
+    }
+}
+
+async fn handle_stream(
+    tcp: TcpStream,
+    script: Arc<Mutex<MockScript>>,
+    mut stream_receiver: broadcast::Receiver<Value>,
+) {
+    let mut socket = match accept_async(tcp).await {
+        Ok(socket) => socket,
+        Err(err) => {
+            log::error!("Mock stream handshake failed: {}", err);
+            return;
+        }
+    };
+
+    let mut subscribed = false;
+
+    loop {
+        tokio::select! {
+            msg = socket.next() => {
+                let txt = match msg {
+                    Some(Ok(Message::Text(txt))) => txt,
+                    Some(Ok(Message::Ping(data))) => {
+                        socket.send(Message::Pong(data)).await.ok();
+                        continue;
+                    }
+                    Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                    Some(Ok(_)) => continue,
+                };
+
+                let request: Value = serde_json::from_str(&txt).unwrap_or(Value::Null);
+                let is_subscription = STREAM_SUBSCRIPTIONS
+                    .iter()
+                    .any(|command| request["command"] == *command);
+
+                let scripted = {
+                    let mut script = script.lock().unwrap();
+                    script.stream_requests.push(request);
+
+                    match is_subscription && !subscribed {
+                        true => script.stream.drain(..).collect(),
+                        false => vec![],
+                    }
+                };
+
+                subscribed = subscribed || is_subscription;
+
+                for response in scripted {
+                    if !send_response(&mut socket, response, None).await {
+                        return;
+                    }
+                }
+            }
+            Ok(message) = stream_receiver.recv() => {
+                if socket.send(Message::text(message.to_string())).await.is_err() {
+                    break;
+                }
+            }
+        }
+    }
+}
+
+async fn send_response(
+    socket: &mut WebSocketStream<TcpStream>,
+    response: MockResponse,
+    custom_tag: Option<Value>,
+) -> bool {
+    let mut response = response;
+
+    while let MockResponse::Delayed(delay, inner) = response {
+        tokio::time::sleep(delay).await;
+        response = *inner;
+    }
+
+    let mut msg = match response {
+        MockResponse::Reply(value) => value,
+        MockResponse::Error(code, description) => json!({
+            "status": false,
+            "errorCode": code,
+            "errorDescr": description
+        }),
+        MockResponse::Silent => return true,
+        MockResponse::Close => {
+            socket.close(None).await.ok();
+            return false;
+        }
+        MockResponse::Delayed(_, _) => unreachable!(),
+    };
+
+    if let (Some(custom_tag), Value::Object(obj)) = (custom_tag, &mut msg) {
+        obj.insert("customTag".to_owned(), custom_tag);
+    }
+
+    socket.send(Message::text(msg.to_string())).await.is_ok()
+}
@@ -1,8 +1,20 @@
+#[cfg(feature = "mock")]
+pub mod mock;
 pub mod models;
+pub mod rate_limiter;
+pub mod reconcile;
+pub mod retry;
+pub mod sim;
+pub mod trading;
 pub mod xtb;
+pub mod xtb_error;
+pub mod xtb_socket;
 pub mod xtb_stream;
 
 pub use crate::ws::message::Message;
 pub use models::*;
+pub use reconcile::{BrokerPosition, Reconciliation};
+pub use sim::SimBroker;
+pub use trading::{BrokerEvent, TradingBroker};
 pub use xtb::Broker;
 pub use xtb_stream::BrokerStream;
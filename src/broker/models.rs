@@ -2,6 +2,7 @@ use crate::helpers::date::{DateTime, Local};
 use crate::models::time_frame::*;
 use crate::models::trade::TradeType;
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
 
 pub type DOHLC = (DateTime<Local>, f64, f64, f64, f64, f64);
 pub type VEC_DOHLC = Vec<DOHLC>;
@@ -73,6 +74,34 @@ impl TransactionCommand {
             TransactionCommand::BuyStop | TransactionCommand::SellStop
         )
     }
+
+    pub fn is_pending(&self) -> bool {
+        matches!(
+            self,
+            TransactionCommand::BuyLimit
+                | TransactionCommand::SellLimit
+                | TransactionCommand::BuyStop
+                | TransactionCommand::SellStop
+        )
+    }
+
+    pub fn is_long(&self) -> bool {
+        matches!(
+            self,
+            TransactionCommand::BuyMarket
+                | TransactionCommand::BuyLimit
+                | TransactionCommand::BuyStop
+        )
+    }
+
+    pub fn pending(is_long: bool, target_price: f64, ask: f64, bid: f64) -> TransactionCommand {
+        match is_long {
+            true if target_price < ask => TransactionCommand::BuyLimit,
+            true => TransactionCommand::BuyStop,
+            false if target_price > bid => TransactionCommand::SellLimit,
+            false => TransactionCommand::SellStop,
+        }
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -94,6 +123,17 @@ impl TransactionAction {
             TransactionAction::Delete => 4,
         }
     }
+
+    pub fn from_value(value: i64) -> Option<TransactionAction> {
+        match value {
+            0 => Some(TransactionAction::Open),
+            1 => Some(TransactionAction::Pending),
+            2 => Some(TransactionAction::Close),
+            3 => Some(TransactionAction::Modify),
+            4 => Some(TransactionAction::Delete),
+            _ => None,
+        }
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -207,6 +247,11 @@ pub struct CommandAllSymbols {
     pub command: String,
 }
 
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CommandMarginLevel {
+    pub command: String,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct CommandStreaming {
     pub command: String,
@@ -354,12 +399,19 @@ pub struct TransactionStatusnResponse {
     pub status: TransactionState,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TransactionDetails {
     pub id: usize,
     pub open_price: f64,
     pub close_price: f64,
     pub profit: f64,
+    // Broker signs, commissions come negative
+    #[serde(default)]
+    pub commission: f64,
+    #[serde(default)]
+    pub swap: f64,
+    #[serde(default)]
+    pub close_time: Option<i64>,
     // pub open_date: usize,
     // pub close_date: usize,
 }
@@ -375,3 +427,136 @@ pub struct TransactionComments {
     pub spread: f64,
     pub trade_type: TradeType,
 }
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StreamEnvelope {
+    pub command: String,
+    #[serde(default)]
+    pub data: Value,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StreamCandle {
+    pub ctm: i64,
+    pub open: f64,
+    pub high: f64,
+    pub low: f64,
+    pub close: f64,
+    pub vol: f64,
+    #[serde(default)]
+    pub symbol: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StreamTickPrices {
+    pub symbol: String,
+    pub ask: f64,
+    pub bid: f64,
+    pub high: f64,
+    pub low: f64,
+    #[serde(rename = "spreadRaw")]
+    pub spread_raw: f64,
+    pub timestamp: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StreamTrade {
+    pub symbol: String,
+    pub position: u64,
+    #[serde(default)]
+    pub order: u64,
+    #[serde(default)]
+    pub cmd: i64,
+    pub closed: bool,
+    pub volume: f64,
+    pub open_price: f64,
+    #[serde(default)]
+    pub close_price: Option<f64>,
+    pub open_time: i64,
+    #[serde(default)]
+    pub close_time: Option<i64>,
+    #[serde(default)]
+    pub expiration: Option<i64>,
+    #[serde(default)]
+    pub sl: Option<f64>,
+    #[serde(default)]
+    pub tp: Option<f64>,
+    #[serde(default)]
+    pub profit: Option<f64>,
+    #[serde(default)]
+    pub storage: Option<f64>,
+    #[serde(default)]
+    pub commission: Option<f64>,
+    #[serde(default)]
+    pub comment: Option<String>,
+    #[serde(rename = "customComment", default)]
+    pub custom_comment: Option<String>,
+    #[serde(default)]
+    pub state: Option<String>,
+    #[serde(rename = "type", default)]
+    pub trans_type: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StreamTradeStatus {
+    pub order: u64,
+    #[serde(rename = "requestStatus")]
+    pub request_status: u64,
+    #[serde(default)]
+    pub price: Option<f64>,
+    #[serde(default)]
+    pub message: Option<String>,
+    #[serde(rename = "customComment", default)]
+    pub custom_comment: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StreamBalance {
+    pub balance: f64,
+    pub equity: f64,
+    pub margin: f64,
+    #[serde(rename = "marginFree")]
+    pub margin_free: f64,
+    #[serde(rename = "marginLevel")]
+    pub margin_level: f64,
+    #[serde(default)]
+    pub credit: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StreamProfit {
+    pub order: u64,
+    pub position: u64,
+    pub profit: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StreamNews {
+    pub key: String,
+    pub time: i64,
+    pub title: String,
+    #[serde(default)]
+    pub body: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StreamKeepAlive {
+    pub timestamp: i64,
+}
+
+#[derive(Debug, Clone)]
+pub enum StreamEvent {
+    Candle(StreamCandle),
+    TickPrices(StreamTickPrices),
+    Trade(StreamTrade),
+    TradeStatus(StreamTradeStatus),
+    Balance(StreamBalance),
+    Profit(StreamProfit),
+    News(StreamNews),
+    KeepAlive(StreamKeepAlive),
+    Unknown(String),
+    Ping,
+    Pong,
+    Close,
+    Binary,
+}
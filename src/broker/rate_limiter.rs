@@ -0,0 +1,150 @@
+use std::time::{Duration, Instant};
+use tokio::time::sleep;
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RateLimiterMetrics {
+    pub queue_depth: usize,
+    pub max_queue_depth: usize,
+    pub sent: u64,
+    pub throttled: u64,
+    pub total_wait: Duration,
+    pub max_wait: Duration,
+}
+
+impl RateLimiterMetrics {
+    pub fn avg_wait(&self) -> Duration {
+        match self.sent {
+            0 => Duration::ZERO,
+            sent => self.total_wait / sent as u32,
+        }
+    }
+
+    pub fn enqueued(&mut self) {
+        self.queue_depth += 1;
+        self.max_queue_depth = self.max_queue_depth.max(self.queue_depth);
+    }
+
+    pub fn dequeued(&mut self, wait: Duration, throttled: bool) {
+        self.queue_depth = self.queue_depth.saturating_sub(1);
+        self.sent += 1;
+        self.total_wait += wait;
+        self.max_wait = self.max_wait.max(wait);
+
+        if throttled {
+            self.throttled += 1;
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RateLimiter {
+    interval: Duration,
+    burst: f64,
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl RateLimiter {
+    pub fn new(interval: Duration, burst: usize) -> Self {
+        let burst = burst.max(1) as f64;
+
+        Self {
+            interval,
+            burst,
+            tokens: burst,
+            last_refill: Instant::now(),
+        }
+    }
+
+    pub fn interval(&self) -> Duration {
+        self.interval
+    }
+
+    pub fn burst(&self) -> usize {
+        self.burst as usize
+    }
+
+    pub async fn acquire(&mut self) -> bool {
+        self.refill();
+
+        let throttled = self.tokens < 1.;
+
+        if throttled {
+            let missing = 1. - self.tokens;
+            sleep(self.interval.mul_f64(missing)).await;
+            self.refill();
+        }
+
+        self.tokens = (self.tokens - 1.).max(0.);
+        throttled
+    }
+
+    fn refill(&mut self) {
+        if self.interval.is_zero() {
+            self.tokens = self.burst;
+            return;
+        }
+
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.last_refill);
+        let refilled = elapsed.as_secs_f64() / self.interval.as_secs_f64();
+
+        self.tokens = (self.tokens + refilled).min(self.burst);
+        self.last_refill = now;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn throttles_past_the_burst() {
+        let interval = Duration::from_millis(40);
+        let mut limiter = RateLimiter::new(interval, 2);
+
+        let start = Instant::now();
+        assert!(!limiter.acquire().await);
+        assert!(!limiter.acquire().await);
+        assert!(start.elapsed() < interval);
+
+        // The third command waits for a token to refill
+        assert!(limiter.acquire().await);
+        assert!(start.elapsed() >= interval);
+
+        // Idle time refills up to the burst
+        sleep(interval * 3).await;
+        assert!(!limiter.acquire().await);
+        assert!(!limiter.acquire().await);
+        assert!(limiter.acquire().await);
+    }
+
+    #[tokio::test]
+    async fn never_throttles_without_interval() {
+        let mut limiter = RateLimiter::new(Duration::ZERO, 0);
+        assert_eq!(limiter.burst(), 1);
+
+        for _ in 0..10 {
+            assert!(!limiter.acquire().await);
+        }
+    }
+
+    #[test]
+    fn tracks_queue_metrics() {
+        let mut metrics = RateLimiterMetrics::default();
+        assert_eq!(metrics.avg_wait(), Duration::ZERO);
+
+        metrics.enqueued();
+        metrics.enqueued();
+        metrics.enqueued();
+        metrics.dequeued(Duration::from_millis(10), false);
+        metrics.dequeued(Duration::from_millis(30), true);
+
+        assert_eq!(metrics.queue_depth, 1);
+        assert_eq!(metrics.max_queue_depth, 3);
+        assert_eq!(metrics.sent, 2);
+        assert_eq!(metrics.throttled, 1);
+        assert_eq!(metrics.max_wait, Duration::from_millis(30));
+        assert_eq!(metrics.avg_wait(), Duration::from_millis(20));
+    }
+}
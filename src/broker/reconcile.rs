@@ -0,0 +1,487 @@
+use crate::broker::models::TransactionDetails;
+use crate::broker::trading::TradingBroker;
+use crate::error::Result;
+use crate::helpers::date::{from_dbtime, parse_time_milliseconds, to_dbtime, Local};
+use crate::helpers::uuid;
+use crate::models::bot::BotData;
+use crate::models::order::Order;
+use crate::models::symbol_spec::symbol_spec;
+use crate::models::tick::InstrumentTick;
+use crate::models::trade::{TradeIn, TradeOut, TradeStatus, TradeType};
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct BrokerPosition {
+    pub trade_in: TradeIn,
+    pub orders: Vec<Order>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Reconciliation {
+    pub orphaned: Vec<BrokerPosition>,
+    pub missing: Vec<TradeIn>,
+    pub size_mismatched: Vec<(TradeIn, BrokerPosition)>,
+    pub trades_in: Vec<TradeIn>,
+    pub trades_out: Vec<TradeOut>,
+    pub orders: Vec<Order>,
+}
+
+impl Reconciliation {
+    pub fn is_synced(&self) -> bool {
+        self.orphaned.is_empty() && self.missing.is_empty() && self.size_mismatched.is_empty()
+    }
+}
+
+pub fn open_trades<'a>(trades_in: &'a [TradeIn], trades_out: &[TradeOut]) -> Vec<&'a TradeIn> {
+    trades_in
+        .iter()
+        .filter(|trade_in| trade_in.status == TradeStatus::Fulfilled)
+        .filter(|trade_in| {
+            !trades_out.iter().any(|trade_out| {
+                trade_out.id == trade_in.id && trade_out.status == TradeStatus::Fulfilled
+            })
+        })
+        .collect()
+}
+
+// Fetches the broker positions and the trades closed since the oldest open
+// one, then reconciles the bot against them
+pub async fn sync_bot<B: TradingBroker + ?Sized>(
+    broker: &mut B,
+    bot: &BotData,
+    tick: &InstrumentTick,
+) -> Result<Reconciliation> {
+    let symbol = bot.symbol();
+    let strategy_name = bot.strategy_name();
+    let positions = broker.open_positions(symbol, strategy_name).await?;
+
+    let from = open_trades(bot.trades_in(), bot.trades_out())
+        .iter()
+        .map(|trade_in| from_dbtime(&trade_in.date_in).timestamp())
+        .min();
+
+    let history = match from {
+        Some(from) => broker.trade_history(symbol, strategy_name, from).await?,
+        None => vec![],
+    };
+
+    Ok(reconcile_bot(bot, &positions, &history, tick))
+}
+
+pub fn reconcile_bot(
+    bot: &BotData,
+    positions: &[BrokerPosition],
+    history: &[TransactionDetails],
+    tick: &InstrumentTick,
+) -> Reconciliation {
+    reconcile(
+        bot.symbol(),
+        bot.trades_in(),
+        bot.trades_out(),
+        bot.orders(),
+        positions,
+        history,
+        tick,
+    )
+}
+
+pub fn reconcile(
+    symbol: &str,
+    trades_in: &[TradeIn],
+    trades_out: &[TradeOut],
+    orders: &[Order],
+    positions: &[BrokerPosition],
+    history: &[TransactionDetails],
+    tick: &InstrumentTick,
+) -> Reconciliation {
+    let mut result = Reconciliation::default();
+    let open_trades = open_trades(trades_in, trades_out);
+
+    // Sizes only differ when they are at least one lot step apart
+    let lot_step = symbol_spec(symbol).map_or(0.01, |spec| spec.lot_step);
+
+    for trade_in in open_trades.iter() {
+        match positions
+            .iter()
+            .find(|position| position.trade_in.id == trade_in.id)
+        {
+            Some(position) if (position.trade_in.size - trade_in.size).abs() >= lot_step / 2. => {
+                log::warn!(
+                    "Position {} size mismatch. Local: {} Broker: {}",
+                    trade_in.id,
+                    trade_in.size,
+                    position.trade_in.size
+                );
+
+                let mut corrected = (*trade_in).clone();
+                corrected.size = position.trade_in.size;
+
+                result.trades_in.push(corrected);
+                result.orders.extend(
+                    pending_orders(orders, trade_in.id)
+                        .map(|order| resize_order(order, position.trade_in.size)),
+                );
+                result
+                    .size_mismatched
+                    .push(((*trade_in).clone(), position.clone()));
+            }
+            Some(_) => (),
+            None => {
+                log::warn!("Position {} not found in Broker", trade_in.id);
+
+                let details = history.iter().find(|details| details.id == trade_in.id);
+
+                result
+                    .trades_out
+                    .push(close_missing(trade_in, details, tick));
+                result
+                    .orders
+                    .extend(pending_orders(orders, trade_in.id).map(cancel_order));
+                result.missing.push((*trade_in).clone());
+            }
+        }
+    }
+
+    for position in positions.iter() {
+        let is_known = open_trades
+            .iter()
+            .any(|trade_in| trade_in.id == position.trade_in.id);
+
+        if !is_known {
+            log::warn!("Broker position {} not found in bot", position.trade_in.id);
+
+            result.trades_in.push(position.trade_in.clone());
+            result.orders.extend(position.orders.iter().cloned());
+            result.orphaned.push(position.clone());
+        }
+    }
+
+    result
+}
+
+fn pending_orders<'a>(orders: &'a [Order], trade_id: usize) -> impl Iterator<Item = &'a Order> {
+    orders
+        .iter()
+        .filter(move |order| order.trade_id == trade_id && order.is_pending())
+}
+
+fn resize_order(order: &Order, size: f64) -> Order {
+    let mut order = order.clone();
+    order.resize(size);
+    order.set_updated_at(to_dbtime(Local::now()));
+    order
+}
+
+fn cancel_order(order: &Order) -> Order {
+    let mut order = order.clone();
+    order.cancel_order(to_dbtime(Local::now()));
+    order
+}
+
+// Closed at the broker while the bot wasn't looking. The trade history has the
+// real close, the current tick is the fallback when it's not there yet
+fn close_missing(
+    trade_in: &TradeIn,
+    details: Option<&TransactionDetails>,
+    tick: &InstrumentTick,
+) -> TradeOut {
+    let is_long = trade_in.trade_type.is_long();
+
+    let trade_type = match is_long {
+        true => TradeType::MarketOutLong,
+        false => TradeType::MarketOutShort,
+    };
+
+    let (price_out, date_out, profit, commission, swap) = match details {
+        Some(details) => (
+            details.close_price,
+            details
+                .close_time
+                .map(parse_time_milliseconds)
+                .unwrap_or_else(Local::now),
+            details.profit,
+            -details.commission,
+            details.swap,
+        ),
+        None => {
+            log::warn!(
+                "Position {} not found in trade history, closing at tick",
+                trade_in.id
+            );
+
+            let price_out = match is_long {
+                true => tick.bid(),
+                false => tick.ask(),
+            };
+
+            (price_out, Local::now(), 0., 0., 0.)
+        }
+    };
+
+    TradeOut {
+        id: trade_in.id,
+        trade_type,
+        status: TradeStatus::Fulfilled,
+        index_in: trade_in.index_in,
+        price_in: trade_in.price_in,
+        size: trade_in.size,
+        ask: trade_in.ask,
+        spread_in: trade_in.spread,
+        date_in: trade_in.date_in,
+        index_out: uuid::generate_ts_id(date_out),
+        price_origin: trade_in.price_in,
+        price_out,
+        bid: tick.bid(),
+        spread_out: tick.spread(),
+        date_out: to_dbtime(date_out),
+        profit,
+        profit_per: 0.,
+        run_up: 0.,
+        run_up_per: 0.,
+        draw_down: 0.,
+        draw_down_per: 0.,
+        commission,
+        spread_cost: 0.,
+        swap,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::order::{OrderStatus, OrderType};
+    use crate::models::stop_loss::StopLossType;
+    use crate::models::symbol_spec::{register, SymbolSpec};
+
+    const SYMBOL: &str = "EURUSD";
+
+    fn tick() -> InstrumentTick {
+        InstrumentTick::new()
+            .symbol(SYMBOL.to_owned())
+            .ask(1.1002)
+            .bid(1.1)
+            .high(1.1002)
+            .low(1.1)
+            .spread(0.0002)
+            .pip_size(0.0001)
+            .time(0)
+            .build()
+            .unwrap()
+    }
+
+    fn trade_in(id: usize, size: f64) -> TradeIn {
+        TradeIn {
+            id,
+            size,
+            price_in: 1.1,
+            trade_type: TradeType::MarketInLong,
+            status: TradeStatus::Fulfilled,
+            date_in: to_dbtime(parse_time_milliseconds(1704704400000)),
+            ..TradeIn::default()
+        }
+    }
+
+    fn orders(trade_id: usize, size: f64) -> Vec<Order> {
+        vec![
+            Order {
+                trade_id,
+                size,
+                order_type: OrderType::StopLossLong(StopLossType::Price(1.098), 1.1),
+                target_price: 1.098,
+                created_at: to_dbtime(parse_time_milliseconds(1704704400000)),
+                ..Order::default()
+            },
+            Order {
+                trade_id,
+                size,
+                order_type: OrderType::TakeProfitLong(size, 1.105),
+                target_price: 1.105,
+                created_at: to_dbtime(parse_time_milliseconds(1704704400000)),
+                ..Order::default()
+            },
+        ]
+    }
+
+    fn position(id: usize, size: f64) -> BrokerPosition {
+        BrokerPosition {
+            trade_in: trade_in(id, size),
+            orders: orders(id, size),
+        }
+    }
+
+    #[test]
+    fn keeps_matching_positions() {
+        let trades_in = vec![trade_in(1, 0.5)];
+        let result = reconcile(
+            SYMBOL,
+            &trades_in,
+            &[],
+            &orders(1, 0.5),
+            &[position(1, 0.5)],
+            &[],
+            &tick(),
+        );
+
+        assert!(result.is_synced());
+        assert!(result.trades_in.is_empty());
+        assert!(result.trades_out.is_empty());
+        assert!(result.orders.is_empty());
+    }
+
+    #[test]
+    fn adopts_orphaned_positions() {
+        let result = reconcile(SYMBOL, &[], &[], &[], &[position(2, 0.3)], &[], &tick());
+
+        assert!(!result.is_synced());
+        assert_eq!(result.orphaned, vec![position(2, 0.3)]);
+        assert_eq!(result.trades_in, vec![trade_in(2, 0.3)]);
+        assert_eq!(result.orders, orders(2, 0.3));
+    }
+
+    #[test]
+    fn closes_missing_positions_from_history() {
+        let trades_in = vec![trade_in(3, 0.5)];
+        let history = vec![TransactionDetails {
+            id: 3,
+            open_price: 1.1,
+            close_price: 1.105,
+            profit: 24.,
+            commission: -2.,
+            swap: -1.,
+            close_time: Some(1704708000000),
+        }];
+
+        let result = reconcile(
+            SYMBOL,
+            &trades_in,
+            &[],
+            &orders(3, 0.5),
+            &[],
+            &history,
+            &tick(),
+        );
+
+        assert_eq!(result.missing, trades_in);
+        assert_eq!(result.trades_out.len(), 1);
+
+        let trade_out = &result.trades_out[0];
+        assert_eq!(trade_out.id, 3);
+        assert_eq!(trade_out.trade_type, TradeType::MarketOutLong);
+        assert_eq!(trade_out.status, TradeStatus::Fulfilled);
+        assert_eq!(trade_out.price_out, 1.105);
+        assert_eq!(trade_out.profit, 24.);
+        assert_eq!(trade_out.commission, 2.);
+        assert_eq!(trade_out.swap, -1.);
+        assert_eq!(
+            from_dbtime(&trade_out.date_out).timestamp_millis(),
+            1704708000000
+        );
+
+        // Pending orders of the closed position are canceled
+        assert_eq!(result.orders.len(), 2);
+        assert!(result
+            .orders
+            .iter()
+            .all(|order| order.status == OrderStatus::Canceled));
+    }
+
+    #[test]
+    fn closes_missing_positions_at_the_tick() {
+        let mut short = trade_in(4, 0.5);
+        short.trade_type = TradeType::MarketInShort;
+
+        let result = reconcile(SYMBOL, &[short.clone()], &[], &[], &[], &[], &tick());
+
+        assert_eq!(result.missing, vec![short]);
+
+        let trade_out = &result.trades_out[0];
+        assert_eq!(trade_out.trade_type, TradeType::MarketOutShort);
+        assert_eq!(trade_out.price_out, 1.1002);
+        assert_eq!(trade_out.profit, 0.);
+        assert!(result.orders.is_empty());
+    }
+
+    #[test]
+    fn ignores_closed_trades() {
+        let trades_in = vec![trade_in(5, 0.5)];
+        let trades_out = vec![close_missing(&trades_in[0], None, &tick())];
+
+        let result = reconcile(SYMBOL, &trades_in, &trades_out, &[], &[], &[], &tick());
+
+        assert!(result.is_synced());
+        assert!(result.trades_out.is_empty());
+    }
+
+    #[test]
+    fn resizes_mismatched_positions() {
+        let trades_in = vec![trade_in(6, 0.5)];
+
+        // Less than half a lot step apart is the same size
+        let result = reconcile(
+            SYMBOL,
+            &trades_in,
+            &[],
+            &orders(6, 0.5),
+            &[position(6, 0.504)],
+            &[],
+            &tick(),
+        );
+        assert!(result.is_synced());
+
+        let result = reconcile(
+            SYMBOL,
+            &trades_in,
+            &[],
+            &orders(6, 0.5),
+            &[position(6, 0.3)],
+            &[],
+            &tick(),
+        );
+
+        assert_eq!(result.size_mismatched.len(), 1);
+        assert_eq!(result.size_mismatched[0].0, trade_in(6, 0.5));
+        assert_eq!(result.trades_in, vec![trade_in(6, 0.3)]);
+
+        // Exit orders follow the broker size
+        assert_eq!(result.orders.len(), 2);
+        assert!(result.orders.iter().all(|order| order.size == 0.3));
+        assert_eq!(
+            result.orders[1].order_type,
+            OrderType::TakeProfitLong(0.3, 1.105)
+        );
+    }
+
+    #[test]
+    fn compares_sizes_on_the_symbol_lot_step() {
+        let spec = SymbolSpec {
+            symbol: "RECONCILEFX".to_owned(),
+            lot_step: 0.1,
+            ..symbol_spec(SYMBOL).unwrap().as_ref().clone()
+        };
+        register(spec);
+
+        let trades_in = vec![trade_in(7, 1.)];
+        let result = reconcile(
+            "RECONCILEFX",
+            &trades_in,
+            &[],
+            &[],
+            &[position(7, 1.04)],
+            &[],
+            &tick(),
+        );
+        assert!(result.is_synced());
+
+        let result = reconcile(
+            "RECONCILEFX",
+            &trades_in,
+            &[],
+            &[],
+            &[position(7, 1.1)],
+            &[],
+            &tick(),
+        );
+        assert_eq!(result.trades_in, vec![trade_in(7, 1.1)]);
+    }
+}
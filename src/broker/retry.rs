@@ -0,0 +1,59 @@
+use crate::config::RsAlgoConfig;
+use crate::error::RsAlgoErrorKind;
+use crate::models::trade::TradeAttempt;
+
+use std::time::Duration;
+
+const RETRYABLE_MESSAGES: [&str; 6] = [
+    "requote",
+    "price changed",
+    "price has changed",
+    "off quotes",
+    "market closed",
+    "timeout",
+];
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RetryPolicy {
+    pub max_attempts: usize,
+    pub retry_after: Duration,
+}
+
+impl RetryPolicy {
+    pub fn new(max_attempts: usize, retry_after: Duration) -> Self {
+        Self {
+            max_attempts: max_attempts.max(1),
+            retry_after,
+        }
+    }
+
+    pub fn from_config(config: &RsAlgoConfig) -> Self {
+        Self::new(
+            config.trade_max_retries,
+            Duration::from_millis(config.trade_retry_after),
+        )
+    }
+
+    pub fn should_retry(&self, attempt: &TradeAttempt) -> bool {
+        attempt.retryable && attempt.attempt < self.max_attempts
+    }
+}
+
+// Broker codes are mapped once in xtb_error, only transient failures are retried.
+// Unmapped codes fall back to the broker description
+pub fn is_retryable_error(err: &RsAlgoErrorKind) -> bool {
+    match err {
+        RsAlgoErrorKind::MarketClosed(..)
+        | RsAlgoErrorKind::InvalidPrice(..)
+        | RsAlgoErrorKind::RequestLimit(..) => true,
+        RsAlgoErrorKind::BrokerError(_, description) => is_retryable_message(description),
+        _ => false,
+    }
+}
+
+pub fn is_retryable_message(message: &str) -> bool {
+    let message = message.to_lowercase();
+    RETRYABLE_MESSAGES
+        .iter()
+        .any(|retryable| message.contains(retryable))
+}
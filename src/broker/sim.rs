@@ -0,0 +1,1469 @@
+use crate::broker::models::{TransactionCommand, TransactionDetails};
+use crate::broker::reconcile::BrokerPosition;
+use crate::broker::trading::{BrokerEvent, TradingBroker};
+use crate::broker::{DOHLC, VEC_DOHLC};
+use crate::config::RsAlgoConfig;
+use crate::error::{Result, RsAlgoError, RsAlgoErrorKind};
+use crate::helpers::calc::{format_symbol_price, number_pips};
+use crate::helpers::date::{
+    from_dbtime, parse_time_seconds, to_dbtime, DateTime, DbDateTime, Local,
+};
+use crate::helpers::uuid;
+use crate::models::account::{Account, AccountInfo};
+use crate::models::costs::CostModel;
+use crate::models::fx::FxRates;
+use crate::models::market::{MarketHour, MarketHours};
+use crate::models::order::{Order, OrderStatus, OrderType};
+use crate::models::stop_loss::StopLossType;
+use crate::models::swap::InstrumentSwap;
+use crate::models::symbol_spec::{self, SymbolSpec};
+use crate::models::tick::InstrumentTick;
+use crate::models::time_frame::TimeFrameType;
+use crate::models::trade::{
+    PositionResult, TradeAmend, TradeIn, TradeOut, TradeResult, TradeStatus, TradeType,
+};
+use crate::ws::message::{InstrumentData, TradeData, TradeResponse};
+
+use std::collections::{HashMap, VecDeque};
+use std::fs;
+use std::path::Path;
+
+#[derive(Debug, Clone)]
+pub enum SimFeed {
+    Candles(VEC_DOHLC),
+    Ticks(Vec<InstrumentTick>),
+}
+
+impl SimFeed {
+    fn len(&self) -> usize {
+        match self {
+            SimFeed::Candles(candles) => candles.len(),
+            SimFeed::Ticks(ticks) => ticks.len(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct SimPosition {
+    strategy_name: String,
+    trade_in: TradeIn,
+    orders: Vec<Order>,
+}
+
+#[derive(Debug)]
+struct SimOrder {
+    strategy_name: String,
+    command: TransactionCommand,
+    order: Order,
+}
+
+#[derive(Debug)]
+pub struct SimBroker {
+    symbol: String,
+    feed: SimFeed,
+    history: VEC_DOHLC,
+    cursor: usize,
+    spread: f64,
+    slippage_pips: f64,
+    costs: CostModel,
+    pip_size: f64,
+    account: Account,
+    fx_rates: FxRates,
+    tick: Option<InstrumentTick>,
+    positions: Vec<SimPosition>,
+    closed: Vec<(String, TransactionDetails)>,
+    orders: Vec<SimOrder>,
+    // Events are queued per polling strategy, feed events go to all of them
+    events: HashMap<String, VecDeque<BrokerEvent>>,
+    subscribed: bool,
+}
+
+impl SimBroker {
+    pub fn new() -> SimBrokerBuilder {
+        SimBrokerBuilder::new()
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.cursor >= self.feed.len() && self.events.values().all(|events| events.is_empty())
+    }
+
+    pub fn balance(&self) -> f64 {
+        self.account.balance()
+    }
+
+    pub async fn next_message(&mut self, strategy_name: &str) -> Result<Option<String>> {
+        let symbol = self.symbol.clone();
+        let event = self.next_event(&symbol, strategy_name).await?;
+        Ok(event.map(|event| event.into_message()))
+    }
+
+    fn current_date(&self) -> DateTime<Local> {
+        match &self.tick {
+            Some(tick) => parse_time_seconds(tick.time()),
+            None => Local::now(),
+        }
+    }
+
+    fn current_tick(&self) -> Result<InstrumentTick> {
+        self.tick.clone().ok_or(RsAlgoError {
+            err: RsAlgoErrorKind::NoResponse,
+        })
+    }
+
+    fn strategy_position(&self, strategy_name: &str) -> Option<&SimPosition> {
+        self.positions
+            .iter()
+            .find(|position| position.strategy_name == strategy_name)
+    }
+
+    // Strategies holding a position or a pending order, in the order they traded
+    fn strategies(&self) -> Vec<String> {
+        let names = self
+            .positions
+            .iter()
+            .map(|position| &position.strategy_name)
+            .chain(self.orders.iter().map(|sim_order| &sim_order.strategy_name));
+
+        names.fold(vec![], |mut strategies, name| {
+            if !strategies.contains(name) {
+                strategies.push(name.clone());
+            }
+            strategies
+        })
+    }
+
+    fn push_event(&mut self, strategy_name: &str, event: BrokerEvent) {
+        match self.events.get_mut(strategy_name) {
+            Some(events) => events.push_back(event),
+            None => log::debug!(
+                "{} Sim {} is not polling events",
+                self.symbol,
+                strategy_name
+            ),
+        }
+    }
+
+    fn check_symbol(&self, symbol: &str) -> Result<()> {
+        match symbol == self.symbol {
+            true => Ok(()),
+            false => {
+                log::error!("{} is not replayed by the simulated broker", symbol);
+                Err(RsAlgoError {
+                    err: RsAlgoErrorKind::WrongInstrumentConf,
+                })
+            }
+        }
+    }
+
+    fn with_slippage(&self, price: f64, trade_type: &TradeType) -> f64 {
+        let slippage = self.slippage_pips * self.pip_size;
+
+        let price = match trade_type.is_stop() {
+            true => price,
+            false => match trade_type.is_long() == trade_type.is_entry() {
+                true => price + slippage,
+                false => price - slippage,
+            },
+        };
+
+        format_symbol_price(price, &self.symbol)
+    }
+
+    fn gross_profit(
+        &self,
+        size: f64,
+        price_in: f64,
+        price_out: f64,
+        trade_type: &TradeType,
+    ) -> Result<f64> {
+        self.fx_rates.trade_profit(
+            size,
+            price_in,
+            price_out,
+            trade_type,
+            &self.symbol,
+            self.current_date(),
+        )
+    }
+
+    // Open positions carry the round trip commission, swaps are booked on close
+    fn net_profit(
+        &self,
+        size: f64,
+        price_in: f64,
+        price_out: f64,
+        trade_type: &TradeType,
+    ) -> Result<f64> {
+        Ok(self.gross_profit(size, price_in, price_out, trade_type)?
+            - self.costs.commission(
+                size,
+                price_in,
+                price_out,
+                &self.symbol,
+                &self.fx_rates,
+                self.current_date(),
+            )?)
+    }
+
+    fn fill_in(
+        &mut self,
+        mut data: TradeIn,
+        price: f64,
+        strategy_name: &str,
+        orders: Vec<Order>,
+    ) -> Result<TradeIn> {
+        // Bots hold one position per strategy, a second fill would orphan the first
+        if self.strategy_position(strategy_name).is_some() {
+            log::error!(
+                "{} Sim {} already has an open position",
+                self.symbol,
+                strategy_name
+            );
+            return Err(RsAlgoError {
+                err: RsAlgoErrorKind::BrokerError(
+                    "POSITION_OPEN".to_owned(),
+                    format!("{} already has an open position", strategy_name),
+                ),
+            });
+        }
+
+        let date_in = self.current_date();
+
+        data.id = uuid::generate_ts_id(date_in);
+        data.price_in = price;
+        data.ask = match data.trade_type.is_long() {
+            true => price,
+            false => price + self.spread,
+        };
+        data.spread = self.spread;
+        data.date_in = to_dbtime(date_in);
+        data.status = TradeStatus::Fulfilled;
+
+        self.account.open(&self.symbol, &data, &self.fx_rates)?;
+
+        log::info!(
+            "{} Sim {:?} {} filled at {}",
+            self.symbol,
+            data.trade_type,
+            data.id,
+            price
+        );
+
+        self.positions.push(SimPosition {
+            strategy_name: strategy_name.to_owned(),
+            trade_in: data.clone(),
+            orders,
+        });
+
+        Ok(data)
+    }
+
+    fn fill_out(
+        &mut self,
+        mut data: TradeOut,
+        price_out: f64,
+        non_profitable_out: bool,
+    ) -> Result<TradeResponse<TradeOut>> {
+        let date_out = self.current_date();
+        let trade_type = data.trade_type.clone();
+
+        data.price_out = price_out;
+        data.bid = match trade_type.is_long() {
+            true => price_out,
+            false => price_out - self.spread,
+        };
+        data.spread_out = self.spread;
+        data.date_out = to_dbtime(date_out);
+        data.index_out = uuid::generate_ts_id(date_out);
+        data.profit = self.gross_profit(data.size, data.price_in, price_out, &trade_type)?;
+        self.costs.apply(&mut data, &self.symbol, &self.fx_rates)?;
+
+        let profit = data.profit;
+        let accepted = non_profitable_out || trade_type.is_stop() || profit > 0.;
+
+        data.status = match accepted {
+            true => TradeStatus::Fulfilled,
+            false => TradeStatus::Rejected,
+        };
+
+        if accepted {
+            log::info!(
+                "{} Sim {:?} {} closed at {} with profit {}",
+                self.symbol,
+                trade_type,
+                data.id,
+                price_out,
+                profit
+            );
+            self.account.close(&data);
+
+            if let Some(position) = self.positions.iter().find(|p| p.trade_in.id == data.id) {
+                self.closed.push((
+                    position.strategy_name.clone(),
+                    TransactionDetails {
+                        id: data.id,
+                        open_price: data.price_in,
+                        close_price: price_out,
+                        profit: data.profit,
+                        commission: -data.commission,
+                        swap: data.swap,
+                        close_time: Some(date_out.timestamp_millis()),
+                    },
+                ));
+            }
+
+            self.positions
+                .retain(|position| position.trade_in.id != data.id);
+        } else {
+            log::error!(
+                "{} Sim {:?} {} NOT accepted with profit {}",
+                self.symbol,
+                trade_type,
+                data.id,
+                profit
+            );
+        }
+
+        Ok(TradeResponse {
+            symbol: self.symbol.clone(),
+            accepted,
+            data,
+            attempts: vec![],
+        })
+    }
+
+    fn stop_loss_hit(
+        &mut self,
+        strategy_name: &str,
+        low: f64,
+        high: f64,
+        open: f64,
+    ) -> Result<Option<BrokerEvent>> {
+        let position = match self.strategy_position(strategy_name) {
+            Some(position) => position,
+            None => return Ok(None),
+        };
+        let trade_in = &position.trade_in;
+        let is_long = trade_in.trade_type.is_long();
+
+        let stop_loss = position
+            .orders
+            .iter()
+            .find(|order| order.is_stop() && order.is_long() == is_long);
+
+        let stop_loss = match stop_loss {
+            Some(stop_loss) => stop_loss,
+            None => return Ok(None),
+        };
+
+        let target_price = stop_loss.target_price;
+
+        let price_out = match is_long {
+            true if low <= target_price => target_price.min(open),
+            false if high + self.spread >= target_price => target_price.max(open + self.spread),
+            _ => return Ok(None),
+        };
+
+        let trade_type = match is_long {
+            true => TradeType::StopLossLong,
+            false => TradeType::StopLossShort,
+        };
+
+        let trade_out = pending_trade_out(trade_in, trade_type);
+
+        Ok(Some(BrokerEvent::TradeOut(self.fill_out(
+            trade_out,
+            format_symbol_price(price_out, &self.symbol),
+            true,
+        )?)))
+    }
+
+    fn take_profit_hit(
+        &mut self,
+        strategy_name: &str,
+        low: f64,
+        high: f64,
+        open: f64,
+    ) -> Result<Option<BrokerEvent>> {
+        let position = match self.strategy_position(strategy_name) {
+            Some(position) => position,
+            None => return Ok(None),
+        };
+        let trade_in = &position.trade_in;
+        let is_long = trade_in.trade_type.is_long();
+
+        let take_profit = position
+            .orders
+            .iter()
+            .find(|order| order.order_type.is_exit() && order.is_long() == is_long);
+
+        let take_profit = match take_profit {
+            Some(take_profit) => take_profit,
+            None => return Ok(None),
+        };
+
+        let target_price = take_profit.target_price;
+
+        let price_out = match is_long {
+            true if high >= target_price => target_price.max(open),
+            false if low + self.spread <= target_price => target_price.min(open + self.spread),
+            _ => return Ok(None),
+        };
+
+        let trade_type = match is_long {
+            true => TradeType::OrderOutLong,
+            false => TradeType::OrderOutShort,
+        };
+
+        let trade_out = pending_trade_out(trade_in, trade_type);
+
+        Ok(Some(BrokerEvent::TradeOut(self.fill_out(
+            trade_out,
+            format_symbol_price(price_out, &self.symbol),
+            true,
+        )?)))
+    }
+
+    fn order_hit(&self, sim_order: &SimOrder, low: f64, high: f64, open: f64) -> Option<f64> {
+        let target_price = sim_order.order.target_price;
+        let spread = self.spread;
+
+        match sim_order.command {
+            TransactionCommand::BuyLimit if low + spread <= target_price => {
+                Some(target_price.min(open + spread))
+            }
+            TransactionCommand::BuyStop if high + spread >= target_price => {
+                Some(target_price.max(open + spread))
+            }
+            TransactionCommand::SellLimit if high >= target_price => Some(target_price.max(open)),
+            TransactionCommand::SellStop if low <= target_price => Some(target_price.min(open)),
+            _ => None,
+        }
+    }
+
+    fn expire_orders(&mut self) {
+        let date = self.current_date();
+        let (expired, orders): (Vec<SimOrder>, Vec<SimOrder>) =
+            self.orders.drain(..).partition(|sim_order| {
+                sim_order
+                    .order
+                    .valid_until
+                    .map_or(false, |valid_until| from_dbtime(&valid_until) < date)
+            });
+
+        self.orders = orders;
+
+        for sim_order in expired {
+            let mut order = sim_order.order;
+            order.cancel_order(to_dbtime(date));
+            log::info!(
+                "{} Sim {:?} order {} expired",
+                self.symbol,
+                order.order_type,
+                order.id
+            );
+            self.push_event(&sim_order.strategy_name, BrokerEvent::Order(order));
+        }
+    }
+
+    fn activate_order(&mut self, strategy_name: &str, low: f64, high: f64, open: f64) {
+        if self.strategy_position(strategy_name).is_some() {
+            return;
+        }
+
+        let hit = self
+            .orders
+            .iter()
+            .enumerate()
+            .filter(|(_, sim_order)| sim_order.strategy_name == strategy_name)
+            .find_map(|(index, sim_order)| {
+                self.order_hit(sim_order, low, high, open)
+                    .map(|price| (index, price))
+            });
+
+        let (index, price) = match hit {
+            Some(hit) => hit,
+            None => return,
+        };
+
+        let mut order = self.orders.remove(index).order;
+        let date = self.current_date();
+        let is_long = order.is_long();
+
+        let trade_type = match is_long {
+            true => TradeType::OrderInLong,
+            false => TradeType::OrderInShort,
+        };
+
+        let trade_in = TradeIn {
+            id: 0,
+            index_in: order.index_created,
+            size: order.size,
+            origin_price: order.origin_price,
+            price_in: 0.,
+            ask: 0.,
+            spread: 0.,
+            trade_type,
+            date_in: to_dbtime(date),
+            status: TradeStatus::Pending,
+            stop_rules: vec![],
+        };
+
+        let price_in = format_symbol_price(price, &self.symbol);
+        let data = match self.fill_in(trade_in, price_in, strategy_name, vec![]) {
+            Ok(data) => data,
+            Err(err) => {
+                log::error!("{} Sim order {} canceled: {}", self.symbol, order.id, err);
+                order.cancel_order(to_dbtime(date));
+                self.push_event(strategy_name, BrokerEvent::Order(order));
+                return;
+            }
+        };
+
+        order.set_trade_id(data.id);
+        order.fulfill_order(self.cursor - 1, date);
+
+        let position = self
+            .positions
+            .iter_mut()
+            .find(|position| position.trade_in.id == data.id);
+
+        if let (Some(meta), Some(position)) = (&order.meta, position) {
+            let order_type = match is_long {
+                true => OrderType::StopLossLong(StopLossType::Price(meta.sl), price_in),
+                false => OrderType::StopLossShort(StopLossType::Price(meta.sl), price_in),
+            };
+
+            position
+                .orders
+                .push(position_order(&data, order_type, meta.sl, to_dbtime(date)));
+        }
+
+        let symbol = self.symbol.clone();
+        self.push_event(strategy_name, BrokerEvent::Order(order));
+        self.push_event(
+            strategy_name,
+            BrokerEvent::TradeIn(TradeResponse {
+                symbol,
+                accepted: true,
+                data,
+                attempts: vec![],
+            }),
+        );
+    }
+
+    // Every position and pending order is evaluated once per bar, whoever is polling
+    fn step(&mut self) -> Result<()> {
+        let index = self.cursor;
+        self.cursor += 1;
+
+        let (tick, candle, open, high, low) = match &self.feed {
+            SimFeed::Candles(candles) => {
+                let candle: DOHLC = candles[index];
+                let (date, open, high, low, close, _) = candle;
+
+                let tick = InstrumentTick::new()
+                    .symbol(self.symbol.clone())
+                    .ask(format_symbol_price(close + self.spread, &self.symbol))
+                    .bid(close)
+                    .high(high)
+                    .low(low)
+                    .spread(self.spread)
+                    .pip_size(self.pip_size)
+                    .time(date.timestamp())
+                    .build()
+                    .unwrap();
+
+                self.history.push(candle);
+                (tick, Some(candle), open, high, low)
+            }
+            SimFeed::Ticks(ticks) => {
+                let tick = ticks[index].clone();
+                let (bid, ask) = (tick.bid(), tick.ask());
+                (tick, None, bid, ask - self.spread, bid)
+            }
+        };
+
+        self.tick = Some(tick.clone());
+        self.fx_rates.update_tick(&tick);
+
+        if let Some(candle) = candle {
+            for events in self.events.values_mut() {
+                events.push_back(BrokerEvent::Candle(candle));
+            }
+        }
+
+        for strategy_name in self.strategies() {
+            if let Some(event) = match self.stop_loss_hit(&strategy_name, low, high, open)? {
+                Some(event) => Some(event),
+                None => self.take_profit_hit(&strategy_name, low, high, open)?,
+            } {
+                self.push_event(&strategy_name, event);
+            }
+        }
+
+        self.expire_orders();
+
+        for strategy_name in self.strategies() {
+            self.activate_order(&strategy_name, low, high, open);
+        }
+
+        for events in self.events.values_mut() {
+            events.push_back(BrokerEvent::Tick(tick.clone()));
+        }
+
+        self.mark_to_market()
+    }
+
+    fn mark_to_market(&mut self) -> Result<()> {
+        let tick = match &self.tick {
+            Some(tick) => tick,
+            None => return Ok(()),
+        };
+
+        let open_profit = self
+            .positions
+            .iter()
+            .map(|position| {
+                let trade_in = &position.trade_in;
+                let price_out = match trade_in.trade_type.is_long() {
+                    true => tick.bid(),
+                    false => tick.ask(),
+                };
+                self.net_profit(
+                    trade_in.size,
+                    trade_in.price_in,
+                    price_out,
+                    &trade_in.trade_type,
+                )
+            })
+            .sum::<Result<f64>>()?;
+
+        self.account.mark_to_market(open_profit);
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+impl TradingBroker for SimBroker {
+    async fn connect(config: &RsAlgoConfig) -> Result<Self> {
+        symbol_spec::init(config)?;
+        SimBroker::new()
+            .config(config.clone())
+            .build()
+            .map_err(|err| {
+                log::error!("Can't build simulated broker: {}", err);
+                RsAlgoError {
+                    err: RsAlgoErrorKind::WrongInstrumentConf,
+                }
+            })
+    }
+
+    async fn authenticate(&mut self, _username: &str, _password: &str) -> Result<()> {
+        Ok(())
+    }
+
+    async fn candles(
+        &mut self,
+        symbol: &str,
+        time_frame: usize,
+        from: i64,
+    ) -> Result<InstrumentData<VEC_DOHLC>> {
+        self.check_symbol(symbol)?;
+
+        let data = self
+            .history
+            .iter()
+            .filter(|candle| candle.0.timestamp() >= from)
+            .cloned()
+            .collect();
+
+        Ok(InstrumentData {
+            symbol: symbol.to_owned(),
+            time_frame: TimeFrameType::from_number(time_frame),
+            data,
+        })
+    }
+
+    async fn historic_candles(
+        &mut self,
+        symbol: &str,
+        time_frame: usize,
+        from: i64,
+        to: i64,
+    ) -> Result<InstrumentData<VEC_DOHLC>> {
+        self.check_symbol(symbol)?;
+
+        let replay = match &self.feed {
+            SimFeed::Candles(candles) => &candles[self.cursor..],
+            SimFeed::Ticks(_) => &[],
+        };
+
+        let data = self
+            .history
+            .iter()
+            .chain(replay.iter())
+            .filter(|candle| candle.0.timestamp() >= from && candle.0.timestamp() <= to)
+            .cloned()
+            .collect();
+
+        Ok(InstrumentData {
+            symbol: symbol.to_owned(),
+            time_frame: TimeFrameType::from_number(time_frame),
+            data,
+        })
+    }
+
+    async fn tick(&mut self, symbol: &str) -> Result<InstrumentTick> {
+        self.check_symbol(symbol)?;
+        self.current_tick()
+    }
+
+    async fn swap(&mut self, symbol: &str) -> Result<InstrumentSwap> {
+        self.check_symbol(symbol)?;
+
+        let swap = match self.costs.swap() {
+            Some(swap) => swap.clone(),
+            None => InstrumentSwap::new()
+                .symbol(symbol.to_owned())
+                .enabled(false)
+                .swap_long(0.)
+                .swap_short(0.)
+                .swap_weekend(0.)
+                .build()
+                .unwrap(),
+        };
+
+        Ok(swap)
+    }
+
+    async fn symbol_spec(&mut self, symbol: &str) -> Result<SymbolSpec> {
+        self.check_symbol(symbol)?;
+
+        symbol_spec::symbol_spec(symbol)
+            .map(|spec| SymbolSpec::clone(&spec))
+            .ok_or(RsAlgoError {
+                err: RsAlgoErrorKind::InvalidSymbol(symbol.to_owned(), "No symbol spec".to_owned()),
+            })
+    }
+
+    // Bundled and file specs are loaded on connect
+    async fn load_symbol_specs(&mut self) -> Result<usize> {
+        Ok(symbol_spec::registered())
+    }
+
+    async fn market_hours(&mut self, symbol: &str) -> Result<MarketHours> {
+        self.check_symbol(symbol)?;
+
+        // Specs files can set the trading hours, open all week otherwise
+        let market_hours = match symbol_spec::symbol_spec(symbol) {
+            Some(spec) if !spec.trading_hours.is_empty() => spec.trading_hours.clone(),
+            _ => (1..=7)
+                .map(|day| MarketHour {
+                    day,
+                    from: 0,
+                    to: 24,
+                })
+                .collect(),
+        };
+
+        Ok(MarketHours::new(symbol.to_owned(), market_hours))
+    }
+
+    async fn market_is_open(&mut self, symbol: &str) -> Result<bool> {
+        self.check_symbol(symbol)?;
+        Ok(!self.is_finished())
+    }
+
+    async fn positions(&mut self, symbol: &str, strategy_name: &str) -> Result<PositionResult> {
+        self.check_symbol(symbol)?;
+
+        let res = match self.strategy_position(strategy_name) {
+            Some(position) => PositionResult::MarketIn(
+                TradeResult::TradeIn(position.trade_in.clone()),
+                Some(position.orders.clone()),
+            ),
+            _ => PositionResult::None,
+        };
+
+        Ok(res)
+    }
+
+    async fn open_positions(
+        &mut self,
+        symbol: &str,
+        strategy_name: &str,
+    ) -> Result<Vec<BrokerPosition>> {
+        self.check_symbol(symbol)?;
+
+        let res = self
+            .positions
+            .iter()
+            .filter(|position| position.strategy_name == strategy_name)
+            .map(|position| BrokerPosition {
+                trade_in: position.trade_in.clone(),
+                orders: position.orders.clone(),
+            })
+            .collect();
+
+        Ok(res)
+    }
+
+    async fn trade_history(
+        &mut self,
+        symbol: &str,
+        strategy_name: &str,
+        from: i64,
+    ) -> Result<Vec<TransactionDetails>> {
+        self.check_symbol(symbol)?;
+
+        let res = self
+            .closed
+            .iter()
+            .filter(|(name, _)| name == strategy_name)
+            .filter(|(_, details)| details.close_time.map_or(true, |time| time >= from * 1000))
+            .map(|(_, details)| details.clone())
+            .collect();
+
+        Ok(res)
+    }
+
+    async fn account(&mut self) -> Result<AccountInfo> {
+        Ok(self.account.info())
+    }
+
+    async fn enter_trade(
+        &mut self,
+        trade: TradeData<TradeIn>,
+        orders: Option<Vec<Order>>,
+    ) -> Result<TradeResponse<TradeIn>> {
+        self.check_symbol(&trade.symbol)?;
+        let tick = self.current_tick()?;
+
+        let trade_type = trade.data.trade_type.clone();
+        let price = match trade_type.is_long() {
+            true => tick.ask(),
+            false => tick.bid(),
+        };
+        let price_in = self.with_slippage(price, &trade_type);
+
+        let data = self.fill_in(
+            trade.data,
+            price_in,
+            &trade.strategy_name,
+            orders.unwrap_or_default(),
+        )?;
+
+        Ok(TradeResponse {
+            symbol: trade.symbol,
+            accepted: true,
+            data,
+            attempts: vec![],
+        })
+    }
+
+    async fn exit_trade(&mut self, trade: TradeData<TradeOut>) -> Result<TradeResponse<TradeOut>> {
+        self.check_symbol(&trade.symbol)?;
+        let tick = self.current_tick()?;
+
+        let trade_type = trade.data.trade_type.clone();
+        let price = match trade_type.is_long() {
+            true => tick.bid(),
+            false => tick.ask(),
+        };
+        let price_out = self.with_slippage(price, &trade_type);
+
+        self.fill_out(trade.data, price_out, trade.options.non_profitable_out)
+    }
+
+    async fn enter_order(
+        &mut self,
+        trade: TradeData<TradeIn>,
+        order: TradeData<Order>,
+    ) -> Result<TradeResponse<TradeIn>> {
+        self.check_symbol(&order.symbol)?;
+
+        let order = order.data;
+        let mut data = trade.data;
+
+        data.trade_type = match order.order_type.is_long() {
+            true => TradeType::OrderInLong,
+            false => TradeType::OrderInShort,
+        };
+        data.size = order.size;
+        data.origin_price = order.origin_price;
+
+        let price = match data.trade_type.is_long() {
+            true => order.target_price + self.spread,
+            false => order.target_price,
+        };
+        let price_in = self.with_slippage(price, &data.trade_type);
+
+        let data = self.fill_in(data, price_in, &trade.strategy_name, vec![])?;
+
+        Ok(TradeResponse {
+            symbol: trade.symbol,
+            accepted: true,
+            data,
+            attempts: vec![],
+        })
+    }
+
+    async fn exit_order(
+        &mut self,
+        trade: TradeData<TradeOut>,
+        order: TradeData<Order>,
+    ) -> Result<TradeResponse<TradeOut>> {
+        self.check_symbol(&order.symbol)?;
+
+        let target_price = order.data.target_price;
+        let trade_type = trade.data.trade_type.clone();
+
+        let price = match trade_type.is_long() || trade_type.is_stop() {
+            true => target_price,
+            false => target_price + self.spread,
+        };
+        let price_out = self.with_slippage(price, &trade_type);
+
+        self.fill_out(trade.data, price_out, trade.options.non_profitable_out)
+    }
+
+    async fn amend_trade(
+        &mut self,
+        trade: TradeData<TradeAmend>,
+    ) -> Result<TradeResponse<TradeAmend>> {
+        self.check_symbol(&trade.symbol)?;
+        let date = to_dbtime(self.current_date());
+        let amend = trade.data;
+
+        let position = self
+            .positions
+            .iter_mut()
+            .find(|position| position.trade_in.id == amend.trade_in.id);
+
+        let accepted = match position {
+            Some(position) => {
+                let trade_in = position.trade_in.clone();
+                let is_long = trade_in.trade_type.is_long();
+                let price_in = trade_in.price_in;
+
+                // Same as the broker, the amended levels replace the current ones
+                position
+                    .orders
+                    .retain(|order| !order.is_stop() && !order.order_type.is_exit());
+
+                if let Some(stop_loss) = amend.stop_loss {
+                    let order_type = match is_long {
+                        true => OrderType::StopLossLong(StopLossType::Price(stop_loss), price_in),
+                        false => OrderType::StopLossShort(StopLossType::Price(stop_loss), price_in),
+                    };
+                    position
+                        .orders
+                        .push(position_order(&trade_in, order_type, stop_loss, date));
+                }
+
+                if let Some(take_profit) = amend.take_profit {
+                    let order_type = match is_long {
+                        true => OrderType::TakeProfitLong(trade_in.size, take_profit),
+                        false => OrderType::TakeProfitShort(trade_in.size, take_profit),
+                    };
+                    position
+                        .orders
+                        .push(position_order(&trade_in, order_type, take_profit, date));
+                }
+
+                log::info!(
+                    "{} Sim trade {} modified. SL: {:?} TP: {:?}",
+                    self.symbol,
+                    trade_in.id,
+                    amend.stop_loss,
+                    amend.take_profit
+                );
+
+                true
+            }
+            _ => {
+                log::error!("{} Sim trade {} not found", self.symbol, amend.trade_in.id);
+                false
+            }
+        };
+
+        Ok(TradeResponse {
+            symbol: trade.symbol,
+            accepted,
+            data: amend,
+            attempts: vec![],
+        })
+    }
+
+    async fn place_order(&mut self, order: TradeData<Order>) -> Result<TradeResponse<Order>> {
+        self.check_symbol(&order.symbol)?;
+        let tick = self.current_tick()?;
+
+        let mut data = order.data;
+        let command =
+            TransactionCommand::pending(data.is_long(), data.target_price, tick.ask(), tick.bid());
+
+        data.set_status(OrderStatus::Pending);
+        data.set_updated_at(to_dbtime(self.current_date()));
+
+        log::info!(
+            "{} Sim {:?} order {} placed at {}",
+            self.symbol,
+            command,
+            data.id,
+            data.target_price
+        );
+
+        self.orders.push(SimOrder {
+            strategy_name: order.strategy_name,
+            command,
+            order: data.clone(),
+        });
+
+        Ok(TradeResponse {
+            symbol: order.symbol,
+            accepted: true,
+            data,
+            attempts: vec![],
+        })
+    }
+
+    async fn modify_order(&mut self, order: TradeData<Order>) -> Result<TradeResponse<Order>> {
+        self.check_symbol(&order.symbol)?;
+        let tick = self.current_tick()?;
+        let date = to_dbtime(self.current_date());
+
+        let mut data = order.data;
+        let sim_order = self
+            .orders
+            .iter_mut()
+            .find(|sim_order| sim_order.order.id == data.id);
+
+        let accepted = match sim_order {
+            Some(sim_order) => {
+                data.set_status(OrderStatus::Pending);
+                data.set_updated_at(date);
+                sim_order.command = TransactionCommand::pending(
+                    data.is_long(),
+                    data.target_price,
+                    tick.ask(),
+                    tick.bid(),
+                );
+                sim_order.order = data.clone();
+                true
+            }
+            None => {
+                log::error!("{} Sim order {} not found", self.symbol, data.id);
+                false
+            }
+        };
+
+        Ok(TradeResponse {
+            symbol: order.symbol,
+            accepted,
+            data,
+            attempts: vec![],
+        })
+    }
+
+    async fn cancel_order(&mut self, order: TradeData<Order>) -> Result<TradeResponse<Order>> {
+        self.check_symbol(&order.symbol)?;
+
+        let mut data = order.data;
+        let position = self
+            .orders
+            .iter()
+            .position(|sim_order| sim_order.order.id == data.id);
+
+        let accepted = match position {
+            Some(index) => {
+                self.orders.remove(index);
+                data.cancel_order(to_dbtime(self.current_date()));
+                true
+            }
+            None => {
+                log::error!("{} Sim order {} not found", self.symbol, data.id);
+                false
+            }
+        };
+
+        Ok(TradeResponse {
+            symbol: order.symbol,
+            accepted,
+            data,
+            attempts: vec![],
+        })
+    }
+
+    async fn pending_orders(&mut self, symbol: &str, strategy_name: &str) -> Result<Vec<Order>> {
+        self.check_symbol(symbol)?;
+
+        Ok(self
+            .orders
+            .iter()
+            .filter(|sim_order| sim_order.strategy_name == strategy_name)
+            .map(|sim_order| sim_order.order.clone())
+            .collect())
+    }
+
+    async fn subscribe(&mut self, symbol: &str) -> Result<()> {
+        self.check_symbol(symbol)?;
+        self.subscribed = true;
+        Ok(())
+    }
+
+    async fn next_event(
+        &mut self,
+        symbol: &str,
+        strategy_name: &str,
+    ) -> Result<Option<BrokerEvent>> {
+        self.check_symbol(symbol)?;
+
+        if !self.subscribed {
+            return Err(RsAlgoError {
+                err: RsAlgoErrorKind::NoResponse,
+            });
+        }
+
+        // Late pollers join on the current bar instead of moving the feed forward
+        if !self.events.contains_key(strategy_name) {
+            let mut events = VecDeque::new();
+
+            if let (SimFeed::Candles(_), Some(candle)) = (&self.feed, self.history.last()) {
+                if self.cursor > 0 {
+                    events.push_back(BrokerEvent::Candle(*candle));
+                }
+            }
+            if let Some(tick) = &self.tick {
+                events.push_back(BrokerEvent::Tick(tick.clone()));
+            }
+
+            self.events.insert(strategy_name.to_owned(), events);
+        }
+
+        if self.events[strategy_name].is_empty() {
+            match self.cursor < self.feed.len() {
+                true => self.step()?,
+                false => {
+                    return Err(RsAlgoError {
+                        err: RsAlgoErrorKind::ConnectionError,
+                    })
+                }
+            }
+        }
+
+        Ok(self
+            .events
+            .get_mut(strategy_name)
+            .and_then(|events| events.pop_front()))
+    }
+
+    async fn shutdown(&mut self) -> Result<()> {
+        log::info!("Stopping simulated broker");
+        self.cursor = self.feed.len();
+        self.orders.clear();
+        self.events.clear();
+        self.subscribed = false;
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SimBrokerBuilder {
+    config: Option<RsAlgoConfig>,
+    symbol: Option<String>,
+    feed: Option<SimFeed>,
+    history: Option<VEC_DOHLC>,
+    spread: Option<f64>,
+    slippage_pips: Option<f64>,
+    commission: Option<f64>,
+    costs: Option<CostModel>,
+    pip_size: Option<f64>,
+    leverage: Option<f64>,
+    fx_rates: Option<FxRates>,
+    currency: Option<String>,
+    balance: Option<f64>,
+}
+
+impl SimBrokerBuilder {
+    pub fn new() -> Self {
+        Self {
+            config: None,
+            symbol: None,
+            feed: None,
+            history: None,
+            spread: None,
+            slippage_pips: None,
+            commission: None,
+            costs: None,
+            pip_size: None,
+            leverage: None,
+            fx_rates: None,
+            currency: None,
+            balance: None,
+        }
+    }
+
+    pub fn config(mut self, config: RsAlgoConfig) -> Self {
+        self.config = Some(config);
+        self
+    }
+
+    pub fn symbol(mut self, symbol: String) -> Self {
+        self.symbol = Some(symbol);
+        self
+    }
+
+    pub fn candles(mut self, candles: VEC_DOHLC) -> Self {
+        self.feed = Some(SimFeed::Candles(candles));
+        self
+    }
+
+    pub fn ticks(mut self, ticks: Vec<InstrumentTick>) -> Self {
+        self.feed = Some(SimFeed::Ticks(ticks));
+        self
+    }
+
+    pub fn history(mut self, history: VEC_DOHLC) -> Self {
+        self.history = Some(history);
+        self
+    }
+
+    pub fn spread(mut self, spread: f64) -> Self {
+        self.spread = Some(spread);
+        self
+    }
+
+    pub fn slippage_pips(mut self, slippage_pips: f64) -> Self {
+        self.slippage_pips = Some(slippage_pips);
+        self
+    }
+
+    // Per lot commission, ignored when a cost model is given
+    pub fn commission(mut self, commission: f64) -> Self {
+        self.commission = Some(commission);
+        self
+    }
+
+    pub fn costs(mut self, costs: CostModel) -> Self {
+        self.costs = Some(costs);
+        self
+    }
+
+    pub fn pip_size(mut self, pip_size: f64) -> Self {
+        self.pip_size = Some(pip_size);
+        self
+    }
+
+    pub fn leverage(mut self, leverage: f64) -> Self {
+        self.leverage = Some(leverage);
+        self
+    }
+
+    pub fn fx_rates(mut self, fx_rates: FxRates) -> Self {
+        self.fx_rates = Some(fx_rates);
+        self
+    }
+
+    pub fn currency(mut self, currency: String) -> Self {
+        self.currency = Some(currency);
+        self
+    }
+
+    pub fn balance(mut self, balance: f64) -> Self {
+        self.balance = Some(balance);
+        self
+    }
+
+    pub fn file<P: AsRef<Path>>(mut self, path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let content = fs::read_to_string(path).map_err(|err| {
+            log::error!("Can't read {} ({})", path.display(), err);
+            RsAlgoError {
+                err: RsAlgoErrorKind::CantRead,
+            }
+        })?;
+
+        let feed = match path.extension().and_then(|ext| ext.to_str()) {
+            Some("json") => parse_json_feed(&content)?,
+            Some("csv") => SimFeed::Candles(parse_csv_candles(&content)?),
+            _ => {
+                log::error!("Unsupported replay file {}", path.display());
+                return Err(RsAlgoError {
+                    err: RsAlgoErrorKind::ParseError,
+                });
+            }
+        };
+
+        self.feed = Some(feed);
+        Ok(self)
+    }
+
+    pub fn build(self) -> std::result::Result<SimBroker, &'static str> {
+        let config = self.config.ok_or("Config is required")?;
+
+        let symbol = match self.symbol {
+            Some(symbol) => symbol,
+            None => config.symbol.clone().ok_or("Symbol is required")?,
+        };
+
+        let pip_size = self.pip_size.unwrap_or_else(|| number_pips(&symbol));
+        let slippage_pips = self.slippage_pips.unwrap_or(config.slippage_pips);
+        let leverage = self.leverage.unwrap_or(config.leverage);
+        let account = Account::new()
+            .currency(
+                self.currency
+                    .unwrap_or_else(|| config.account_currency.clone()),
+            )
+            .balance(self.balance.unwrap_or(config.equity))
+            .leverage(leverage)
+            .contract_size(config.contract_size)
+            .build()?;
+        let fx_rates = self
+            .fx_rates
+            .unwrap_or_else(|| FxRates::new(account.currency(), config.contract_size));
+        let costs = match (self.costs, self.commission) {
+            (Some(costs), _) => costs,
+            (None, Some(commission)) => CostModel::new()
+                .commission(commission)
+                .contract_size(config.contract_size)
+                .build()?,
+            (None, None) => CostModel::from_config(&config, &symbol),
+        };
+
+        Ok(SimBroker {
+            symbol,
+            feed: self.feed.unwrap_or(SimFeed::Candles(vec![])),
+            history: self.history.unwrap_or_default(),
+            cursor: 0,
+            spread: self.spread.unwrap_or(0.),
+            slippage_pips,
+            costs,
+            pip_size,
+            account,
+            fx_rates,
+            tick: None,
+            positions: vec![],
+            closed: vec![],
+            orders: vec![],
+            events: HashMap::new(),
+            subscribed: false,
+        })
+    }
+}
+
+fn pending_trade_out(trade_in: &TradeIn, trade_type: TradeType) -> TradeOut {
+    TradeOut {
+        id: trade_in.id,
+        trade_type,
+        status: TradeStatus::Pending,
+        index_in: trade_in.index_in,
+        price_in: trade_in.price_in,
+        size: trade_in.size,
+        ask: trade_in.ask,
+        spread_in: trade_in.spread,
+        date_in: trade_in.date_in,
+        index_out: 0,
+        price_origin: trade_in.price_in,
+        price_out: 0.,
+        bid: 0.,
+        spread_out: 0.,
+        date_out: trade_in.date_in,
+        profit: 0.,
+        profit_per: 0.,
+        run_up: 0.,
+        run_up_per: 0.,
+        draw_down: 0.,
+        draw_down_per: 0.,
+        commission: 0.,
+        spread_cost: 0.,
+        swap: 0.,
+    }
+}
+
+fn position_order(
+    trade_in: &TradeIn,
+    order_type: OrderType,
+    target_price: f64,
+    date: DbDateTime,
+) -> Order {
+    Order {
+        id: trade_in.id,
+        trade_id: trade_in.id,
+        index_created: trade_in.index_in,
+        index_fulfilled: 0,
+        size: trade_in.size,
+        order_type,
+        status: OrderStatus::Pending,
+        origin_price: trade_in.price_in,
+        target_price,
+        created_at: date,
+        updated_at: None,
+        full_filled_at: None,
+        valid_until: None,
+        meta: None,
+    }
+}
+
+fn parse_json_feed(content: &str) -> Result<SimFeed> {
+    if let Ok(candles) = serde_json::from_str::<VEC_DOHLC>(content) {
+        return Ok(SimFeed::Candles(candles));
+    }
+
+    serde_json::from_str::<Vec<InstrumentTick>>(content)
+        .map(SimFeed::Ticks)
+        .map_err(|err| {
+            log::error!("Can't parse replay data ({})", err);
+            RsAlgoError {
+                err: RsAlgoErrorKind::ParseError,
+            }
+        })
+}
+
+fn parse_csv_candles(content: &str) -> Result<VEC_DOHLC> {
+    let mut candles = vec![];
+
+    for line in content.lines().map(|line| line.trim()) {
+        if line.is_empty() {
+            continue;
+        }
+
+        let values: Vec<&str> = line.split(',').map(|value| value.trim()).collect();
+
+        let timestamp = match values[0].parse::<i64>() {
+            Ok(timestamp) => timestamp,
+            Err(_) if candles.is_empty() => continue,
+            Err(_) => {
+                return Err(RsAlgoError {
+                    err: RsAlgoErrorKind::ParseError,
+                })
+            }
+        };
+
+        let prices = values[1..]
+            .iter()
+            .map(|value| value.parse::<f64>())
+            .collect::<std::result::Result<Vec<f64>, _>>()
+            .map_err(|_| RsAlgoError {
+                err: RsAlgoErrorKind::ParseError,
+            })?;
+
+        if prices.len() < 5 {
+            return Err(RsAlgoError {
+                err: RsAlgoErrorKind::InvalidCandle,
+            });
+        }
+
+        candles.push((
+            parse_time_seconds(timestamp),
+            prices[0],
+            prices[1],
+            prices[2],
+            prices[3],
+            prices[4],
+        ));
+    }
+
+    Ok(candles)
+}
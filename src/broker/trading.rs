@@ -0,0 +1,177 @@
+use crate::broker::reconcile::BrokerPosition;
+use crate::broker::{TransactionDetails, DOHLC, VEC_DOHLC};
+use crate::config::RsAlgoConfig;
+use crate::error::Result;
+use crate::models::account::AccountInfo;
+use crate::models::bot::BotData;
+use crate::models::market::MarketHours;
+use crate::models::order::Order;
+use crate::models::risk::RiskGuard;
+use crate::models::swap::InstrumentSwap;
+use crate::models::symbol_spec::SymbolSpec;
+use crate::models::tick::InstrumentTick;
+use crate::models::trade::{PositionResult, TradeAmend, TradeIn, TradeOut};
+use crate::scanner::instrument::Instrument;
+use crate::ws::message::{
+    InstrumentData, ResponseBody, ResponseType, TradeData, TradeOptions, TradeResponse,
+};
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Serialize, Deserialize)]
+pub enum BrokerEvent {
+    Candle(DOHLC),
+    Tick(InstrumentTick),
+    TradeIn(TradeResponse<TradeIn>),
+    TradeOut(TradeResponse<TradeOut>),
+    Order(Order),
+    Account(AccountInfo),
+    Reconnect,
+}
+
+impl BrokerEvent {
+    pub fn into_message(self) -> String {
+        match self {
+            BrokerEvent::Candle(ohlc) => serde_json::to_string(&ResponseBody {
+                response: ResponseType::SubscribeStream,
+                payload: Some(ohlc),
+            }),
+            BrokerEvent::Tick(tick) => serde_json::to_string(&ResponseBody {
+                response: ResponseType::SubscribeTickPrices,
+                payload: Some(tick),
+            }),
+            BrokerEvent::TradeIn(trade_in) => serde_json::to_string(&ResponseBody {
+                response: ResponseType::TradeInFulfilled,
+                payload: Some(trade_in),
+            }),
+            BrokerEvent::TradeOut(trade_out) => serde_json::to_string(&ResponseBody {
+                response: ResponseType::TradeOutFulfilled,
+                payload: Some(trade_out),
+            }),
+            BrokerEvent::Order(order) => serde_json::to_string(&ResponseBody {
+                response: ResponseType::OrderUpdated,
+                payload: Some(order),
+            }),
+            BrokerEvent::Account(account) => serde_json::to_string(&ResponseBody {
+                response: ResponseType::AccountUpdated,
+                payload: Some(account),
+            }),
+            BrokerEvent::Reconnect => serde_json::to_string(&ResponseBody::<()> {
+                response: ResponseType::Reconnect,
+                payload: None,
+            }),
+        }
+        .unwrap()
+    }
+}
+
+#[async_trait::async_trait]
+pub trait TradingBroker {
+    async fn connect(config: &RsAlgoConfig) -> Result<Self>
+    where
+        Self: Sized;
+    async fn authenticate(&mut self, username: &str, password: &str) -> Result<()>;
+    async fn candles(
+        &mut self,
+        symbol: &str,
+        time_frame: usize,
+        from: i64,
+    ) -> Result<InstrumentData<VEC_DOHLC>>;
+    async fn historic_candles(
+        &mut self,
+        symbol: &str,
+        time_frame: usize,
+        from: i64,
+        to: i64,
+    ) -> Result<InstrumentData<VEC_DOHLC>>;
+    async fn tick(&mut self, symbol: &str) -> Result<InstrumentTick>;
+    async fn swap(&mut self, symbol: &str) -> Result<InstrumentSwap>;
+    async fn symbol_spec(&mut self, symbol: &str) -> Result<SymbolSpec>;
+    async fn load_symbol_specs(&mut self) -> Result<usize>;
+    async fn market_hours(&mut self, symbol: &str) -> Result<MarketHours>;
+    async fn market_is_open(&mut self, symbol: &str) -> Result<bool>;
+    async fn positions(&mut self, symbol: &str, strategy_name: &str) -> Result<PositionResult>;
+    async fn open_positions(
+        &mut self,
+        symbol: &str,
+        strategy_name: &str,
+    ) -> Result<Vec<BrokerPosition>>;
+    async fn trade_history(
+        &mut self,
+        symbol: &str,
+        strategy_name: &str,
+        from: i64,
+    ) -> Result<Vec<TransactionDetails>>;
+    async fn account(&mut self) -> Result<AccountInfo>;
+    async fn enter_trade(
+        &mut self,
+        trade: TradeData<TradeIn>,
+        orders: Option<Vec<Order>>,
+    ) -> Result<TradeResponse<TradeIn>>;
+    async fn exit_trade(&mut self, trade: TradeData<TradeOut>) -> Result<TradeResponse<TradeOut>>;
+    async fn enter_order(
+        &mut self,
+        trade: TradeData<TradeIn>,
+        order: TradeData<Order>,
+    ) -> Result<TradeResponse<TradeIn>>;
+    async fn exit_order(
+        &mut self,
+        trade: TradeData<TradeOut>,
+        order: TradeData<Order>,
+    ) -> Result<TradeResponse<TradeOut>>;
+    async fn amend_trade(
+        &mut self,
+        trade: TradeData<TradeAmend>,
+    ) -> Result<TradeResponse<TradeAmend>>;
+    async fn place_order(&mut self, order: TradeData<Order>) -> Result<TradeResponse<Order>>;
+    async fn modify_order(&mut self, order: TradeData<Order>) -> Result<TradeResponse<Order>>;
+    async fn cancel_order(&mut self, order: TradeData<Order>) -> Result<TradeResponse<Order>>;
+    async fn pending_orders(&mut self, symbol: &str, strategy_name: &str) -> Result<Vec<Order>>;
+    async fn subscribe(&mut self, symbol: &str) -> Result<()>;
+    async fn next_event(
+        &mut self,
+        symbol: &str,
+        strategy_name: &str,
+    ) -> Result<Option<BrokerEvent>>;
+    async fn shutdown(&mut self) -> Result<()>;
+}
+
+// Entries go through the portfolio risk guard of all the running bots first
+pub async fn enter_guarded_trade<B: TradingBroker + ?Sized>(
+    broker: &mut B,
+    guard: &mut RiskGuard,
+    bots: &[BotData],
+    instrument: &Instrument,
+    trade: TradeData<TradeIn>,
+    orders: Option<Vec<Order>>,
+) -> Result<TradeResponse<TradeIn>> {
+    guard.check_trade_in(bots, instrument, &trade.data)?;
+    broker.enter_trade(trade, orders).await
+}
+
+// Sends the stop and target changes of open trades, trailed stops among them
+pub async fn amend_trades<B: TradingBroker + ?Sized>(
+    broker: &mut B,
+    symbol: &str,
+    strategy_name: &str,
+    amends: Vec<TradeAmend>,
+) -> Result<Vec<TradeResponse<TradeAmend>>> {
+    let mut responses = vec![];
+
+    for amend in amends {
+        let response = broker
+            .amend_trade(TradeData {
+                symbol: symbol.to_owned(),
+                strategy_name: strategy_name.to_owned(),
+                data: amend,
+                options: TradeOptions {
+                    non_profitable_out: false,
+                },
+            })
+            .await?;
+
+        responses.push(response);
+    }
+
+    Ok(responses)
+}
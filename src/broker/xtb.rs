@@ -1,5 +1,6 @@
 use super::*;
-use crate::error::Result;
+use crate::config::RsAlgoConfig;
+use crate::error::{Result, RsAlgoError, RsAlgoErrorKind};
 use crate::ws::ws_client::WebSocket;
 
 use crate::helpers::date::parse_time_seconds;
@@ -10,12 +11,13 @@ use futures_util::Future;
 use crate::ws::message::{Message, ResponseBody, ResponseType};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
-use std::env;
 use std::fmt::Debug;
 
 #[async_trait::async_trait]
 pub trait Broker {
-    async fn new() -> Self;
+    async fn new(config: &RsAlgoConfig) -> Result<Self>
+    where
+        Self: Sized;
     async fn login(&mut self, username: &str, password: &str) -> Result<&mut Self>
     where
         Self: Sized;
@@ -58,16 +60,16 @@ pub struct Xtb {
 
 #[async_trait::async_trait]
 impl Broker for Xtb {
-    async fn new() -> Self {
-        let url = &env::var("BROKER_URL").unwrap();
+    async fn new(config: &RsAlgoConfig) -> Result<Self> {
+        let url = &config.broker_url;
 
-        Self {
-            websocket: WebSocket::connect(url),
+        Ok(Self {
+            websocket: WebSocket::connect(url)?,
             streamSessionId: "".to_owned(),
             symbol: "".to_owned(),
             time_frame: 0,
             from_date: 0,
-        }
+        })
     }
 
     async fn login(&mut self, username: &str, password: &str) -> Result<&mut Self> {
@@ -302,18 +304,21 @@ impl Xtb {
             Message::Text(txt) => txt,
             _ => panic!(),
         };
-        let res = self.handle_response::<VEC_DOHLC>(&txt_msg).await.unwrap();
+        let res = self.handle_response::<VEC_DOHLC>(&txt_msg).await?;
 
         Ok(res)
     }
 
     pub async fn parse_message(&mut self, msg: &str) -> Result<Value> {
-        let parsed: Value = serde_json::from_str(&msg).expect("Can't parse to JSON");
+        let parsed: Value = serde_json::from_str(&msg).map_err(|_| RsAlgoError {
+            err: RsAlgoErrorKind::ParseError,
+        })?;
+        xtb_error::check_response(&parsed)?;
         Ok(parsed)
     }
 
     pub async fn handle_response<'a, T>(&mut self, msg: &str) -> Result<Response<VEC_DOHLC>> {
-        let data = self.parse_message(&msg).await.unwrap();
+        let data = self.parse_message(&msg).await?;
 
         let response: Response<VEC_DOHLC> = match &data {
             // Login
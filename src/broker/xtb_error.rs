@@ -0,0 +1,40 @@
+use crate::error::{Result, RsAlgoError, RsAlgoErrorKind};
+
+use serde_json::Value;
+
+pub fn error_kind(code: &str, description: &str) -> RsAlgoErrorKind {
+    let (code, description) = (code.to_owned(), description.to_owned());
+
+    match code.as_str() {
+        "BE004" | "BE005" | "BE117" | "EX004" | "EX007" => {
+            RsAlgoErrorKind::LoginFailed(code, description)
+        }
+        "BE094" | "BE095" | "BE115" | "BE116" => RsAlgoErrorKind::InvalidSymbol(code, description),
+        "BE006" => RsAlgoErrorKind::MarketClosed(code, description),
+        "BE018" | "BE019" => RsAlgoErrorKind::TradingDisabled(code, description),
+        "BE101" | "BE103" => RsAlgoErrorKind::OrderNotFound(code, description),
+        "BE009" => RsAlgoErrorKind::InsufficientMargin(code, description),
+        "BE003" => RsAlgoErrorKind::InvalidVolume(code, description),
+        "BE001" | "BE010" | "BE013" => RsAlgoErrorKind::InvalidPrice(code, description),
+        "BE002" => RsAlgoErrorKind::InvalidStop(code, description),
+        "BE014" | "BE016" | "BE017" | "EX008" | "EX009" | "EX010" => {
+            RsAlgoErrorKind::RequestLimit(code, description)
+        }
+        _ => RsAlgoErrorKind::BrokerError(code, description),
+    }
+}
+
+pub fn check_response(data: &Value) -> Result<()> {
+    match data["status"].as_bool() {
+        Some(false) => {
+            let code = data["errorCode"].as_str().unwrap_or_default();
+            let description = data["errorDescr"].as_str().unwrap_or_default();
+            log::error!("Broker error {}: {}", code, description);
+
+            Err(RsAlgoError {
+                err: error_kind(code, description),
+            })
+        }
+        _ => Ok(()),
+    }
+}
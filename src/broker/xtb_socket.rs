@@ -0,0 +1,226 @@
+use crate::broker::rate_limiter::{RateLimiter, RateLimiterMetrics};
+use crate::error::{Result, RsAlgoError, RsAlgoErrorKind};
+
+use futures_util::stream::SplitStream;
+use futures_util::{SinkExt, StreamExt};
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::net::TcpStream;
+use tokio::sync::{mpsc, oneshot};
+use tokio::time::timeout;
+use tokio_tungstenite::{connect_async, MaybeTlsStream, WebSocketStream};
+use tungstenite::Message;
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+type PendingRequests = Arc<Mutex<VecDeque<(String, oneshot::Sender<String>)>>>;
+
+#[derive(Debug, Clone)]
+pub struct XtbSocket {
+    url: String,
+    limiter: RateLimiter,
+    metrics: Arc<Mutex<RateLimiterMetrics>>,
+    sender: mpsc::UnboundedSender<(Message, Instant)>,
+    pending: PendingRequests,
+    unsolicited: Arc<tokio::sync::Mutex<mpsc::UnboundedReceiver<String>>>,
+    next_tag: Arc<AtomicU64>,
+}
+
+impl XtbSocket {
+    pub async fn connect(url: &str, limiter: RateLimiter) -> Result<Self> {
+        let (socket, _) = connect_async(url).await.map_err(|err| {
+            log::error!("Can't connect to {} ({})", url, err);
+            RsAlgoError {
+                err: RsAlgoErrorKind::ConnectionError,
+            }
+        })?;
+
+        log::info!("Connected to {} !", &url);
+
+        let (mut write, read) = socket.split();
+        let (sender, mut outgoing) = mpsc::unbounded_channel::<(Message, Instant)>();
+        let (unsolicited_sender, unsolicited) = mpsc::unbounded_channel();
+        let pending: PendingRequests = Arc::new(Mutex::new(VecDeque::new()));
+        let metrics = Arc::new(Mutex::new(RateLimiterMetrics::default()));
+
+        let mut writer_limiter = limiter.clone();
+        let writer_metrics = metrics.clone();
+
+        tokio::spawn(async move {
+            while let Some((msg, queued_at)) = outgoing.recv().await {
+                let is_close = matches!(msg, Message::Close(_));
+
+                if let Message::Text(_) = msg {
+                    let throttled = writer_limiter.acquire().await;
+                    writer_metrics
+                        .lock()
+                        .unwrap()
+                        .dequeued(queued_at.elapsed(), throttled);
+                }
+
+                if write.send(msg).await.is_err() || is_close {
+                    break;
+                }
+            }
+        });
+
+        tokio::spawn(read_responses(
+            read,
+            pending.clone(),
+            unsolicited_sender,
+            sender.clone(),
+        ));
+
+        Ok(Self {
+            url: url.to_string(),
+            limiter,
+            metrics,
+            sender,
+            pending,
+            unsolicited: Arc::new(tokio::sync::Mutex::new(unsolicited)),
+            next_tag: Arc::new(AtomicU64::new(1)),
+        })
+    }
+
+    pub async fn re_connect(&mut self) -> Result<()> {
+        log::info!("Reconnecting to the server...");
+        self.disconnect().await.ok();
+        *self = Self::connect(&self.url, self.limiter.clone()).await?;
+        Ok(())
+    }
+
+    pub async fn request<T: Serialize>(&self, command: &T) -> Result<Message> {
+        let mut value = serde_json::to_value(command).map_err(|_| RsAlgoError {
+            err: RsAlgoErrorKind::ParseError,
+        })?;
+
+        let tag = self.next_tag.fetch_add(1, Ordering::Relaxed).to_string();
+        value["customTag"] = Value::String(tag.clone());
+
+        let (response_sender, response) = oneshot::channel();
+        self.pending
+            .lock()
+            .unwrap()
+            .push_back((tag.clone(), response_sender));
+
+        self.metrics.lock().unwrap().enqueued();
+
+        if self
+            .sender
+            .send((Message::text(value.to_string()), Instant::now()))
+            .is_err()
+        {
+            self.metrics.lock().unwrap().dequeued(Duration::ZERO, false);
+            self.forget(&tag);
+            log::error!("Error sending command {}", value["command"]);
+            return Err(RsAlgoError {
+                err: RsAlgoErrorKind::SendingAfter,
+            });
+        }
+
+        match timeout(REQUEST_TIMEOUT, response).await {
+            Ok(Ok(txt)) => Ok(Message::Text(txt)),
+            Ok(Err(_)) => Err(RsAlgoError {
+                err: RsAlgoErrorKind::ConnectionError,
+            }),
+            Err(_) => {
+                self.forget(&tag);
+                log::error!("No response for command {}", value["command"]);
+                Err(RsAlgoError {
+                    err: RsAlgoErrorKind::NoResponse,
+                })
+            }
+        }
+    }
+
+    pub async fn read(&self) -> Result<Message> {
+        match self.unsolicited.lock().await.recv().await {
+            Some(txt) => Ok(Message::Text(txt)),
+            None => Err(RsAlgoError {
+                err: RsAlgoErrorKind::ConnectionError,
+            }),
+        }
+    }
+
+    pub fn in_flight(&self) -> usize {
+        self.pending.lock().unwrap().len()
+    }
+
+    pub fn metrics(&self) -> RateLimiterMetrics {
+        self.metrics.lock().unwrap().clone()
+    }
+
+    pub async fn disconnect(&self) -> Result<()> {
+        self.sender
+            .send((Message::Close(None), Instant::now()))
+            .map_err(|_| RsAlgoError {
+                err: RsAlgoErrorKind::ConnectionError,
+            })
+    }
+
+    fn forget(&self, tag: &str) {
+        self.pending
+            .lock()
+            .unwrap()
+            .retain(|(pending_tag, _)| pending_tag != tag);
+    }
+}
+
+async fn read_responses(
+    mut read: SplitStream<WebSocketStream<MaybeTlsStream<TcpStream>>>,
+    pending: PendingRequests,
+    unsolicited: mpsc::UnboundedSender<String>,
+    sender: mpsc::UnboundedSender<(Message, Instant)>,
+) {
+    while let Some(msg) = read.next().await {
+        match msg {
+            Ok(Message::Text(txt)) => dispatch_response(txt, &pending, &unsolicited),
+            Ok(Message::Ping(data)) => {
+                sender.send((Message::Pong(data), Instant::now())).ok();
+            }
+            Ok(Message::Close(_)) => break,
+            Ok(_) => (),
+            Err(err) => {
+                log::error!("Error reading msg: {}", err);
+                break;
+            }
+        }
+    }
+
+    // Dropping the senders fails every request still waiting for an answer
+    pending.lock().unwrap().clear();
+}
+
+fn dispatch_response(
+    txt: String,
+    pending: &PendingRequests,
+    unsolicited: &mpsc::UnboundedSender<String>,
+) {
+    let tag = serde_json::from_str::<Value>(&txt)
+        .ok()
+        .and_then(|value| value["customTag"].as_str().map(|tag| tag.to_owned()));
+
+    let mut pending = pending.lock().unwrap();
+
+    // Untagged responses are answered in order, as the protocol does without customTag
+    let position = match tag {
+        Some(tag) => pending
+            .iter()
+            .position(|(pending_tag, _)| *pending_tag == tag),
+        None if !pending.is_empty() => Some(0),
+        None => None,
+    };
+
+    match position.and_then(|index| pending.remove(index)) {
+        Some((_, response_sender)) => {
+            response_sender.send(txt).ok();
+        }
+        None => {
+            unsolicited.send(txt).ok();
+        }
+    }
+}
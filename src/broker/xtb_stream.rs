@@ -1,40 +1,51 @@
 use crate::broker::models::*;
+use crate::broker::rate_limiter::RateLimiter;
+use crate::broker::reconcile::BrokerPosition;
+use crate::broker::retry::{self, RetryPolicy};
+use crate::broker::trading::{BrokerEvent, TradingBroker};
+use crate::broker::xtb_error;
+use crate::broker::xtb_socket::XtbSocket;
+use crate::config::RsAlgoConfig;
 use crate::error::{Result, RsAlgoError, RsAlgoErrorKind};
 use crate::helpers::calc::*;
-use crate::helpers::date::{self, parse_time_seconds, DateTime, Local, Timelike};
+use crate::helpers::date::{self, parse_time_seconds, Local, Timelike};
 use crate::helpers::http::request;
 use crate::helpers::http::HttpMethod;
 use crate::helpers::uuid;
 use crate::helpers::{calc, date::*};
-use crate::models::environment;
+use crate::models::account::AccountInfo;
 use crate::models::market::*;
 use crate::models::mode;
 use crate::models::order::*;
 use crate::models::stop_loss::StopLossType;
 use crate::models::swap::InstrumentSwap;
+use crate::models::symbol_spec::{self, SymbolSpec};
 use crate::models::tick::InstrumentTick;
 use crate::models::time_frame::*;
 use crate::models::trade::*;
 use crate::ws::message::{
     InstrumentData, Message, ResponseBody, ResponseType, TradeData, TradeResponse,
 };
-use crate::ws::ws_client::WebSocket;
 use crate::ws::ws_stream_client::WebSocket as WebSocketClientStream;
 
 use futures_util::{stream::SplitStream, Future};
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
-use std::env;
+use std::collections::HashMap;
 use std::fmt::Debug;
 use std::time::Duration;
 use tokio::net::TcpStream;
-use tokio::time::sleep;
+use tokio::task::JoinHandle;
+use tokio::time::{sleep, timeout};
 use tokio_tungstenite::MaybeTlsStream;
 use tokio_tungstenite::WebSocketStream;
 
 #[async_trait::async_trait]
 pub trait BrokerStream {
-    async fn new() -> Self;
+    async fn new(config: &RsAlgoConfig) -> Result<Self>
+    where
+        Self: Sized;
     async fn login(&mut self, username: &str, password: &str) -> Result<&mut Self>
     where
         Self: Sized;
@@ -107,6 +118,45 @@ pub trait BrokerStream {
         trade: TradeData<TradeOut>,
         order: TradeData<Order>,
     ) -> Result<ResponseBody<TradeResponse<TradeOut>>>;
+    async fn modify_trade(
+        &mut self,
+        trade: TradeData<TradeAmend>,
+    ) -> Result<ResponseBody<TradeResponse<TradeAmend>>>;
+    async fn modify_trade_real(
+        &mut self,
+        trade: TradeData<TradeAmend>,
+    ) -> Result<ResponseBody<TradeResponse<TradeAmend>>>;
+    async fn modify_trade_test(
+        &mut self,
+        trade: TradeData<TradeAmend>,
+    ) -> Result<ResponseBody<TradeResponse<TradeAmend>>>;
+    async fn place_order(
+        &mut self,
+        order: TradeData<Order>,
+    ) -> Result<ResponseBody<TradeResponse<Order>>>;
+    async fn modify_order(
+        &mut self,
+        order: TradeData<Order>,
+    ) -> Result<ResponseBody<TradeResponse<Order>>>;
+    async fn cancel_order(
+        &mut self,
+        order: TradeData<Order>,
+    ) -> Result<ResponseBody<TradeResponse<Order>>>;
+    async fn order_transaction_real(
+        &mut self,
+        order: TradeData<Order>,
+        action: TransactionAction,
+    ) -> Result<ResponseBody<TradeResponse<Order>>>;
+    async fn order_transaction_test(
+        &mut self,
+        order: TradeData<Order>,
+        action: TransactionAction,
+    ) -> Result<ResponseBody<TradeResponse<Order>>>;
+    async fn get_pending_orders(
+        &mut self,
+        symbol: &str,
+        strategy_name: &str,
+    ) -> Result<ResponseBody<Vec<Order>>>;
     async fn get_active_positions(
         &mut self,
         symbol: &str,
@@ -114,7 +164,7 @@ pub trait BrokerStream {
     ) -> Result<ResponseBody<PositionResult>>;
     async fn get_market_hours(&mut self, symbol: &str) -> Result<ResponseBody<MarketHours>>;
     async fn is_market_open(&mut self, symbol: &str) -> Result<ResponseBody<bool>>;
-    async fn is_market_available(&mut self, symbol: &str) -> bool;
+    async fn is_market_available(&mut self, symbol: &str) -> Result<bool>;
     async fn get_instrument_tick(&mut self, symbol: &str) -> Result<ResponseBody<InstrumentTick>>;
     async fn get_instrument_swap(&mut self, symbol: &str) -> Result<ResponseBody<InstrumentSwap>>;
     async fn get_instrument_tick_test(
@@ -140,49 +190,71 @@ pub trait BrokerStream {
     async fn subscribe_stream(&mut self, symbol: &str) -> Result<()>;
     async fn subscribe_tick_prices(&mut self, symbol: &str) -> Result<()>;
     async fn subscribe_trades(&mut self, symbol: &str) -> Result<()>;
+    async fn subscribe_balance(&mut self) -> Result<()>;
     async fn parse_stream_data(msg: Message, symbol: &str, strategy_name: &str) -> Option<String>;
     async fn keepalive_ping(&mut self) -> Result<String>;
+    async fn heartbeat(&mut self) -> Result<()>;
+    async fn reconnect(&mut self) -> Result<()>;
     async fn disconnect(&mut self) -> Result<()>;
 }
 
+#[derive(Debug, Clone, PartialEq)]
+enum StreamSubscription {
+    Candles(String),
+    TickPrices(String),
+    Trades,
+    Balance,
+}
+
 #[derive(Debug)]
 pub struct Xtb {
-    socket: WebSocket,
+    config: RsAlgoConfig,
+    socket: XtbSocket,
     stream: WebSocketClientStream,
     symbol: String,
     streamSessionId: String,
     time_frame: usize,
     from_date: i64,
+    credentials: Option<(String, String)>,
+    subscriptions: Vec<StreamSubscription>,
+    heartbeat: Option<JoinHandle<()>>,
+    // Open positions of the bot by position id, with their current size
+    positions: HashMap<usize, f64>,
 }
 
 #[async_trait::async_trait]
 impl BrokerStream for Xtb {
-    async fn new() -> Self {
+    async fn new(config: &RsAlgoConfig) -> Result<Self> {
         let mut socket;
         let stream;
-        let socket_url = &env::var("BROKER_URL").unwrap();
-        let stream_url = &env::var("BROKER_STREAM_URL").unwrap();
-        let stream_subscribe = env::var("STREAM_SUBSCRIBE")
-            .unwrap()
-            .parse::<bool>()
-            .unwrap();
+        let socket_url = &config.broker_url;
+        let stream_url = &config.broker_stream_url;
+        let limiter = RateLimiter::new(
+            Duration::from_millis(config.command_interval),
+            config.command_burst,
+        );
 
-        if stream_subscribe {
-            socket = WebSocket::connect(socket_url);
-            stream = WebSocketClientStream::connect(stream_url).await;
+        if config.stream_subscribe {
+            socket = XtbSocket::connect(socket_url, limiter.clone()).await?;
+            stream = WebSocketClientStream::connect(stream_url).await?;
         } else {
-            socket = WebSocket::connect(socket_url);
-            stream = WebSocketClientStream::connect(socket_url).await;
+            socket = XtbSocket::connect(socket_url, limiter.clone()).await?;
+            stream = WebSocketClientStream::connect(socket_url).await?;
         }
 
-        Self {
+        Ok(Self {
+            config: config.clone(),
             socket: socket,
             stream: stream,
             streamSessionId: "".to_owned(),
             symbol: "".to_owned(),
             time_frame: 0,
             from_date: 0,
-        }
+            credentials: None,
+            subscriptions: vec![],
+            heartbeat: None,
+            positions: HashMap::new(),
+        })
     }
 
     fn get_session_id(&mut self) -> &String {
@@ -190,17 +262,18 @@ impl BrokerStream for Xtb {
     }
 
     async fn login(&mut self, username: &str, password: &str) -> Result<&mut Self> {
-        self.send(&Command {
-            command: String::from("login"),
-            arguments: LoginParams {
-                userId: String::from(username),
-                password: String::from(password),
-                appName: String::from("rs-algo-scanner"),
-            },
-        })
-        .await?;
-
-        let res = self.get_response().await?;
+        let res = self
+            .get_response(&Command {
+                command: String::from("login"),
+                arguments: LoginParams {
+                    userId: String::from(username),
+                    password: String::from(password),
+                    appName: String::from("rs-algo-scanner"),
+                },
+            })
+            .await?;
+        self.credentials = Some((username.to_owned(), password.to_owned()));
+        self.spawn_heartbeat();
 
         Ok(self)
     }
@@ -210,21 +283,18 @@ impl BrokerStream for Xtb {
     }
 
     async fn read(&mut self) -> Result<ResponseBody<InstrumentData<VEC_DOHLC>>> {
-        let msg = self.socket.read().await.unwrap();
-        let res = match msg {
-            Message::Text(txt) => txt,
-            _ => panic!(),
-        };
-        let response = self.handle_response::<VEC_DOHLC>(&res).await.unwrap();
+        let msg = self.socket.read().await?;
+        let res = text_message(msg)?;
+        let response = self.handle_response::<VEC_DOHLC>(&res).await?;
         Ok(response)
     }
 
     async fn get_symbols(&mut self) -> Result<ResponseBody<InstrumentData<VEC_DOHLC>>> {
-        self.send(&CommandAllSymbols {
-            command: "getAllSymbols".to_owned(),
-        })
-        .await?;
-        let res = self.get_response().await?;
+        let res = self
+            .get_response(&CommandAllSymbols {
+                command: "getAllSymbols".to_owned(),
+            })
+            .await?;
 
         Ok(res)
     }
@@ -254,9 +324,7 @@ impl BrokerStream for Xtb {
             date::parse_time_seconds(from_date)
         );
 
-        self.send(&instrument_command).await.unwrap();
-
-        let res = self.get_response().await?;
+        let res = self.get_response(&instrument_command).await?;
         Ok(res)
     }
 
@@ -267,21 +335,13 @@ impl BrokerStream for Xtb {
                 symbol: symbol.to_owned(),
             },
         };
-        self.send(&tick_command).await.unwrap();
-        let msg = self.socket.read().await.unwrap();
-        let res = match msg {
-            Message::Text(txt) => {
-                let tick = self.parse_tick_data(symbol.to_owned(), txt).unwrap();
-
-                ResponseBody {
-                    response: ResponseType::GetInstrumentTick,
-                    payload: Some(tick),
-                }
-            }
-            _ => panic!(),
-        };
+        let msg = self.socket.request(&tick_command).await?;
+        let tick = self.parse_tick_data(symbol.to_owned(), text_message(msg)?)?;
 
-        Ok(res)
+        Ok(ResponseBody {
+            response: ResponseType::GetInstrumentTick,
+            payload: Some(tick),
+        })
     }
 
     async fn get_instrument_swap(&mut self, symbol: &str) -> Result<ResponseBody<InstrumentSwap>> {
@@ -291,21 +351,13 @@ impl BrokerStream for Xtb {
                 symbol: symbol.to_owned(),
             },
         };
-        self.send(&tick_command).await.unwrap();
-        let msg = self.socket.read().await.unwrap();
-        let res = match msg {
-            Message::Text(txt) => {
-                let tick = self.parse_swap_data(symbol.to_owned(), txt).unwrap();
-
-                ResponseBody {
-                    response: ResponseType::GetInstrumentSwap,
-                    payload: Some(tick),
-                }
-            }
-            _ => panic!(),
-        };
+        let msg = self.socket.request(&tick_command).await?;
+        let swap = self.parse_swap_data(symbol.to_owned(), text_message(msg)?)?;
 
-        Ok(res)
+        Ok(ResponseBody {
+            response: ResponseType::GetInstrumentSwap,
+            payload: Some(swap),
+        })
     }
 
     async fn get_ask_bid(&mut self, symbol: &str) -> Result<(f64, f64)> {
@@ -315,21 +367,13 @@ impl BrokerStream for Xtb {
                 symbol: symbol.to_owned(),
             },
         };
-        self.send(&tick_command).await?;
-        let msg = self.socket.read().await.unwrap();
+        let msg = self.socket.request(&tick_command).await?;
+        let data = self.parse_message(&text_message(msg)?)?;
+        let return_data = field(&data, "returnData", Some)?;
+        let ask = field(return_data, "ask", Value::as_f64)?;
+        let bid = field(return_data, "bid", Value::as_f64)?;
 
-        if let Message::Text(txt) = msg {
-            let data = self.parse_message(&txt)?;
-            let return_data = data
-                .get("returnData")
-                .ok_or_else(|| RsAlgoErrorKind::ParseError)
-                .unwrap();
-            let ask = return_data["ask"].as_f64().unwrap();
-            let bid = return_data["bid"].as_f64().unwrap();
-            Ok((ask, bid))
-        } else {
-            panic!();
-        }
+        Ok((ask, bid))
     }
 
     async fn get_transaction_details(
@@ -347,30 +391,35 @@ impl BrokerStream for Xtb {
             },
         };
 
-        self.send(&command).await.unwrap();
-        let msg = self.socket.read().await.unwrap();
+        let msg = self.socket.request(&command).await.ok()?;
 
         if let Message::Text(txt) = msg {
-            let data = self.parse_message(&txt).unwrap();
-            let data = data["returnData"].as_array().unwrap();
+            let data = self.parse_message(&txt).ok()?;
+            let data = data["returnData"].as_array()?;
             for obj in data {
-                let order_symbol = obj["symbol"].as_str().unwrap();
-                let id = obj["position"].as_i64().unwrap() as usize;
-                let comments = obj["customComment"].as_str().unwrap();
-                let comments: TransactionComments = serde_json::from_str(comments).unwrap();
+                let order_symbol = obj["symbol"].as_str()?;
+                let id = obj["position"].as_i64()? as usize;
+                let comments = obj["customComment"].as_str().unwrap_or_default();
+                let comments: TransactionComments = match serde_json::from_str(comments) {
+                    Ok(comments) => comments,
+                    Err(_) => continue,
+                };
 
                 if order_symbol == symbol
                     && strategy_name == &comments.strategy_name
                     && position_id.map_or(true, |pid| pid == id)
                 {
-                    let open_price = obj["open_price"].as_f64().unwrap();
-                    let close_price = obj["close_price"].as_f64().unwrap();
+                    let open_price = obj["open_price"].as_f64()?;
+                    let close_price = obj["close_price"].as_f64()?;
 
                     return Some(TransactionDetails {
                         id,
                         open_price,
                         close_price,
                         profit: 0.,
+                        commission: obj["commission"].as_f64().unwrap_or(0.0),
+                        swap: obj["storage"].as_f64().unwrap_or(0.0),
+                        close_time: None,
                     });
                 }
             }
@@ -395,51 +444,13 @@ impl BrokerStream for Xtb {
             },
         };
 
-        self.send(&command).await.unwrap();
-        let msg = self.socket.read().await.unwrap();
-
-        if let Message::Text(txt) = msg {
-            let data = self.parse_message(&txt).unwrap();
-            let data = data["returnData"].as_array().unwrap();
-
-            for obj in data {
-                let order_symbol = obj["symbol"].as_str().unwrap();
-                let id = obj["position"].as_i64().unwrap() as usize;
-                let comments = obj["customComment"].as_str().unwrap();
-                let comments: TransactionComments = serde_json::from_str(comments).unwrap();
-
-                if order_symbol == symbol
-                    && strategy_name == &comments.strategy_name
-                    && position_id.map_or(true, |pid| pid == id)
-                {
-                    let open_price = obj["open_price"].as_f64().unwrap();
-                    let close_price = obj["close_price"].as_f64().unwrap();
-
-                    let gross_profit = obj["profit"].as_f64().unwrap();
-                    let swap = obj["storage"].as_f64().unwrap_or(0.0);
-                    let commission = obj["commission"].as_f64().unwrap_or(0.0);
-
-                    let profit = gross_profit + swap + commission;
-
-                    log::info!(
-                        "PROFIT DATA Gross Profit: {} Swap: {} Comission: {} Net Profit: {}",
-                        gross_profit,
-                        swap,
-                        commission,
-                        profit
-                    );
-
-                    return Some(TransactionDetails {
-                        id,
-                        open_price,
-                        close_price,
-                        profit,
-                    });
-                }
-            }
-            None
-        } else {
-            None
+        match self.socket.request(&command).await.ok()? {
+            Message::Text(txt) => self
+                .parse_trades_history(&txt, symbol, strategy_name)
+                .ok()?
+                .into_iter()
+                .find(|details| position_id.map_or(true, |pid| pid == details.id)),
+            _ => None,
         }
     }
 
@@ -473,9 +484,7 @@ impl BrokerStream for Xtb {
             date::parse_time_seconds(to)
         );
 
-        self.send(&instrument_command).await.unwrap();
-
-        let res = self.get_response().await?;
+        let res = self.get_response(&instrument_command).await?;
 
         Ok(res)
     }
@@ -488,17 +497,26 @@ impl BrokerStream for Xtb {
     ) -> Result<ResponseBody<InstrumentTick>> {
         let tick_url = &format!(
             "{}{}",
-            env::var("BACKEND_BACKTEST_PRICING_ENDPOINT").unwrap(),
-            symbol
+            self.config.backend_backtest_pricing_endpoint, symbol
         );
         let tick: InstrumentTick = request(&tick_url, &String::from("all"), HttpMethod::Get)
             .await
-            .unwrap()
+            .map_err(|err| {
+                log::error!("Can't get {} backtest price: {}", symbol, err);
+                RsAlgoError {
+                    err: RsAlgoErrorKind::RequestError,
+                }
+            })?
             .json()
             .await
-            .unwrap();
+            .map_err(|err| {
+                log::error!("Invalid {} backtest price: {}", symbol, err);
+                RsAlgoError {
+                    err: RsAlgoErrorKind::ParseError,
+                }
+            })?;
 
-        let slippage_pips = env::var("SLIPPAGE_PIPS").unwrap().parse::<f64>().unwrap();
+        let slippage_pips = self.config.slippage_pips;
 
         let price_with_slippage = match trade_type.is_long() == trade_type.is_entry() {
             true => price + calc::to_pips(slippage_pips, &tick),
@@ -534,7 +552,12 @@ impl BrokerStream for Xtb {
             .pip_size(tick.pip_size())
             .time(0)
             .build()
-            .unwrap();
+            .map_err(|err| {
+                log::error!("Invalid {} backtest tick: {}", symbol, err);
+                RsAlgoError {
+                    err: RsAlgoErrorKind::ParseError,
+                }
+            })?;
 
         Ok(ResponseBody {
             response: ResponseType::GetInstrumentTick,
@@ -550,73 +573,60 @@ impl BrokerStream for Xtb {
             },
         };
 
-        self.send(&command).await.unwrap();
-        let msg = self.socket.read().await.unwrap();
+        let msg = self.socket.request(&command).await?;
+        let data = self.parse_message(&text_message(msg)?)?;
 
-        let res = match msg {
-            Message::Text(txt) => {
-                let data = self.parse_message(&txt).unwrap();
-
-                let mut market_hours: Vec<MarketHour> = vec![];
+        let mut market_hours: Vec<MarketHour> = vec![];
 
-                for obj in data["returnData"][0]["trading"].as_array().unwrap() {
-                    let day = obj["day"].as_i64().unwrap() as u32;
-                    let from = obj["fromT"].as_i64().unwrap() as u32 / 3600 / 1000;
-                    let to = obj["toT"].as_i64().unwrap() as u32 / 3600 / 1000;
+        for obj in field(&data["returnData"][0], "trading", Value::as_array)? {
+            let day = field(obj, "day", Value::as_i64)? as u32;
+            let from = field(obj, "fromT", Value::as_i64)? as u32 / 3600 / 1000;
+            let to = field(obj, "toT", Value::as_i64)? as u32 / 3600 / 1000;
 
-                    let market_hour = MarketHour { day, from, to };
-
-                    market_hours.push(market_hour);
-                }
+            let market_hour = MarketHour { day, from, to };
 
-                market_hours.sort_by(|a, b| a.day.cmp(&b.day));
+            market_hours.push(market_hour);
+        }
 
-                ResponseBody {
-                    response: ResponseType::GetMarketHours,
-                    payload: Some(MarketHours::new(symbol.to_owned(), market_hours)),
-                }
-            }
-            _ => panic!(),
-        };
+        market_hours.sort_by(|a, b| a.day.cmp(&b.day));
+        symbol_spec::set_trading_hours(symbol, market_hours.clone());
 
-        Ok(res)
+        Ok(ResponseBody {
+            response: ResponseType::GetMarketHours,
+            payload: Some(MarketHours::new(symbol.to_owned(), market_hours)),
+        })
     }
 
     async fn is_market_open(&mut self, symbol: &str) -> Result<ResponseBody<bool>> {
-        match self.is_market_available(symbol).await {
-            true => Ok(ResponseBody {
-                response: ResponseType::IsMarketOpen,
-                payload: Some(true),
-            }),
-            false => Ok(ResponseBody {
-                response: ResponseType::IsMarketOpen,
-                payload: Some(false),
-            }),
-        }
+        let is_open = self.is_market_available(symbol).await?;
+
+        Ok(ResponseBody {
+            response: ResponseType::IsMarketOpen,
+            payload: Some(is_open),
+        })
     }
 
-    async fn is_market_available(&mut self, symbol: &str) -> bool {
+    async fn is_market_available(&mut self, symbol: &str) -> Result<bool> {
         let minutes = 5;
         let from = (Local::now() - date::Duration::minutes(minutes)).timestamp();
         let res = self
             .get_instrument_data(&symbol, minutes as usize, from)
-            .await
-            .unwrap();
+            .await?;
 
         match res.payload {
             Some(inst) => {
                 if inst.data.len() > 0 {
-                    true
+                    Ok(true)
                 } else {
                     log::warn!(
                         "No {} data found in last {}. Market not open",
                         symbol,
                         minutes
                     );
-                    false
+                    Ok(false)
                 }
             }
-            None => false,
+            None => Ok(false),
         }
     }
 
@@ -625,7 +635,7 @@ impl BrokerStream for Xtb {
         trade: TradeData<TradeIn>,
         orders: Option<Vec<Order>>,
     ) -> Result<ResponseBody<TradeResponse<TradeIn>>> {
-        let is_prod = environment::from_str(&env::var("ENV").unwrap()).is_prod();
+        let is_prod = self.config.env.is_prod();
 
         match is_prod {
             true => self.open_trade_real(trade, orders).await,
@@ -638,11 +648,9 @@ impl BrokerStream for Xtb {
         trade: TradeData<TradeIn>,
         orders: Option<Vec<Order>>,
     ) -> Result<ResponseBody<TradeResponse<TradeIn>>> {
-        const MAX_RETRIES: usize = 3;
-        const RETRY_AFTER: u64 = 500;
-        let mut attempts = 0;
+        let retry_policy = RetryPolicy::from_config(&self.config);
+        let mut attempts: Vec<TradeAttempt> = vec![];
         let mut accepted = false;
-        let mut status = TradeStatus::Rejected;
         let symbol = trade.symbol;
         let strategy_name = trade.strategy_name;
         let mut trade_in = trade.data;
@@ -651,10 +659,7 @@ impl BrokerStream for Xtb {
         let valid_until = (Local::now() + date::Duration::minutes(3)).timestamp_millis();
         let is_long = trade_in.trade_type.is_long();
 
-        let order_size_limit = env::var("ORDER_SIZE_LIMIT")
-            .unwrap()
-            .parse::<f64>()
-            .unwrap();
+        let order_size_limit = self.config.order_size_limit;
 
         let size = trade_in.size;
         let trade_size = if size > order_size_limit {
@@ -692,111 +697,119 @@ impl BrokerStream for Xtb {
             false => TransactionCommand::SellMarket.value(),
         };
 
-        //while !accepted && attempts < MAX_RETRIES {
-        let trade_type = trade_in.trade_type.clone();
-        let start = Local::now();
-        let (ask, bid) = self.get_ask_bid(&symbol).await.unwrap();
-        let spread = ask - bid;
-
-        let comment = serde_json::to_string(&TransactionComments {
-            strategy_name: strategy_name.clone(),
-            index_in: trade_in.index_in.clone(),
-            sell_order_price,
-            stop_loss_order_price,
-            trade_type,
-            spread: spread,
-            bid: bid,
-        })
-        .unwrap();
+        let take_profit = match self.config.order_engine.as_ref() {
+            "broker" => sell_order_price.unwrap_or(0.),
+            _ => 0.,
+        };
 
-        let opening_price = 1.;
+        loop {
+            let trade_type = trade_in.trade_type.clone();
+            let start = Local::now();
+            let (ask, bid) = self.get_ask_bid(&symbol).await?;
+            let spread = ask - bid;
+
+            let comment = serde_json::to_string(&TransactionComments {
+                strategy_name: strategy_name.clone(),
+                index_in: trade_in.index_in.clone(),
+                sell_order_price,
+                stop_loss_order_price,
+                trade_type,
+                spread: spread,
+                bid: bid,
+            })
+            .unwrap();
 
-        let trade_command: Command<TransactionInfo> = Command {
-            command: "tradeTransaction".to_owned(),
-            arguments: TransactionInfo {
-                tradeTransInfo: TradeTransactionInfo {
-                    cmd: command,
-                    symbol: symbol.to_owned(),
-                    trans_type: TransactionAction::Open.value(),
-                    customComment: comment,
-                    expiration: valid_until,
-                    order: 0,
-                    price: opening_price,
-                    offset: 0,
-                    sl: stop_loss_order_price.unwrap(),
-                    tp: 0.,
-                    volume: trade_size,
+            let opening_price = 1.;
+
+            let trade_command: Command<TransactionInfo> = Command {
+                command: "tradeTransaction".to_owned(),
+                arguments: TransactionInfo {
+                    tradeTransInfo: TradeTransactionInfo {
+                        cmd: command,
+                        symbol: symbol.to_owned(),
+                        trans_type: TransactionAction::Open.value(),
+                        customComment: comment,
+                        expiration: valid_until,
+                        order: 0,
+                        price: opening_price,
+                        offset: 0,
+                        sl: stop_loss_order_price.unwrap_or(0.),
+                        tp: take_profit,
+                        volume: trade_size,
+                    },
                 },
-            },
-        };
+            };
 
-        self.send(&trade_command).await.unwrap();
-        let msg = self.socket.read().await.unwrap();
+            log::info!(
+                "Real Opening {} {:?} trade. Attempt {}",
+                &symbol,
+                &trade_in.trade_type,
+                attempts.len() + 1
+            );
 
-        log::info!("Real Opening {} {:?} trade", &symbol, &trade_in.trade_type,);
+            let (attempt, trans_status) = self
+                .execute_transaction(&trade_command, attempts.len() + 1, ask, bid)
+                .await?;
 
-        match msg {
-            Message::Text(txt) => {
+            if let Some(trans_status) = trans_status {
                 let end = Local::now();
+                let transaction_details = self
+                    .get_transaction_details(&symbol, &strategy_name, None)
+                    .await
+                    .ok_or(RsAlgoError {
+                        err: RsAlgoErrorKind::NoResponse,
+                    })?;
+
+                log::info!(
+                    "Real Opened {} {:?} trade {}. Openinig price: {}",
+                    &symbol,
+                    &trade_in.trade_type,
+                    &transaction_details.id,
+                    &transaction_details.open_price
+                );
+
+                trade_in.id = transaction_details.id;
+                trade_in.price_in = transaction_details.open_price;
+                trade_in.ask = trans_status.ask;
+                trade_in.spread = spread;
+                accepted = true;
+                self.positions.insert(trade_in.id, trade_size);
+
+                log::info!(
+                    "Operation total time: {:?}",
+                    (end - start).num_milliseconds()
+                );
+            }
 
-                let (executed, order_id) = self.get_order_id_executed(&txt).unwrap();
-
-                match executed {
-                    true => {
-                        let trans_status = self.get_transaction_status(order_id).await?;
-                        match trans_status.status.is_accepted() {
-                            true => {
-                                let transaction_details = self
-                                    .get_transaction_details(&symbol, &strategy_name, None)
-                                    .await
-                                    .unwrap();
-
-                                log::info!(
-                                    "Real Opened {} {:?} trade {}. Openinig price: {}",
-                                    &symbol,
-                                    &trade_in.trade_type,
-                                    &transaction_details.id,
-                                    &transaction_details.open_price
-                                );
-
-                                trade_in.id = transaction_details.id;
-                                trade_in.price_in = transaction_details.open_price;
-                                trade_in.ask = trans_status.ask;
-                                trade_in.spread = spread;
-                                accepted = true;
-                                status = TradeStatus::Fulfilled;
-
-                                log::info!(
-                                    "Operation total time: {:?}",
-                                    (end - start).num_milliseconds()
-                                );
-                            }
-                            false => {
-                                log::error!("Position {} not accepted by Broker:", order_id);
-                                attempts += 1;
-                            }
-                        }
-                    }
-                    false => {
-                        attempts += 1;
-                    }
-                }
+            let retry = !accepted && retry_policy.should_retry(&attempt);
+            attempts.push(attempt);
+
+            if !retry {
+                break;
             }
-            _ => todo!(),
-        }
 
-        trade_in.status = status.clone();
+            log::warn!(
+                "{:?} rejected in Broker. Retrying in {} ms...",
+                &trade_in.trade_type,
+                retry_policy.retry_after.as_millis()
+            );
+
+            sleep(retry_policy.retry_after).await;
+        }
 
-        //sleep(Duration::from_millis(RETRY_AFTER));
+        trade_in.status = match accepted {
+            true => TradeStatus::Fulfilled,
+            false => TradeStatus::Rejected,
+        };
 
         if !accepted {
             log::error!(
-                "{:?} {:?} in Broker. Retrying...",
+                "{:?} {:?} in Broker after {} attempts",
                 &trade_in.trade_type,
-                &trade_in.status
+                &trade_in.status,
+                attempts.len()
             );
         }
-        // }
 
         Ok(ResponseBody {
             response: ResponseType::TradeInFulfilled,
@@ -804,6 +817,7 @@ impl BrokerStream for Xtb {
                 symbol,
                 accepted,
                 data: trade_in,
+                attempts,
             }),
         })
     }
@@ -812,11 +826,9 @@ impl BrokerStream for Xtb {
         &mut self,
         trade: TradeData<TradeOut>,
     ) -> Result<ResponseBody<TradeResponse<TradeOut>>> {
-        const MAX_RETRIES: usize = 3;
-        const RETRY_AFTER: u64 = 250;
-        let mut attempts = 0;
+        let retry_policy = RetryPolicy::from_config(&self.config);
+        let mut attempts: Vec<TradeAttempt> = vec![];
         let mut accepted = false;
-        let mut status = TradeStatus::Rejected;
         let mut trade_out = trade.data;
         let symbol = trade.symbol;
         let strategy_name = trade.strategy_name;
@@ -827,112 +839,107 @@ impl BrokerStream for Xtb {
             false => TransactionCommand::BuyMarket.value(),
         };
 
-        // while !accepted && attempts < MAX_RETRIES {
-        let start = Local::now();
-        let closing_price = 1.;
-        let custom_comment = format!("Closing order {}", trade_out.id);
-
-        let trade_command: Command<TransactionInfo> = Command {
-            command: "tradeTransaction".to_owned(),
-            arguments: TransactionInfo {
-                tradeTransInfo: TradeTransactionInfo {
-                    cmd: command,
-                    trans_type: TransactionAction::Close.value(),
-                    symbol: symbol.to_owned(),
-                    customComment: custom_comment,
-                    expiration: valid_until,
-                    order: trade_out.id as isize,
-                    price: closing_price,
-                    offset: 0,
-                    sl: 0.,
-                    tp: 0.,
-                    volume: trade_out.size,
+        loop {
+            let start = Local::now();
+            let closing_price = 1.;
+            let custom_comment = format!("Closing order {}", trade_out.id);
+            let (ask, bid) = self.get_ask_bid(&symbol).await?;
+
+            let trade_command: Command<TransactionInfo> = Command {
+                command: "tradeTransaction".to_owned(),
+                arguments: TransactionInfo {
+                    tradeTransInfo: TradeTransactionInfo {
+                        cmd: command,
+                        trans_type: TransactionAction::Close.value(),
+                        symbol: symbol.to_owned(),
+                        customComment: custom_comment,
+                        expiration: valid_until,
+                        order: trade_out.id as isize,
+                        price: closing_price,
+                        offset: 0,
+                        sl: 0.,
+                        tp: 0.,
+                        volume: trade_out.size,
+                    },
                 },
-            },
-        };
-
-        self.send(&trade_command).await.unwrap();
-        let msg = self.socket.read().await.unwrap();
+            };
 
-        let (ask, bid) = self.get_ask_bid(&symbol).await?;
-        let spread = ask - bid;
+            log::info!(
+                "Real Closing {} {:?} at {}. Attempt {}",
+                &symbol,
+                &trade_out.trade_type,
+                closing_price,
+                attempts.len() + 1
+            );
 
-        log::info!(
-            "Real Closing {} {:?} at {}.",
-            &symbol,
-            &trade_out.trade_type,
-            closing_price,
-        );
+            let (attempt, trans_status) = self
+                .execute_transaction(&trade_command, attempts.len() + 1, ask, bid)
+                .await?;
 
-        match msg {
-            Message::Text(txt) => {
+            if let Some(trans_status) = trans_status {
                 let end = Local::now();
+                let transaction_details = self
+                    .get_transactions_history(&symbol, &strategy_name, Some(trade_out.id))
+                    .await
+                    .ok_or(RsAlgoError {
+                        err: RsAlgoErrorKind::NoResponse,
+                    })?;
+
+                log::info!(
+                    "Real Closed {}_{} {:?} trade {}. Closing price: {} Profit: {}",
+                    &symbol,
+                    &strategy_name,
+                    &trade_out.trade_type,
+                    &transaction_details.id,
+                    &transaction_details.close_price,
+                    &transaction_details.profit,
+                );
+
+                trade_out.price_out = transaction_details.close_price;
+                trade_out.profit = transaction_details.profit;
+                trade_out.commission = -transaction_details.commission;
+                trade_out.swap = transaction_details.swap;
+                trade_out.date_out = to_dbtime(Local::now());
+                trade_out.bid = trans_status.bid;
+                trade_out.ask = trans_status.ask;
+                trade_out.spread_out = trans_status.ask - trans_status.bid;
+                accepted = true;
+
+                log::info!(
+                    "Operation total time: {:?}",
+                    (end - start).num_milliseconds()
+                );
+            }
 
-                let (executed, order_id) = self.get_order_id_executed(&txt).unwrap();
-
-                match executed {
-                    true => {
-                        let trans_status = self.get_transaction_status(order_id).await?;
-                        match trans_status.status.is_accepted() {
-                            true => {
-                                let transaction_details = self
-                                    .get_transactions_history(
-                                        &symbol,
-                                        &strategy_name,
-                                        Some(trade_out.id),
-                                    )
-                                    .await
-                                    .unwrap();
-
-                                log::info!(
-                                    "Real Closed {}_{} {:?} trade {}. Closing price: {} Profit: {}",
-                                    &symbol,
-                                    &strategy_name,
-                                    &trade_out.trade_type,
-                                    &transaction_details.id,
-                                    &transaction_details.close_price,
-                                    &transaction_details.profit,
-                                );
-
-                                trade_out.price_out = transaction_details.close_price;
-                                trade_out.profit = transaction_details.profit;
-                                trade_out.date_out = to_dbtime(Local::now());
-                                trade_out.bid = trans_status.bid;
-                                trade_out.ask = trans_status.ask;
-                                trade_out.spread_out = trans_status.ask - trans_status.bid;
-                                accepted = true;
-                                status = TradeStatus::Fulfilled;
-
-                                log::info!(
-                                    "Operation total time: {:?}",
-                                    (end - start).num_milliseconds()
-                                );
-                            }
-                            false => {
-                                attempts += 1;
-                            }
-                        }
-                    }
-                    false => {
-                        attempts += 1;
-                    }
-                }
+            let retry = !accepted && retry_policy.should_retry(&attempt);
+            attempts.push(attempt);
+
+            if !retry {
+                break;
             }
-            _ => todo!(),
-        }
 
-        trade_out.status = status.clone();
+            log::warn!(
+                "{:?} rejected in Broker. Retrying in {} ms...",
+                &trade_out.trade_type,
+                retry_policy.retry_after.as_millis()
+            );
+
+            sleep(retry_policy.retry_after).await;
+        }
 
-        //sleep(Duration::from_millis(RETRY_AFTER));
+        trade_out.status = match accepted {
+            true => TradeStatus::Fulfilled,
+            false => TradeStatus::Rejected,
+        };
 
         if !accepted {
             log::error!(
-                "{:?} {:?} in Broker. Retrying...",
+                "{:?} {:?} in Broker after {} attempts",
                 &trade_out.trade_type,
-                &trade_out.status
+                &trade_out.status,
+                attempts.len()
             );
         }
-        //}
 
         Ok(ResponseBody {
             response: ResponseType::TradeOutFulfilled,
@@ -940,6 +947,7 @@ impl BrokerStream for Xtb {
                 symbol,
                 accepted,
                 data: trade_out,
+                attempts,
             }),
         })
     }
@@ -958,30 +966,23 @@ impl BrokerStream for Xtb {
         };
 
         while attempts < MAX_RETRIES {
-            self.send(&status_command).await.unwrap();
-            let msg = self.socket.read().await.unwrap();
-
-            match msg {
-                Message::Text(txt) => {
-                    let transaction_status = self.parse_trade_status_data(txt)?;
-                    let status = &transaction_status.status;
-
-                    if !transaction_status.status.is_pending() {
-                        log::info!("Transaction {} status {:?}", order_id, status);
-                        return Ok(transaction_status);
-                    } else {
-                        log::info!(
-                            "Transaction {:?} status {:?}. Retry after {} ms",
-                            order_id,
-                            status,
-                            retry_after.as_millis()
-                        );
-
-                        sleep(retry_after).await;
-                    }
-                }
-                _ => todo!(),
-            };
+            let msg = self.socket.request(&status_command).await?;
+            let transaction_status = self.parse_trade_status_data(text_message(msg)?)?;
+            let status = &transaction_status.status;
+
+            if !transaction_status.status.is_pending() {
+                log::info!("Transaction {} status {:?}", order_id, status);
+                return Ok(transaction_status);
+            } else {
+                log::info!(
+                    "Transaction {:?} status {:?}. Retry after {} ms",
+                    order_id,
+                    status,
+                    retry_after.as_millis()
+                );
+
+                sleep(retry_after).await;
+            }
 
             attempts += 1;
         }
@@ -996,7 +997,7 @@ impl BrokerStream for Xtb {
         trade: TradeData<TradeIn>,
         _orders: Option<Vec<Order>>,
     ) -> Result<ResponseBody<TradeResponse<TradeIn>>> {
-        let execution_mode = mode::from_str(&env::var("EXECUTION_MODE").unwrap());
+        let execution_mode = self.config.execution_mode.clone();
 
         let symbol = &trade.symbol;
         let mut data = trade.data;
@@ -1005,20 +1006,13 @@ impl BrokerStream for Xtb {
         let trade_type = data.trade_type.clone();
 
         let tick = match execution_mode {
-            mode::ExecutionMode::Bot => self
-                .get_instrument_tick(&symbol)
-                .await
-                .unwrap()
-                .payload
-                .unwrap(),
+            mode::ExecutionMode::Bot => self.get_instrument_tick(&symbol).await?.into_payload()?,
 
             _ => {
                 date_in = data.date_in;
                 self.get_instrument_tick_test(&symbol, data.price_in, &trade_type)
-                    .await
-                    .unwrap()
-                    .payload
-                    .unwrap()
+                    .await?
+                    .into_payload()?
             }
         };
 
@@ -1051,6 +1045,7 @@ impl BrokerStream for Xtb {
                 symbol: trade.symbol,
                 accepted: true,
                 data: data,
+                attempts: vec![],
             }),
         };
 
@@ -1061,7 +1056,7 @@ impl BrokerStream for Xtb {
         &mut self,
         trade: TradeData<TradeOut>,
     ) -> Result<ResponseBody<TradeResponse<TradeOut>>> {
-        let is_prod = environment::from_str(&env::var("ENV").unwrap()).is_prod();
+        let is_prod = self.config.env.is_prod();
 
         match is_prod {
             true => self.close_trade_real(trade).await,
@@ -1073,7 +1068,7 @@ impl BrokerStream for Xtb {
         &mut self,
         trade: TradeData<TradeOut>,
     ) -> Result<ResponseBody<TradeResponse<TradeOut>>> {
-        let execution_mode = mode::from_str(&env::var("EXECUTION_MODE").unwrap());
+        let execution_mode = self.config.execution_mode.clone();
 
         let symbol = &trade.symbol;
         let mut data = trade.data;
@@ -1082,19 +1077,12 @@ impl BrokerStream for Xtb {
         let mut date_out = to_dbtime(Local::now());
 
         let tick = match execution_mode {
-            mode::ExecutionMode::Bot => self
-                .get_instrument_tick(&symbol)
-                .await
-                .unwrap()
-                .payload
-                .unwrap(),
+            mode::ExecutionMode::Bot => self.get_instrument_tick(&symbol).await?.into_payload()?,
             _ => {
                 date_out = data.date_out;
                 self.get_instrument_tick_test(&symbol, data.price_out, &trade_type)
-                    .await
-                    .unwrap()
-                    .payload
-                    .unwrap()
+                    .await?
+                    .into_payload()?
             }
         };
 
@@ -1161,6 +1149,7 @@ impl BrokerStream for Xtb {
                 symbol: trade.symbol,
                 accepted,
                 data,
+                attempts: vec![],
             }),
         };
         Ok(res)
@@ -1176,23 +1165,14 @@ impl BrokerStream for Xtb {
             arguments: GetTrades { openedOnly: true },
         };
 
-        self.send(&active_positions_command).await.unwrap();
-        let msg = self.socket.read().await.unwrap();
-
-        let res = match msg {
-            Message::Text(txt) => {
-                let position_result = self
-                    .parse_active_positions_data(txt, symbol, strategy_name)
-                    .unwrap();
-                ResponseBody {
-                    response: ResponseType::GetActivePositions,
-                    payload: Some(position_result),
-                }
-            }
-            _ => panic!(),
-        };
+        let msg = self.socket.request(&active_positions_command).await?;
+        let position_result =
+            self.parse_active_positions_data(text_message(msg)?, symbol, strategy_name)?;
 
-        Ok(res)
+        Ok(ResponseBody {
+            response: ResponseType::GetActivePositions,
+            payload: Some(position_result),
+        })
     }
 
     async fn open_order(
@@ -1200,7 +1180,7 @@ impl BrokerStream for Xtb {
         trade: TradeData<TradeIn>,
         order: TradeData<Order>,
     ) -> Result<ResponseBody<TradeResponse<TradeIn>>> {
-        let is_prod = environment::from_str(&env::var("ENV").unwrap()).is_prod();
+        let is_prod = self.config.env.is_prod();
 
         match is_prod {
             true => self.open_trade_real(trade, Some(vec![order.data])).await,
@@ -1213,7 +1193,7 @@ impl BrokerStream for Xtb {
         trade: TradeData<TradeIn>,
         order: TradeData<Order>,
     ) -> Result<ResponseBody<TradeResponse<TradeIn>>> {
-        let execution_mode = mode::from_str(&env::var("EXECUTION_MODE").unwrap());
+        let execution_mode = self.config.execution_mode.clone();
 
         let mut date_in = to_dbtime(Local::now());
 
@@ -1227,19 +1207,12 @@ impl BrokerStream for Xtb {
         let size = order.size;
 
         let tick = match execution_mode {
-            mode::ExecutionMode::Bot => self
-                .get_instrument_tick(&symbol)
-                .await
-                .unwrap()
-                .payload
-                .unwrap(),
+            mode::ExecutionMode::Bot => self.get_instrument_tick(&symbol).await?.into_payload()?,
             _ => {
                 date_in = trade.date_in;
                 self.get_instrument_tick_test(&symbol, order.target_price, &trade_type)
-                    .await
-                    .unwrap()
-                    .payload
-                    .unwrap()
+                    .await?
+                    .into_payload()?
             }
         };
 
@@ -1286,6 +1259,7 @@ impl BrokerStream for Xtb {
             trade_type,
             date_in: date_in,
             status,
+            stop_rules: trade.stop_rules.clone(),
         };
 
         let res = ResponseBody {
@@ -1294,6 +1268,7 @@ impl BrokerStream for Xtb {
                 symbol: symbol.clone(),
                 accepted: true,
                 data: trade_in,
+                attempts: vec![],
             }),
         };
 
@@ -1304,7 +1279,7 @@ impl BrokerStream for Xtb {
         trade: TradeData<TradeOut>,
         order: TradeData<Order>,
     ) -> Result<ResponseBody<TradeResponse<TradeOut>>> {
-        let is_prod = environment::from_str(&env::var("ENV").unwrap()).is_prod();
+        let is_prod = self.config.env.is_prod();
 
         match is_prod {
             true => self.close_trade_real(trade).await,
@@ -1316,7 +1291,7 @@ impl BrokerStream for Xtb {
         trade: TradeData<TradeOut>,
         order: TradeData<Order>,
     ) -> Result<ResponseBody<TradeResponse<TradeOut>>> {
-        let execution_mode = mode::from_str(&env::var("EXECUTION_MODE").unwrap());
+        let execution_mode = self.config.execution_mode.clone();
 
         let symbol = &order.symbol;
         let order_data = order.data;
@@ -1326,19 +1301,12 @@ impl BrokerStream for Xtb {
         let order_type = order_data.order_type;
 
         let tick = match execution_mode {
-            mode::ExecutionMode::Bot => self
-                .get_instrument_tick(&symbol)
-                .await
-                .unwrap()
-                .payload
-                .unwrap(),
+            mode::ExecutionMode::Bot => self.get_instrument_tick(&symbol).await?.into_payload()?,
             _ => {
                 date_out = trade_data.date_out;
                 self.get_instrument_tick_test(&symbol, order_data.target_price, &trade_type)
-                    .await
-                    .unwrap()
-                    .payload
-                    .unwrap()
+                    .await?
+                    .into_payload()?
             }
         };
 
@@ -1418,319 +1386,1667 @@ impl BrokerStream for Xtb {
                 symbol: "".to_owned(),
                 accepted,
                 data: trade_data,
+                attempts: vec![],
             }),
         };
         Ok(res)
     }
 
-    async fn subscribe_stream(&mut self, symbol: &str) -> Result<()> {
-        let command_alive = CommandStreaming {
-            command: "getKeepAlive".to_owned(),
-            streamSessionId: self.streamSessionId.clone(),
-        };
+    async fn modify_trade(
+        &mut self,
+        trade: TradeData<TradeAmend>,
+    ) -> Result<ResponseBody<TradeResponse<TradeAmend>>> {
+        match self.config.env.is_prod() {
+            true => self.modify_trade_real(trade).await,
+            false => self.modify_trade_test(trade).await,
+        }
+    }
 
-        self.send_stream(&command_alive).await.unwrap();
+    async fn modify_trade_real(
+        &mut self,
+        trade: TradeData<TradeAmend>,
+    ) -> Result<ResponseBody<TradeResponse<TradeAmend>>> {
+        let retry_policy = RetryPolicy::from_config(&self.config);
+        let mut attempts: Vec<TradeAttempt> = vec![];
+        let mut accepted;
+        let symbol = trade.symbol;
+        let strategy_name = trade.strategy_name;
+        let amend = trade.data;
+        let trade_in = &amend.trade_in;
 
-        let command = CommandGetCandles {
-            command: "getCandles".to_owned(),
-            streamSessionId: self.streamSessionId.clone(),
-            symbol: symbol.to_owned(),
+        let command = match trade_in.trade_type.is_long() {
+            true => TransactionCommand::BuyMarket.value(),
+            false => TransactionCommand::SellMarket.value(),
         };
 
-        self.send_stream(&command).await.unwrap();
-        Ok(())
-    }
-
-    async fn subscribe_tick_prices(&mut self, symbol: &str) -> Result<()> {
-        let is_prod = environment::from_str(&env::var("ENV").unwrap()).is_prod();
+        // XTB sets both levels on every modification, a missing one is removed
+        let comment = serde_json::to_string(&TransactionComments {
+            strategy_name: strategy_name.clone(),
+            index_in: trade_in.index_in,
+            sell_order_price: amend.take_profit,
+            stop_loss_order_price: amend.stop_loss,
+            trade_type: trade_in.trade_type.clone(),
+            spread: trade_in.spread,
+            bid: trade_in.ask - trade_in.spread,
+        })
+        .unwrap();
 
-        let arrival_time = match is_prod {
-            true => 1,
-            false => 1, //1000,
-        };
+        loop {
+            let (ask, bid) = self.get_ask_bid(&symbol).await?;
+
+            let trade_command: Command<TransactionInfo> = Command {
+                command: "tradeTransaction".to_owned(),
+                arguments: TransactionInfo {
+                    tradeTransInfo: TradeTransactionInfo {
+                        cmd: command,
+                        symbol: symbol.to_owned(),
+                        trans_type: TransactionAction::Modify.value(),
+                        customComment: comment.clone(),
+                        expiration: 0,
+                        order: trade_in.id as isize,
+                        price: trade_in.price_in,
+                        offset: 0,
+                        sl: amend.stop_loss.unwrap_or(0.),
+                        tp: amend.take_profit.unwrap_or(0.),
+                        volume: trade_in.size,
+                    },
+                },
+            };
 
-        self.symbol = symbol.to_owned();
-        let command = CommandTickStreamParams {
-            command: "getTickPrices".to_owned(),
-            streamSessionId: self.streamSessionId.clone(),
-            symbol: symbol.to_string(),
-            minArrivalTime: arrival_time,
-            maxLevel: 0,
-        };
+            log::info!(
+                "Real Modifying {} trade {}. SL: {:?} TP: {:?}. Attempt {}",
+                &symbol,
+                trade_in.id,
+                amend.stop_loss,
+                amend.take_profit,
+                attempts.len() + 1
+            );
 
-        self.send_stream(&command).await.unwrap();
+            let (attempt, trans_status) = self
+                .execute_transaction(&trade_command, attempts.len() + 1, ask, bid)
+                .await?;
 
-        Ok(())
-    }
+            accepted = trans_status.is_some();
 
-    async fn subscribe_trades(&mut self, symbol: &str) -> Result<()> {
-        let command = CommandTradeStatusParams {
-            command: "getTrades".to_owned(),
-            streamSessionId: self.streamSessionId.clone(),
-        };
+            let retry = !accepted && retry_policy.should_retry(&attempt);
+            attempts.push(attempt);
 
-        self.send_stream(&command).await.unwrap();
+            if !retry {
+                break;
+            }
 
-        Ok(())
-    }
+            log::warn!(
+                "Trade {} modification rejected in Broker. Retrying in {} ms...",
+                trade_in.id,
+                retry_policy.retry_after.as_millis()
+            );
 
-    async fn listen<F, T>(&mut self, symbol: &str, session_id: String, mut callback: F)
-    where
-        F: Send + FnMut(Message) -> T,
-        T: Future<Output = Result<()>> + Send + 'static,
-    {
-    }
+            sleep(retry_policy.retry_after).await;
+        }
 
-    async fn parse_stream_data(msg: Message, symbol: &str, strategy_name: &str) -> Option<String> {
-        let txt = match msg {
-            Message::Text(txt) => txt,
-            _ => "".to_owned(),
+        match accepted {
+            true => log::info!("Real Modified {} trade {}", &symbol, trade_in.id),
+            false => log::error!(
+                "Trade {} modification rejected in Broker after {} attempts",
+                trade_in.id,
+                attempts.len()
+            ),
         };
 
-        let obj: Value = serde_json::from_str(&txt).unwrap();
-
-        let msg = match &obj {
-            Value::Object(obj) => {
-                let command = &obj["command"];
-                let data = &obj["data"];
-                if command == "candle" {
-                    let date = parse_time_seconds(data["ctm"].as_i64().unwrap() / 1000);
-                    let open = data["open"].as_f64().unwrap();
-                    let high = data["high"].as_f64().unwrap();
-                    let low = data["low"].as_f64().unwrap();
-                    let close = data["close"].as_f64().unwrap();
-                    let size = data["vol"].as_f64().unwrap() * 1000.;
+        Ok(ResponseBody {
+            response: ResponseType::TradeModified,
+            payload: Some(TradeResponse {
+                symbol,
+                accepted,
+                data: amend,
+                attempts,
+            }),
+        })
+    }
 
-                    let ohlc = (date, open, high, low, close, size);
+    async fn modify_trade_test(
+        &mut self,
+        trade: TradeData<TradeAmend>,
+    ) -> Result<ResponseBody<TradeResponse<TradeAmend>>> {
+        let amend = trade.data;
 
-                    let msg: ResponseBody<(DateTime<Local>, f64, f64, f64, f64, f64)> =
-                        ResponseBody {
-                            response: ResponseType::SubscribeStream,
-                            payload: Some(ohlc),
-                        };
+        log::info!(
+            "{} Test Modified trade {}. SL: {:?} TP: {:?}",
+            trade.symbol,
+            amend.trade_in.id,
+            amend.stop_loss,
+            amend.take_profit
+        );
 
-                    Some(serde_json::to_string(&msg).unwrap())
-                } else if command == "tickPrices" {
-                    let symbol = data["symbol"].as_str().unwrap().to_owned();
-                    let ask = data["ask"].as_f64().unwrap();
-                    let bid = data["bid"].as_f64().unwrap();
-                    let high = data["high"].as_f64().unwrap();
-                    let low = data["low"].as_f64().unwrap();
-                    let timestamp = data["timestamp"].as_i64().unwrap();
-                    let spread = data["spreadRaw"].as_f64().unwrap();
-                    let pip_size = number_pips(&symbol);
-
-                    let tick = InstrumentTick::new()
-                        .symbol(symbol)
-                        .ask(ask)
-                        .bid(bid)
-                        .high(high)
-                        .low(low)
-                        .spread(spread)
-                        .pip_size(pip_size)
-                        .time(timestamp)
-                        .build()
-                        .unwrap();
-
-                    let msg: ResponseBody<InstrumentTick> = ResponseBody {
-                        response: ResponseType::SubscribeTickPrices,
-                        payload: Some(tick),
-                    };
-                    Some(serde_json::to_string(&msg).unwrap())
-                } else if command == "trade" {
-                    match data["closed"].as_bool() {
-                        Some(is_closed) => {
-                            // let cmd = TransactionCommand::from_value(
-                            //     data["cmd"].as_u64().unwrap() as i64
-                            // )
-                            // .unwrap();
-
-                            let comment = data["comment"].as_str().unwrap_or_default();
-                            let is_stop = comment == "[S/L]";
-
-                            //FILTER ONLY STOPS FOR NOW
-                            if is_closed && is_stop {
-                                let stream_symbol = data["symbol"].as_str().unwrap();
-                                let comments = data["customComment"].as_str().unwrap();
-                                let trans_comments: TransactionComments =
-                                    serde_json::from_str(&comments).unwrap();
-
-                                // TAKING ONLY THE OWN SYMBOL & STRATEGY COMBINATION
-                                if symbol == stream_symbol
-                                    && strategy_name == trans_comments.strategy_name
-                                {
-                                    let id = data["position"].as_u64().unwrap() as usize;
-                                    let size = data["volume"].as_f64().unwrap();
-                                    let price_in = data["open_price"].as_f64().unwrap();
-                                    let price_out = data["close_price"].as_f64().unwrap();
-
-                                    let index_in = trans_comments.index_in;
-                                    let spread_in = trans_comments.spread;
-                                    let strategy_name = trans_comments.strategy_name;
-
-                                    let gross_profit = data["profit"].as_f64().unwrap_or(0.0);
-                                    let swap = data["storage"].as_f64().unwrap_or(0.0);
-                                    let commission = data["commission"].as_f64().unwrap_or(0.0);
-
-                                    let profit = gross_profit + swap + commission;
-
-                                    log::info!("PROFIT DATA Gross Profit: {} Swap: {} Comission: {} Net Profit: {}", gross_profit, swap, commission, profit);
-
-                                    let spread_out = 0.;
-                                    let close_time = Local::now();
-                                    let date_in = to_dbtime(parse_time_seconds(
-                                        data["open_time"].as_i64().unwrap() / 1000,
-                                    ));
-
-                                    let date_out = to_dbtime(close_time);
-                                    let index_out = uuid::generate_ts_id(close_time);
-
-                                    let trade_type = match is_stop {
-                                        true => match trans_comments.trade_type.is_long() {
-                                            true => TradeType::StopLossLong,
-                                            false => TradeType::StopLossShort,
-                                        },
-                                        false => match trans_comments.trade_type.is_long() {
-                                            true => TradeType::MarketOutLong,
-                                            false => TradeType::MarketOutShort,
-                                        },
-                                    };
-
-                                    let bid = match trade_type.is_long() {
-                                        true => price_out,
-                                        false => price_out + spread_out,
-                                    };
-
-                                    let profit_per = 0.;
-                                    let run_up = 0.;
-                                    let run_up_per = 0.;
-                                    let draw_down = 0.;
-                                    let draw_down_per = 0.;
-                                    let status = TradeStatus::Fulfilled;
-
-                                    log::info!(
-                                    "Real StopLoss {}_{} {:?} trade {}. Closing price: {} Profit: {}",
-                                    &symbol,
-                                    &strategy_name,
-                                    &trade_type,
-                                    &id,
-                                    &price_out,
-                                    &profit,
-                                );
-
-                                    let trade_out = TradeOut {
-                                        id,
-                                        index_in,
-                                        price_in,
-                                        status,
-                                        size,
-                                        trade_type: trade_type.clone(),
-                                        date_in,
-                                        spread_in,
-                                        ask: price_in,
-                                        index_out,
-                                        price_origin: price_in,
-                                        price_out,
-                                        bid,
-                                        spread_out,
-                                        date_out,
-                                        profit,
-                                        profit_per,
-                                        run_up,
-                                        run_up_per,
-                                        draw_down,
-                                        draw_down_per,
-                                    };
-
-                                    let msg = ResponseBody {
-                                        response: ResponseType::TradeOutFulfilled,
-                                        payload: Some(TradeResponse {
-                                            symbol: symbol.to_owned(),
-                                            accepted: true,
-                                            data: trade_out,
-                                        }),
-                                    };
-
-                                    Some(serde_json::to_string(&msg).unwrap())
-                                } else {
-                                    None
-                                }
-                            } else {
-                                None
-                            }
-                        }
-                        None => None,
-                    }
-                } else {
-                    None
+        Ok(ResponseBody {
+            response: ResponseType::TradeModified,
+            payload: Some(TradeResponse {
+                symbol: trade.symbol,
+                accepted: true,
+                data: amend,
+                attempts: vec![],
+            }),
+        })
+    }
+
+    async fn place_order(
+        &mut self,
+        order: TradeData<Order>,
+    ) -> Result<ResponseBody<TradeResponse<Order>>> {
+        match self.config.env.is_prod() {
+            true => {
+                self.order_transaction_real(order, TransactionAction::Open)
+                    .await
+            }
+            false => {
+                self.order_transaction_test(order, TransactionAction::Open)
+                    .await
+            }
+        }
+    }
+
+    async fn modify_order(
+        &mut self,
+        order: TradeData<Order>,
+    ) -> Result<ResponseBody<TradeResponse<Order>>> {
+        match self.config.env.is_prod() {
+            true => {
+                self.order_transaction_real(order, TransactionAction::Modify)
+                    .await
+            }
+            false => {
+                self.order_transaction_test(order, TransactionAction::Modify)
+                    .await
+            }
+        }
+    }
+
+    async fn cancel_order(
+        &mut self,
+        order: TradeData<Order>,
+    ) -> Result<ResponseBody<TradeResponse<Order>>> {
+        match self.config.env.is_prod() {
+            true => {
+                self.order_transaction_real(order, TransactionAction::Delete)
+                    .await
+            }
+            false => {
+                self.order_transaction_test(order, TransactionAction::Delete)
+                    .await
+            }
+        }
+    }
+
+    async fn order_transaction_real(
+        &mut self,
+        order: TradeData<Order>,
+        action: TransactionAction,
+    ) -> Result<ResponseBody<TradeResponse<Order>>> {
+        let retry_policy = RetryPolicy::from_config(&self.config);
+        let mut attempts: Vec<TradeAttempt> = vec![];
+        let mut accepted = false;
+        let symbol = order.symbol;
+        let strategy_name = order.strategy_name;
+        let mut order = order.data;
+        let is_long = order.is_long();
+        let stop_loss_order_price = order.meta.as_ref().map(|meta| meta.sl);
+
+        let order_size_limit = self.config.order_size_limit;
+        let order_size = if order.size > order_size_limit {
+            log::error!("Order size bigger than {:?} !!", order_size_limit);
+            order_size_limit
+        } else {
+            order.size
+        };
+
+        let expiration = order
+            .valid_until
+            .map(|valid_until| from_dbtime(&valid_until).timestamp_millis())
+            .unwrap_or(0);
+
+        // Pending orders are opened with a limit or stop cmd and get their number
+        // from the broker, modify and delete refer to it
+        let order_number = match action {
+            TransactionAction::Open => 0,
+            _ => order.id as isize,
+        };
+
+        let trade_type = match is_long {
+            true => TradeType::OrderInLong,
+            false => TradeType::OrderInShort,
+        };
+
+        loop {
+            let (ask, bid) = self.get_ask_bid(&symbol).await?;
+            let command = TransactionCommand::pending(is_long, order.target_price, ask, bid);
+
+            let comment = serde_json::to_string(&TransactionComments {
+                strategy_name: strategy_name.clone(),
+                index_in: order.index_created,
+                sell_order_price: None,
+                stop_loss_order_price,
+                trade_type: trade_type.clone(),
+                spread: ask - bid,
+                bid,
+            })
+            .unwrap();
+
+            let trade_command: Command<TransactionInfo> = Command {
+                command: "tradeTransaction".to_owned(),
+                arguments: TransactionInfo {
+                    tradeTransInfo: TradeTransactionInfo {
+                        cmd: command.value(),
+                        symbol: symbol.to_owned(),
+                        trans_type: action.value(),
+                        customComment: comment,
+                        expiration,
+                        order: order_number,
+                        price: order.target_price,
+                        offset: 0,
+                        sl: stop_loss_order_price.unwrap_or(0.),
+                        tp: 0.,
+                        volume: order_size,
+                    },
+                },
+            };
+
+            log::info!(
+                "Real {:?} {} {:?} order at {}. Attempt {}",
+                &action,
+                &symbol,
+                &command,
+                order.target_price,
+                attempts.len() + 1
+            );
+
+            let (attempt, trans_status) = self
+                .execute_transaction(&trade_command, attempts.len() + 1, ask, bid)
+                .await?;
+
+            if let Some(trans_status) = trans_status {
+                if let TransactionAction::Open = action {
+                    order.id = trans_status.order as usize;
                 }
+
+                log::info!("Real {:?} {} order {} accepted", &action, &symbol, order.id);
+
+                accepted = true;
             }
-            _ => None,
+
+            let retry = !accepted && retry_policy.should_retry(&attempt);
+            attempts.push(attempt);
+
+            if !retry {
+                break;
+            }
+
+            log::warn!(
+                "{:?} order rejected in Broker. Retrying in {} ms...",
+                &action,
+                retry_policy.retry_after.as_millis()
+            );
+
+            sleep(retry_policy.retry_after).await;
+        }
+
+        let now = to_dbtime(Local::now());
+
+        match (accepted, &action) {
+            (true, TransactionAction::Delete) => order.cancel_order(now),
+            (true, _) => {
+                order.set_status(OrderStatus::Pending);
+                order.set_updated_at(now);
+            }
+            (false, _) => log::error!(
+                "{:?} order {} rejected in Broker after {} attempts",
+                &action,
+                order.id,
+                attempts.len()
+            ),
+        };
+
+        Ok(ResponseBody {
+            response: ResponseType::OrderUpdated,
+            payload: Some(TradeResponse {
+                symbol,
+                accepted,
+                data: order,
+                attempts,
+            }),
+        })
+    }
+
+    async fn order_transaction_test(
+        &mut self,
+        order: TradeData<Order>,
+        action: TransactionAction,
+    ) -> Result<ResponseBody<TradeResponse<Order>>> {
+        let symbol = order.symbol;
+        let mut order = order.data;
+        let now = to_dbtime(Local::now());
+
+        match action {
+            TransactionAction::Delete => order.cancel_order(now),
+            _ => {
+                order.set_status(OrderStatus::Pending);
+                order.set_updated_at(now);
+            }
+        };
+
+        log::info!(
+            "{} Test {:?} {:?} order {} at {}",
+            symbol,
+            &action,
+            order.order_type,
+            order.id,
+            order.target_price
+        );
+
+        Ok(ResponseBody {
+            response: ResponseType::OrderUpdated,
+            payload: Some(TradeResponse {
+                symbol,
+                accepted: true,
+                data: order,
+                attempts: vec![],
+            }),
+        })
+    }
+
+    async fn get_pending_orders(
+        &mut self,
+        symbol: &str,
+        strategy_name: &str,
+    ) -> Result<ResponseBody<Vec<Order>>> {
+        let command = Command {
+            command: "getTrades".to_owned(),
+            arguments: GetTrades { openedOnly: true },
+        };
+
+        match self.socket.request(&command).await? {
+            Message::Text(txt) => Ok(ResponseBody {
+                response: ResponseType::GetPendingOrders,
+                payload: Some(self.parse_pending_orders(&txt, symbol, strategy_name)?),
+            }),
+            _ => Err(RsAlgoError {
+                err: RsAlgoErrorKind::CantRead,
+            }),
+        }
+    }
+
+    async fn subscribe_stream(&mut self, symbol: &str) -> Result<()> {
+        self.subscribe(StreamSubscription::Candles(symbol.to_owned()))
+            .await
+    }
+
+    async fn subscribe_tick_prices(&mut self, symbol: &str) -> Result<()> {
+        self.symbol = symbol.to_owned();
+        self.subscribe(StreamSubscription::TickPrices(symbol.to_owned()))
+            .await
+    }
+
+    async fn subscribe_trades(&mut self, symbol: &str) -> Result<()> {
+        self.subscribe(StreamSubscription::Trades).await
+    }
+
+    async fn subscribe_balance(&mut self) -> Result<()> {
+        self.subscribe(StreamSubscription::Balance).await
+    }
+
+    async fn listen<F, T>(&mut self, symbol: &str, session_id: String, mut callback: F)
+    where
+        F: Send + FnMut(Message) -> T,
+        T: Future<Output = Result<()>> + Send + 'static,
+    {
+    }
+
+    async fn parse_stream_data(msg: Message, symbol: &str, strategy_name: &str) -> Option<String> {
+        let event = Xtb::parse_stream_message(&msg)
+            .and_then(|event| Xtb::broker_event(event, symbol, strategy_name));
+
+        match event {
+            Ok(event) => event.map(|event| event.into_message()),
+            Err(err) => {
+                log::error!("Skipping stream message: {}", err);
+                None
+            }
+        }
+    }
+
+    async fn keepalive_ping(&mut self) -> Result<String> {
+        let ping_command = Ping {
+            command: "ping".to_owned(),
+        };
+
+        match self.socket.request(&ping_command).await {
+            Ok(Message::Text(txt)) => Ok(txt),
+            _ => Err(RsAlgoError {
+                err: RsAlgoErrorKind::CantRead,
+            }),
+        }
+    }
+
+    // The pings run on their own task, once it stops the session is gone
+    async fn heartbeat(&mut self) -> Result<()> {
+        match &self.heartbeat {
+            Some(heartbeat) if heartbeat.is_finished() => Err(RsAlgoError {
+                err: RsAlgoErrorKind::ConnectionError,
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    async fn reconnect(&mut self) -> Result<()> {
+        let max_retries = self.config.reconnect_retries;
+        let max_backoff = Duration::from_secs(60);
+        let mut backoff = Duration::from_millis(self.config.reconnect_backoff);
+        let mut attempts = 0;
+
+        while attempts < max_retries {
+            attempts += 1;
+            log::warn!(
+                "Reconnecting to broker in {} ms. Attempt {} of {}",
+                backoff.as_millis(),
+                attempts,
+                max_retries
+            );
+
+            sleep(backoff).await;
+
+            match self.restore_session().await {
+                Ok(_) => {
+                    log::info!("Reconnected to broker after {} attempts", attempts);
+                    return Ok(());
+                }
+                Err(err) => {
+                    log::error!("Reconnection failed: {}", err);
+                    backoff = (backoff * 2).min(max_backoff);
+                }
+            }
+        }
+
+        Err(RsAlgoError {
+            err: RsAlgoErrorKind::ConnectionError,
+        })
+    }
+
+    async fn disconnect(&mut self) -> Result<()> {
+        log::info!("Disconnecting from broker");
+        self.stop_heartbeat();
+        self.socket.disconnect().await?;
+        self.stream.disconnect().await?;
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+impl TradingBroker for Xtb {
+    async fn connect(config: &RsAlgoConfig) -> Result<Self> {
+        symbol_spec::init(config)?;
+        <Xtb as BrokerStream>::new(config).await
+    }
+
+    async fn authenticate(&mut self, username: &str, password: &str) -> Result<()> {
+        self.login(username, password).await?;
+        Ok(())
+    }
+
+    async fn candles(
+        &mut self,
+        symbol: &str,
+        time_frame: usize,
+        from: i64,
+    ) -> Result<InstrumentData<VEC_DOHLC>> {
+        self.get_instrument_data(symbol, time_frame, from)
+            .await?
+            .into_payload()
+    }
+
+    async fn historic_candles(
+        &mut self,
+        symbol: &str,
+        time_frame: usize,
+        from: i64,
+        to: i64,
+    ) -> Result<InstrumentData<VEC_DOHLC>> {
+        self.get_historic_data(symbol, time_frame, from, to)
+            .await?
+            .into_payload()
+    }
+
+    async fn tick(&mut self, symbol: &str) -> Result<InstrumentTick> {
+        self.get_instrument_tick(symbol).await?.into_payload()
+    }
+
+    async fn swap(&mut self, symbol: &str) -> Result<InstrumentSwap> {
+        self.get_instrument_swap(symbol).await?.into_payload()
+    }
+
+    // getSymbol registers the spec while parsing the tick
+    async fn symbol_spec(&mut self, symbol: &str) -> Result<SymbolSpec> {
+        self.get_instrument_tick(symbol).await?;
+
+        symbol_spec::symbol_spec(symbol)
+            .map(|spec| SymbolSpec::clone(&spec))
+            .ok_or(RsAlgoError {
+                err: RsAlgoErrorKind::InvalidSymbol(symbol.to_owned(), "No symbol spec".to_owned()),
+            })
+    }
+
+    async fn load_symbol_specs(&mut self) -> Result<usize> {
+        let command = CommandAllSymbols {
+            command: "getAllSymbols".to_owned(),
+        };
+
+        match self.socket.request(&command).await? {
+            Message::Text(txt) => {
+                let data = self.parse_message(&txt)?;
+                let specs: Vec<SymbolSpec> = data["returnData"]
+                    .as_array()
+                    .ok_or(RsAlgoError {
+                        err: RsAlgoErrorKind::ParseError,
+                    })?
+                    .iter()
+                    .filter_map(Xtb::parse_symbol_spec)
+                    .collect();
+
+                let len = specs.len();
+                symbol_spec::register_all(specs);
+                log::info!("{} symbol specs loaded from broker", len);
+                Ok(len)
+            }
+            _ => Err(RsAlgoError {
+                err: RsAlgoErrorKind::CantRead,
+            }),
+        }
+    }
+
+    async fn market_hours(&mut self, symbol: &str) -> Result<MarketHours> {
+        self.get_market_hours(symbol).await?.into_payload()
+    }
+
+    async fn market_is_open(&mut self, symbol: &str) -> Result<bool> {
+        self.is_market_open(symbol).await?.into_payload()
+    }
+
+    async fn positions(&mut self, symbol: &str, strategy_name: &str) -> Result<PositionResult> {
+        self.get_active_positions(symbol, strategy_name)
+            .await?
+            .into_payload()
+    }
+
+    async fn open_positions(
+        &mut self,
+        symbol: &str,
+        strategy_name: &str,
+    ) -> Result<Vec<BrokerPosition>> {
+        let command = Command {
+            command: "getTrades".to_owned(),
+            arguments: GetTrades { openedOnly: true },
+        };
+
+        let msg = self.socket.request(&command).await?;
+        let positions = self.parse_broker_positions(&text_message(msg)?, symbol, strategy_name)?;
+
+        for position in positions.iter() {
+            self.positions
+                .insert(position.trade_in.id, position.trade_in.size);
+        }
+
+        Ok(positions)
+    }
+
+    async fn trade_history(
+        &mut self,
+        symbol: &str,
+        strategy_name: &str,
+        from: i64,
+    ) -> Result<Vec<TransactionDetails>> {
+        let command = Command {
+            command: "getTradesHistory".to_owned(),
+            arguments: GetTradesHistory {
+                start: from * 1000,
+                end: 0,
+            },
+        };
+
+        match self.socket.request(&command).await? {
+            Message::Text(txt) => self.parse_trades_history(&txt, symbol, strategy_name),
+            _ => Err(RsAlgoError {
+                err: RsAlgoErrorKind::CantRead,
+            }),
+        }
+    }
+
+    async fn account(&mut self) -> Result<AccountInfo> {
+        let command = CommandMarginLevel {
+            command: "getMarginLevel".to_owned(),
+        };
+
+        match self.socket.request(&command).await {
+            Ok(Message::Text(txt)) => self.parse_account_data(&txt),
+            _ => Err(RsAlgoError {
+                err: RsAlgoErrorKind::CantRead,
+            }),
+        }
+    }
+
+    async fn enter_trade(
+        &mut self,
+        trade: TradeData<TradeIn>,
+        orders: Option<Vec<Order>>,
+    ) -> Result<TradeResponse<TradeIn>> {
+        self.open_trade(trade, orders).await?.into_payload()
+    }
+
+    async fn exit_trade(&mut self, trade: TradeData<TradeOut>) -> Result<TradeResponse<TradeOut>> {
+        self.close_trade(trade).await?.into_payload()
+    }
+
+    async fn enter_order(
+        &mut self,
+        trade: TradeData<TradeIn>,
+        order: TradeData<Order>,
+    ) -> Result<TradeResponse<TradeIn>> {
+        self.open_order(trade, order).await?.into_payload()
+    }
+
+    async fn exit_order(
+        &mut self,
+        trade: TradeData<TradeOut>,
+        order: TradeData<Order>,
+    ) -> Result<TradeResponse<TradeOut>> {
+        self.close_order(trade, order).await?.into_payload()
+    }
+
+    async fn amend_trade(
+        &mut self,
+        trade: TradeData<TradeAmend>,
+    ) -> Result<TradeResponse<TradeAmend>> {
+        self.modify_trade(trade).await?.into_payload()
+    }
+
+    async fn place_order(&mut self, order: TradeData<Order>) -> Result<TradeResponse<Order>> {
+        BrokerStream::place_order(self, order).await?.into_payload()
+    }
+
+    async fn modify_order(&mut self, order: TradeData<Order>) -> Result<TradeResponse<Order>> {
+        BrokerStream::modify_order(self, order)
+            .await?
+            .into_payload()
+    }
+
+    async fn cancel_order(&mut self, order: TradeData<Order>) -> Result<TradeResponse<Order>> {
+        BrokerStream::cancel_order(self, order)
+            .await?
+            .into_payload()
+    }
+
+    async fn pending_orders(&mut self, symbol: &str, strategy_name: &str) -> Result<Vec<Order>> {
+        self.get_pending_orders(symbol, strategy_name)
+            .await?
+            .into_payload()
+    }
+
+    async fn subscribe(&mut self, symbol: &str) -> Result<()> {
+        self.subscribe_stream(symbol).await?;
+        self.subscribe_tick_prices(symbol).await?;
+        self.subscribe_trades(symbol).await?;
+        self.subscribe_balance().await
+    }
+
+    async fn next_event(
+        &mut self,
+        symbol: &str,
+        strategy_name: &str,
+    ) -> Result<Option<BrokerEvent>> {
+        let is_alive = match self.heartbeat().await {
+            Ok(_) => {
+                let stream_timeout = Duration::from_secs(self.config.stream_timeout);
+
+                match timeout(stream_timeout, self.stream.read()).await {
+                    Ok(Some(Ok(Message::Close(_)))) => {
+                        log::error!("Stream closed by broker");
+                        false
+                    }
+                    Ok(Some(Ok(msg))) => {
+                        let event = Xtb::parse_stream_message(&msg).and_then(|event| match event {
+                            StreamEvent::Trade(trade) => {
+                                Ok(self.track_stream_trade(trade, symbol, strategy_name))
+                            }
+                            event => Xtb::broker_event(event, symbol, strategy_name),
+                        });
+
+                        return match event {
+                            Ok(event) => Ok(event),
+                            Err(err) => {
+                                log::error!("Skipping stream message: {}", err);
+                                Ok(None)
+                            }
+                        };
+                    }
+                    Ok(Some(Err(err))) => {
+                        log::error!("Error reading stream: {}", err);
+                        false
+                    }
+                    Ok(None) => {
+                        log::error!("Stream closed by broker");
+                        false
+                    }
+                    Err(_) => {
+                        log::error!("No stream data in {} secs", stream_timeout.as_secs());
+                        false
+                    }
+                }
+            }
+            Err(err) => {
+                log::error!("Heartbeat failed: {}", err);
+                false
+            }
+        };
+
+        match is_alive {
+            true => Ok(None),
+            false => {
+                self.reconnect().await?;
+                Ok(Some(BrokerEvent::Reconnect))
+            }
+        }
+    }
+
+    async fn shutdown(&mut self) -> Result<()> {
+        self.disconnect().await
+    }
+}
+
+impl Drop for Xtb {
+    fn drop(&mut self) {
+        self.stop_heartbeat();
+    }
+}
+
+impl Xtb {
+    // Pings the command socket and the stream every keepalive interval
+    fn spawn_heartbeat(&mut self) {
+        self.stop_heartbeat();
+
+        let interval = Duration::from_secs(self.config.keepalive_interval);
+        if interval.is_zero() {
+            return;
+        }
+
+        let socket = self.socket.clone();
+        let stream = self.stream.sender();
+        let stream_ping = serde_json::json!({
+            "command": "ping",
+            "streamSessionId": self.streamSessionId,
+        })
+        .to_string();
+
+        self.heartbeat = Some(tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            // The first tick completes right away
+            ticker.tick().await;
+
+            loop {
+                ticker.tick().await;
+
+                let ping = Ping {
+                    command: "ping".to_owned(),
+                };
+
+                if let Err(err) = socket.request(&ping).await {
+                    log::error!("Keepalive ping failed: {}", err);
+                    break;
+                }
+
+                if stream.send(Message::text(stream_ping.clone())).is_err() {
+                    log::error!("Stream ping failed");
+                    break;
+                }
+            }
+        }));
+    }
+
+    fn stop_heartbeat(&mut self) {
+        if let Some(heartbeat) = self.heartbeat.take() {
+            heartbeat.abort();
+        }
+    }
+
+    async fn send_stream<T>(&mut self, command: &T) -> Result<()>
+    where
+        for<'de> T: Serialize + Deserialize<'de> + Debug,
+    {
+        self.stream
+            .send(&serde_json::to_string(&command).unwrap())
+            .await?;
+
+        Ok(())
+    }
+
+    async fn execute_transaction(
+        &mut self,
+        command: &Command<TransactionInfo>,
+        attempt: usize,
+        ask: f64,
+        bid: f64,
+    ) -> Result<(TradeAttempt, Option<TransactionStatusnResponse>)> {
+        let msg = self.socket.request(command).await?;
+
+        let txt = match msg {
+            Message::Text(txt) => txt,
+            _ => {
+                return Err(RsAlgoError {
+                    err: RsAlgoErrorKind::CantRead,
+                })
+            }
+        };
+
+        let mut trade_attempt = TradeAttempt {
+            attempt,
+            status: TradeStatus::Rejected,
+            ask,
+            bid,
+            error_code: None,
+            message: None,
+            retryable: false,
         };
 
-        msg
-    }
+        let order_id = match self.get_order_id_executed(&txt) {
+            Ok((true, order_id)) => order_id,
+            Ok((false, _)) => return Ok((trade_attempt, None)),
+            Err(RsAlgoError { err }) => match err.broker_error() {
+                Some((error_code, message)) => {
+                    trade_attempt.retryable = retry::is_retryable_error(&err);
+                    trade_attempt.error_code = Some(error_code.to_owned());
+                    trade_attempt.message = Some(message.to_owned());
 
-    async fn keepalive_ping(&mut self) -> Result<String> {
-        let ping_command = Ping {
-            command: "ping".to_owned(),
+                    return Ok((trade_attempt, None));
+                }
+                None => return Err(RsAlgoError { err }),
+            },
         };
 
-        self.send(&ping_command).await.unwrap();
-        let msg = self.socket.read().await.unwrap();
-        let res = match msg {
-            Message::Text(txt) => txt,
-            _ => panic!(),
+        let trans_status = self.get_transaction_status(order_id).await?;
+
+        match trans_status.status.is_accepted() {
+            true => {
+                trade_attempt.status = TradeStatus::Fulfilled;
+                trade_attempt.ask = trans_status.ask;
+                trade_attempt.bid = trans_status.bid;
+                Ok((trade_attempt, Some(trans_status)))
+            }
+            false => {
+                log::error!(
+                    "Position {} not accepted by Broker: {}",
+                    order_id,
+                    trans_status.message
+                );
+                trade_attempt.retryable = retry::is_retryable_message(&trans_status.message);
+                trade_attempt.message = Some(trans_status.message);
+                Ok((trade_attempt, None))
+            }
+        }
+    }
+
+    async fn get_response<T>(
+        &mut self,
+        command: &T,
+    ) -> Result<ResponseBody<InstrumentData<VEC_DOHLC>>>
+    where
+        T: Serialize + Sync,
+    {
+        let res = match self.socket.request(command).await {
+            Ok(Message::Text(txt)) => txt,
+            _ => {
+                return Err(RsAlgoError {
+                    err: RsAlgoErrorKind::CantRead,
+                })
+            }
         };
+        let res = self.handle_response::<VEC_DOHLC>(&res).await?;
 
         Ok(res)
     }
 
-    async fn disconnect(&mut self) -> Result<()> {
-        log::info!("Disconnecting from broker");
-        self.socket.disconnect().await.unwrap();
-        self.stream.disconnect().await.unwrap();
+    async fn subscribe(&mut self, subscription: StreamSubscription) -> Result<()> {
+        self.send_subscription(&subscription).await?;
+
+        if !self.subscriptions.contains(&subscription) {
+            self.subscriptions.push(subscription);
+        }
+
         Ok(())
     }
-}
 
-impl Xtb {
-    async fn send<T>(&mut self, command: &T) -> Result<()>
-    where
-        for<'de> T: Serialize + Deserialize<'de> + Debug,
-    {
-        self.socket
-            .send(&serde_json::to_string(&command).unwrap())
-            .await?;
+    async fn send_subscription(&mut self, subscription: &StreamSubscription) -> Result<()> {
+        let stream_session_id = self.streamSessionId.clone();
 
-        Ok(())
+        match subscription {
+            StreamSubscription::Candles(symbol) => {
+                let command_alive = CommandStreaming {
+                    command: "getKeepAlive".to_owned(),
+                    streamSessionId: stream_session_id.clone(),
+                };
+
+                self.send_stream(&command_alive).await?;
+
+                let command = CommandGetCandles {
+                    command: "getCandles".to_owned(),
+                    streamSessionId: stream_session_id,
+                    symbol: symbol.to_owned(),
+                };
+
+                self.send_stream(&command).await
+            }
+            StreamSubscription::TickPrices(symbol) => {
+                let is_prod = self.config.env.is_prod();
+
+                let arrival_time = match is_prod {
+                    true => 1,
+                    false => 1, //1000,
+                };
+
+                let command = CommandTickStreamParams {
+                    command: "getTickPrices".to_owned(),
+                    streamSessionId: stream_session_id,
+                    symbol: symbol.to_string(),
+                    minArrivalTime: arrival_time,
+                    maxLevel: 0,
+                };
+
+                self.send_stream(&command).await
+            }
+            StreamSubscription::Trades => {
+                let command = CommandTradeStatusParams {
+                    command: "getTrades".to_owned(),
+                    streamSessionId: stream_session_id,
+                };
+
+                self.send_stream(&command).await
+            }
+            StreamSubscription::Balance => {
+                let command = CommandStreaming {
+                    command: "getBalance".to_owned(),
+                    streamSessionId: stream_session_id,
+                };
+
+                self.send_stream(&command).await
+            }
+        }
     }
 
-    async fn send_stream<T>(&mut self, command: &T) -> Result<()>
-    where
-        for<'de> T: Serialize + Deserialize<'de> + Debug,
-    {
-        self.stream
-            .send(&serde_json::to_string(&command).unwrap())
-            .await
-            .unwrap();
+    async fn restore_session(&mut self) -> Result<()> {
+        self.socket.re_connect().await?;
+        self.stream.re_connect().await?;
+
+        if let Some((username, password)) = self.credentials.clone() {
+            self.login(&username, &password).await?;
+        }
+
+        for subscription in self.subscriptions.clone().iter() {
+            self.send_subscription(subscription).await?;
+        }
+
+        log::info!(
+            "Session restored with {} stream subscriptions",
+            self.subscriptions.len()
+        );
 
         Ok(())
     }
 
-    async fn get_response(&mut self) -> Result<ResponseBody<InstrumentData<VEC_DOHLC>>> {
-        let msg = self.socket.read().await.unwrap();
-        let res = match msg {
-            Message::Text(txt) => txt,
-            _ => panic!(),
+    pub fn parse_stream_message(msg: &Message) -> Result<StreamEvent> {
+        match msg {
+            Message::Text(txt) => Xtb::parse_stream_text(txt),
+            Message::Ping(_) => Ok(StreamEvent::Ping),
+            Message::Pong(_) => Ok(StreamEvent::Pong),
+            Message::Close(_) => Ok(StreamEvent::Close),
+            Message::Binary(_) | Message::Frame(_) => Ok(StreamEvent::Binary),
+        }
+    }
+
+    pub fn parse_stream_text(txt: &str) -> Result<StreamEvent> {
+        let value: Value = serde_json::from_str(txt).map_err(|err| {
+            log::error!("Can't parse stream message {} ({})", txt, err);
+            RsAlgoError {
+                err: RsAlgoErrorKind::ParseError,
+            }
+        })?;
+
+        xtb_error::check_response(&value)?;
+
+        let envelope: StreamEnvelope = serde_json::from_value(value).map_err(|err| {
+            log::error!("Stream message without command {} ({})", txt, err);
+            RsAlgoError {
+                err: RsAlgoErrorKind::ParseError,
+            }
+        })?;
+
+        let event = match envelope.command.as_str() {
+            "candle" => StreamEvent::Candle(stream_data(envelope)?),
+            "tickPrices" => StreamEvent::TickPrices(stream_data(envelope)?),
+            "trade" => StreamEvent::Trade(stream_data(envelope)?),
+            "tradeStatus" => StreamEvent::TradeStatus(stream_data(envelope)?),
+            "balance" => StreamEvent::Balance(stream_data(envelope)?),
+            "profit" => StreamEvent::Profit(stream_data(envelope)?),
+            "news" => StreamEvent::News(stream_data(envelope)?),
+            "keepAlive" => StreamEvent::KeepAlive(stream_data(envelope)?),
+            command => {
+                log::warn!("Unknown stream command {}", command);
+                StreamEvent::Unknown(command.to_owned())
+            }
         };
-        let res = self.handle_response::<VEC_DOHLC>(&res).await.unwrap();
 
-        Ok(res)
+        Ok(event)
+    }
+
+    pub fn parse_stream_event(
+        txt: &str,
+        symbol: &str,
+        strategy_name: &str,
+    ) -> Result<Option<BrokerEvent>> {
+        let event = Xtb::parse_stream_text(txt)?;
+        Xtb::broker_event(event, symbol, strategy_name)
+    }
+
+    pub fn broker_event(
+        event: StreamEvent,
+        symbol: &str,
+        strategy_name: &str,
+    ) -> Result<Option<BrokerEvent>> {
+        match event {
+            StreamEvent::Candle(candle) => {
+                let date = parse_time_seconds(candle.ctm / 1000);
+                let size = candle.vol * 1000.;
+                let ohlc = (
+                    date,
+                    candle.open,
+                    candle.high,
+                    candle.low,
+                    candle.close,
+                    size,
+                );
+
+                Ok(Some(BrokerEvent::Candle(ohlc)))
+            }
+            StreamEvent::TickPrices(tick) => {
+                let pip_size = number_pips(&tick.symbol);
+
+                let tick = InstrumentTick::new()
+                    .symbol(tick.symbol)
+                    .ask(tick.ask)
+                    .bid(tick.bid)
+                    .high(tick.high)
+                    .low(tick.low)
+                    .spread(tick.spread_raw)
+                    .pip_size(pip_size)
+                    .time(tick.timestamp)
+                    .build()
+                    .map_err(|err| {
+                        log::error!("Invalid stream tick ({})", err);
+                        RsAlgoError {
+                            err: RsAlgoErrorKind::ParseError,
+                        }
+                    })?;
+
+                Ok(Some(BrokerEvent::Tick(tick)))
+            }
+            StreamEvent::Trade(trade) => Ok(Xtb::parse_stream_trade(trade, symbol, strategy_name)),
+            // The balance stream doesn't carry the account currency
+            StreamEvent::Balance(balance) => Ok(Some(BrokerEvent::Account(AccountInfo {
+                currency: String::new(),
+                balance: balance.balance,
+                equity: balance.equity,
+                credit: balance.credit,
+                margin: balance.margin,
+                margin_free: balance.margin_free,
+                margin_level: balance.margin_level,
+            }))),
+            _ => Ok(None),
+        }
+    }
+
+    fn own_trade_comments(
+        trade: &StreamTrade,
+        symbol: &str,
+        strategy_name: &str,
+    ) -> Option<TransactionComments> {
+        // Trades opened outside the bot don't carry our comments
+        let trans_comments: TransactionComments =
+            serde_json::from_str(trade.custom_comment.as_deref().unwrap_or_default()).ok()?;
+
+        // TAKING ONLY THE OWN SYMBOL & STRATEGY COMBINATION
+        match symbol == trade.symbol && strategy_name == trans_comments.strategy_name {
+            true => Some(trans_comments),
+            false => None,
+        }
+    }
+
+    // Checks position updates against the known open positions. Trades opened by
+    // open_trade_real are skipped and partial closes keep the rest on the original trade
+    pub fn track_stream_trade(
+        &mut self,
+        trade: StreamTrade,
+        symbol: &str,
+        strategy_name: &str,
+    ) -> Option<BrokerEvent> {
+        let is_position = TransactionCommand::from_value(trade.cmd)
+            .map_or(false, |command| !command.is_pending());
+
+        let trans_comments = match Xtb::own_trade_comments(&trade, symbol, strategy_name) {
+            Some(trans_comments) if is_position => trans_comments,
+            _ => return Xtb::parse_stream_trade(trade, symbol, strategy_name),
+        };
+
+        let id = trade.position as usize;
+        let volume = trade.volume;
+        let lot_step = symbol_spec::symbol_spec(symbol).map_or(0.01, |spec| spec.lot_step);
+        let is_open = matches!(
+            TransactionAction::from_value(trade.trans_type),
+            Some(TransactionAction::Open)
+        );
+
+        match (trade.closed, self.positions.get(&id).copied()) {
+            // The position update with the remaining size follows
+            (true, Some(size)) if volume < size - lot_step / 2. => {
+                log::info!("Trade {} partially closed {} of {}", id, volume, size);
+            }
+            (true, _) => {
+                self.positions.remove(&id);
+            }
+            (false, Some(size)) if (size - volume).abs() < lot_step / 2. => {
+                if is_open {
+                    log::info!("Skipping already opened trade {}", id);
+                    return None;
+                }
+            }
+            // Remaining size after a partial close
+            (false, Some(size)) => {
+                log::info!("Trade {} resized from {} to {}", id, size, volume);
+                self.positions.insert(id, volume);
+                return Some(Xtb::stream_trade_in(trade, symbol, trans_comments));
+            }
+            (false, None) => {
+                self.positions.insert(id, volume);
+            }
+        }
+
+        Xtb::parse_stream_trade(trade, symbol, strategy_name)
+    }
+
+    fn parse_stream_trade(
+        trade: StreamTrade,
+        symbol: &str,
+        strategy_name: &str,
+    ) -> Option<BrokerEvent> {
+        let trans_comments = Xtb::own_trade_comments(&trade, symbol, strategy_name)?;
+
+        let command = TransactionCommand::from_value(trade.cmd)?;
+        let action = TransactionAction::from_value(trade.trans_type)?;
+
+        match (command.is_pending(), trade.closed, &action) {
+            (true, _, _) => Some(BrokerEvent::Order(Xtb::stream_pending_order(
+                &trade,
+                &command,
+                &action,
+                &trans_comments,
+            ))),
+            (false, true, _) => Some(Xtb::stream_trade_out(trade, symbol, trans_comments)),
+            (false, false, TransactionAction::Open) => {
+                Some(Xtb::stream_trade_in(trade, symbol, trans_comments))
+            }
+            (false, false, TransactionAction::Modify) => {
+                Xtb::stream_position_order(&trade, &trans_comments).map(BrokerEvent::Order)
+            }
+            _ => None,
+        }
+    }
+
+    fn stream_trade_in(
+        trade: StreamTrade,
+        symbol: &str,
+        trans_comments: TransactionComments,
+    ) -> BrokerEvent {
+        let trade_in = Xtb::record_trade_in(&trade, &trans_comments);
+        let trade_type = &trade_in.trade_type;
+        let price_in = trade_in.price_in;
+
+        match trade_type.is_order() {
+            true => log::info!(
+                "Real Order activated {}_{} {:?} trade {}. Opening price: {}",
+                symbol,
+                &trans_comments.strategy_name,
+                &trade_type,
+                trade.position,
+                price_in
+            ),
+            false => log::info!(
+                "Real Opened {}_{} {:?} trade {}. Opening price: {}",
+                symbol,
+                &trans_comments.strategy_name,
+                &trade_type,
+                trade.position,
+                price_in
+            ),
+        };
+
+        BrokerEvent::TradeIn(TradeResponse {
+            symbol: symbol.to_owned(),
+            accepted: true,
+            data: trade_in,
+            attempts: vec![],
+        })
+    }
+
+    fn stream_trade_out(
+        trade: StreamTrade,
+        symbol: &str,
+        trans_comments: TransactionComments,
+    ) -> BrokerEvent {
+        let comment = trade.comment.as_deref().unwrap_or_default();
+        let is_long = trans_comments.trade_type.is_long();
+
+        // Partial closes arrive as a closed trade with the closed volume,
+        // followed by an update of the position with the remaining size
+        let trade_type = match (comment, is_long) {
+            ("[S/L]", true) => TradeType::StopLossLong,
+            ("[S/L]", false) => TradeType::StopLossShort,
+            ("[T/P]", true) => TradeType::OrderOutLong,
+            ("[T/P]", false) => TradeType::OrderOutShort,
+            (_, true) => TradeType::MarketOutLong,
+            (_, false) => TradeType::MarketOutShort,
+        };
+
+        if comment.starts_with("[S/O") {
+            log::warn!(
+                "Trade {} liquidated by margin call: {}",
+                trade.position,
+                comment
+            );
+        }
+
+        let id = trade.position as usize;
+        let price_in = trade.open_price;
+        let price_out = trade.close_price.unwrap_or_default();
+        let strategy_name = trans_comments.strategy_name;
+
+        let gross_profit = trade.profit.unwrap_or(0.0);
+        let swap = trade.storage.unwrap_or(0.0);
+        let commission = trade.commission.unwrap_or(0.0);
+
+        let profit = gross_profit + swap + commission;
+
+        log::info!(
+            "PROFIT DATA Gross Profit: {} Swap: {} Comission: {} Net Profit: {}",
+            gross_profit,
+            swap,
+            commission,
+            profit
+        );
+
+        let spread_out = 0.;
+        let close_time = match trade.close_time {
+            Some(close_time) => parse_time_seconds(close_time / 1000),
+            None => Local::now(),
+        };
+
+        let bid = match trade_type.is_long() {
+            true => price_out,
+            false => price_out + spread_out,
+        };
+
+        log::info!(
+            "Real Closed {}_{} {:?} trade {}. Closing price: {} Profit: {}",
+            &symbol,
+            &strategy_name,
+            &trade_type,
+            &id,
+            &price_out,
+            &profit,
+        );
+
+        let trade_out = TradeOut {
+            id,
+            index_in: trans_comments.index_in,
+            price_in,
+            status: TradeStatus::Fulfilled,
+            size: trade.volume,
+            trade_type,
+            date_in: to_dbtime(parse_time_seconds(trade.open_time / 1000)),
+            spread_in: trans_comments.spread,
+            ask: price_in,
+            index_out: uuid::generate_ts_id(close_time),
+            price_origin: price_in,
+            price_out,
+            bid,
+            spread_out,
+            date_out: to_dbtime(close_time),
+            profit,
+            profit_per: 0.,
+            run_up: 0.,
+            run_up_per: 0.,
+            draw_down: 0.,
+            draw_down_per: 0.,
+            commission: -commission,
+            spread_cost: 0.,
+            swap,
+        };
+
+        BrokerEvent::TradeOut(TradeResponse {
+            symbol: symbol.to_owned(),
+            accepted: true,
+            data: trade_out,
+            attempts: vec![],
+        })
+    }
+
+    fn stream_pending_order(
+        trade: &StreamTrade,
+        command: &TransactionCommand,
+        action: &TransactionAction,
+        trans_comments: &TransactionComments,
+    ) -> Order {
+        let is_deleted = matches!(action, TransactionAction::Delete)
+            || (!trade.closed && trade.state.as_deref() == Some("Deleted"));
+
+        // A pending order closed without being deleted has been activated,
+        // the position it opened follows as a separate trade record
+        let status = match (is_deleted, trade.closed) {
+            (true, _) => OrderStatus::Canceled,
+            (false, true) => OrderStatus::Fulfilled,
+            (false, false) => OrderStatus::Pending,
+        };
+
+        let order_type = match command.is_long() {
+            true => OrderType::BuyOrderLong(trade.volume, trade.open_price),
+            false => OrderType::BuyOrderShort(trade.volume, trade.open_price),
+        };
+
+        log::info!(
+            "Real {:?} order {} {:?} at {}",
+            &order_type,
+            trade.order,
+            &status,
+            trade.open_price
+        );
+
+        Order {
+            id: trade.order as usize,
+            trade_id: 0,
+            index_created: trans_comments.index_in,
+            index_fulfilled: 0,
+            size: trade.volume,
+            order_type,
+            status,
+            origin_price: trade.open_price,
+            target_price: trade.open_price,
+            created_at: to_dbtime(parse_time_seconds(trade.open_time / 1000)),
+            updated_at: Some(to_dbtime(Local::now())),
+            full_filled_at: None,
+            valid_until: trade
+                .expiration
+                .filter(|expiration| *expiration > 0)
+                .map(|expiration| to_dbtime(parse_time_seconds(expiration / 1000))),
+            meta: trade.sl.filter(|sl| *sl > 0.).map(|sl| MetaData { sl }),
+        }
+    }
+
+    fn stream_position_order(
+        trade: &StreamTrade,
+        trans_comments: &TransactionComments,
+    ) -> Option<Order> {
+        let is_long = trans_comments.trade_type.is_long();
+        let price_in = trade.open_price;
+        let stop_loss = trade.sl.unwrap_or_default();
+        let take_profit = trade.tp.unwrap_or_default();
+
+        let (order_type, target_price) = match (stop_loss > 0., take_profit > 0., is_long) {
+            (true, _, true) => (
+                OrderType::StopLossLong(StopLossType::Price(stop_loss), price_in),
+                stop_loss,
+            ),
+            (true, _, false) => (
+                OrderType::StopLossShort(StopLossType::Price(stop_loss), price_in),
+                stop_loss,
+            ),
+            (false, true, true) => (
+                OrderType::TakeProfitLong(trade.volume, take_profit),
+                take_profit,
+            ),
+            (false, true, false) => (
+                OrderType::TakeProfitShort(trade.volume, take_profit),
+                take_profit,
+            ),
+            _ => return None,
+        };
+
+        log::info!(
+            "Real position {} modified. SL: {} TP: {}",
+            trade.position,
+            stop_loss,
+            take_profit
+        );
+
+        Some(Order {
+            id: trade.order as usize,
+            trade_id: trade.position as usize,
+            index_created: trans_comments.index_in,
+            index_fulfilled: 0,
+            size: trade.volume,
+            order_type,
+            status: OrderStatus::Pending,
+            origin_price: price_in,
+            target_price,
+            created_at: to_dbtime(parse_time_seconds(trade.open_time / 1000)),
+            updated_at: Some(to_dbtime(Local::now())),
+            full_filled_at: None,
+            valid_until: None,
+            meta: None,
+        })
+    }
+
+    fn record_trade_in(trade: &StreamTrade, trans_comments: &TransactionComments) -> TradeIn {
+        TradeIn {
+            id: trade.position as usize,
+            trade_type: trans_comments.trade_type.clone(),
+            status: TradeStatus::Fulfilled,
+            index_in: trans_comments.index_in,
+            size: trade.volume,
+            origin_price: trade.open_price,
+            price_in: trade.open_price,
+            ask: trans_comments.bid + trans_comments.spread,
+            spread: trans_comments.spread,
+            date_in: to_dbtime(parse_time_seconds(trade.open_time / 1000)),
+            stop_rules: vec![],
+        }
+    }
+
+    fn record_orders(trade: &StreamTrade, trans_comments: &TransactionComments) -> Vec<Order> {
+        let id = trade.position as usize;
+        let is_long = trans_comments.trade_type.is_long();
+        let price_in = trade.open_price;
+        let size = trade.volume;
+        let stop_loss = trade.sl.unwrap_or_default();
+        let date_in = to_dbtime(parse_time_seconds(trade.open_time / 1000));
+
+        let mut targets = vec![];
+
+        if stop_loss > 0. {
+            let stop_order_type = match is_long {
+                true => OrderType::StopLossLong(StopLossType::Price(stop_loss), price_in),
+                false => OrderType::StopLossShort(StopLossType::Price(stop_loss), price_in),
+            };
+            targets.push((stop_order_type, stop_loss));
+        }
+
+        if let Some(sell_price) = trans_comments.sell_order_price {
+            let sell_order_type = match is_long {
+                true => OrderType::SellOrderLong(size, sell_price),
+                false => OrderType::SellOrderShort(size, sell_price),
+            };
+            targets.push((sell_order_type, sell_price));
+        }
+
+        targets
+            .into_iter()
+            .map(|(order_type, target_price)| Order {
+                id,
+                trade_id: id,
+                index_created: trans_comments.index_in,
+                index_fulfilled: 0,
+                size,
+                order_type,
+                status: OrderStatus::Pending,
+                origin_price: price_in,
+                target_price,
+                created_at: date_in,
+                updated_at: None,
+                full_filled_at: None,
+                valid_until: None,
+                meta: None,
+            })
+            .collect()
+    }
+
+    pub fn parse_broker_positions(
+        &mut self,
+        txt: &str,
+        symbol: &str,
+        strategy_name: &str,
+    ) -> Result<Vec<BrokerPosition>> {
+        let data = self.parse_message(txt)?;
+        let records: Vec<StreamTrade> = serde_json::from_value(data["returnData"].clone())
+            .map_err(|err| {
+                log::error!("Can't parse broker positions ({})", err);
+                RsAlgoError {
+                    err: RsAlgoErrorKind::ParseError,
+                }
+            })?;
+
+        let positions = records
+            .iter()
+            .filter(|record| record.symbol == symbol)
+            .filter(|record| {
+                TransactionCommand::from_value(record.cmd)
+                    .map_or(false, |command| !command.is_pending())
+            })
+            .filter_map(|record| {
+                let comments = record.custom_comment.as_deref().unwrap_or_default();
+                let trans_comments: TransactionComments = serde_json::from_str(comments).ok()?;
+
+                match trans_comments.strategy_name == strategy_name {
+                    true => Some(BrokerPosition {
+                        trade_in: Xtb::record_trade_in(record, &trans_comments),
+                        orders: Xtb::record_orders(record, &trans_comments),
+                    }),
+                    false => None,
+                }
+            })
+            .collect();
+
+        Ok(positions)
+    }
+
+    // Closed trades of the strategy, profit is net of swap and commission
+    pub fn parse_trades_history(
+        &mut self,
+        txt: &str,
+        symbol: &str,
+        strategy_name: &str,
+    ) -> Result<Vec<TransactionDetails>> {
+        let data = self.parse_message(txt)?;
+        let records: Vec<StreamTrade> = serde_json::from_value(data["returnData"].clone())
+            .map_err(|err| {
+                log::error!("Can't parse trades history ({})", err);
+                RsAlgoError {
+                    err: RsAlgoErrorKind::ParseError,
+                }
+            })?;
+
+        let history = records
+            .iter()
+            .filter(|record| record.symbol == symbol)
+            .filter(|record| {
+                let comments = record.custom_comment.as_deref().unwrap_or_default();
+                serde_json::from_str::<TransactionComments>(comments)
+                    .map_or(false, |comments| comments.strategy_name == strategy_name)
+            })
+            .map(|record| {
+                let gross_profit = record.profit.unwrap_or(0.);
+                let swap = record.storage.unwrap_or(0.);
+                let commission = record.commission.unwrap_or(0.);
+                let profit = gross_profit + swap + commission;
+
+                log::info!(
+                    "PROFIT DATA Gross Profit: {} Swap: {} Comission: {} Net Profit: {}",
+                    gross_profit,
+                    swap,
+                    commission,
+                    profit
+                );
+
+                TransactionDetails {
+                    id: record.position as usize,
+                    open_price: record.open_price,
+                    close_price: record.close_price.unwrap_or(record.open_price),
+                    profit,
+                    commission,
+                    swap,
+                    close_time: record.close_time,
+                }
+            })
+            .collect();
+
+        Ok(history)
+    }
+
+    pub fn parse_pending_orders(
+        &mut self,
+        txt: &str,
+        symbol: &str,
+        strategy_name: &str,
+    ) -> Result<Vec<Order>> {
+        let data = self.parse_message(txt)?;
+        let records: Vec<StreamTrade> = serde_json::from_value(data["returnData"].clone())
+            .map_err(|err| {
+                log::error!("Can't parse pending orders ({})", err);
+                RsAlgoError {
+                    err: RsAlgoErrorKind::ParseError,
+                }
+            })?;
+
+        let orders = records
+            .iter()
+            .filter(|record| record.symbol == symbol)
+            .filter_map(|record| {
+                let command = TransactionCommand::from_value(record.cmd)
+                    .filter(|command| command.is_pending())?;
+                let action = TransactionAction::from_value(record.trans_type)?;
+                let comments = record.custom_comment.as_deref().unwrap_or_default();
+                let trans_comments: TransactionComments = serde_json::from_str(comments).ok()?;
+
+                match trans_comments.strategy_name == strategy_name {
+                    true => Some(Xtb::stream_pending_order(
+                        record,
+                        &command,
+                        &action,
+                        &trans_comments,
+                    )),
+                    false => None,
+                }
+            })
+            .collect();
+
+        Ok(orders)
     }
 
     pub fn parse_message(&mut self, msg: &str) -> Result<Value> {
-        let parsed: Value = serde_json::from_str(&msg).expect("Can't parse to JSON");
+        let parsed: Value = serde_json::from_str(&msg).map_err(|_| RsAlgoError {
+            err: RsAlgoErrorKind::ParseError,
+        })?;
+        xtb_error::check_response(&parsed)?;
         Ok(parsed)
     }
 
@@ -1738,11 +3054,11 @@ impl Xtb {
         &mut self,
         msg: &str,
     ) -> Result<ResponseBody<InstrumentData<VEC_DOHLC>>> {
-        let data = self.parse_message(&msg).unwrap();
+        let data = self.parse_message(&msg)?;
         let response: ResponseBody<InstrumentData<VEC_DOHLC>> = match &data {
             // Login
             _x if matches!(&data["streamSessionId"], Value::String(_x)) => {
-                self.streamSessionId = data["streamSessionId"].as_str().unwrap().to_owned();
+                self.streamSessionId = field(&data, "streamSessionId", Value::as_str)?.to_owned();
                 ResponseBody {
                     response: ResponseType::GetInstrumentData,
                     payload: Some(InstrumentData {
@@ -1758,7 +3074,7 @@ impl Xtb {
                 payload: Some(InstrumentData {
                     symbol: self.symbol.clone(),
                     time_frame: TimeFrameType::from_number(self.time_frame),
-                    data: self.parse_price_data(&data).await.unwrap(),
+                    data: self.parse_price_data(&data).await?,
                 }),
             },
             _ => ResponseBody {
@@ -1771,21 +3087,18 @@ impl Xtb {
 
     async fn parse_price_data(&mut self, data: &Value) -> Result<VEC_DOHLC> {
         let mut result: VEC_DOHLC = vec![];
-        let return_data = data
-            .get("returnData")
-            .ok_or_else(|| RsAlgoErrorKind::ParseError)
-            .unwrap();
-        let digits = return_data["digits"].as_f64().unwrap();
+        let return_data = field(data, "returnData", Some)?;
+        let digits = field(return_data, "digits", Value::as_f64)?;
         let x = 10.0_f64;
         let pow = x.powf(digits);
-        for obj in return_data["rateInfos"].as_array().unwrap() {
+        for obj in field(return_data, "rateInfos", Value::as_array)? {
             //FIXME!!
-            let date = parse_time_seconds(obj["ctm"].as_i64().unwrap() / 1000);
-            let open = obj["open"].as_f64().unwrap() / pow;
-            let high = open + obj["high"].as_f64().unwrap() / pow;
-            let low = open + obj["low"].as_f64().unwrap() / pow;
-            let close = open + obj["close"].as_f64().unwrap() / pow;
-            let size = obj["vol"].as_f64().unwrap() * 1000.;
+            let date = parse_time_seconds(field(obj, "ctm", Value::as_i64)? / 1000);
+            let open = field(obj, "open", Value::as_f64)? / pow;
+            let high = open + field(obj, "high", Value::as_f64)? / pow;
+            let low = open + field(obj, "low", Value::as_f64)? / pow;
+            let close = open + field(obj, "close", Value::as_f64)? / pow;
+            let size = field(obj, "vol", Value::as_f64)? * 1000.;
 
             result.push((date, open, high, low, close, size));
         }
@@ -1794,18 +3107,23 @@ impl Xtb {
     }
 
     pub fn parse_tick_data(&mut self, symbol: String, txt: String) -> Result<InstrumentTick> {
-        let data = self.parse_message(&txt).unwrap();
-        let return_data = data
-            .get("returnData")
-            .ok_or_else(|| RsAlgoErrorKind::ParseError)
-            .unwrap();
-
-        let ask = return_data["ask"].as_f64().unwrap();
-        let bid = return_data["bid"].as_f64().unwrap();
-        let high = return_data["high"].as_f64().unwrap();
-        let low = return_data["low"].as_f64().unwrap();
-        let spread = return_data["spreadRaw"].as_f64().unwrap();
-        let pip_size = number_pips(&symbol);
+        let data = self.parse_message(&txt)?;
+        let return_data = field(&data, "returnData", Some)?;
+
+        let ask = field(return_data, "ask", Value::as_f64)?;
+        let bid = field(return_data, "bid", Value::as_f64)?;
+        let high = field(return_data, "high", Value::as_f64)?;
+        let low = field(return_data, "low", Value::as_f64)?;
+        let spread = field(return_data, "spreadRaw", Value::as_f64)?;
+
+        let pip_size = match Xtb::parse_symbol_spec(return_data) {
+            Some(spec) => {
+                let pip_size = spec.pip_size;
+                symbol_spec::register(spec);
+                pip_size
+            }
+            None => number_pips(&symbol),
+        };
 
         let tick = InstrumentTick::new()
             .symbol(symbol)
@@ -1817,22 +3135,47 @@ impl Xtb {
             .pip_size(pip_size)
             .time(Local::now().timestamp())
             .build()
-            .unwrap();
+            .map_err(|_| RsAlgoError {
+                err: RsAlgoErrorKind::ParseError,
+            })?;
 
         Ok(tick)
     }
 
+    // getSymbol record, leverage is the margin requirement in percent
+    pub fn parse_symbol_spec(data: &Value) -> Option<SymbolSpec> {
+        let digits = data["precision"].as_u64()? as u32;
+        let pip_size = match data["pipsPrecision"].as_i64() {
+            Some(precision) => 10_f64.powi(-(precision as i32)),
+            None => return None,
+        };
+
+        Some(SymbolSpec {
+            symbol: data["symbol"].as_str()?.to_owned(),
+            digits,
+            pip_size,
+            tick_size: data["tickSize"]
+                .as_f64()
+                .unwrap_or_else(|| 10_f64.powi(-(digits as i32))),
+            contract_size: data["contractSize"].as_f64()?,
+            lot_step: data["lotStep"].as_f64().unwrap_or(0.01),
+            min_volume: data["lotMin"].as_f64().unwrap_or(0.01),
+            max_volume: data["lotMax"].as_f64().unwrap_or(0.),
+            margin_rate: data["leverage"].as_f64().unwrap_or(100.) / 100.,
+            base_currency: data["currency"].as_str().unwrap_or_default().to_owned(),
+            quote_currency: data["currencyProfit"].as_str()?.to_owned(),
+            trading_hours: vec![],
+        })
+    }
+
     pub fn parse_swap_data(&mut self, symbol: String, txt: String) -> Result<InstrumentSwap> {
-        let data = self.parse_message(&txt).unwrap();
-        let return_data = data
-            .get("returnData")
-            .ok_or_else(|| RsAlgoErrorKind::ParseError)
-            .unwrap();
+        let data = self.parse_message(&txt)?;
+        let return_data = field(&data, "returnData", Some)?;
 
-        let enabled = return_data["swapEnable"].as_bool().unwrap();
-        let swap_long = return_data["swapLong"].as_f64().unwrap();
-        let swap_short = return_data["swapShort"].as_f64().unwrap();
-        let swap_weekend = return_data["swap_rollover3days"].as_f64().unwrap();
+        let enabled = field(return_data, "swapEnable", Value::as_bool)?;
+        let swap_long = field(return_data, "swapLong", Value::as_f64)?;
+        let swap_short = field(return_data, "swapShort", Value::as_f64)?;
+        let swap_weekend = field(return_data, "swap_rollover3days", Value::as_f64)?;
 
         let instrument_swap = InstrumentSwap::new()
             .symbol(symbol)
@@ -1841,20 +3184,21 @@ impl Xtb {
             .swap_short(swap_short)
             .swap_weekend(swap_weekend)
             .build()
-            .unwrap();
-
-        println!("{:?}", instrument_swap);
+            .map_err(|_| RsAlgoError {
+                err: RsAlgoErrorKind::ParseError,
+            })?;
 
         Ok(instrument_swap)
     }
 
+    pub fn command_socket(&self) -> XtbSocket {
+        self.socket.clone()
+    }
+
     pub fn get_order_id_executed(&mut self, txt: &str) -> Result<(bool, u64)> {
-        let data = self.parse_message(txt).unwrap();
+        let data = self.parse_message(txt)?;
 
-        let mut status = data["status"]
-            .as_bool()
-            .ok_or("Missing or invalid 'status' in response data")
-            .unwrap();
+        let mut status = field(&data, "status", Value::as_bool)?;
 
         let order_num_result = data["returnData"]["order"]
             .as_u64()
@@ -1872,18 +3216,48 @@ impl Xtb {
         Ok((status, order_num))
     }
 
+    pub fn parse_account_data(&mut self, txt: &str) -> Result<AccountInfo> {
+        let data = self.parse_message(txt)?;
+        let return_data = data.get("returnData").ok_or(RsAlgoError {
+            err: RsAlgoErrorKind::ParseError,
+        })?;
+
+        let value = |key: &str| {
+            return_data[key].as_f64().ok_or(RsAlgoError {
+                err: RsAlgoErrorKind::ParseError,
+            })
+        };
+
+        Ok(AccountInfo {
+            currency: return_data["currency"]
+                .as_str()
+                .unwrap_or_default()
+                .to_owned(),
+            balance: value("balance")?,
+            equity: value("equity")?,
+            credit: value("credit").unwrap_or(0.),
+            margin: value("margin")?,
+            margin_free: value("margin_free")?,
+            margin_level: value("margin_level")?,
+        })
+    }
+
     pub fn parse_trade_status_data(&mut self, txt: String) -> Result<TransactionStatusnResponse> {
-        let data = self.parse_message(&txt).unwrap();
-        let return_data = data
-            .get("returnData")
-            .ok_or_else(|| RsAlgoErrorKind::ParseError)
-            .unwrap();
-        let order = return_data["order"].as_u64().unwrap();
-        let ask = return_data["ask"].as_f64().unwrap();
-        let bid = return_data["bid"].as_f64().unwrap();
-        let message = "".to_owned(); //data["returnData"]["message"].as_str().unwrap().to_owned();
-        let status = TransactionState::from_value(return_data["requestStatus"].as_u64().unwrap());
-        let comment = return_data["customComment"].as_str().unwrap().to_owned();
+        let data = self.parse_message(&txt)?;
+        let return_data = field(&data, "returnData", Some)?;
+        let order = field(return_data, "order", Value::as_u64)?;
+        let ask = field(return_data, "ask", Value::as_f64)?;
+        let bid = field(return_data, "bid", Value::as_f64)?;
+        let message = return_data["message"]
+            .as_str()
+            .unwrap_or_default()
+            .to_owned();
+        let status =
+            TransactionState::from_value(field(return_data, "requestStatus", Value::as_u64)?);
+        let comment = return_data["customComment"]
+            .as_str()
+            .unwrap_or_default()
+            .to_owned();
 
         Ok(TransactionStatusnResponse {
             comment,
@@ -1899,15 +3273,12 @@ impl Xtb {
         let mut result: Vec<MarketHour> = vec![];
         let current_date = Local::now();
         let base = current_date.date().and_hms(0, 0, 0);
-        let return_data = data
-            .get("returnData")
-            .ok_or_else(|| RsAlgoErrorKind::ParseError)
-            .unwrap();
+        let return_data = field(data, "returnData", Some)?;
 
-        for obj in return_data["trading"].as_array().unwrap() {
-            let day = obj["day"].as_i64().unwrap().try_into().unwrap();
-            let from = obj["from"].as_i64().unwrap();
-            let to = obj["to"].as_i64().unwrap();
+        for obj in field(return_data, "trading", Value::as_array)? {
+            let day = field(obj, "day", Value::as_u64)? as u32;
+            let from = field(obj, "from", Value::as_i64)?;
+            let to = field(obj, "to", Value::as_i64)?;
             let date_from = (base + date::Duration::milliseconds(from)).hour();
             let date_to = (base + date::Duration::milliseconds(to)).hour();
             let market_hour = MarketHour {
@@ -1926,29 +3297,36 @@ impl Xtb {
         symbol: &str,
         strategy_name: &str,
     ) -> Result<PositionResult> {
-        let data = self.parse_message(&txt).unwrap();
+        let data = self.parse_message(&txt)?;
         let current_date = Local::now();
         let mut trade_in = TradeIn::default();
-        let data = data["returnData"].as_array().unwrap();
+        let data = field(&data, "returnData", Value::as_array)?;
         let mut found = false;
 
         let mut orders: Vec<Order> = vec![];
         for obj in data {
-            let order_symbol = obj["symbol"].as_str().unwrap();
-            let comment = obj["customComment"].as_str().unwrap().to_owned();
-            let trans_comments: TransactionComments = serde_json::from_str(&comment).unwrap();
+            let order_symbol = field(obj, "symbol", Value::as_str)?;
+            let comment = obj["customComment"].as_str().unwrap_or_default();
+
+            // Positions opened outside the bots carry no strategy comments
+            let trans_comments: TransactionComments = match serde_json::from_str(comment) {
+                Ok(comments) => comments,
+                Err(_) => continue,
+            };
             let symbol_strategy_name = trans_comments.strategy_name;
 
             if order_symbol == symbol && strategy_name == symbol_strategy_name {
                 found = true;
-                let id = obj["position"].as_i64().unwrap().try_into().unwrap();
-                let size = obj["volume"].as_f64().unwrap().try_into().unwrap();
-                let price_in = obj["open_price"].as_f64().unwrap().try_into().unwrap();
+                let id = field(obj, "position", Value::as_u64)? as usize;
+                let size = field(obj, "volume", Value::as_f64)?;
+                let price_in = field(obj, "open_price", Value::as_f64)?;
                 let origin_price = price_in;
-                let stop_loss = obj["sl"].as_f64().unwrap().try_into().unwrap();
-                let date_in = to_dbtime(date::parse_time_milliseconds(
-                    obj["open_time"].as_i64().unwrap().try_into().unwrap(),
-                ));
+                let stop_loss = field(obj, "sl", Value::as_f64)?;
+                let date_in = to_dbtime(date::parse_time_milliseconds(field(
+                    obj,
+                    "open_time",
+                    Value::as_i64,
+                )?));
 
                 let index_in = trans_comments.index_in;
                 let trade_type = trans_comments.trade_type;
@@ -1981,6 +3359,7 @@ impl Xtb {
                     date_in,
                     trade_type,
                     status: TradeStatus::Fulfilled,
+                    stop_rules: vec![],
                 };
 
                 let stop_loss_order = Order {
@@ -2048,3 +3427,33 @@ impl Xtb {
         }
     }
 }
+
+fn text_message(msg: Message) -> Result<String> {
+    match msg {
+        Message::Text(txt) => Ok(txt),
+        msg => {
+            log::error!("Unexpected broker message {:?}", msg);
+            Err(RsAlgoError {
+                err: RsAlgoErrorKind::CantRead,
+            })
+        }
+    }
+}
+
+fn field<'a, T>(data: &'a Value, key: &str, value: impl Fn(&'a Value) -> Option<T>) -> Result<T> {
+    data.get(key).and_then(value).ok_or_else(|| {
+        log::error!("Missing or invalid '{}' in broker response", key);
+        RsAlgoError {
+            err: RsAlgoErrorKind::ParseError,
+        }
+    })
+}
+
+fn stream_data<T: DeserializeOwned>(envelope: StreamEnvelope) -> Result<T> {
+    serde_json::from_value(envelope.data).map_err(|err| {
+        log::error!("Invalid {} stream data ({})", envelope.command, err);
+        RsAlgoError {
+            err: RsAlgoErrorKind::ParseError,
+        }
+    })
+}
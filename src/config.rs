@@ -0,0 +1,646 @@
+use crate::error::{Result, RsAlgoError, RsAlgoErrorKind};
+use crate::models::environment::{self, Environment};
+use crate::models::mode::{self, ExecutionMode};
+
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+
+pub const CONFIG_KEYS: &[&str] = &[
+    "ENV",
+    "EXECUTION_MODE",
+    "SYMBOL",
+    "BROKER_URL",
+    "BROKER_STREAM_URL",
+    "STREAM_SUBSCRIBE",
+    "DISCONNECTED_RETRY",
+    "KEEPALIVE_INTERVAL",
+    "STREAM_TIMEOUT",
+    "RECONNECT_RETRIES",
+    "RECONNECT_BACKOFF",
+    "COMMAND_INTERVAL",
+    "COMMAND_BURST",
+    "TRADE_MAX_RETRIES",
+    "TRADE_RETRY_AFTER",
+    "BACKEND_BACKTEST_PRICING_ENDPOINT",
+    "SLIPPAGE_PIPS",
+    "ORDER_ENGINE",
+    "ORDER_ACTIVATION_SOURCE",
+    "ORDER_SIZE",
+    "ORDER_SIZE_LIMIT",
+    "SIZING_MODE",
+    "RISK_PER_TRADE",
+    "CONTRACT_SIZE",
+    "SYMBOL_SPECS_FILE",
+    "SIZING_ATR_MULTIPLIER",
+    "KELLY_FRACTION",
+    "ORDER_WITH_SPREAD",
+    "ORDER_VALID_UNTIL_BARS",
+    "ORDERS_OVERWRITE",
+    "MAX_BUY_ORDERS",
+    "MAX_SELL_ORDERS",
+    "MAX_STOP_LOSSES",
+    "MAX_PENDING_ORDERS",
+    "MAX_OPEN_POSITIONS",
+    "MAX_CURRENCY_EXPOSURE",
+    "MAX_DAILY_LOSS",
+    "MAX_CORRELATED_POSITIONS",
+    "CORRELATION_THRESHOLD",
+    "STOP_LOSS_SPREAD",
+    "NON_PROFITABLE_OUTS",
+    "LEVERAGE",
+    "EQUITY",
+    "ACCOUNT_CURRENCY",
+    "COMMISSION_MODE",
+    "COMMISSION",
+    "SWAP_LONG",
+    "SWAP_SHORT",
+    "SWAP_TRIPLE_DAY",
+    "WAIT_FOR_NEW_ENTRY",
+    "WAIT_FOR_NEW_EXIT",
+    "CANDLES_UNTIL_NEW_ENTRY",
+    "NUM_BARS",
+    "NEXT_DELETE",
+    "MAX_PREVIOUS_BARS",
+    "MIN_PRICE",
+    "AVG_VOLUME_DAYS",
+    "LOGARITHMIC_SCANNER",
+    "INDICATORS",
+    "PATTERNS",
+    "DIVERGENCES",
+    "HORIZONTAL_LEVELS",
+    "CANDLE_TYPES",
+    "SELECTED_CANDLE_TYPES",
+    "PATTERNS_MAX_POINTS",
+    "PATTERNS_MIN_POINTS",
+    "PATTERNS_WINDOW_SIZE",
+    "EQUAL_THRESHOLD",
+    "SLOPE_DEVIATION_THRESHOLD",
+    "PARALLEL_LINES_THRESHOLD",
+    "MIN_PATTERN_BARS",
+    "MINIMUM_PATTERN_TARGET",
+    "LOCAL_MIN_PROMINENCE",
+    "EXTREMA_MIN_PROMINENCE",
+    "LOCAL_PROMINENCE_MIN_DISTANCE",
+    "EXTREMA_PROMINENCE_MIN_DISTANCE",
+    "KERNEL_PRICE_SMOOTHING",
+    "KERNEL_REGRESSION_BANDWIDTH",
+    "PRICE_SOURCE",
+    "MIN_HORIZONTAL_LEVELS_OCCURENCES",
+    "HORIZONTAL_LEVELS_THRESHOLD",
+    "DIVERGENCE_MIN_PROMINENCE",
+    "DIVERGENCE_PROMINENCE_MIN_DISTANCE",
+    "DIVERGENCES_MIN_POINTS",
+    "DIVERGENCES_WINDOW_SIZE",
+    "INDICATORS_MACD",
+    "INDICATORS_RSI",
+    "INDICATORS_ATR",
+    "INDICATORS_BB",
+    "INDICATORS_BBW",
+    "INDICATORS_EMA_A",
+    "INDICATORS_EMA_B",
+    "INDICATORS_EMA_C",
+    "EMA_A",
+    "EMA_B",
+    "EMA_C",
+    "MACD_A",
+    "MACD_B",
+    "MACD_C",
+    "BB_PERIOD",
+    "BB_MULTIPLIER",
+];
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RsAlgoConfig {
+    pub env: Environment,
+    pub execution_mode: ExecutionMode,
+    pub symbol: Option<String>,
+    pub broker_url: String,
+    pub broker_stream_url: String,
+    pub stream_subscribe: bool,
+    pub disconnected_retry: u64,
+    pub keepalive_interval: u64,
+    pub stream_timeout: u64,
+    pub reconnect_retries: usize,
+    pub reconnect_backoff: u64,
+    pub command_interval: u64,
+    pub command_burst: usize,
+    pub trade_max_retries: usize,
+    pub trade_retry_after: u64,
+    pub backend_backtest_pricing_endpoint: String,
+    pub slippage_pips: f64,
+    pub order_engine: String,
+    pub order_activation_source: String,
+    pub order_size: f64,
+    pub order_size_limit: f64,
+    pub sizing_mode: String,
+    pub risk_per_trade: f64,
+    pub contract_size: f64,
+    pub symbol_specs_file: String,
+    pub sizing_atr_multiplier: f64,
+    pub kelly_fraction: f64,
+    pub order_with_spread: bool,
+    pub order_valid_until_bars: i64,
+    pub orders_overwrite: bool,
+    pub max_buy_orders: usize,
+    pub max_sell_orders: usize,
+    pub max_stop_losses: usize,
+    pub max_pending_orders: usize,
+    pub max_open_positions: usize,
+    pub max_currency_exposure: f64,
+    pub max_daily_loss: f64,
+    pub max_correlated_positions: usize,
+    pub correlation_threshold: f64,
+    pub stop_loss_spread: bool,
+    pub non_profitable_outs: bool,
+    pub leverage: f64,
+    pub equity: f64,
+    pub account_currency: String,
+    pub commission_mode: String,
+    pub commission: f64,
+    pub swap_long: f64,
+    pub swap_short: f64,
+    pub swap_triple_day: u32,
+    pub wait_for_new_entry: bool,
+    pub wait_for_new_exit: bool,
+    pub candles_until_new_entry: i64,
+    pub num_bars: usize,
+    pub next_delete: usize,
+    pub max_previous_bars: usize,
+    pub min_price: f64,
+    pub avg_volume_days: usize,
+    pub logarithmic_scanner: bool,
+    pub indicators: bool,
+    pub patterns: bool,
+    pub divergences: bool,
+    pub horizontal_levels: bool,
+    pub candle_types: bool,
+    pub selected_candle_types: String,
+    pub patterns_max_points: usize,
+    pub patterns_min_points: usize,
+    pub patterns_window_size: usize,
+    pub equal_threshold: f64,
+    pub slope_deviation_threshold: f64,
+    pub parallel_lines_threshold: f64,
+    pub min_pattern_bars: usize,
+    pub minimum_pattern_target: f64,
+    pub local_min_prominence: f64,
+    pub extrema_min_prominence: f64,
+    pub local_prominence_min_distance: usize,
+    pub extrema_prominence_min_distance: usize,
+    pub kernel_price_smoothing: bool,
+    pub kernel_regression_bandwidth: f64,
+    pub price_source: String,
+    pub min_horizontal_levels_occurences: usize,
+    pub horizontal_levels_threshold: f64,
+    pub divergence_min_prominence: f64,
+    pub divergence_prominence_min_distance: usize,
+    pub divergences_min_points: usize,
+    pub divergences_window_size: usize,
+    pub indicators_macd: bool,
+    pub indicators_rsi: bool,
+    pub indicators_atr: bool,
+    pub indicators_bb: bool,
+    pub indicators_bbw: bool,
+    pub indicators_ema_a: bool,
+    pub indicators_ema_b: bool,
+    pub indicators_ema_c: bool,
+    pub ema_a: usize,
+    pub ema_b: usize,
+    pub ema_c: usize,
+    pub macd_a: usize,
+    pub macd_b: usize,
+    pub macd_c: usize,
+    pub bb_period: usize,
+    pub bb_multiplier: f64,
+}
+
+impl RsAlgoConfig {
+    pub fn new() -> RsAlgoConfigBuilder {
+        RsAlgoConfigBuilder::new()
+    }
+
+    pub fn from_env() -> Result<Self> {
+        RsAlgoConfigBuilder::new().env().build()
+    }
+
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
+        RsAlgoConfigBuilder::new().file(path)?.build()
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct RsAlgoConfigBuilder {
+    values: HashMap<String, String>,
+}
+
+impl RsAlgoConfigBuilder {
+    pub fn new() -> Self {
+        Self {
+            values: HashMap::new(),
+        }
+    }
+
+    pub fn set<T: ToString>(mut self, key: &str, value: T) -> Self {
+        self.values.insert(key.to_uppercase(), value.to_string());
+        self
+    }
+
+    pub fn env(mut self) -> Self {
+        for key in CONFIG_KEYS.iter() {
+            if let Ok(value) = env::var(key) {
+                self.values.insert(key.to_string(), value);
+            }
+        }
+        self
+    }
+
+    pub fn file<P: AsRef<Path>>(mut self, path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let content = fs::read_to_string(path)
+            .map_err(|err| config_error(format!("can't read {} ({})", path.display(), err)))?;
+
+        let values = match path.extension().and_then(|ext| ext.to_str()) {
+            Some("toml") => parse_toml(&content)?,
+            Some("json") => parse_json(&content)?,
+            _ => {
+                return Err(config_error(format!(
+                    "unsupported config file {}",
+                    path.display()
+                )))
+            }
+        };
+
+        self.values.extend(values);
+        Ok(self)
+    }
+
+    pub fn build(self) -> Result<RsAlgoConfig> {
+        let mut reader = ConfigReader::new(&self.values);
+
+        let indicators_macd = reader.optional("INDICATORS_MACD", false);
+        let indicators_bb = reader.optional("INDICATORS_BB", false);
+        let indicators_ema_a = reader.optional("INDICATORS_EMA_A", false);
+        let indicators_ema_b = reader.optional("INDICATORS_EMA_B", false);
+        let indicators_ema_c = reader.optional("INDICATORS_EMA_C", false);
+
+        let config = RsAlgoConfig {
+            env: reader.with("ENV", environment::try_from_str, Environment::default()),
+            execution_mode: reader.with(
+                "EXECUTION_MODE",
+                mode::try_from_str,
+                ExecutionMode::Scanner,
+            ),
+            symbol: reader.parse("SYMBOL"),
+            broker_url: reader.required("BROKER_URL"),
+            broker_stream_url: reader.required("BROKER_STREAM_URL"),
+            stream_subscribe: reader.required("STREAM_SUBSCRIBE"),
+            disconnected_retry: reader.required("DISCONNECTED_RETRY"),
+            keepalive_interval: reader.optional("KEEPALIVE_INTERVAL", 30),
+            stream_timeout: reader.optional("STREAM_TIMEOUT", 90),
+            reconnect_retries: reader.optional("RECONNECT_RETRIES", 10),
+            reconnect_backoff: reader.optional("RECONNECT_BACKOFF", 1000),
+            command_interval: reader.optional("COMMAND_INTERVAL", 200),
+            command_burst: reader.optional("COMMAND_BURST", 5),
+            trade_max_retries: reader.optional("TRADE_MAX_RETRIES", 3),
+            trade_retry_after: reader.optional("TRADE_RETRY_AFTER", 500),
+            backend_backtest_pricing_endpoint: reader.required("BACKEND_BACKTEST_PRICING_ENDPOINT"),
+            slippage_pips: reader.required("SLIPPAGE_PIPS"),
+            order_engine: reader.one_of("ORDER_ENGINE", &["broker", "bot"]),
+            order_activation_source: reader.required("ORDER_ACTIVATION_SOURCE"),
+            order_size: reader.required("ORDER_SIZE"),
+            order_size_limit: reader.required("ORDER_SIZE_LIMIT"),
+            sizing_mode: reader.optional_one_of(
+                "SIZING_MODE",
+                &["fixed_lot", "fixed_fractional", "volatility", "kelly"],
+                "fixed_lot",
+            ),
+            risk_per_trade: reader.optional("RISK_PER_TRADE", 1.),
+            contract_size: reader.optional("CONTRACT_SIZE", 100000.),
+            symbol_specs_file: reader.optional("SYMBOL_SPECS_FILE", String::new()),
+            sizing_atr_multiplier: reader.optional("SIZING_ATR_MULTIPLIER", 2.),
+            kelly_fraction: reader.optional("KELLY_FRACTION", 0.5),
+            order_with_spread: reader.required("ORDER_WITH_SPREAD"),
+            order_valid_until_bars: reader.required("ORDER_VALID_UNTIL_BARS"),
+            orders_overwrite: reader.required("ORDERS_OVERWRITE"),
+            max_buy_orders: reader.required("MAX_BUY_ORDERS"),
+            max_sell_orders: reader.required("MAX_SELL_ORDERS"),
+            max_stop_losses: reader.required("MAX_STOP_LOSSES"),
+            max_pending_orders: reader.required("MAX_PENDING_ORDERS"),
+            max_open_positions: reader.optional("MAX_OPEN_POSITIONS", 0),
+            max_currency_exposure: reader.optional("MAX_CURRENCY_EXPOSURE", 0.),
+            max_daily_loss: reader.optional("MAX_DAILY_LOSS", 0.),
+            max_correlated_positions: reader.optional("MAX_CORRELATED_POSITIONS", 0),
+            correlation_threshold: reader.optional("CORRELATION_THRESHOLD", 0.8),
+            stop_loss_spread: reader.required("STOP_LOSS_SPREAD"),
+            non_profitable_outs: reader.required("NON_PROFITABLE_OUTS"),
+            leverage: reader.required("LEVERAGE"),
+            equity: reader.required("EQUITY"),
+            account_currency: reader.optional("ACCOUNT_CURRENCY", "USD".to_owned()),
+            commission_mode: reader.optional_one_of(
+                "COMMISSION_MODE",
+                &["per_lot", "percentage"],
+                "per_lot",
+            ),
+            commission: reader.optional("COMMISSION", 0.),
+            swap_long: reader.optional("SWAP_LONG", 0.),
+            swap_short: reader.optional("SWAP_SHORT", 0.),
+            swap_triple_day: reader.optional("SWAP_TRIPLE_DAY", 2),
+            wait_for_new_entry: reader.required("WAIT_FOR_NEW_ENTRY"),
+            wait_for_new_exit: reader.required("WAIT_FOR_NEW_EXIT"),
+            candles_until_new_entry: reader.required("CANDLES_UNTIL_NEW_ENTRY"),
+            num_bars: reader.required("NUM_BARS"),
+            next_delete: reader.required("NEXT_DELETE"),
+            max_previous_bars: reader.required("MAX_PREVIOUS_BARS"),
+            min_price: reader.required("MIN_PRICE"),
+            avg_volume_days: reader.required("AVG_VOLUME_DAYS"),
+            logarithmic_scanner: reader.required("LOGARITHMIC_SCANNER"),
+            indicators: reader.required("INDICATORS"),
+            patterns: reader.required("PATTERNS"),
+            divergences: reader.required("DIVERGENCES"),
+            horizontal_levels: reader.required("HORIZONTAL_LEVELS"),
+            candle_types: reader.required("CANDLE_TYPES"),
+            selected_candle_types: reader.optional("SELECTED_CANDLE_TYPES", String::new()),
+            patterns_max_points: reader.required("PATTERNS_MAX_POINTS"),
+            patterns_min_points: reader.required("PATTERNS_MIN_POINTS"),
+            patterns_window_size: reader.required("PATTERNS_WINDOW_SIZE"),
+            equal_threshold: reader.required("EQUAL_THRESHOLD"),
+            slope_deviation_threshold: reader.required("SLOPE_DEVIATION_THRESHOLD"),
+            parallel_lines_threshold: reader.required("PARALLEL_LINES_THRESHOLD"),
+            min_pattern_bars: reader.required("MIN_PATTERN_BARS"),
+            minimum_pattern_target: reader.required("MINIMUM_PATTERN_TARGET"),
+            local_min_prominence: reader.required("LOCAL_MIN_PROMINENCE"),
+            extrema_min_prominence: reader.required("EXTREMA_MIN_PROMINENCE"),
+            local_prominence_min_distance: reader.required("LOCAL_PROMINENCE_MIN_DISTANCE"),
+            extrema_prominence_min_distance: reader.required("EXTREMA_PROMINENCE_MIN_DISTANCE"),
+            kernel_price_smoothing: reader.required("KERNEL_PRICE_SMOOTHING"),
+            kernel_regression_bandwidth: reader.required("KERNEL_REGRESSION_BANDWIDTH"),
+            price_source: reader.required("PRICE_SOURCE"),
+            min_horizontal_levels_occurences: reader.required("MIN_HORIZONTAL_LEVELS_OCCURENCES"),
+            horizontal_levels_threshold: reader.required("HORIZONTAL_LEVELS_THRESHOLD"),
+            divergence_min_prominence: reader.required("DIVERGENCE_MIN_PROMINENCE"),
+            divergence_prominence_min_distance: reader
+                .required("DIVERGENCE_PROMINENCE_MIN_DISTANCE"),
+            divergences_min_points: reader.required("DIVERGENCES_MIN_POINTS"),
+            divergences_window_size: reader.required("DIVERGENCES_WINDOW_SIZE"),
+            indicators_macd,
+            indicators_rsi: reader.optional("INDICATORS_RSI", false),
+            indicators_atr: reader.optional("INDICATORS_ATR", false),
+            indicators_bb,
+            indicators_bbw: reader.optional("INDICATORS_BBW", false),
+            indicators_ema_a,
+            indicators_ema_b,
+            indicators_ema_c,
+            ema_a: reader.required_if(indicators_ema_a, "EMA_A"),
+            ema_b: reader.required_if(indicators_ema_b, "EMA_B"),
+            ema_c: reader.required_if(indicators_ema_c, "EMA_C"),
+            macd_a: reader.required_if(indicators_macd, "MACD_A"),
+            macd_b: reader.required_if(indicators_macd, "MACD_B"),
+            macd_c: reader.required_if(indicators_macd, "MACD_C"),
+            bb_period: reader.required_if(indicators_bb, "BB_PERIOD"),
+            bb_multiplier: reader.required_if(indicators_bb, "BB_MULTIPLIER"),
+        };
+
+        match reader.errors.is_empty() {
+            true => Ok(config),
+            false => Err(config_error(reader.errors.join(", "))),
+        }
+    }
+}
+
+struct ConfigReader<'a> {
+    values: &'a HashMap<String, String>,
+    errors: Vec<String>,
+}
+
+impl<'a> ConfigReader<'a> {
+    fn new(values: &'a HashMap<String, String>) -> Self {
+        Self {
+            values,
+            errors: vec![],
+        }
+    }
+
+    fn parse<T: FromStr>(&mut self, key: &str) -> Option<T> {
+        let value = self.values.get(key)?;
+        match value.trim().parse::<T>() {
+            Ok(val) => Some(val),
+            Err(_) => {
+                self.errors
+                    .push(format!("{} has an invalid value '{}'", key, value));
+                None
+            }
+        }
+    }
+
+    fn required<T: FromStr + Default>(&mut self, key: &str) -> T {
+        if !self.values.contains_key(key) {
+            self.errors.push(format!("{} is missing", key));
+        }
+        self.parse(key).unwrap_or_default()
+    }
+
+    fn required_if<T: FromStr + Default>(&mut self, condition: bool, key: &str) -> T {
+        match condition {
+            true => self.required(key),
+            false => self.parse(key).unwrap_or_default(),
+        }
+    }
+
+    fn optional<T: FromStr>(&mut self, key: &str, default: T) -> T {
+        self.parse(key).unwrap_or(default)
+    }
+
+    fn one_of(&mut self, key: &str, allowed: &[&str]) -> String {
+        let value: String = self.required(key);
+        if self.values.contains_key(key) && !allowed.contains(&value.as_str()) {
+            self.errors.push(format!(
+                "{} has an invalid value '{}', expected one of {:?}",
+                key, value, allowed
+            ));
+        }
+        value
+    }
+
+    fn optional_one_of(&mut self, key: &str, allowed: &[&str], default: &str) -> String {
+        match self.values.contains_key(key) {
+            true => self.one_of(key, allowed),
+            false => default.to_owned(),
+        }
+    }
+
+    fn with<T>(&mut self, key: &str, parser: fn(&str) -> Option<T>, fallback: T) -> T {
+        match self.values.get(key) {
+            Some(value) => match parser(value.trim()) {
+                Some(val) => val,
+                None => {
+                    self.errors
+                        .push(format!("{} has an invalid value '{}'", key, value));
+                    fallback
+                }
+            },
+            None => {
+                self.errors.push(format!("{} is missing", key));
+                fallback
+            }
+        }
+    }
+}
+
+fn parse_toml(content: &str) -> Result<HashMap<String, String>> {
+    let table: toml::Table = content
+        .parse()
+        .map_err(|err| config_error(format!("malformed toml ({})", err)))?;
+
+    let mut values = HashMap::new();
+    for (key, value) in table {
+        let value = match value {
+            toml::Value::String(val) => val,
+            toml::Value::Integer(val) => val.to_string(),
+            toml::Value::Float(val) => val.to_string(),
+            toml::Value::Boolean(val) => val.to_string(),
+            _ => {
+                return Err(config_error(format!(
+                    "{} must be a string, number or boolean",
+                    key
+                )))
+            }
+        };
+        values.insert(key.to_uppercase(), value);
+    }
+    Ok(values)
+}
+
+fn parse_json(content: &str) -> Result<HashMap<String, String>> {
+    let object: serde_json::Map<String, serde_json::Value> = serde_json::from_str(content)
+        .map_err(|err| config_error(format!("malformed json ({})", err)))?;
+
+    let mut values = HashMap::new();
+    for (key, value) in object {
+        let value = match value {
+            serde_json::Value::String(val) => val,
+            serde_json::Value::Number(val) => val.to_string(),
+            serde_json::Value::Bool(val) => val.to_string(),
+            _ => {
+                return Err(config_error(format!(
+                    "{} must be a string, number or boolean",
+                    key
+                )))
+            }
+        };
+        values.insert(key.to_uppercase(), value);
+    }
+    Ok(values)
+}
+
+fn config_error(msg: String) -> RsAlgoError {
+    RsAlgoError {
+        err: RsAlgoErrorKind::InvalidConfig(msg),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixture() -> RsAlgoConfigBuilder {
+        RsAlgoConfig::new()
+            .file(concat!(
+                env!("CARGO_MANIFEST_DIR"),
+                "/tests/fixtures/config.toml"
+            ))
+            .unwrap()
+    }
+
+    fn config_message(result: Result<RsAlgoConfig>) -> String {
+        match result {
+            Ok(_) => panic!("config should not build"),
+            Err(err) => match err.err {
+                RsAlgoErrorKind::InvalidConfig(msg) => msg,
+                err => panic!("unexpected error {:?}", err),
+            },
+        }
+    }
+
+    #[test]
+    fn builds_from_file() {
+        let config = fixture().build().unwrap();
+
+        assert_eq!(config.symbol.as_deref(), Some("EURUSD"));
+        assert_eq!(config.equity, 10000.);
+        assert_eq!(config.order_engine, "broker");
+        assert_eq!(config.keepalive_interval, 30);
+    }
+
+    #[test]
+    fn missing_required_key() {
+        let mut builder = fixture();
+        builder.values.remove("EQUITY");
+        builder.values.remove("LEVERAGE");
+
+        let msg = config_message(builder.build());
+
+        assert!(msg.contains("LEVERAGE is missing"));
+        assert!(msg.contains("EQUITY is missing"));
+    }
+
+    #[test]
+    fn missing_optional_key_uses_default() {
+        let mut builder = fixture();
+        builder.values.remove("COMMAND_BURST");
+
+        assert_eq!(builder.build().unwrap().command_burst, 5);
+    }
+
+    #[test]
+    fn bad_values_are_reported() {
+        let msg = config_message(
+            fixture()
+                .set("EQUITY", "ten thousand")
+                .set("ORDER_ENGINE", "manual")
+                .set("EXECUTION_MODE", "paper")
+                .build(),
+        );
+
+        assert!(msg.contains("EQUITY has an invalid value 'ten thousand'"));
+        assert!(msg.contains("ORDER_ENGINE has an invalid value 'manual'"));
+        assert!(msg.contains("EXECUTION_MODE has an invalid value 'paper'"));
+    }
+
+    #[test]
+    fn last_source_wins() {
+        env::set_var("MIN_PRICE", "25");
+
+        let env_over_file = fixture().env().build().unwrap();
+        let file_over_env = RsAlgoConfig::new()
+            .env()
+            .file(concat!(
+                env!("CARGO_MANIFEST_DIR"),
+                "/tests/fixtures/config.toml"
+            ))
+            .unwrap()
+            .build()
+            .unwrap();
+        let set_over_env = fixture().env().set("MIN_PRICE", 30).build().unwrap();
+
+        env::remove_var("MIN_PRICE");
+
+        assert_eq!(env_over_file.min_price, 25.);
+        assert_eq!(file_over_env.min_price, 0.);
+        assert_eq!(set_over_env.min_price, 30.);
+    }
+
+    #[test]
+    fn unsupported_file() {
+        let result = RsAlgoConfig::new().file("config.yaml");
+
+        assert!(matches!(
+            result,
+            Err(RsAlgoError {
+                err: RsAlgoErrorKind::InvalidConfig(_)
+            })
+        ));
+    }
+}
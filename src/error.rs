@@ -3,7 +3,8 @@ use thiserror::Error;
 
 pub type Result<T> = ::anyhow::Result<T, RsAlgoError>;
 
-#[derive(Copy, Clone, Eq, PartialEq, Debug, Error)]
+// Not Copy since 0.2.0, config and broker errors carry their messages
+#[derive(Clone, Eq, PartialEq, Debug, Error)]
 pub enum RsAlgoErrorKind {
     #[error("Error while Parsing!")]
     ParseError,
@@ -23,6 +24,53 @@ pub enum RsAlgoErrorKind {
     CantRead,
     #[error("No response!")]
     NoResponse,
+    #[error("Invalid config: {0}")]
+    InvalidConfig(String),
+    #[error("Login failed! {0}: {1}")]
+    LoginFailed(String, String),
+    #[error("Invalid symbol! {0}: {1}")]
+    InvalidSymbol(String, String),
+    #[error("Market closed! {0}: {1}")]
+    MarketClosed(String, String),
+    #[error("Insufficient margin! {0}: {1}")]
+    InsufficientMargin(String, String),
+    #[error("Invalid volume! {0}: {1}")]
+    InvalidVolume(String, String),
+    #[error("Invalid price! {0}: {1}")]
+    InvalidPrice(String, String),
+    #[error("Invalid stop! {0}: {1}")]
+    InvalidStop(String, String),
+    #[error("Trading disabled! {0}: {1}")]
+    TradingDisabled(String, String),
+    #[error("Order not found! {0}: {1}")]
+    OrderNotFound(String, String),
+    #[error("Request limit reached! {0}: {1}")]
+    RequestLimit(String, String),
+    #[error("Broker error! {0}: {1}")]
+    BrokerError(String, String),
+    #[error("Risk limit reached! {0}")]
+    RiskLimit(String),
+    #[error("No conversion rate! {0}")]
+    MissingRate(String),
+}
+
+impl RsAlgoErrorKind {
+    pub fn broker_error(&self) -> Option<(&str, &str)> {
+        match self {
+            RsAlgoErrorKind::LoginFailed(code, description)
+            | RsAlgoErrorKind::InvalidSymbol(code, description)
+            | RsAlgoErrorKind::MarketClosed(code, description)
+            | RsAlgoErrorKind::InsufficientMargin(code, description)
+            | RsAlgoErrorKind::InvalidVolume(code, description)
+            | RsAlgoErrorKind::InvalidPrice(code, description)
+            | RsAlgoErrorKind::InvalidStop(code, description)
+            | RsAlgoErrorKind::TradingDisabled(code, description)
+            | RsAlgoErrorKind::OrderNotFound(code, description)
+            | RsAlgoErrorKind::RequestLimit(code, description)
+            | RsAlgoErrorKind::BrokerError(code, description) => Some((code, description)),
+            _ => None,
+        }
+    }
 }
 
 #[derive(Debug, Error)]
@@ -32,7 +80,7 @@ pub struct RsAlgoError {
 
 impl RsAlgoError {
     pub fn kind(&self) -> RsAlgoErrorKind {
-        self.err
+        self.err.clone()
     }
 }
 
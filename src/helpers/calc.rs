@@ -1,4 +1,7 @@
 use crate::helpers::comp::*;
+use crate::helpers::date::{from_dbtime, DateTime, Local};
+use crate::models::order::{initial_risk, Order};
+use crate::models::symbol_spec::symbol_spec;
 use crate::models::tick::InstrumentTick;
 use crate::models::trade::*;
 use crate::scanner::candle::Candle;
@@ -43,9 +46,29 @@ pub fn calculate_trade_profit(
         log::warn!("Zero Profit!");
     }
 
-    let symbol_factor = format_symbol_factor(symbol) * 10.;
+    // Registered symbols return the profit in their quote currency
+    match symbol_spec(symbol) {
+        Some(spec) => round(profit_without_leverage * spec.contract_size, 2),
+        None => {
+            let symbol_factor = format_symbol_factor(symbol) * 10.;
+            format_symbol_price(profit_without_leverage * symbol_factor, symbol)
+        }
+    }
+}
 
-    format_symbol_price(profit_without_leverage * symbol_factor, symbol)
+pub fn calculate_quote_profit(
+    size: f64,
+    price_in: f64,
+    price_out: f64,
+    contract_size: f64,
+    trade_type: &TradeType,
+) -> f64 {
+    let price_diff = match trade_type.is_long() {
+        true => price_out - price_in,
+        false => price_in - price_out,
+    };
+
+    price_diff * size * contract_size
 }
 
 pub fn calculate_trade_profit_per(equity: f64, profit: f64, _price_in: f64, _symbol: &str) -> f64 {
@@ -283,6 +306,155 @@ pub fn total_profit_factor(gross_profits: f64, gross_losses: f64) -> f64 {
     }
 }
 
+const SECONDS_PER_YEAR: f64 = 365.25 * 24. * 3600.;
+
+// Compound annual growth rate in percent
+pub fn calculate_annual_return(
+    initial_equity: f64,
+    final_equity: f64,
+    date_start: DateTime<Local>,
+    date_end: DateTime<Local>,
+) -> f64 {
+    let years = (date_end - date_start).num_seconds() as f64 / SECONDS_PER_YEAR;
+
+    if initial_equity <= 0. || years <= 0. {
+        return 0.;
+    }
+
+    match final_equity > 0. {
+        true => ((final_equity / initial_equity).powf(1. / years) - 1.) * 100.,
+        false => -100.,
+    }
+}
+
+// Annualized with a zero risk free rate
+pub fn calculate_sharpe_ratio(returns: &[f64], periods_per_year: f64) -> f64 {
+    if returns.len() < 2 {
+        return 0.;
+    }
+
+    let mean = returns.iter().sum::<f64>() / returns.len() as f64;
+    let variance =
+        returns.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / (returns.len() - 1) as f64;
+
+    match variance > 0. {
+        true => mean / variance.sqrt() * periods_per_year.sqrt(),
+        false => 0.,
+    }
+}
+
+// Like Sharpe but only penalizes the returns below zero
+pub fn calculate_sortino_ratio(returns: &[f64], periods_per_year: f64) -> f64 {
+    if returns.len() < 2 {
+        return 0.;
+    }
+
+    let mean = returns.iter().sum::<f64>() / returns.len() as f64;
+    let downside = returns.iter().map(|x| x.min(0.).powi(2)).sum::<f64>() / returns.len() as f64;
+
+    match downside > 0. {
+        true => mean / downside.sqrt() * periods_per_year.sqrt(),
+        false => 0.,
+    }
+}
+
+pub fn calculate_calmar_ratio(annual_return: f64, max_drawdown_per: f64) -> f64 {
+    match max_drawdown_per > 0. {
+        true => annual_return / max_drawdown_per,
+        false => 0.,
+    }
+}
+
+pub fn calculate_expectancy(trades_out: &[TradeOut]) -> f64 {
+    match trades_out.is_empty() {
+        true => 0.,
+        false => trades_out.iter().map(|trade| trade.profit).sum::<f64>() / trades_out.len() as f64,
+    }
+}
+
+// Price move of each trade over the distance to its initial stop. Trades
+// without a stop carry no risk unit and are left out
+pub fn calculate_avg_r_multiple(trades_out: &[TradeOut], orders: &[Order]) -> f64 {
+    let r_multiples: Vec<f64> = trades_out
+        .iter()
+        .filter_map(|trade| {
+            let risk = initial_risk(trade.id, trade.price_in, orders)?;
+            let profit_move = match trade.trade_type.is_long() {
+                true => trade.price_out - trade.price_in,
+                false => trade.price_in - trade.price_out,
+            };
+            Some(profit_move / risk)
+        })
+        .collect();
+
+    average_f64(&r_multiples)
+}
+
+pub fn max_consecutive_trades(trades_out: &[TradeOut]) -> (usize, usize) {
+    let (mut wins, mut losses) = (0, 0);
+    let (mut max_wins, mut max_losses) = (0, 0);
+
+    for trade in trades_out {
+        match trade.profit > 0. {
+            true => {
+                wins += 1;
+                losses = 0;
+            }
+            false => {
+                losses += 1;
+                wins = 0;
+            }
+        }
+        max_wins = max_wins.max(wins);
+        max_losses = max_losses.max(losses);
+    }
+
+    (max_wins, max_losses)
+}
+
+// Average time in the market per trade, in hours
+pub fn avg_holding_time(trades_out: &[TradeOut]) -> f64 {
+    let hours: Vec<f64> = trades_out
+        .iter()
+        .map(|trade| {
+            (from_dbtime(&trade.date_out) - from_dbtime(&trade.date_in)).num_seconds() as f64
+                / 3600.
+        })
+        .collect();
+
+    average_f64(&hours)
+}
+
+// Percentage of bars with at least one open position
+pub fn total_exposure(trades_in: &[TradeIn], trades_out: &[TradeOut], bars: usize) -> f64 {
+    if bars == 0 {
+        return 0.;
+    }
+
+    let mut in_market = vec![false; bars];
+
+    for trade_in in trades_in
+        .iter()
+        .filter(|trade| trade.status != TradeStatus::Rejected)
+    {
+        let index_out = trades_out
+            .iter()
+            .find(|trade_out| trade_out.id == trade_in.id)
+            .map(|trade_out| trade_out.index_out)
+            .unwrap_or(bars - 1);
+
+        for bar in in_market
+            .iter_mut()
+            .take(index_out.min(bars - 1) + 1)
+            .skip(trade_in.index_in)
+        {
+            *bar = true;
+        }
+    }
+
+    in_market.iter().filter(|bar| **bar).count() as f64 / bars as f64 * 100.
+}
+
 // pub fn total_profit_factor(gross_profits: f64, gross_loses: f64) -> f64 {
 //     if gross_loses == 0.0 {
 //         if gross_profits > 0.0 {
@@ -367,7 +539,7 @@ pub fn calculate_percentile(data: &[f64], percentile: f64) -> f64 {
 
     if rank < 1 {
         return sorted_data[0];
-    } else if rank >= sorted_data.len() {
+    } else if rank + 1 >= sorted_data.len() {
         return sorted_data[sorted_data.len() - 1];
     }
 
@@ -378,24 +550,122 @@ pub fn calculate_percentile(data: &[f64], percentile: f64) -> f64 {
     lower + (upper - lower) * fraction
 }
 
+// Unregistered symbols fall back to the forex JPY heuristic
 pub fn format_symbol_factor(symbol: &str) -> f64 {
-    match symbol.contains("JPY") {
-        true => 100.,
-        false => 10000.,
+    match symbol_spec(symbol) {
+        Some(spec) => spec.price_factor(),
+        None => match symbol.contains("JPY") {
+            true => 100.,
+            false => 10000.,
+        },
     }
 }
 
 pub fn number_pips(symbol: &str) -> f64 {
-    let decimals = format_symbol_factor(symbol);
-    let pips = 1.0 / decimals;
-    pips
+    match symbol_spec(symbol) {
+        Some(spec) => spec.pip_size,
+        None => 1.0 / format_symbol_factor(symbol),
+    }
 }
 
 pub fn format_symbol_price(value: f64, symbol: &str) -> f64 {
-    let decimals = match symbol.contains("JPY") {
-        true => 3,
-        false => 5,
+    let decimals = match symbol_spec(symbol) {
+        Some(spec) => spec.digits as i32,
+        None => match symbol.contains("JPY") {
+            true => 3,
+            false => 5,
+        },
     };
 
     round(value, decimals)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helpers::date::{to_dbtime, Duration, TimeZone};
+
+    fn trade_in(id: usize, index_in: usize, status: TradeStatus) -> TradeIn {
+        TradeIn {
+            id,
+            index_in,
+            status,
+            ..TradeIn::default()
+        }
+    }
+
+    fn trade_out(id: usize, index_out: usize) -> TradeOut {
+        let date = to_dbtime(Local::now());
+
+        TradeOut {
+            id,
+            trade_type: TradeType::MarketOutLong,
+            status: TradeStatus::Fulfilled,
+            index_in: 0,
+            price_in: 0.,
+            size: 1.,
+            ask: 0.,
+            spread_in: 0.,
+            date_in: date,
+            index_out,
+            price_origin: 0.,
+            price_out: 0.,
+            bid: 0.,
+            spread_out: 0.,
+            date_out: date,
+            profit: 0.,
+            profit_per: 0.,
+            run_up: 0.,
+            run_up_per: 0.,
+            draw_down: 0.,
+            draw_down_per: 0.,
+            commission: 0.,
+            spread_cost: 0.,
+            swap: 0.,
+        }
+    }
+
+    #[test]
+    fn compounds_the_annual_return() {
+        let start = Local.with_ymd_and_hms(2022, 1, 1, 0, 0, 0).unwrap();
+        let end = start + Duration::seconds((2. * SECONDS_PER_YEAR) as i64);
+
+        // 21% over two years is 10% a year
+        let cagr = calculate_annual_return(10000., 12100., start, end);
+        assert!((cagr - 10.).abs() < 1e-9);
+
+        assert_eq!(calculate_annual_return(10000., 0., start, end), -100.);
+        assert_eq!(calculate_annual_return(10000., 12100., start, start), 0.);
+        assert_eq!(calculate_annual_return(0., 12100., start, end), 0.);
+        assert!((calculate_calmar_ratio(cagr, 20.) - 0.5).abs() < 1e-9);
+    }
+
+    #[test]
+    fn annualizes_sharpe_and_sortino() {
+        // Mean 1%, 2.83% deviation and 0.71% downside deviation
+        let returns = [0.03, -0.01];
+
+        assert!((calculate_sharpe_ratio(&returns, 2.) - 0.5).abs() < 1e-9);
+        assert!((calculate_sortino_ratio(&returns, 2.) - 2.).abs() < 1e-9);
+
+        // Flat, winning only or too short series have no ratio
+        assert_eq!(calculate_sharpe_ratio(&[0.01, 0.01], 252.), 0.);
+        assert_eq!(calculate_sortino_ratio(&[0.01, 0.02], 252.), 0.);
+        assert_eq!(calculate_sharpe_ratio(&[0.01], 252.), 0.);
+    }
+
+    #[test]
+    fn counts_bars_in_the_market() {
+        let trades_in = vec![
+            trade_in(1, 2, TradeStatus::Fulfilled),
+            trade_in(2, 3, TradeStatus::Fulfilled),
+            trade_in(3, 8, TradeStatus::Fulfilled),
+            trade_in(4, 0, TradeStatus::Rejected),
+        ];
+        let trades_out = vec![trade_out(1, 4), trade_out(2, 5)];
+
+        // Overlapping trades cover bars 2 to 5, the open one 8 and 9
+        assert!((total_exposure(&trades_in, &trades_out, 10) - 60.).abs() < 1e-9);
+        assert_eq!(total_exposure(&trades_in, &trades_out, 0), 0.);
+    }
+}
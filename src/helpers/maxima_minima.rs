@@ -1,19 +1,17 @@
+use crate::config::RsAlgoConfig;
 use crate::error::Result;
 
 use find_peaks::PeakFinder;
 use std::cmp::Ordering;
-use std::env;
 
 pub fn maxima_minima(
     x_values: &Vec<f64>,
     y_values: &Vec<f64>,
     min_prominence: f64,
     min_distance: usize,
+    config: &RsAlgoConfig,
 ) -> Result<Vec<(usize, f64)>> {
-    let logarithmic = env::var("LOGARITHMIC_SCANNER")
-        .unwrap()
-        .parse::<bool>()
-        .unwrap();
+    let logarithmic = config.logarithmic_scanner;
     let result: Vec<(usize, f64)> = PeakFinder::new(x_values)
         .with_min_prominence(min_prominence)
         .with_min_distance(min_distance)
@@ -1,4 +1,4 @@
-use std::env;
+use crate::config::RsAlgoConfig;
 
 fn gauss_const(h: f64) -> f64 {
     let pi = std::f64::consts::PI * 2.;
@@ -34,8 +34,8 @@ fn weights(bandwidth: f64, x: f64, data: &Vec<f64>, logarithmic: bool) -> Vec<f6
     w_row
 }
 
-pub fn kernel_regression(bandwidth: f64, x: f64, data: &Vec<f64>) -> f64 {
-    let logarithmic = env::var("LOGARITHMIC_SCANNER").unwrap().parse::<bool>().unwrap();
+pub fn kernel_regression(bandwidth: f64, x: f64, data: &Vec<f64>, config: &RsAlgoConfig) -> f64 {
+    let logarithmic = config.logarithmic_scanner;
     let w = weights(bandwidth, x, data, logarithmic);
     data.iter().zip(w.iter()).map(|(a, b)| (a * b)).sum()
 }
@@ -0,0 +1,49 @@
+use crate::models::symbol_spec::SymbolSpec;
+
+// Symbol, digits, pip size, tick size, contract size, margin rate, base and quote currency
+const SPECS: [(&str, u32, f64, f64, f64, f64, &str, &str); 19] = [
+    ("EURUSD", 5, 0.0001, 0.00001, 100000., 0.0333, "EUR", "USD"),
+    ("GBPUSD", 5, 0.0001, 0.00001, 100000., 0.0333, "GBP", "USD"),
+    ("AUDUSD", 5, 0.0001, 0.00001, 100000., 0.0333, "AUD", "USD"),
+    ("NZDUSD", 5, 0.0001, 0.00001, 100000., 0.0333, "NZD", "USD"),
+    ("USDCAD", 5, 0.0001, 0.00001, 100000., 0.0333, "USD", "CAD"),
+    ("USDCHF", 5, 0.0001, 0.00001, 100000., 0.0333, "USD", "CHF"),
+    ("EURGBP", 5, 0.0001, 0.00001, 100000., 0.0333, "EUR", "GBP"),
+    ("EURCHF", 5, 0.0001, 0.00001, 100000., 0.0333, "EUR", "CHF"),
+    ("USDJPY", 3, 0.01, 0.001, 100000., 0.0333, "USD", "JPY"),
+    ("EURJPY", 3, 0.01, 0.001, 100000., 0.0333, "EUR", "JPY"),
+    ("GBPJPY", 3, 0.01, 0.001, 100000., 0.0333, "GBP", "JPY"),
+    ("AUDJPY", 3, 0.01, 0.001, 100000., 0.0333, "AUD", "JPY"),
+    ("GOLD", 2, 0.1, 0.01, 100., 0.05, "USD", "USD"),
+    ("SILVER", 3, 0.01, 0.001, 5000., 0.1, "USD", "USD"),
+    ("US500", 1, 1., 0.1, 50., 0.05, "USD", "USD"),
+    ("US100", 1, 1., 0.1, 20., 0.05, "USD", "USD"),
+    ("DE40", 1, 1., 0.1, 25., 0.05, "EUR", "EUR"),
+    ("BITCOIN", 2, 1., 0.01, 1., 0.5, "USD", "USD"),
+    ("ETHEREUM", 2, 0.1, 0.01, 1., 0.5, "USD", "USD"),
+];
+
+// Bundled defaults, overridden by the broker's getSymbol data or a specs file
+pub fn get_specs() -> Vec<SymbolSpec> {
+    SPECS
+        .iter()
+        .map(
+            |&(symbol, digits, pip_size, tick_size, contract_size, margin_rate, base, quote)| {
+                SymbolSpec {
+                    symbol: symbol.to_owned(),
+                    digits,
+                    pip_size,
+                    tick_size,
+                    contract_size,
+                    lot_step: 0.01,
+                    min_volume: 0.01,
+                    max_volume: 100.,
+                    margin_rate,
+                    base_currency: base.to_owned(),
+                    quote_currency: quote.to_owned(),
+                    trading_hours: vec![],
+                }
+            },
+        )
+        .collect()
+}
@@ -2,7 +2,6 @@ use super::Indicator;
 use crate::error::Result;
 
 use serde::{Deserialize, Serialize};
-use std::env;
 use ta::indicators::BollingerBands;
 use ta::{Next, Reset};
 
@@ -17,11 +16,8 @@ pub struct BollingerB {
     data_c: Vec<f64>,
 }
 
-impl Indicator for BollingerB {
-    fn new() -> Result<Self> {
-        let period = env::var("BB_PERIOD").unwrap().parse::<usize>().unwrap();
-        let multiplier = env::var("BB_MULTIPLIER").unwrap().parse::<f64>().unwrap();
-
+impl BollingerB {
+    pub fn new_bb(period: usize, multiplier: f64) -> Result<Self> {
         Ok(Self {
             bb: BollingerBands::new(period, multiplier).unwrap(),
             bb_tmp: BollingerBands::new(period, multiplier).unwrap(),
@@ -30,6 +26,12 @@ impl Indicator for BollingerB {
             data_c: vec![],
         })
     }
+}
+
+impl Indicator for BollingerB {
+    fn new() -> Result<Self> {
+        Self::new_bb(20, 2.0)
+    }
 
     fn get_data_a(&self) -> &Vec<f64> {
         &self.data_a
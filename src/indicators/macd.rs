@@ -2,7 +2,6 @@ use super::Indicator;
 use crate::error::Result;
 
 use serde::{Deserialize, Serialize};
-use std::env;
 use ta::indicators::ExponentialMovingAverage;
 use ta::{Next, Reset};
 
@@ -25,12 +24,8 @@ pub struct Macd {
     data_c: Vec<f64>,
 }
 
-impl Indicator for Macd {
-    fn new() -> Result<Self> {
-        let macd_a = env::var("MACD_A").unwrap().parse::<usize>().unwrap();
-        let macd_b = env::var("MACD_B").unwrap().parse::<usize>().unwrap();
-        let macd_c = env::var("MACD_C").unwrap().parse::<usize>().unwrap();
-
+impl Macd {
+    pub fn new_macd(macd_a: usize, macd_b: usize, macd_c: usize) -> Result<Self> {
         Ok(Self {
             ema_a: ExponentialMovingAverage::new(macd_a).unwrap(),
             ema_b: ExponentialMovingAverage::new(macd_b).unwrap(),
@@ -43,6 +38,13 @@ impl Indicator for Macd {
             data_c: vec![],
         })
     }
+}
+
+impl Indicator for Macd {
+    fn new() -> Result<Self> {
+        Self::new_macd(12, 26, 9)
+    }
+
     fn get_data_a(&self) -> &Vec<f64> {
         &self.data_a
     }
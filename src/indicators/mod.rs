@@ -8,6 +8,7 @@ pub mod rsi;
 //pub mod sd;
 //pub mod stoch;
 
+use crate::config::RsAlgoConfig;
 use crate::error::Result;
 use crate::indicators::atr::Atr;
 use crate::indicators::bb::BollingerB;
@@ -19,7 +20,6 @@ use crate::models::time_frame::TimeFrameType;
 use crate::scanner::candle::Candle;
 
 use serde::{Deserialize, Serialize};
-use std::env;
 use std::marker::Sized;
 
 pub trait Indicator {
@@ -65,89 +65,56 @@ pub struct Indicators {
 }
 
 impl Indicators {
-    pub fn new() -> Result<Self> {
-        let macd = env::var("INDICATORS_MACD")
-            .ok()
-            .and_then(|v| v.parse::<bool>().ok())
-            .and_then(|enabled| if enabled { Macd::new().ok() } else { None });
+    pub fn new(config: &RsAlgoConfig) -> Result<Self> {
+        let macd = match config.indicators_macd {
+            true => Macd::new_macd(config.macd_a, config.macd_b, config.macd_c).ok(),
+            false => None,
+        };
 
-        let rsi = env::var("INDICATORS_RSI")
-            .ok()
-            .and_then(|v| v.parse::<bool>().ok())
-            .and_then(|enabled| if enabled { Rsi::new().ok() } else { None });
+        let rsi = match config.indicators_rsi {
+            true => Rsi::new().ok(),
+            false => None,
+        };
 
         // let stoch = env::var("INDICATORS_STOCH")
         //     .ok()
         //     .and_then(|v| v.parse::<bool>().ok())
         //     .and_then(|enabled| if enabled { Stoch::new().ok() } else { None });
 
-        let atr = env::var("INDICATORS_ATR")
-            .ok()
-            .and_then(|v| v.parse::<bool>().ok())
-            .and_then(|enabled| if enabled { Atr::new().ok() } else { None });
+        let atr = match config.indicators_atr {
+            true => Atr::new().ok(),
+            false => None,
+        };
 
         // let adx = env::var("INDICATORS_ADX")
         //     .ok()
         //     .and_then(|v| v.parse::<bool>().ok())
         //     .and_then(|enabled| if enabled { Adx::new().ok() } else { None });
 
-        let bb = env::var("INDICATORS_BB")
-            .ok()
-            .and_then(|v| v.parse::<bool>().ok())
-            .and_then(|enabled| {
-                if enabled {
-                    BollingerB::new().ok()
-                } else {
-                    None
-                }
-            });
-
-        let bbw = env::var("INDICATORS_BBW")
-            .ok()
-            .and_then(|v| v.parse::<bool>().ok())
-            .and_then(|enabled| {
-                if enabled {
-                    BollingerBW::new().ok()
-                } else {
-                    None
-                }
-            });
-
-        let ema_a = env::var("INDICATORS_EMA_A")
-            .ok()
-            .and_then(|v| v.parse::<bool>().ok())
-            .and_then(|enabled| {
-                if enabled {
-                    let ema_a = env::var("EMA_A").unwrap().parse::<usize>().unwrap();
-                    Some(Ema::new_ema(ema_a).unwrap())
-                } else {
-                    None
-                }
-            });
-
-        let ema_b = env::var("INDICATORS_EMA_B")
-            .ok()
-            .and_then(|v| v.parse::<bool>().ok())
-            .and_then(|enabled| {
-                if enabled {
-                    let ema_a = env::var("EMA_B").unwrap().parse::<usize>().unwrap();
-                    Some(Ema::new_ema(ema_a).unwrap())
-                } else {
-                    None
-                }
-            });
-
-        let ema_c = env::var("INDICATORS_EMA_C")
-            .ok()
-            .and_then(|v| v.parse::<bool>().ok())
-            .and_then(|enabled| {
-                if enabled {
-                    let ema_a = env::var("EMA_C").unwrap().parse::<usize>().unwrap();
-                    Some(Ema::new_ema(ema_a).unwrap())
-                } else {
-                    None
-                }
-            });
+        let bb = match config.indicators_bb {
+            true => BollingerB::new_bb(config.bb_period, config.bb_multiplier).ok(),
+            false => None,
+        };
+
+        let bbw = match config.indicators_bbw {
+            true => BollingerBW::new().ok(),
+            false => None,
+        };
+
+        let ema_a = match config.indicators_ema_a {
+            true => Some(Ema::new_ema(config.ema_a).unwrap()),
+            false => None,
+        };
+
+        let ema_b = match config.indicators_ema_b {
+            true => Some(Ema::new_ema(config.ema_b).unwrap()),
+            false => None,
+        };
+
+        let ema_c = match config.indicators_ema_c {
+            true => Some(Ema::new_ema(config.ema_c).unwrap()),
+            false => None,
+        };
 
         Ok(Self {
             macd,
@@ -204,9 +171,10 @@ impl Indicators {
         ohlc: (f64, f64, f64, f64),
         remove_first: bool,
         time_frame: &TimeFrameType,
+        config: &RsAlgoConfig,
     ) -> Result<()> {
         let close = ohlc.3;
-        let num_bars = env::var("NUM_BARS").unwrap().parse::<usize>().unwrap();
+        let num_bars = config.num_bars;
         let max_bars = num_bars / time_frame.clone().to_number() as usize;
 
         // STOCH
@@ -408,8 +376,9 @@ impl Indicators {
         &mut self,
         time_frame: &TimeFrameType,
         remove_first: bool,
+        config: &RsAlgoConfig,
     ) -> Result<()> {
-        let num_bars = env::var("NUM_BARS").unwrap().parse::<usize>().unwrap();
+        let num_bars = config.num_bars;
         let max_bars = num_bars / time_frame.clone().to_number() as usize;
 
         if let Some(rsi) = &mut self.rsi {
@@ -10,6 +10,7 @@ pub mod indicators;
 #[cfg(feature = "websocket")]
 pub mod ws;
 
+pub mod config;
 pub mod error;
 pub mod helpers;
 pub mod models;
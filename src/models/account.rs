@@ -0,0 +1,305 @@
+use super::equity::equity_curve;
+use super::fx::FxRates;
+use super::symbol_spec::symbol_spec;
+use super::trade::{TradeIn, TradeOut};
+
+use crate::config::RsAlgoConfig;
+use crate::error::{Result, RsAlgoError, RsAlgoErrorKind};
+use crate::helpers::date::{from_dbtime, DateTime, Local};
+use crate::scanner::instrument::Instrument;
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct AccountInfo {
+    pub currency: String,
+    pub balance: f64,
+    pub equity: f64,
+    pub credit: f64,
+    pub margin: f64,
+    pub margin_free: f64,
+    pub margin_level: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Account {
+    currency: String,
+    balance: f64,
+    equity: f64,
+    credit: f64,
+    margin: f64,
+    leverage: f64,
+    contract_size: f64,
+    positions: Vec<(usize, f64)>,
+    samples: usize,
+    total_margin_utilization: f64,
+    max_margin_utilization: f64,
+}
+
+impl Account {
+    pub fn new() -> AccountBuilder {
+        AccountBuilder::new()
+    }
+
+    pub fn from_config(config: &RsAlgoConfig) -> Self {
+        Self {
+            currency: config.account_currency.clone(),
+            balance: config.equity,
+            equity: config.equity,
+            credit: 0.,
+            margin: 0.,
+            leverage: config.leverage,
+            contract_size: config.contract_size,
+            positions: vec![],
+            samples: 0,
+            total_margin_utilization: 0.,
+            max_margin_utilization: 0.,
+        }
+    }
+
+    pub fn currency(&self) -> &str {
+        &self.currency
+    }
+
+    pub fn balance(&self) -> f64 {
+        self.balance
+    }
+
+    pub fn equity(&self) -> f64 {
+        self.equity
+    }
+
+    pub fn margin(&self) -> f64 {
+        self.margin
+    }
+
+    pub fn margin_free(&self) -> f64 {
+        self.equity - self.margin
+    }
+
+    pub fn margin_level(&self) -> f64 {
+        match self.margin > 0. {
+            true => self.equity / self.margin * 100.,
+            false => 0.,
+        }
+    }
+
+    pub fn margin_utilization(&self) -> f64 {
+        match self.equity > 0. {
+            true => self.margin / self.equity * 100.,
+            false => 0.,
+        }
+    }
+
+    pub fn avg_margin_utilization(&self) -> f64 {
+        match self.samples > 0 {
+            true => self.total_margin_utilization / self.samples as f64,
+            false => 0.,
+        }
+    }
+
+    pub fn max_margin_utilization(&self) -> f64 {
+        self.max_margin_utilization
+    }
+
+    pub fn is_open(&self, id: usize) -> bool {
+        self.positions
+            .iter()
+            .any(|(position_id, _)| *position_id == id)
+    }
+
+    // Registered symbols use their own contract size and margin rate. The
+    // notional is in the quote currency and gets converted to the account one
+    pub fn margin_required(
+        &self,
+        symbol: &str,
+        price: f64,
+        size: f64,
+        fx_rates: &FxRates,
+        date: DateTime<Local>,
+    ) -> Result<f64> {
+        let margin = match symbol_spec(symbol) {
+            Some(spec) => price * size * spec.contract_size * spec.margin_rate,
+            None => price * size * self.contract_size / self.leverage,
+        };
+
+        fx_rates.quote_to_account(margin, symbol, price, date)
+    }
+
+    pub fn check_margin(
+        &self,
+        symbol: &str,
+        price: f64,
+        size: f64,
+        fx_rates: &FxRates,
+        date: DateTime<Local>,
+    ) -> Result<f64> {
+        let required = self.margin_required(symbol, price, size, fx_rates, date)?;
+        let margin_free = self.margin_free();
+
+        match required <= margin_free {
+            true => Ok(required),
+            false => {
+                log::warn!(
+                    "Insufficient margin {:.2} required, {:.2} free",
+                    required,
+                    margin_free
+                );
+
+                Err(RsAlgoError {
+                    err: RsAlgoErrorKind::InsufficientMargin(
+                        "MARGIN".to_owned(),
+                        format!("{:.2} required, {:.2} free", required, margin_free),
+                    ),
+                })
+            }
+        }
+    }
+
+    pub fn open(&mut self, symbol: &str, trade_in: &TradeIn, fx_rates: &FxRates) -> Result<()> {
+        let margin = self.check_margin(
+            symbol,
+            trade_in.price_in,
+            trade_in.size,
+            fx_rates,
+            from_dbtime(&trade_in.date_in),
+        )?;
+
+        self.positions.push((trade_in.id, margin));
+        self.margin += margin;
+        self.sample();
+
+        Ok(())
+    }
+
+    pub fn close(&mut self, trade_out: &TradeOut) {
+        if let Some(position) = self
+            .positions
+            .iter()
+            .position(|(id, _)| *id == trade_out.id)
+        {
+            let (_, margin) = self.positions.remove(position);
+            self.margin = (self.margin - margin).max(0.);
+        }
+
+        self.balance += trade_out.profit;
+        self.equity = self.balance + self.credit;
+    }
+
+    pub fn mark_to_market(&mut self, open_profit: f64) {
+        self.equity = self.balance + self.credit + open_profit;
+        self.sample();
+    }
+
+    // Broker snapshots (getMarginLevel or the balance stream) overwrite the local state
+    pub fn update(&mut self, info: &AccountInfo) {
+        if !info.currency.is_empty() {
+            self.currency = info.currency.clone();
+        }
+        self.balance = info.balance;
+        self.equity = info.equity;
+        self.credit = info.credit;
+        self.margin = info.margin;
+        self.sample();
+    }
+
+    pub fn info(&self) -> AccountInfo {
+        AccountInfo {
+            currency: self.currency.clone(),
+            balance: self.balance,
+            equity: self.equity,
+            credit: self.credit,
+            margin: self.margin,
+            margin_free: self.margin_free(),
+            margin_level: self.margin_level(),
+        }
+    }
+
+    fn sample(&mut self) {
+        let utilization = self.margin_utilization();
+        self.samples += 1;
+        self.total_margin_utilization += utilization;
+        self.max_margin_utilization = self.max_margin_utilization.max(utilization);
+    }
+}
+
+// Replays backtest trades on an account built from the config
+pub fn simulate_account(
+    instrument: &Instrument,
+    trades_in: &[TradeIn],
+    trades_out: &[TradeOut],
+    fx_rates: &FxRates,
+    config: &RsAlgoConfig,
+) -> Result<Account> {
+    let mut account = Account::from_config(config);
+    equity_curve(instrument, trades_in, trades_out, fx_rates, &mut account)?;
+    Ok(account)
+}
+
+#[derive(Debug, Clone)]
+pub struct AccountBuilder {
+    currency: Option<String>,
+    balance: Option<f64>,
+    leverage: Option<f64>,
+    contract_size: Option<f64>,
+}
+
+impl AccountBuilder {
+    pub fn new() -> Self {
+        Self {
+            currency: None,
+            balance: None,
+            leverage: None,
+            contract_size: None,
+        }
+    }
+
+    pub fn currency(mut self, val: String) -> Self {
+        self.currency = Some(val);
+        self
+    }
+
+    pub fn balance(mut self, val: f64) -> Self {
+        self.balance = Some(val);
+        self
+    }
+
+    pub fn leverage(mut self, val: f64) -> Self {
+        self.leverage = Some(val);
+        self
+    }
+
+    pub fn contract_size(mut self, val: f64) -> Self {
+        self.contract_size = Some(val);
+        self
+    }
+
+    pub fn build(self) -> std::result::Result<Account, &'static str> {
+        let balance = self.balance.ok_or("Balance is required")?;
+        let leverage = self.leverage.ok_or("Leverage is required")?;
+
+        if leverage <= 0. {
+            return Err("Leverage must be positive");
+        }
+
+        Ok(Account {
+            currency: self.currency.unwrap_or_else(|| "USD".to_owned()),
+            balance,
+            equity: balance,
+            credit: 0.,
+            margin: 0.,
+            leverage,
+            contract_size: self.contract_size.unwrap_or(100000.),
+            positions: vec![],
+            samples: 0,
+            total_margin_utilization: 0.,
+            max_margin_utilization: 0.,
+        })
+    }
+}
+
+impl Default for AccountBuilder {
+    fn default() -> Self {
+        AccountBuilder::new()
+    }
+}
@@ -1,3 +1,4 @@
+use crate::helpers::calc::calculate_annual_return;
 use crate::helpers::date::*;
 use crate::models::market::*;
 
@@ -28,6 +29,10 @@ pub struct BackTestInstrumentResult {
     pub stop_losses: usize,
     pub gross_profit: f64,
     pub commissions: f64,
+    #[serde(default)]
+    pub spread_costs: f64,
+    #[serde(default)]
+    pub swaps: f64,
     pub net_profit: f64,
     pub net_profit_per: f64,
     pub profitable_trades: f64,
@@ -36,6 +41,36 @@ pub struct BackTestInstrumentResult {
     pub max_drawdown: f64,
     pub buy_hold: f64,
     pub annual_return: f64,
+    #[serde(default)]
+    pub sharpe_ratio: f64,
+    #[serde(default)]
+    pub sortino_ratio: f64,
+    #[serde(default)]
+    pub calmar_ratio: f64,
+    #[serde(default)]
+    pub expectancy: f64,
+    #[serde(default)]
+    pub avg_r_multiple: f64,
+    #[serde(default)]
+    pub max_consecutive_wins: usize,
+    #[serde(default)]
+    pub max_consecutive_losses: usize,
+    #[serde(default)]
+    pub avg_holding_time: f64,
+    #[serde(default)]
+    pub exposure: f64,
+}
+
+impl BackTestInstrumentResult {
+    // CAGR over the backtested period
+    pub fn cagr(&self, initial_equity: f64) -> f64 {
+        calculate_annual_return(
+            initial_equity,
+            initial_equity + self.net_profit,
+            from_dbtime(&self.date_start),
+            from_dbtime(&self.date_end),
+        )
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -1,4 +1,6 @@
+use crate::helpers::comp::{average_f64, average_usize};
 use crate::helpers::{date::*};
+use crate::models::backtest_instrument::BackTestInstrumentResult;
 use crate::models::market::*;
 use crate::models::strategy::*;
 pub use bson::Uuid;
@@ -31,6 +33,10 @@ pub struct BackTestStrategyResult {
     pub avg_stop_losses: usize,
     pub avg_gross_profit: f64,
     pub avg_commissions: f64,
+    #[serde(default)]
+    pub avg_spread_costs: f64,
+    #[serde(default)]
+    pub avg_swaps: f64,
     pub avg_net_profit: f64,
     pub avg_net_profit_per: f64,
     pub avg_profitable_trades: f64,
@@ -39,4 +45,54 @@ pub struct BackTestStrategyResult {
     pub avg_max_drawdown: f64,
     pub avg_buy_hold: f64,
     pub avg_annual_return: f64,
+    #[serde(default)]
+    pub avg_sharpe_ratio: f64,
+    #[serde(default)]
+    pub avg_sortino_ratio: f64,
+    #[serde(default)]
+    pub avg_calmar_ratio: f64,
+    #[serde(default)]
+    pub avg_expectancy: f64,
+    #[serde(default)]
+    pub avg_r_multiple: f64,
+    #[serde(default)]
+    pub avg_max_consecutive_wins: usize,
+    #[serde(default)]
+    pub avg_max_consecutive_losses: usize,
+    #[serde(default)]
+    pub avg_holding_time: f64,
+    #[serde(default)]
+    pub avg_exposure: f64,
+}
+
+impl BackTestStrategyResult {
+    // Averages the risk adjusted metrics and costs of every backtested instrument
+    pub fn aggregate_risk_metrics(&mut self, results: &[BackTestInstrumentResult]) {
+        let avg = |value: fn(&BackTestInstrumentResult) -> f64| {
+            average_f64(&results.iter().map(value).collect())
+        };
+
+        self.avg_spread_costs = avg(|result| result.spread_costs);
+        self.avg_swaps = avg(|result| result.swaps);
+        self.avg_annual_return = avg(|result| result.annual_return);
+        self.avg_sharpe_ratio = avg(|result| result.sharpe_ratio);
+        self.avg_sortino_ratio = avg(|result| result.sortino_ratio);
+        self.avg_calmar_ratio = avg(|result| result.calmar_ratio);
+        self.avg_expectancy = avg(|result| result.expectancy);
+        self.avg_r_multiple = avg(|result| result.avg_r_multiple);
+        self.avg_holding_time = avg(|result| result.avg_holding_time);
+        self.avg_exposure = avg(|result| result.exposure);
+        self.avg_max_consecutive_wins = average_usize(
+            &results
+                .iter()
+                .map(|result| result.max_consecutive_wins)
+                .collect(),
+        );
+        self.avg_max_consecutive_losses = average_usize(
+            &results
+                .iter()
+                .map(|result| result.max_consecutive_losses)
+                .collect(),
+        );
+    }
 }
@@ -0,0 +1,404 @@
+use super::fx::{symbol_currencies, FxRates};
+use super::swap::InstrumentSwap;
+use super::symbol_spec::symbol_spec;
+use super::trade::{TradeOut, TradeType};
+
+use crate::config::RsAlgoConfig;
+use crate::error::Result;
+use crate::helpers::date::{from_dbtime, DateTime, Datelike, Duration, Local};
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub enum CommissionMode {
+    PerLot,
+    Percentage,
+}
+
+impl CommissionMode {
+    pub fn from_str(commission_mode: &str) -> CommissionMode {
+        match commission_mode {
+            "percentage" => CommissionMode::Percentage,
+            _ => CommissionMode::PerLot,
+        }
+    }
+}
+
+// Per lot commissions are charged in the account currency on each side,
+// percentages apply to the traded notional. Swaps are quote currency
+// amounts per lot and rollover
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct CostModel {
+    commission_mode: CommissionMode,
+    commission: f64,
+    contract_size: f64,
+    swap: Option<InstrumentSwap>,
+}
+
+impl CostModel {
+    pub fn new() -> CostModelBuilder {
+        CostModelBuilder::new()
+    }
+
+    // Configured swap rates belong to the traded symbol
+    pub fn from_config(config: &RsAlgoConfig, symbol: &str) -> Self {
+        let swap = match config.swap_long != 0. || config.swap_short != 0. {
+            true => InstrumentSwap::new()
+                .symbol(symbol.to_owned())
+                .enabled(true)
+                .swap_long(config.swap_long)
+                .swap_short(config.swap_short)
+                .swap_weekend(config.swap_triple_day as f64)
+                .build()
+                .ok(),
+            false => None,
+        };
+
+        Self {
+            commission_mode: CommissionMode::from_str(&config.commission_mode),
+            commission: config.commission,
+            contract_size: config.contract_size,
+            swap,
+        }
+    }
+
+    // Broker swap rates replace the configured ones
+    pub fn with_swap(mut self, swap: InstrumentSwap) -> Self {
+        self.swap = Some(swap);
+        self
+    }
+
+    pub fn commission_mode(&self) -> &CommissionMode {
+        &self.commission_mode
+    }
+
+    pub fn swap(&self) -> Option<&InstrumentSwap> {
+        self.swap.as_ref()
+    }
+
+    fn contract_size(&self, symbol: &str) -> f64 {
+        symbol_spec(symbol)
+            .map(|spec| spec.contract_size)
+            .unwrap_or(self.contract_size)
+    }
+
+    // Quote currency amounts converted to the account currency when the quote is known
+    fn to_account(
+        &self,
+        amount: f64,
+        symbol: &str,
+        fx_rates: &FxRates,
+        date: DateTime<Local>,
+    ) -> Result<f64> {
+        let quote = match symbol_spec(symbol) {
+            Some(spec) => Some(spec.quote_currency.clone()),
+            None => symbol_currencies(symbol).map(|(_, quote)| quote),
+        };
+
+        match quote {
+            Some(quote) => fx_rates.to_account(amount, &quote, date),
+            None => Ok(amount),
+        }
+    }
+
+    pub fn commission(
+        &self,
+        size: f64,
+        price_in: f64,
+        price_out: f64,
+        symbol: &str,
+        fx_rates: &FxRates,
+        date: DateTime<Local>,
+    ) -> Result<f64> {
+        match self.commission_mode {
+            CommissionMode::PerLot => Ok(self.commission * size * 2.),
+            CommissionMode::Percentage => {
+                let notional = (price_in + price_out) * size * self.contract_size(symbol);
+                self.to_account(notional * self.commission / 100., symbol, fx_rates, date)
+            }
+        }
+    }
+
+    // The spread is already inside the fill prices, this only breaks it out
+    pub fn spread_cost(
+        &self,
+        trade_out: &TradeOut,
+        symbol: &str,
+        fx_rates: &FxRates,
+    ) -> Result<f64> {
+        let spread = match trade_out.trade_type.is_long() {
+            true => trade_out.spread_in,
+            false => trade_out.spread_out,
+        };
+
+        self.to_account(
+            spread * trade_out.size * self.contract_size(symbol),
+            symbol,
+            fx_rates,
+            from_dbtime(&trade_out.date_out),
+        )
+    }
+
+    // Signed amount, negative when the broker charges the position
+    pub fn swap_amount(
+        &self,
+        trade_type: &TradeType,
+        size: f64,
+        date_in: DateTime<Local>,
+        date_out: DateTime<Local>,
+        symbol: &str,
+        fx_rates: &FxRates,
+    ) -> Result<f64> {
+        let swap = match &self.swap {
+            Some(swap) if swap.enabled() => swap,
+            _ => return Ok(0.),
+        };
+
+        let rate = match trade_type.is_long() {
+            true => swap.swap_long(),
+            false => swap.swap_short(),
+        };
+
+        let nights = rollovers(date_in, date_out, swap.swap_weekend() as u32);
+        self.to_account(rate * size * nights, symbol, fx_rates, date_out)
+    }
+
+    // Expects the gross price profit on the trade and leaves the net one
+    pub fn apply(&self, trade_out: &mut TradeOut, symbol: &str, fx_rates: &FxRates) -> Result<()> {
+        let date_in = from_dbtime(&trade_out.date_in);
+        let date_out = from_dbtime(&trade_out.date_out);
+
+        trade_out.commission = self.commission(
+            trade_out.size,
+            trade_out.price_in,
+            trade_out.price_out,
+            symbol,
+            fx_rates,
+            date_out,
+        )?;
+        trade_out.spread_cost = self.spread_cost(trade_out, symbol, fx_rates)?;
+        trade_out.swap = self.swap_amount(
+            &trade_out.trade_type,
+            trade_out.size,
+            date_in,
+            date_out,
+            symbol,
+            fx_rates,
+        )?;
+        trade_out.profit = trade_out.profit - trade_out.commission + trade_out.swap;
+
+        Ok(())
+    }
+}
+
+// Weekday midnights crossed while the position is open. The triple day
+// also pays for the weekend
+pub fn rollovers(date_in: DateTime<Local>, date_out: DateTime<Local>, triple_day: u32) -> f64 {
+    let mut day = date_in.date_naive();
+    let last = date_out.date_naive();
+    let mut nights = 0.;
+
+    while day < last {
+        nights += match day.weekday().num_days_from_monday() {
+            5 | 6 => 0.,
+            weekday if weekday == triple_day => 3.,
+            _ => 1.,
+        };
+        day += Duration::days(1);
+    }
+
+    nights
+}
+
+#[derive(Debug, Clone)]
+pub struct CostModelBuilder {
+    commission_mode: Option<CommissionMode>,
+    commission: Option<f64>,
+    contract_size: Option<f64>,
+    swap: Option<InstrumentSwap>,
+}
+
+impl CostModelBuilder {
+    pub fn new() -> Self {
+        Self {
+            commission_mode: None,
+            commission: None,
+            contract_size: None,
+            swap: None,
+        }
+    }
+
+    pub fn commission_mode(mut self, val: CommissionMode) -> Self {
+        self.commission_mode = Some(val);
+        self
+    }
+
+    pub fn commission(mut self, val: f64) -> Self {
+        self.commission = Some(val);
+        self
+    }
+
+    pub fn contract_size(mut self, val: f64) -> Self {
+        self.contract_size = Some(val);
+        self
+    }
+
+    pub fn swap(mut self, val: InstrumentSwap) -> Self {
+        self.swap = Some(val);
+        self
+    }
+
+    pub fn build(self) -> std::result::Result<CostModel, &'static str> {
+        let commission = self.commission.unwrap_or(0.);
+
+        if commission < 0. {
+            return Err("Commission can't be negative");
+        }
+
+        Ok(CostModel {
+            commission_mode: self.commission_mode.unwrap_or(CommissionMode::PerLot),
+            commission,
+            contract_size: self.contract_size.unwrap_or(100000.),
+            swap: self.swap,
+        })
+    }
+}
+
+impl Default for CostModelBuilder {
+    fn default() -> Self {
+        CostModelBuilder::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helpers::date::{to_dbtime, TimeZone};
+    use crate::models::trade::TradeStatus;
+
+    // Monday
+    fn date(day: u32) -> DateTime<Local> {
+        Local.with_ymd_and_hms(2024, 1, day, 10, 0, 0).unwrap()
+    }
+
+    fn swap(swap_long: f64, swap_short: f64) -> InstrumentSwap {
+        InstrumentSwap::new()
+            .symbol("EURUSD".to_owned())
+            .enabled(true)
+            .swap_long(swap_long)
+            .swap_short(swap_short)
+            .swap_weekend(2.)
+            .build()
+            .unwrap()
+    }
+
+    fn trade_out(trade_type: TradeType, profit: f64, date_in: u32, date_out: u32) -> TradeOut {
+        TradeOut {
+            id: 1,
+            trade_type,
+            status: TradeStatus::Fulfilled,
+            index_in: 0,
+            price_in: 1.1,
+            size: 1.,
+            ask: 1.1,
+            spread_in: 0.0001,
+            date_in: to_dbtime(date(date_in)),
+            index_out: 0,
+            price_origin: 1.1,
+            price_out: 1.1,
+            bid: 1.1,
+            spread_out: 0.0002,
+            date_out: to_dbtime(date(date_out)),
+            profit,
+            profit_per: 0.,
+            run_up: 0.,
+            run_up_per: 0.,
+            draw_down: 0.,
+            draw_down_per: 0.,
+            commission: 0.,
+            spread_cost: 0.,
+            swap: 0.,
+        }
+    }
+
+    #[test]
+    fn counts_rollovers() {
+        // Same day and a single weekday night
+        assert_eq!(rollovers(date(8), date(8), 2), 0.);
+        assert_eq!(rollovers(date(8), date(9), 2), 1.);
+
+        // Wednesday pays three nights
+        assert_eq!(rollovers(date(10), date(11), 2), 3.);
+        assert_eq!(rollovers(date(8), date(12), 2), 6.);
+
+        // Weekend nights are already paid on the triple day
+        assert_eq!(rollovers(date(12), date(15), 2), 1.);
+        assert_eq!(rollovers(date(13), date(15), 2), 0.);
+        assert_eq!(rollovers(date(8), date(15), 2), 7.);
+    }
+
+    #[test]
+    fn charges_commissions_by_mode() {
+        let fx_rates = FxRates::new("USD", 100000.);
+
+        let per_lot = CostModel::new()
+            .commission_mode(CommissionMode::PerLot)
+            .commission(3.5)
+            .build()
+            .unwrap();
+        let commission = per_lot
+            .commission(2., 1.1, 1.2, "EURUSD", &fx_rates, date(8))
+            .unwrap();
+        assert!((commission - 14.).abs() < 1e-9);
+
+        // 0.002% of 1.1 + 1.2 over 100000 units
+        let percentage = CostModel::new()
+            .commission_mode(CommissionMode::Percentage)
+            .commission(0.002)
+            .build()
+            .unwrap();
+        let commission = percentage
+            .commission(1., 1.1, 1.2, "EURUSD", &fx_rates, date(8))
+            .unwrap();
+        assert!((commission - 4.6).abs() < 1e-9);
+
+        assert!(CostModel::new().commission(-1.).build().is_err());
+    }
+
+    #[test]
+    fn applies_costs_with_the_broker_sign_convention() {
+        let fx_rates = FxRates::new("USD", 100000.);
+        let cost_model = CostModel::new()
+            .commission(3.)
+            .swap(swap(-7., 2.))
+            .build()
+            .unwrap();
+
+        // Longs pay the swap, commission and swap come out of the gross profit
+        let mut long = trade_out(TradeType::StopLossLong, 100., 8, 9);
+        cost_model.apply(&mut long, "EURUSD", &fx_rates).unwrap();
+        assert!((long.commission - 6.).abs() < 1e-9);
+        assert!((long.swap + 7.).abs() < 1e-9);
+        assert!((long.spread_cost - 10.).abs() < 1e-9);
+        assert!((long.profit - 87.).abs() < 1e-9);
+        assert!((long.gross_profit() - 100.).abs() < 1e-9);
+
+        // Shorts earn it, three nights over the triple day
+        let mut short = trade_out(TradeType::MarketOutShort, 100., 10, 11);
+        cost_model.apply(&mut short, "EURUSD", &fx_rates).unwrap();
+        assert!((short.swap - 6.).abs() < 1e-9);
+        assert!((short.spread_cost - 20.).abs() < 1e-9);
+        assert!((short.profit - 100.).abs() < 1e-9);
+        assert!((short.gross_profit() - 100.).abs() < 1e-9);
+
+        // Disabled swaps cost nothing
+        let mut long = trade_out(TradeType::MarketOutLong, 100., 8, 9);
+        CostModel::new()
+            .build()
+            .unwrap()
+            .apply(&mut long, "EURUSD", &fx_rates)
+            .unwrap();
+        assert_eq!(long.swap, 0.);
+        assert_eq!(long.profit, 100.);
+    }
+}
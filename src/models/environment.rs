@@ -40,14 +40,19 @@ impl Environment {
     }
 }
 
-
+pub fn try_from_str(env: &str) -> Option<Environment> {
+    match env.to_lowercase().as_str() {
+        "production" => Some(Environment::Production),
+        "development" => Some(Environment::Development),
+        "backtesting" => Some(Environment::Backtesting),
+        _ => None,
+    }
+}
 
 pub fn from_str(env: &str) -> Environment {
-    match env.to_lowercase().as_str() {
-        "production" => Environment::Production,
-        "development" => Environment::Development,
-        "backtesting" => Environment::Backtesting,
-        _ => {
+    match try_from_str(env) {
+        Some(env) => env,
+        None => {
             log::error!("No {} env found!", env);
             panic!();
         }
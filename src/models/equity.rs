@@ -0,0 +1,296 @@
+use super::account::Account;
+use super::fx::FxRates;
+use super::trade::{TradeIn, TradeOut, TradeStatus};
+
+use crate::error::Result;
+use crate::helpers::date::{from_dbtime, to_dbtime, DateTime, DbDateTime, Local};
+use crate::scanner::instrument::Instrument;
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct EquityPoint {
+    pub date: DbDateTime,
+    pub balance: f64,
+    pub equity: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Drawdown {
+    pub max_drawdown: f64,
+    pub max_drawdown_per: f64,
+    // Durations are counted in bars
+    pub max_duration: usize,
+    pub recovery: Option<usize>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct EquityCurve {
+    initial_equity: f64,
+    points: Vec<EquityPoint>,
+}
+
+impl EquityCurve {
+    pub fn new(initial_equity: f64) -> Self {
+        Self {
+            initial_equity,
+            points: vec![],
+        }
+    }
+
+    pub fn push(&mut self, date: DateTime<Local>, balance: f64, equity: f64) {
+        self.points.push(EquityPoint {
+            date: to_dbtime(date),
+            balance,
+            equity,
+        });
+    }
+
+    pub fn points(&self) -> &Vec<EquityPoint> {
+        &self.points
+    }
+
+    pub fn initial_equity(&self) -> f64 {
+        self.initial_equity
+    }
+
+    pub fn len(&self) -> usize {
+        self.points.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.points.is_empty()
+    }
+
+    pub fn final_equity(&self) -> f64 {
+        self.points
+            .last()
+            .map(|point| point.equity)
+            .unwrap_or(self.initial_equity)
+    }
+
+    // Deepest peak to trough fall, the longest time under water and
+    // the bars the deepest fall took to get back to its peak
+    pub fn drawdown(&self) -> Drawdown {
+        let mut peak = self.initial_equity;
+        let mut peak_index = 0;
+        let mut max_drawdown = 0.;
+        let mut max_drawdown_per = 0.;
+        let mut max_duration = 0;
+        let mut trough_index = None;
+        let mut recovery = None;
+
+        for (index, point) in self.points.iter().enumerate() {
+            let bar = index + 1;
+
+            if point.equity >= peak {
+                if let (Some(trough), None) = (trough_index, recovery) {
+                    recovery = Some(bar - trough);
+                }
+                peak = point.equity;
+                peak_index = bar;
+                continue;
+            }
+
+            let drawdown = peak - point.equity;
+            max_duration = max_duration.max(bar - peak_index);
+
+            if drawdown > max_drawdown {
+                max_drawdown = drawdown;
+                max_drawdown_per = match peak > 0. {
+                    true => drawdown / peak * 100.,
+                    false => 0.,
+                };
+                trough_index = Some(bar);
+                recovery = None;
+            }
+        }
+
+        Drawdown {
+            max_drawdown,
+            max_drawdown_per,
+            max_duration,
+            recovery,
+        }
+    }
+
+    // Equity change per bar
+    pub fn returns(&self) -> Vec<f64> {
+        let mut previous = self.initial_equity;
+
+        self.points
+            .iter()
+            .map(|point| {
+                let change = match previous > 0. {
+                    true => point.equity / previous - 1.,
+                    false => 0.,
+                };
+                previous = point.equity;
+                change
+            })
+            .collect()
+    }
+
+    // Bar frequency used to annualize ratios
+    pub fn periods_per_year(&self) -> f64 {
+        match (self.points.first(), self.points.last()) {
+            (Some(first), Some(last)) if self.points.len() > 1 => {
+                let years = (from_dbtime(&last.date) - from_dbtime(&first.date)).num_seconds()
+                    as f64
+                    / (365.25 * 24. * 3600.);
+
+                match years > 0. {
+                    true => (self.points.len() - 1) as f64 / years,
+                    false => 0.,
+                }
+            }
+            _ => 0.,
+        }
+    }
+
+    // Largest rise from a previous low, as a percentage of that low
+    pub fn max_runup(&self) -> f64 {
+        let mut trough = self.initial_equity;
+        let mut max_runup = 0.;
+
+        for point in &self.points {
+            trough = trough.min(point.equity);
+
+            if trough > 0. {
+                max_runup = f64::max(max_runup, (point.equity - trough) / trough * 100.);
+            }
+        }
+
+        max_runup
+    }
+}
+
+// Replays backtest trades bar by bar on the account, marking open positions
+// to the close price. Entries the account can't afford are left out
+pub fn equity_curve(
+    instrument: &Instrument,
+    trades_in: &[TradeIn],
+    trades_out: &[TradeOut],
+    fx_rates: &FxRates,
+    account: &mut Account,
+) -> Result<EquityCurve> {
+    let mut curve = EquityCurve::new(account.equity());
+    let symbol = instrument.symbol();
+
+    let mut opened_at: HashMap<usize, Vec<&TradeIn>> = HashMap::new();
+    for trade in trades_in
+        .iter()
+        .filter(|trade| trade.status != TradeStatus::Rejected)
+    {
+        opened_at.entry(trade.index_in).or_default().push(trade);
+    }
+
+    let mut closed_at: HashMap<usize, Vec<&TradeOut>> = HashMap::new();
+    for trade in trades_out {
+        closed_at.entry(trade.index_out).or_default().push(trade);
+    }
+
+    let mut open: Vec<&TradeIn> = vec![];
+
+    for (index, candle) in instrument.data().iter().enumerate() {
+        for trade_in in opened_at.remove(&index).unwrap_or_default() {
+            match account.open(symbol, trade_in, fx_rates) {
+                Ok(()) => open.push(trade_in),
+                Err(err) => log::warn!("Trade {} left out of the replay: {}", trade_in.id, err),
+            }
+        }
+
+        for trade_out in closed_at.remove(&index).unwrap_or_default() {
+            if account.is_open(trade_out.id) {
+                account.close(trade_out);
+                open.retain(|trade_in| trade_in.id != trade_out.id);
+            }
+        }
+
+        let open_profit = open
+            .iter()
+            .map(|trade| {
+                fx_rates.trade_profit(
+                    trade.size,
+                    trade.price_in,
+                    candle.close(),
+                    &trade.trade_type,
+                    symbol,
+                    candle.date(),
+                )
+            })
+            .sum::<Result<f64>>()?;
+
+        account.mark_to_market(open_profit);
+        curve.push(candle.date(), account.balance(), account.equity());
+    }
+
+    Ok(curve)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helpers::date::{Duration, TimeZone};
+
+    fn curve(equities: &[f64]) -> EquityCurve {
+        let start = Local.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        let mut curve = EquityCurve::new(100.);
+
+        for (index, equity) in equities.iter().enumerate() {
+            curve.push(start + Duration::days(index as i64), *equity, *equity);
+        }
+
+        curve
+    }
+
+    #[test]
+    fn measures_drawdown_and_recovery() {
+        let curve = curve(&[110., 99., 88., 99., 121., 110., 121., 132.]);
+
+        // 110 down to 88, three bars under water and back above the peak two bars
+        // after the trough. The later 121 to 110 dip is shallower
+        assert_eq!(
+            curve.drawdown(),
+            Drawdown {
+                max_drawdown: 22.,
+                max_drawdown_per: 20.,
+                max_duration: 3,
+                recovery: Some(2),
+            }
+        );
+        assert_eq!(curve.final_equity(), 132.);
+        assert!((curve.max_runup() - 50.).abs() < 1e-9);
+    }
+
+    #[test]
+    fn leaves_unrecovered_drawdowns_open() {
+        let drawdown = curve(&[90., 80., 95.]).drawdown();
+
+        assert_eq!(drawdown.max_drawdown, 20.);
+        assert_eq!(drawdown.max_drawdown_per, 20.);
+        assert_eq!(drawdown.max_duration, 3);
+        assert_eq!(drawdown.recovery, None);
+
+        let flat = EquityCurve::new(100.).drawdown();
+        assert_eq!(flat.max_drawdown, 0.);
+        assert_eq!(flat.max_duration, 0);
+        assert_eq!(flat.recovery, None);
+    }
+
+    #[test]
+    fn computes_returns_per_bar() {
+        let curve = curve(&[110., 99., 99.]);
+        let returns = curve.returns();
+
+        assert_eq!(returns.len(), 3);
+        assert!((returns[0] - 0.1).abs() < 1e-9);
+        assert!((returns[1] + 0.1).abs() < 1e-9);
+        assert_eq!(returns[2], 0.);
+
+        // Daily bars
+        assert!((curve.periods_per_year() - 365.25).abs() < 1e-9);
+        assert_eq!(EquityCurve::new(100.).periods_per_year(), 0.);
+    }
+}
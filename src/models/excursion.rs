@@ -0,0 +1,226 @@
+use super::order::{initial_risk, Order};
+use super::trade::{TradeOut, TradeType};
+
+use crate::helpers::calc::{calculate_percentile, get_max_price, get_min_price, number_pips};
+use crate::scanner::instrument::Instrument;
+
+use serde::{Deserialize, Serialize};
+
+const HISTOGRAM_BINS: usize = 10;
+// Share of winners a stop has to leave untouched
+const STOP_PERCENTILE: f64 = 0.9;
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct TradeExcursion {
+    pub id: usize,
+    pub trade_type: TradeType,
+    pub mae: f64,
+    pub mfe: f64,
+    pub mae_pips: f64,
+    pub mfe_pips: f64,
+    pub mae_r: Option<f64>,
+    pub mfe_r: Option<f64>,
+    pub profit: f64,
+    pub profit_pips: f64,
+}
+
+impl TradeExcursion {
+    pub fn is_winner(&self) -> bool {
+        self.profit > 0.
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct HistogramBin {
+    pub from: f64,
+    pub to: f64,
+    pub count: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Distribution {
+    pub min: f64,
+    pub max: f64,
+    pub mean: f64,
+    pub p25: f64,
+    pub p50: f64,
+    pub p75: f64,
+    pub p90: f64,
+    pub histogram: Vec<HistogramBin>,
+}
+
+impl Distribution {
+    pub fn new(values: &[f64]) -> Self {
+        if values.is_empty() {
+            return Self {
+                min: 0.,
+                max: 0.,
+                mean: 0.,
+                p25: 0.,
+                p50: 0.,
+                p75: 0.,
+                p90: 0.,
+                histogram: vec![],
+            };
+        }
+
+        let min = values.iter().cloned().fold(f64::INFINITY, f64::min);
+        let max = values.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+
+        Self {
+            min,
+            max,
+            mean: values.iter().sum::<f64>() / values.len() as f64,
+            p25: calculate_percentile(values, 0.25),
+            p50: calculate_percentile(values, 0.5),
+            p75: calculate_percentile(values, 0.75),
+            p90: calculate_percentile(values, 0.9),
+            histogram: histogram(values, min, max),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ExcursionReport {
+    pub trades: Vec<TradeExcursion>,
+    pub mae_pips: Distribution,
+    pub mfe_pips: Distribution,
+    pub winners_mae_pips: Distribution,
+    pub losers_mfe_pips: Distribution,
+    pub mae_r: Distribution,
+    pub mfe_r: Distribution,
+    pub optimal_stop_pips: f64,
+    pub optimal_target_pips: f64,
+}
+
+impl ExcursionReport {
+    pub fn new(trades: Vec<TradeExcursion>) -> Self {
+        let collect = |filter: fn(&TradeExcursion) -> bool, value: fn(&TradeExcursion) -> f64| {
+            trades
+                .iter()
+                .filter(|trade| filter(trade))
+                .map(value)
+                .collect::<Vec<f64>>()
+        };
+
+        let mae_pips = collect(|_| true, |trade| trade.mae_pips);
+        let mfe_pips = collect(|_| true, |trade| trade.mfe_pips);
+        let winners_mae_pips = collect(|trade| trade.is_winner(), |trade| trade.mae_pips);
+        let losers_mfe_pips = collect(|trade| !trade.is_winner(), |trade| trade.mfe_pips);
+        let mae_r: Vec<f64> = trades.iter().filter_map(|trade| trade.mae_r).collect();
+        let mfe_r: Vec<f64> = trades.iter().filter_map(|trade| trade.mfe_r).collect();
+
+        // A stop beyond most of the winners adverse moves, the target that
+        // captures the most pips when only the trades reaching it get paid
+        let optimal_stop_pips = calculate_percentile(&winners_mae_pips, STOP_PERCENTILE);
+        let optimal_target_pips = mfe_pips
+            .iter()
+            .map(|target| {
+                let hits = mfe_pips.iter().filter(|mfe| *mfe >= target).count();
+                (*target, target * hits as f64)
+            })
+            .max_by(|a, b| a.1.partial_cmp(&b.1).unwrap())
+            .map(|(target, _)| target)
+            .unwrap_or(0.);
+
+        Self {
+            mae_pips: Distribution::new(&mae_pips),
+            mfe_pips: Distribution::new(&mfe_pips),
+            winners_mae_pips: Distribution::new(&winners_mae_pips),
+            losers_mfe_pips: Distribution::new(&losers_mfe_pips),
+            mae_r: Distribution::new(&mae_r),
+            mfe_r: Distribution::new(&mfe_r),
+            optimal_stop_pips,
+            optimal_target_pips,
+            trades,
+        }
+    }
+}
+
+// Worst and best price reached between entry and exit. The initial stop
+// of the trade, when present, gives the risk used for R units
+pub fn trade_excursion(
+    instrument: &Instrument,
+    trade_out: &TradeOut,
+    orders: &[Order],
+) -> Option<TradeExcursion> {
+    let data = instrument.data();
+
+    if trade_out.index_in > trade_out.index_out || trade_out.index_out >= data.len() {
+        return None;
+    }
+
+    let pip_size = number_pips(instrument.symbol());
+    let price_in = trade_out.price_in;
+    let low = get_min_price(data, trade_out.index_in, trade_out.index_out);
+    let high = get_max_price(data, trade_out.index_in, trade_out.index_out);
+
+    let (mae, mfe, profit_move) = match trade_out.trade_type.is_long() {
+        true => (
+            price_in - low,
+            high - price_in,
+            trade_out.price_out - price_in,
+        ),
+        false => (
+            high - price_in,
+            price_in - low,
+            price_in - trade_out.price_out,
+        ),
+    };
+    let (mae, mfe) = (mae.max(0.), mfe.max(0.));
+
+    let risk = initial_risk(trade_out.id, price_in, orders);
+
+    Some(TradeExcursion {
+        id: trade_out.id,
+        trade_type: trade_out.trade_type.clone(),
+        mae,
+        mfe,
+        mae_pips: mae / pip_size,
+        mfe_pips: mfe / pip_size,
+        mae_r: risk.map(|risk| mae / risk),
+        mfe_r: risk.map(|risk| mfe / risk),
+        profit: trade_out.profit,
+        profit_pips: profit_move / pip_size,
+    })
+}
+
+pub fn excursion_report(
+    instrument: &Instrument,
+    trades_out: &[TradeOut],
+    orders: &[Order],
+) -> ExcursionReport {
+    ExcursionReport::new(
+        trades_out
+            .iter()
+            .filter_map(|trade_out| trade_excursion(instrument, trade_out, orders))
+            .collect(),
+    )
+}
+
+fn histogram(values: &[f64], min: f64, max: f64) -> Vec<HistogramBin> {
+    let width = (max - min) / HISTOGRAM_BINS as f64;
+
+    if width <= 0. {
+        return vec![HistogramBin {
+            from: min,
+            to: max,
+            count: values.len(),
+        }];
+    }
+
+    let mut bins: Vec<HistogramBin> = (0..HISTOGRAM_BINS)
+        .map(|bin| HistogramBin {
+            from: min + width * bin as f64,
+            to: min + width * (bin + 1) as f64,
+            count: 0,
+        })
+        .collect();
+
+    for value in values {
+        let bin = (((value - min) / width) as usize).min(HISTOGRAM_BINS - 1);
+        bins[bin].count += 1;
+    }
+
+    bins
+}
@@ -0,0 +1,234 @@
+use super::symbol_spec::symbol_spec;
+use super::tick::InstrumentTick;
+use super::trade::TradeType;
+
+use crate::config::RsAlgoConfig;
+use crate::error::{Result, RsAlgoError, RsAlgoErrorKind};
+use crate::helpers::calc::{calculate_quote_profit, calculate_trade_profit};
+use crate::helpers::date::{DateTime, Local};
+use crate::scanner::candle::DOHLCV;
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+const MAX_LIVE_RATES: usize = 10000;
+const CROSS_CURRENCY: &str = "USD";
+// Market convention for which currency quotes as the base of a pair
+const BASE_PRIORITY: [&str; 8] = ["EUR", "GBP", "AUD", "NZD", "USD", "CAD", "CHF", "JPY"];
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct FxRates {
+    account_currency: String,
+    contract_size: f64,
+    rates: HashMap<String, Vec<(i64, f64)>>,
+}
+
+impl FxRates {
+    pub fn new(account_currency: &str, contract_size: f64) -> Self {
+        Self {
+            account_currency: account_currency.to_owned(),
+            contract_size,
+            rates: HashMap::new(),
+        }
+    }
+
+    pub fn from_config(config: &RsAlgoConfig) -> Self {
+        Self::new(&config.account_currency, config.contract_size)
+    }
+
+    pub fn account_currency(&self) -> &str {
+        &self.account_currency
+    }
+
+    pub fn add_rate(&mut self, pair: &str, date: DateTime<Local>, rate: f64) {
+        let series = self.rates.entry(pair.to_owned()).or_default();
+        let time = date.timestamp();
+
+        match series.last() {
+            Some((last_time, _)) if *last_time >= time => {
+                let index = series.partition_point(|(t, _)| *t < time);
+                match series.get(index) {
+                    Some((t, _)) if *t == time => series[index] = (time, rate),
+                    _ => series.insert(index, (time, rate)),
+                }
+            }
+            _ => series.push((time, rate)),
+        }
+    }
+
+    // Historical rate table for backtests, one close per candle
+    pub fn add_candles(&mut self, pair: &str, candles: &[DOHLCV]) {
+        for (date, _, _, _, close, _) in candles {
+            self.add_rate(pair, *date, *close);
+        }
+    }
+
+    pub fn update_tick(&mut self, tick: &InstrumentTick) {
+        let symbol = tick.symbol();
+
+        if symbol_currencies(&symbol).is_none() {
+            return;
+        }
+
+        let mid = (tick.ask() + tick.bid()) / 2.;
+        let series = self.rates.entry(symbol).or_default();
+
+        if series
+            .last()
+            .map(|(time, _)| *time < tick.time())
+            .unwrap_or(true)
+        {
+            series.push((tick.time(), mid));
+        }
+
+        if series.len() > MAX_LIVE_RATES {
+            series.drain(..series.len() - MAX_LIVE_RATES);
+        }
+    }
+
+    pub fn rate(&self, from: &str, to: &str, date: DateTime<Local>) -> Option<f64> {
+        self.direct_rate(from, to, date).or_else(|| {
+            match from != CROSS_CURRENCY && to != CROSS_CURRENCY {
+                true => Some(
+                    self.direct_rate(from, CROSS_CURRENCY, date)?
+                        * self.direct_rate(CROSS_CURRENCY, to, date)?,
+                ),
+                false => None,
+            }
+        })
+    }
+
+    fn direct_rate(&self, from: &str, to: &str, date: DateTime<Local>) -> Option<f64> {
+        if from == to {
+            return Some(1.);
+        }
+
+        match self.rate_at(&[from, to].concat(), date) {
+            Some(rate) => Some(rate),
+            None => self
+                .rate_at(&[to, from].concat(), date)
+                .filter(|rate| *rate > 0.)
+                .map(|rate| 1. / rate),
+        }
+    }
+
+    // Last known rate at or before the date. Rates quoted later are never used
+    fn rate_at(&self, pair: &str, date: DateTime<Local>) -> Option<f64> {
+        let series = self.rates.get(pair)?;
+        let index = series.partition_point(|(time, _)| *time <= date.timestamp());
+
+        match index {
+            0 => None,
+            _ => series.get(index - 1).map(|(_, rate)| *rate),
+        }
+    }
+
+    pub fn to_account(&self, amount: f64, currency: &str, date: DateTime<Local>) -> Result<f64> {
+        match self.rate(currency, &self.account_currency, date) {
+            Some(rate) => Ok(amount * rate),
+            None => Err(missing_rate(currency, &self.account_currency, date)),
+        }
+    }
+
+    // Quote currency amounts of a symbol in the account currency. The traded
+    // pair itself converts quote into base
+    pub fn quote_to_account(
+        &self,
+        amount: f64,
+        symbol: &str,
+        price: f64,
+        date: DateTime<Local>,
+    ) -> Result<f64> {
+        let (base, quote) = match symbol_spec(symbol) {
+            Some(spec) => (spec.base_currency.clone(), spec.quote_currency.clone()),
+            None => match symbol_currencies(symbol) {
+                Some(currencies) => currencies,
+                None => return Ok(amount),
+            },
+        };
+
+        match (
+            self.rate(&quote, &self.account_currency, date),
+            base == self.account_currency && base != quote && price > 0.,
+        ) {
+            (Some(rate), _) => Ok(amount * rate),
+            (None, true) => Ok(amount / price),
+            (None, false) => self.to_account(amount, &quote, date),
+        }
+    }
+
+    // Profit in the quote currency converted to the account currency.
+    // Unknown non forex symbols keep the pip factor profit
+    pub fn trade_profit(
+        &self,
+        size: f64,
+        price_in: f64,
+        price_out: f64,
+        trade_type: &TradeType,
+        symbol: &str,
+        date: DateTime<Local>,
+    ) -> Result<f64> {
+        let contract_size = match symbol_spec(symbol) {
+            Some(spec) => spec.contract_size,
+            None => match symbol_currencies(symbol) {
+                Some(_) => self.contract_size,
+                None => {
+                    return Ok(calculate_trade_profit(
+                        size, price_in, price_out, 1., trade_type, symbol,
+                    ))
+                }
+            },
+        };
+
+        let profit = calculate_quote_profit(size, price_in, price_out, contract_size, trade_type);
+        self.quote_to_account(profit, symbol, price_out, date)
+    }
+
+    // Pair to load in backtests so the symbol's quote currency converts to the account one
+    pub fn conversion_pair(&self, symbol: &str) -> Option<String> {
+        let (base, quote) = match symbol_spec(symbol) {
+            Some(spec) => (spec.base_currency.clone(), spec.quote_currency.clone()),
+            None => symbol_currencies(symbol)?,
+        };
+
+        let account = self.account_currency.as_str();
+
+        if quote == account || (base == account && base != quote) {
+            return None;
+        }
+
+        let priority = |currency: &str| {
+            BASE_PRIORITY
+                .iter()
+                .position(|c| *c == currency)
+                .unwrap_or(BASE_PRIORITY.len())
+        };
+
+        match priority(&quote) < priority(account) {
+            true => Some([quote.as_str(), account].concat()),
+            false => Some([account, quote.as_str()].concat()),
+        }
+    }
+}
+
+fn missing_rate(from: &str, to: &str, date: DateTime<Local>) -> RsAlgoError {
+    log::error!("No {}{} rate at {}", from, to, date);
+
+    RsAlgoError {
+        err: RsAlgoErrorKind::MissingRate(format!("{}{} at {}", from, to, date)),
+    }
+}
+
+// Base and quote of currency pairs, from the registry or the six letter symbol
+pub fn symbol_currencies(symbol: &str) -> Option<(String, String)> {
+    match symbol_spec(symbol) {
+        Some(spec) => match spec.is_currency_pair() {
+            true => Some((spec.base_currency.clone(), spec.quote_currency.clone())),
+            false => None,
+        },
+        None => match symbol.len() == 6 && symbol.chars().all(|c| c.is_ascii_uppercase()) {
+            true => Some((symbol[..3].to_owned(), symbol[3..].to_owned())),
+            false => None,
+        },
+    }
+}
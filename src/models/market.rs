@@ -1,5 +1,4 @@
-use std::env;
-
+use crate::config::RsAlgoConfig;
 use crate::helpers::date;
 use crate::helpers::date::{DateTime, Duration, Local, Timelike};
 use serde::{Deserialize, Serialize};
@@ -30,7 +29,7 @@ pub struct MarketSessionHours {
     pub to: u32,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct MarketHour {
     pub day: u32,
     pub from: u32,
@@ -123,7 +122,10 @@ impl MarketHours {
     }
 
     pub fn is_week_close(&self) -> bool {
-        let current_date = Local::now();
+        self.is_week_close_at(Local::now())
+    }
+
+    pub fn is_week_close_at(&self, current_date: DateTime<Local>) -> bool {
         let current_hours = current_date.hour();
         let week_day = date::get_week_day(current_date);
         let mut open = false;
@@ -156,7 +158,7 @@ impl MarketHours {
         None
     }
 
-    pub fn wait_until(&self) -> DateTime<Local> {
+    pub fn wait_until(&self, config: &RsAlgoConfig) -> DateTime<Local> {
         let current_date = Local::now();
         let weekday = date::get_week_day(current_date);
 
@@ -165,10 +167,7 @@ impl MarketHours {
         let opening_hours = sunday.from;
         let diff_days = 7 - weekday as i64;
         let mut opening_date = current_date + Duration::days(diff_days);
-        let mut secs = env::var("DISCONNECTED_RETRY")
-            .unwrap()
-            .parse::<u64>()
-            .unwrap();
+        let mut secs = config.disconnected_retry;
 
         let ten_random_secs = (std::time::SystemTime::now()
             .duration_since(std::time::UNIX_EPOCH)
@@ -1,16 +1,24 @@
+pub mod account;
 pub mod api;
 pub mod backtest_instrument;
 pub mod backtest_strategy;
 pub mod bot;
+pub mod costs;
 pub mod environment;
+pub mod equity;
+pub mod excursion;
+pub mod fx;
 pub mod indicator;
 pub mod market;
 pub mod mode;
 pub mod order;
+pub mod risk;
+pub mod sizing;
 pub mod status;
 pub mod stop_loss;
 pub mod strategy;
 pub mod swap;
+pub mod symbol_spec;
 pub mod tick;
 pub mod time_frame;
 pub mod trade;
@@ -1,4 +1,4 @@
-#[derive(PartialEq, Debug)]
+#[derive(Clone, PartialEq, Debug)]
 pub enum ExecutionMode {
     Scanner,
     ScannerBackTest,
@@ -30,14 +30,21 @@ impl ExecutionMode {
     }
 }
 
-pub fn from_str(execution_mode: &str) -> ExecutionMode {
+pub fn try_from_str(execution_mode: &str) -> Option<ExecutionMode> {
     match execution_mode.to_lowercase().as_str() {
-        "scanner" => ExecutionMode::Scanner,
-        "backtest" => ExecutionMode::BackTest,
-        "scannerbacktest" => ExecutionMode::ScannerBackTest,
-        "bot" => ExecutionMode::Bot,
-        "botbacktest" => ExecutionMode::BotBackTest,
-        _ => {
+        "scanner" => Some(ExecutionMode::Scanner),
+        "backtest" => Some(ExecutionMode::BackTest),
+        "scannerbacktest" => Some(ExecutionMode::ScannerBackTest),
+        "bot" => Some(ExecutionMode::Bot),
+        "botbacktest" => Some(ExecutionMode::BotBackTest),
+        _ => None,
+    }
+}
+
+pub fn from_str(execution_mode: &str) -> ExecutionMode {
+    match try_from_str(execution_mode) {
+        Some(mode) => mode,
+        None => {
             log::error!("No {} EXECUTION_MODE found!", &execution_mode);
             panic!();
         }
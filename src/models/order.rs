@@ -1,12 +1,15 @@
-use std::env;
-
-use super::environment;
-use super::mode::{self, ExecutionMode};
+use super::account::Account;
+use super::fx::FxRates;
+use super::market::MarketHours;
+use super::mode::ExecutionMode;
 use super::tick::InstrumentTick;
-use super::trade::{Trade, TradeIn, TradeType};
+use super::trade::{Trade, TradeAmend, TradeIn, TradeOut, TradeType};
 
+use crate::config::RsAlgoConfig;
+use crate::error::Result;
 use crate::helpers::{calc, uuid};
 use crate::helpers::{date, date::*};
+use crate::models::sizing::{self, PositionSizer};
 use crate::models::stop_loss::*;
 use crate::models::trade::Position;
 use crate::scanner::candle::Candle;
@@ -92,6 +95,14 @@ impl OrderType {
             _ => false,
         }
     }
+
+    pub fn stop_loss_type(&self) -> Option<&StopLossType> {
+        match self {
+            OrderType::StopLossLong(stop_loss_type, _)
+            | OrderType::StopLossShort(stop_loss_type, _) => Some(stop_loss_type),
+            _ => None,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -203,6 +214,39 @@ impl Order {
         self.set_updated_at(date);
     }
 
+    pub fn resize(&mut self, size: f64) {
+        self.order_type = match &self.order_type {
+            OrderType::BuyOrderLong(_, target) => OrderType::BuyOrderLong(size, *target),
+            OrderType::BuyOrderShort(_, target) => OrderType::BuyOrderShort(size, *target),
+            OrderType::SellOrderLong(_, target) => OrderType::SellOrderLong(size, *target),
+            OrderType::SellOrderShort(_, target) => OrderType::SellOrderShort(size, *target),
+            OrderType::TakeProfitLong(_, target) => OrderType::TakeProfitLong(size, *target),
+            OrderType::TakeProfitShort(_, target) => OrderType::TakeProfitShort(size, *target),
+            order_type => order_type.clone(),
+        };
+        self.size = size;
+    }
+
+    pub fn amend_target(&mut self, target_price: f64, date: DbDateTime) {
+        self.order_type = match &self.order_type {
+            OrderType::BuyOrderLong(size, _) => OrderType::BuyOrderLong(*size, target_price),
+            OrderType::BuyOrderShort(size, _) => OrderType::BuyOrderShort(*size, target_price),
+            OrderType::SellOrderLong(size, _) => OrderType::SellOrderLong(*size, target_price),
+            OrderType::SellOrderShort(size, _) => OrderType::SellOrderShort(*size, target_price),
+            OrderType::TakeProfitLong(size, _) => OrderType::TakeProfitLong(*size, target_price),
+            OrderType::TakeProfitShort(size, _) => OrderType::TakeProfitShort(*size, target_price),
+            // Trailing and ATR stops keep moving from the amended level
+            OrderType::StopLossLong(stop_loss_type, price_in) => {
+                OrderType::StopLossLong(amend_stop_type(stop_loss_type, target_price), *price_in)
+            }
+            OrderType::StopLossShort(stop_loss_type, price_in) => {
+                OrderType::StopLossShort(amend_stop_type(stop_loss_type, target_price), *price_in)
+            }
+        };
+        self.target_price = target_price;
+        self.set_updated_at(date);
+    }
+
     pub fn is_long(&self) -> bool {
         match self.order_type {
             OrderType::BuyOrderLong(_, _)
@@ -229,6 +273,15 @@ impl Order {
         }
     }
 
+    // Stop level the trade was opened with. Trailing and break even stops
+    // only move the target price
+    pub fn initial_stop(&self) -> Option<f64> {
+        match self.is_stop() {
+            true => Some(self.meta.as_ref().map_or(self.target_price, |meta| meta.sl)),
+            false => None,
+        }
+    }
+
     pub fn is_entry(&self) -> bool {
         self.order_type.is_entry()
     }
@@ -274,8 +327,12 @@ pub fn prepare_orders(
     trade_type: &TradeType,
     order_types: &Vec<OrderType>,
     tick: &InstrumentTick,
-) -> Vec<Order> {
-    let execution_mode = mode::from_str(&env::var("EXECUTION_MODE").unwrap());
+    account: &Account,
+    trades_out: &[TradeOut],
+    fx_rates: &FxRates,
+    config: &RsAlgoConfig,
+) -> Result<Vec<Order>> {
+    let execution_mode = config.execution_mode.clone();
     let mut buy_order_target = 0.;
     let mut sell_order_target = 0.;
     let mut stop_order_target = 0.;
@@ -295,10 +352,21 @@ pub fn prepare_orders(
     };
 
     let _trade_id = uuid::generate_ts_id(current_candle.date());
-    let order_with_spread = env::var("ORDER_WITH_SPREAD")
-        .unwrap()
-        .parse::<bool>()
-        .unwrap();
+    let order_with_spread = config.order_with_spread;
+
+    let sized_orders = match PositionSizer::from_config(config).is_fixed() {
+        true => None,
+        false => Some(sizing::trade_size(
+            index,
+            instrument,
+            order_types,
+            tick,
+            account,
+            trades_out,
+            fx_rates,
+            config,
+        )?),
+    };
 
     for order_type in order_types {
         match order_type {
@@ -310,8 +378,14 @@ pub fn prepare_orders(
             | OrderType::TakeProfitShort(order_size, target_price) => {
                 let direction = order_type.get_direction();
                 if validate_target_price(order_type, direction, &current_price, target_price) {
-                    let order =
-                        create_order(index, instrument, order_type, target_price, order_size);
+                    let order = create_order(
+                        index,
+                        instrument,
+                        order_type,
+                        target_price,
+                        order_size,
+                        config,
+                    );
 
                     match order_type.is_entry() {
                         true => {
@@ -370,14 +444,18 @@ pub fn prepare_orders(
                 let direction = order_type.get_direction();
 
                 if is_valid_buy_sell_order {
-                    let stop_loss = create_stop_loss_order(
+                    let stop_loss = match create_stop_loss_order(
                         index,
                         *buy_price,
                         instrument,
                         direction,
                         stop_loss_type,
                         tick,
-                    );
+                        config,
+                    ) {
+                        Some(stop_loss) => stop_loss,
+                        None => continue,
+                    };
 
                     stop_order_target = stop_loss.target_price;
                     stop_loss_direction = direction.clone();
@@ -423,7 +501,36 @@ pub fn prepare_orders(
         }
     }
 
+    if let Some(size) = sized_orders {
+        // Nothing tradable within the risk and margin limits, the entry is skipped
+        if size <= 0. {
+            log::warn!("{} entry skipped, sized to zero", instrument.symbol());
+            return Ok(vec![]);
+        }
+
+        for order in orders.iter_mut() {
+            order.resize(size);
+        }
+    }
+
+    Ok(orders)
+}
+
+fn amend_stop_type(stop_loss_type: &StopLossType, target_price: f64) -> StopLossType {
+    match stop_loss_type {
+        StopLossType::Price(_) => StopLossType::Price(target_price),
+        stop_loss_type => stop_loss_type.clone(),
+    }
+}
+
+// Distance between the entry and the initial stop of a trade, the 1R unit
+pub fn initial_risk(trade_id: usize, price_in: f64, orders: &[Order]) -> Option<f64> {
     orders
+        .iter()
+        .filter(|order| order.trade_id == trade_id)
+        .filter_map(|order| order.initial_stop())
+        .map(|stop| (price_in - stop).abs())
+        .find(|risk| *risk > 0.)
 }
 
 pub fn validate_target_price(
@@ -468,8 +575,9 @@ pub fn create_order(
     order_type: &OrderType,
     target_price: &f64,
     order_size: &f64,
+    config: &RsAlgoConfig,
 ) -> Order {
-    let execution_mode = mode::from_str(&env::var("EXECUTION_MODE").unwrap());
+    let execution_mode = config.execution_mode.clone();
 
     let current_candle = match execution_mode.is_back_test() {
         true => instrument.data().get(index + 1).unwrap(),
@@ -483,10 +591,7 @@ pub fn create_order(
 
     let current_date = &current_candle.date();
     let time_frame = instrument.time_frame();
-    let valid_until_bars = &env::var("ORDER_VALID_UNTIL_BARS")
-        .unwrap()
-        .parse::<i64>()
-        .unwrap();
+    let valid_until_bars = &config.order_valid_until_bars;
 
     let valid_until = match instrument.time_frame().is_minutely_time_frame() {
         true => *current_date + date::Duration::minutes(valid_until_bars * time_frame.to_minutes()),
@@ -524,6 +629,7 @@ pub fn resolve_active_orders(
     orders: &Vec<Order>,
     tick: &InstrumentTick,
     use_tick_price: bool,
+    config: &RsAlgoConfig,
 ) -> Position {
     let mut order_position: Position = Position::None;
 
@@ -536,7 +642,7 @@ pub fn resolve_active_orders(
         .collect();
 
     for (_id, order) in filtered_orders.iter().enumerate() {
-        match is_activated_order(index, order, instrument, tick, use_tick_price) {
+        match is_activated_order(index, order, instrument, tick, use_tick_price, config) {
             true => {
                 match order.order_type {
                     OrderType::BuyOrderLong(_, _) | OrderType::BuyOrderShort(_, _) => {
@@ -557,7 +663,7 @@ pub fn resolve_active_orders(
         }
     }
 
-    let (_, _, pending_stop_losses) = get_num_pending_orders(orders);
+    let (_, _, pending_stop_losses) = get_num_pending_orders(orders, config);
     let has_active_trade = pending_stop_losses > 0;
 
     match has_active_trade {
@@ -566,12 +672,79 @@ pub fn resolve_active_orders(
     }
 }
 
+// Order engine step for every closed candle or tick. Activation runs on the
+// stops of the previous bar, then the stop rules and trailing stops of the
+// open trades move them. Live bots send the returned amends to the broker
+pub fn resolve_orders(
+    index: usize,
+    instrument: &Instrument,
+    open_trades: &[TradeIn],
+    orders: &mut Vec<Order>,
+    market_hours: &MarketHours,
+    tick: &InstrumentTick,
+    use_tick_price: bool,
+    config: &RsAlgoConfig,
+) -> (Position, Vec<TradeAmend>) {
+    let position = resolve_active_orders(index, instrument, orders, tick, use_tick_price, config);
+
+    if !matches!(position, Position::None) {
+        return (position, vec![]);
+    }
+
+    let mut position = Position::None;
+    let mut amended = vec![];
+
+    for trade_in in open_trades
+        .iter()
+        .filter(|trade_in| !trade_in.stop_rules.is_empty())
+    {
+        let (rule_position, rule_orders) = resolve_stop_rules(
+            index,
+            trade_in,
+            orders,
+            instrument,
+            market_hours,
+            tick,
+            use_tick_price,
+            config,
+        );
+
+        if !matches!(rule_position, Position::None) {
+            position = rule_position;
+        }
+        amended.extend(rule_orders);
+    }
+
+    if matches!(position, Position::None) {
+        amended.extend(update_trailing_stops(
+            index,
+            orders,
+            instrument,
+            tick,
+            use_tick_price,
+            config,
+        ));
+    }
+
+    let amends = match config.execution_mode.is_bot() {
+        true => open_trades
+            .iter()
+            .filter(|trade_in| amended.iter().any(|order| order.trade_id == trade_in.id))
+            .map(|trade_in| trade_amend(trade_in, orders))
+            .collect(),
+        false => vec![],
+    };
+
+    (position, amends)
+}
+
 fn calculate_order_price_origin(
     execution_mode: &ExecutionMode,
     candle: &Candle,
     activation_source: &str,
+    config: &RsAlgoConfig,
 ) -> (f64, f64) {
-    let order_engine = &env::var("ORDER_ENGINE").unwrap();
+    let order_engine = &config.order_engine;
 
     match execution_mode.is_bot() || execution_mode.is_bot_test() {
         true => (candle.close(), candle.close()),
@@ -592,10 +765,11 @@ fn is_activated_order(
     instrument: &Instrument,
     tick: &InstrumentTick,
     use_tick_price: bool,
+    config: &RsAlgoConfig,
 ) -> bool {
-    let activation_source = &env::var("ORDER_ACTIVATION_SOURCE").unwrap();
-    let execution_mode = mode::from_str(&env::var("EXECUTION_MODE").unwrap());
-    let env = environment::from_str(&env::var("ENV").unwrap());
+    let activation_source = &config.order_activation_source;
+    let execution_mode = config.execution_mode.clone();
+    let env = config.env.clone();
 
     let candle = match execution_mode.is_back_test() {
         true => instrument.data().get(index).unwrap(),
@@ -607,6 +781,15 @@ fn is_activated_order(
     let is_bot = execution_mode.is_bot();
     let is_bot_test = execution_mode.is_bot_test();
     let is_stop = order.order_type.is_stop();
+    let is_native = order.order_type.is_entry()
+        || matches!(
+            order.order_type,
+            OrderType::SellOrderLong(_, _) | OrderType::SellOrderShort(_, _)
+        );
+    // Stop losses always live at the broker, entries and targets too with the broker engine
+    let is_broker_order = env.is_prod()
+        && execution_mode.is_bot()
+        && (is_stop || (config.order_engine == "broker" && is_native));
     let is_next_bar = candle_ts > order.id;
     let direction = order.order_type.get_direction();
     let target_price = order.target_price;
@@ -617,42 +800,47 @@ fn is_activated_order(
 
     let spread = tick.spread();
 
-    let (price_over, price_below) =
-        match execution_mode.is_back_test() || execution_mode.is_bot_test() {
-            //ONLY BACKTESTING & BOT BACKTESTING, NO TICK PRICING
-            true => {
-                let (price_over, price_below) =
-                    calculate_order_price_origin(&execution_mode, candle, activation_source);
+    let (price_over, price_below) = match execution_mode.is_back_test()
+        || execution_mode.is_bot_test()
+    {
+        //ONLY BACKTESTING & BOT BACKTESTING, NO TICK PRICING
+        true => {
+            let (price_over, price_below) =
+                calculate_order_price_origin(&execution_mode, candle, activation_source, config);
 
+            if order.is_stop() && order.is_short() {
+                (price_over + spread, price_below + spread)
+            } else if order.is_entry() == order.is_long() {
+                (price_over, price_below)
+                //(price_over + spread, price_below + spread)
+            } else {
+                (price_over, price_below)
+            }
+        }
+        //PRODUCTION BOT
+        false => {
+            let (price_over, price_below) = if use_tick_price {
                 if order.is_stop() && order.is_short() {
-                    (price_over + spread, price_below + spread)
+                    (tick.ask(), tick.ask())
                 } else if order.is_entry() == order.is_long() {
-                    (price_over, price_below)
-                    //(price_over + spread, price_below + spread)
+                    (tick.bid(), tick.bid())
                 } else {
-                    (price_over, price_below)
+                    (tick.bid(), tick.bid())
                 }
-            }
-            //PRODUCTION BOT
-            false => {
-                let (price_over, price_below) = if use_tick_price {
-                    if order.is_stop() && order.is_short() {
-                        (tick.ask(), tick.ask())
-                    } else if order.is_entry() == order.is_long() {
-                        (tick.bid(), tick.bid())
-                    } else {
-                        (tick.bid(), tick.bid())
-                    }
-                } else {
-                    let (price_over, price_below) =
-                        calculate_order_price_origin(&execution_mode, candle, activation_source);
-
-                    (price_over, price_below)
-                };
+            } else {
+                let (price_over, price_below) = calculate_order_price_origin(
+                    &execution_mode,
+                    candle,
+                    activation_source,
+                    config,
+                );
 
                 (price_over, price_below)
-            }
-        };
+            };
+
+            (price_over, price_below)
+        }
+    };
 
     // log::info!(
     //     "Source: {} Target: {} Over: {} Below: {} Ask/Bid: {:?}",
@@ -683,7 +871,7 @@ fn is_activated_order(
                 );
             }
 
-            if env.is_prod() && execution_mode.is_bot() && is_stop {
+            if is_broker_order {
                 false
             } else {
                 cross_over
@@ -708,7 +896,7 @@ fn is_activated_order(
                 );
             }
 
-            if env.is_prod() && execution_mode.is_bot() && is_stop {
+            if is_broker_order {
                 false
             } else {
                 cross_below
@@ -717,31 +905,20 @@ fn is_activated_order(
     }
 }
 
-pub fn add_pending(orders: Vec<Order>, new_orders: Vec<Order>) -> Vec<Order> {
-    let max_buy_orders = env::var("MAX_BUY_ORDERS")
-        .unwrap()
-        .parse::<usize>()
-        .unwrap();
-    let max_sell_orders = env::var("MAX_SELL_ORDERS")
-        .unwrap()
-        .parse::<usize>()
-        .unwrap();
-    let max_stop_losses = env::var("MAX_STOP_LOSSES")
-        .unwrap()
-        .parse::<usize>()
-        .unwrap();
-
-    let max_pending_orders = env::var("MAX_PENDING_ORDERS")
-        .unwrap()
-        .parse::<usize>()
-        .unwrap();
-
-    let _overwrite_orders = env::var("ORDERS_OVERWRITE")
-        .unwrap()
-        .parse::<bool>()
-        .unwrap();
-
-    let (buy_orders, sell_orders, stop_losses) = get_num_pending_orders(&orders);
+pub fn add_pending(
+    orders: Vec<Order>,
+    new_orders: Vec<Order>,
+    config: &RsAlgoConfig,
+) -> Vec<Order> {
+    let max_buy_orders = config.max_buy_orders;
+    let max_sell_orders = config.max_sell_orders;
+    let max_stop_losses = config.max_stop_losses;
+
+    let max_pending_orders = config.max_pending_orders;
+
+    let _overwrite_orders = config.orders_overwrite;
+
+    let (buy_orders, sell_orders, stop_losses) = get_num_pending_orders(&orders, config);
     let result: Vec<Order> = new_orders
         .iter()
         .filter(|order| order.status == OrderStatus::Pending)
@@ -767,11 +944,8 @@ pub fn add_pending(orders: Vec<Order>, new_orders: Vec<Order>) -> Vec<Order> {
     }
 }
 
-pub fn get_pending(orders: &Vec<Order>) -> Vec<Order> {
-    let max_pending_orders = env::var("MAX_PENDING_ORDERS")
-        .unwrap()
-        .parse::<usize>()
-        .unwrap();
+pub fn get_pending(orders: &Vec<Order>, config: &RsAlgoConfig) -> Vec<Order> {
+    let max_pending_orders = config.max_pending_orders;
 
     let len = orders.len();
 
@@ -792,11 +966,8 @@ pub fn get_pending(orders: &Vec<Order>) -> Vec<Order> {
     pending_orders
 }
 
-pub fn get_num_pending_orders(orders: &Vec<Order>) -> (usize, usize, usize) {
-    let max_pending_orders = env::var("MAX_PENDING_ORDERS")
-        .unwrap()
-        .parse::<usize>()
-        .unwrap();
+pub fn get_num_pending_orders(orders: &Vec<Order>, config: &RsAlgoConfig) -> (usize, usize, usize) {
+    let max_pending_orders = config.max_pending_orders;
     let mut buy_orders = 0;
     let mut sell_orders = 0;
     let mut stop_losses = 0;
@@ -823,8 +994,9 @@ pub fn cancel_pending_expired_orders(
     index: usize,
     instrument: &Instrument,
     orders: &mut Vec<Order>,
+    config: &RsAlgoConfig,
 ) -> Vec<Order> {
-    let execution_mode = mode::from_str(&env::var("EXECUTION_MODE").unwrap());
+    let execution_mode = config.execution_mode.clone();
     match execution_mode.is_bot_test() || execution_mode.is_back_test() {
         true => {
             let current_date = instrument.data.get(index).unwrap().date();
@@ -867,8 +1039,12 @@ pub fn update_trade_pending_orders(orders: &mut Vec<Order>, trade_in: &TradeIn)
     }
 }
 
-pub fn update_state_pending_orders<T: Trade>(trade: &T, orders: &mut Vec<Order>) {
-    let execution_mode = mode::from_str(&env::var("EXECUTION_MODE").unwrap());
+pub fn update_state_pending_orders<T: Trade>(
+    trade: &T,
+    orders: &mut Vec<Order>,
+    config: &RsAlgoConfig,
+) {
+    let execution_mode = config.execution_mode.clone();
     match execution_mode.is_back_test() {
         true => {
             let mut i = 0;
@@ -923,6 +1099,55 @@ pub fn fulfill_bot_order<T: Trade>(
     fulfill_trade_order(index, trade, order, orders)
 }
 
+pub fn amend_trade_orders(
+    orders: &mut Vec<Order>,
+    amend: &TradeAmend,
+    date: DbDateTime,
+) -> Vec<Order> {
+    let mut amended = vec![];
+
+    for order in orders.iter_mut() {
+        if order.trade_id != amend.trade_in.id || !order.is_pending() {
+            continue;
+        }
+
+        let target_price = match order.order_type.is_stop() {
+            true => amend.stop_loss,
+            false if order.order_type.is_exit() => amend.take_profit,
+            false => None,
+        };
+
+        if let Some(target_price) = target_price {
+            order.amend_target(target_price, date);
+            amended.push(order.clone());
+        }
+    }
+
+    amended
+}
+
+pub fn trade_amend(trade_in: &TradeIn, orders: &[Order]) -> TradeAmend {
+    let pending = orders
+        .iter()
+        .filter(|order| order.trade_id == trade_in.id && order.is_pending());
+
+    let mut amend = TradeAmend {
+        trade_in: trade_in.clone(),
+        stop_loss: None,
+        take_profit: None,
+    };
+
+    for order in pending {
+        match order.order_type.is_stop() {
+            true => amend.stop_loss = Some(order.target_price),
+            false if order.order_type.is_exit() => amend.take_profit = Some(order.target_price),
+            false => (),
+        }
+    }
+
+    amend
+}
+
 pub fn order_exists(orders: &[Order], search_id: usize) -> bool {
     orders.iter().any(|order| order.id == search_id)
 }
@@ -939,3 +1164,46 @@ pub fn update_orders(orders: &mut Vec<Order>, new_orders: &[Order]) -> bool {
 
     updated
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helpers::date::{to_dbtime, Local};
+
+    fn stop_loss(stop_loss_type: StopLossType) -> Order {
+        Order {
+            order_type: OrderType::StopLossLong(stop_loss_type, 1.1),
+            target_price: 1.098,
+            ..Order::default()
+        }
+    }
+
+    #[test]
+    fn amends_stop_targets_keeping_the_type() {
+        let date = to_dbtime(Local::now());
+
+        let mut order = stop_loss(StopLossType::Price(1.098));
+        order.amend_target(1.099, date);
+        assert_eq!(
+            order.order_type,
+            OrderType::StopLossLong(StopLossType::Price(1.099), 1.1)
+        );
+        assert_eq!(order.target_price, 1.099);
+
+        let trailing = StopLossType::Trailing {
+            distance: 0.002,
+            step: 0.0005,
+        };
+        let mut order = stop_loss(trailing.clone());
+        order.amend_target(1.099, date);
+        assert_eq!(order.order_type, OrderType::StopLossLong(trailing, 1.1));
+        assert_eq!(order.target_price, 1.099);
+
+        let mut order = stop_loss(StopLossType::TrailingAtr(2.));
+        order.amend_target(1.099, date);
+        assert_eq!(
+            order.order_type,
+            OrderType::StopLossLong(StopLossType::TrailingAtr(2.), 1.1)
+        );
+    }
+}
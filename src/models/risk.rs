@@ -0,0 +1,342 @@
+use super::bot::BotData;
+use super::fx::{symbol_currencies, FxRates};
+use super::tick::InstrumentTick;
+use super::trade::{trade_exists, TradeIn, TradeStatus};
+
+use crate::config::RsAlgoConfig;
+use crate::error::{Result, RsAlgoError, RsAlgoErrorKind};
+use crate::helpers::date::{from_dbtime, DateTime, Local};
+use crate::scanner::candle::Candle;
+use crate::scanner::instrument::Instrument;
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+const CORRELATION_BARS: usize = 100;
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub enum RiskBreach {
+    MaxOpenPositions(usize),
+    CurrencyExposure(String, f64),
+    DailyLoss(f64),
+    CorrelatedPositions(usize),
+}
+
+impl RiskBreach {
+    pub fn description(&self) -> String {
+        match self {
+            RiskBreach::MaxOpenPositions(positions) => {
+                format!("{} open positions", positions)
+            }
+            RiskBreach::CurrencyExposure(currency, exposure) => {
+                format!("{} exposure of {:.2} lots", currency, exposure)
+            }
+            RiskBreach::DailyLoss(loss) => format!("daily loss of {:.2}%", loss),
+            RiskBreach::CorrelatedPositions(positions) => {
+                format!("{} correlated positions", positions)
+            }
+        }
+    }
+}
+
+// A zero value disables the limit
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct RiskLimits {
+    pub max_open_positions: usize,
+    pub max_currency_exposure: f64,
+    pub max_daily_loss: f64,
+    pub max_correlated_positions: usize,
+    pub correlation_threshold: f64,
+}
+
+impl RiskLimits {
+    pub fn from_config(config: &RsAlgoConfig) -> Self {
+        Self {
+            max_open_positions: config.max_open_positions,
+            max_currency_exposure: config.max_currency_exposure,
+            max_daily_loss: config.max_daily_loss,
+            max_correlated_positions: config.max_correlated_positions,
+            correlation_threshold: config.correlation_threshold,
+        }
+    }
+}
+
+struct OpenPosition<'a> {
+    symbol: &'a str,
+    trade_in: &'a TradeIn,
+    data: &'a [Candle],
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct RiskGuard {
+    limits: RiskLimits,
+    equity: f64,
+    fx_rates: FxRates,
+    breach: Option<RiskBreach>,
+}
+
+impl RiskGuard {
+    pub fn new(limits: RiskLimits, equity: f64, fx_rates: FxRates) -> Self {
+        Self {
+            limits,
+            equity,
+            fx_rates,
+            breach: None,
+        }
+    }
+
+    pub fn from_config(config: &RsAlgoConfig) -> Self {
+        Self::new(
+            RiskLimits::from_config(config),
+            config.equity,
+            FxRates::from_config(config),
+        )
+    }
+
+    pub fn update_tick(&mut self, tick: &InstrumentTick) {
+        self.fx_rates.update_tick(tick);
+    }
+
+    pub fn limits(&self) -> &RiskLimits {
+        &self.limits
+    }
+
+    pub fn breach(&self) -> Option<&RiskBreach> {
+        self.breach.as_ref()
+    }
+
+    pub fn is_halted(&self) -> bool {
+        self.breach.is_some()
+    }
+
+    pub fn reset(&mut self) {
+        log::info!("Risk guard kill switch reset");
+        self.breach = None;
+    }
+
+    // Position limits only reject the entry, the daily loss engages the kill
+    // switch until reset
+    pub fn check_trade_in(
+        &mut self,
+        bots: &[BotData],
+        instrument: &Instrument,
+        trade_in: &TradeIn,
+    ) -> Result<()> {
+        if let Some(breach) = &self.breach {
+            return Err(risk_error(breach));
+        }
+
+        match self.find_breach(bots, instrument, trade_in)? {
+            Some(breach @ RiskBreach::DailyLoss(_)) => {
+                log::error!(
+                    "Kill switch engaged for {} {:?}: {}",
+                    instrument.symbol(),
+                    trade_in.trade_type,
+                    breach.description()
+                );
+                let err = risk_error(&breach);
+                self.breach = Some(breach);
+                Err(err)
+            }
+            Some(breach) => {
+                log::warn!(
+                    "{} {:?} rejected: {}",
+                    instrument.symbol(),
+                    trade_in.trade_type,
+                    breach.description()
+                );
+                Err(risk_error(&breach))
+            }
+            None => Ok(()),
+        }
+    }
+
+    fn find_breach(
+        &self,
+        bots: &[BotData],
+        instrument: &Instrument,
+        trade_in: &TradeIn,
+    ) -> Result<Option<RiskBreach>> {
+        let limits = &self.limits;
+        let positions = open_positions(bots);
+
+        if limits.max_open_positions > 0 && positions.len() >= limits.max_open_positions {
+            return Ok(Some(RiskBreach::MaxOpenPositions(positions.len())));
+        }
+
+        if limits.max_currency_exposure > 0. {
+            let mut exposures = currency_exposures(
+                positions
+                    .iter()
+                    .map(|position| (position.symbol, position.trade_in)),
+            );
+
+            for (currency, exposure) in currency_legs(instrument.symbol(), trade_in) {
+                let total = exposures.entry(currency.clone()).or_insert(0.);
+                *total += exposure;
+
+                if total.abs() > limits.max_currency_exposure {
+                    return Ok(Some(RiskBreach::CurrencyExposure(currency, *total)));
+                }
+            }
+        }
+
+        if limits.max_daily_loss > 0. && self.equity > 0. {
+            let loss = -self.daily_profit(bots, trade_in)? / self.equity * 100.;
+
+            if loss >= limits.max_daily_loss {
+                return Ok(Some(RiskBreach::DailyLoss(loss)));
+            }
+        }
+
+        if limits.max_correlated_positions > 0 {
+            let correlated = positions
+                .iter()
+                .filter(|position| {
+                    let correlation = match position.symbol == instrument.symbol() {
+                        true => 1.,
+                        false => returns_correlation(position.data, instrument.data()),
+                    };
+                    let same_direction =
+                        position.trade_in.trade_type.is_long() == trade_in.trade_type.is_long();
+                    let correlation = match same_direction {
+                        true => correlation,
+                        false => -correlation,
+                    };
+                    correlation >= limits.correlation_threshold
+                })
+                .count();
+
+            if correlated >= limits.max_correlated_positions {
+                return Ok(Some(RiskBreach::CorrelatedPositions(correlated)));
+            }
+        }
+
+        Ok(None)
+    }
+
+    // Realized profit of the trade day plus the open profit at the last close
+    fn daily_profit(&self, bots: &[BotData], trade_in: &TradeIn) -> Result<f64> {
+        let day = from_dbtime(&trade_in.date_in).date_naive();
+
+        let realized: f64 = bots
+            .iter()
+            .flat_map(|bot| bot.trades_out().iter())
+            .filter(|trade_out| from_dbtime(&trade_out.date_out).date_naive() == day)
+            .map(|trade_out| trade_out.profit)
+            .sum();
+
+        let open: f64 = open_positions(bots)
+            .iter()
+            .filter_map(|position| {
+                position.data.last().map(|candle| {
+                    self.fx_rates.trade_profit(
+                        position.trade_in.size,
+                        position.trade_in.price_in,
+                        candle.close(),
+                        &position.trade_in.trade_type,
+                        position.symbol,
+                        candle.date(),
+                    )
+                })
+            })
+            .sum::<Result<f64>>()?;
+
+        Ok(realized + open)
+    }
+}
+
+fn risk_error(breach: &RiskBreach) -> RsAlgoError {
+    RsAlgoError {
+        err: RsAlgoErrorKind::RiskLimit(breach.description()),
+    }
+}
+
+fn open_positions(bots: &[BotData]) -> Vec<OpenPosition<'_>> {
+    bots.iter()
+        .flat_map(|bot| {
+            bot.trades_in()
+                .iter()
+                .filter(|trade_in| {
+                    trade_in.status != TradeStatus::Rejected
+                        && !trade_exists(bot.trades_out(), trade_in.id)
+                })
+                .map(|trade_in| OpenPosition {
+                    symbol: bot.symbol(),
+                    trade_in,
+                    data: bot.instrument().data(),
+                })
+        })
+        .collect()
+}
+
+// Forex pairs expose the base and quote currencies, anything else the symbol itself
+fn currency_legs(symbol: &str, trade_in: &TradeIn) -> Vec<(String, f64)> {
+    let size = match trade_in.trade_type.is_long() {
+        true => trade_in.size,
+        false => -trade_in.size,
+    };
+
+    match symbol_currencies(symbol) {
+        Some((base, quote)) => vec![(base, size), (quote, -size)],
+        None => vec![(symbol.to_owned(), size)],
+    }
+}
+
+fn currency_exposures<'a>(
+    positions: impl Iterator<Item = (&'a str, &'a TradeIn)>,
+) -> HashMap<String, f64> {
+    let mut exposures = HashMap::new();
+
+    for (symbol, trade_in) in positions {
+        for (currency, exposure) in currency_legs(symbol, trade_in) {
+            *exposures.entry(currency).or_insert(0.) += exposure;
+        }
+    }
+
+    exposures
+}
+
+// Close returns by candle date
+fn returns(data: &[Candle]) -> Vec<(DateTime<Local>, f64)> {
+    let data = &data[data.len().saturating_sub(CORRELATION_BARS * 2 + 1)..];
+
+    data.windows(2)
+        .map(|pair| match pair[0].close() > 0. {
+            true => (pair[1].date(), pair[1].close() / pair[0].close() - 1.),
+            false => (pair[1].date(), 0.),
+        })
+        .collect()
+}
+
+// Pearson correlation of the most recent close returns on the same candle dates
+fn returns_correlation(a: &[Candle], b: &[Candle]) -> f64 {
+    let b: HashMap<DateTime<Local>, f64> = returns(b).into_iter().collect();
+    let (a, b): (Vec<f64>, Vec<f64>) = returns(a)
+        .into_iter()
+        .rev()
+        .filter_map(|(date, x)| b.get(&date).map(|y| (x, *y)))
+        .take(CORRELATION_BARS)
+        .unzip();
+    let len = a.len();
+
+    if len < 2 {
+        return 0.;
+    }
+
+    let mean_a = a.iter().sum::<f64>() / len as f64;
+    let mean_b = b.iter().sum::<f64>() / len as f64;
+
+    let (mut cov, mut var_a, mut var_b) = (0., 0., 0.);
+
+    for (x, y) in a.iter().zip(b.iter()) {
+        cov += (x - mean_a) * (y - mean_b);
+        var_a += (x - mean_a).powi(2);
+        var_b += (y - mean_b).powi(2);
+    }
+
+    match var_a > 0. && var_b > 0. {
+        true => cov / (var_a.sqrt() * var_b.sqrt()),
+        false => 0.,
+    }
+}
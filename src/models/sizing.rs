@@ -0,0 +1,478 @@
+use super::account::Account;
+use super::fx::FxRates;
+use super::order::{Order, OrderDirection, OrderType};
+use super::stop_loss;
+use super::symbol_spec::symbol_spec;
+use super::tick::InstrumentTick;
+use super::trade::TradeOut;
+
+use crate::config::RsAlgoConfig;
+use crate::error::Result;
+use crate::helpers::calc;
+use crate::helpers::date::{DateTime, Local};
+use crate::scanner::instrument::Instrument;
+
+use serde::{Deserialize, Serialize};
+
+const LOT_STEP: f64 = 0.01;
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub enum SizingMode {
+    FixedLot,
+    FixedFractional,
+    Volatility,
+    Kelly,
+}
+
+impl SizingMode {
+    pub fn from_str(sizing_mode: &str) -> SizingMode {
+        match sizing_mode {
+            "fixed_fractional" => SizingMode::FixedFractional,
+            "volatility" => SizingMode::Volatility,
+            "kelly" => SizingMode::Kelly,
+            _ => SizingMode::FixedLot,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct PositionSizer {
+    mode: SizingMode,
+    order_size: f64,
+    order_size_limit: f64,
+    risk_per_trade: f64,
+    contract_size: f64,
+    leverage: f64,
+    atr_multiplier: f64,
+    kelly_fraction: f64,
+    kelly_stats: Option<(f64, f64)>,
+}
+
+impl PositionSizer {
+    pub fn new() -> PositionSizerBuilder {
+        PositionSizerBuilder::new()
+    }
+
+    pub fn from_config(config: &RsAlgoConfig) -> Self {
+        Self {
+            mode: SizingMode::from_str(&config.sizing_mode),
+            order_size: config.order_size,
+            order_size_limit: config.order_size_limit,
+            risk_per_trade: config.risk_per_trade,
+            contract_size: config.contract_size,
+            leverage: config.leverage,
+            atr_multiplier: config.sizing_atr_multiplier,
+            kelly_fraction: config.kelly_fraction,
+            kelly_stats: None,
+        }
+    }
+
+    pub fn mode(&self) -> &SizingMode {
+        &self.mode
+    }
+
+    pub fn is_fixed(&self) -> bool {
+        self.mode == SizingMode::FixedLot
+    }
+
+    pub fn with_kelly_stats(mut self, win_rate: f64, payoff_ratio: f64) -> Self {
+        self.kelly_stats = Some((win_rate, payoff_ratio));
+        self
+    }
+
+    pub fn risk_per_trade(&self) -> f64 {
+        match (&self.mode, self.kelly_stats) {
+            (SizingMode::Kelly, Some((win_rate, payoff_ratio))) if payoff_ratio > 0. => {
+                let kelly = win_rate - (1. - win_rate) / payoff_ratio;
+                (kelly * self.kelly_fraction * 100.).max(0.)
+            }
+            _ => self.risk_per_trade,
+        }
+    }
+
+    // Pip value and margin are quoted in the symbol's quote currency and
+    // converted to the account one. Risk is sized on the equity, the margin
+    // cap on the free margin left by the open positions. Zero when the
+    // allowed size is below the tradable volume
+    pub fn size(
+        &self,
+        account: &Account,
+        price: f64,
+        stop_distance: Option<f64>,
+        atr: f64,
+        tick: &InstrumentTick,
+        fx_rates: &FxRates,
+        date: DateTime<Local>,
+    ) -> Result<f64> {
+        let stop_distance = match self.mode {
+            SizingMode::FixedLot => None,
+            SizingMode::Volatility => Some(atr * self.atr_multiplier),
+            _ => stop_distance,
+        }
+        .filter(|distance| *distance > 0.);
+
+        let symbol = tick.symbol();
+        let spec = symbol_spec(&symbol);
+
+        let pip_size = match tick.pip_size() > 0. {
+            true => tick.pip_size(),
+            false => calc::number_pips(&symbol),
+        };
+
+        // Registered symbols trade with their own contract and margin terms
+        let (contract_size, leverage) = match &spec {
+            Some(spec) => (spec.contract_size, spec.leverage()),
+            None => (self.contract_size, self.leverage),
+        };
+
+        let size = match stop_distance {
+            Some(distance) => {
+                let risk_amount = account.equity() * self.risk_per_trade() / 100.;
+                let stop_pips = distance / pip_size;
+                let pip_value =
+                    fx_rates.quote_to_account(pip_size * contract_size, &symbol, price, date)?;
+                risk_amount / (stop_pips * pip_value)
+            }
+            None => {
+                if !self.is_fixed() {
+                    log::warn!("{:?} sizing without stop distance", self.mode);
+                }
+                self.order_size
+            }
+        };
+
+        let margin_limit = match price > 0. {
+            true => {
+                let lot_margin = fx_rates.quote_to_account(
+                    price * contract_size / leverage,
+                    &symbol,
+                    price,
+                    date,
+                )?;
+                account.margin_free().max(0.) / lot_margin
+            }
+            false => size,
+        };
+
+        let size = size.min(margin_limit).min(self.order_size_limit);
+
+        let size = match spec {
+            Some(spec) => spec.round_volume(size),
+            // Epsilon keeps float noise from dropping a whole lot step
+            None => ((size / LOT_STEP + 1e-9).floor() * LOT_STEP * 100.).round() / 100.,
+        };
+
+        if size <= 0. {
+            log::warn!("{} risk and margin allow no tradable volume", symbol);
+        }
+
+        Ok(size)
+    }
+}
+
+// Win rate and average win over average loss of the closed trades. Both
+// wins and losses are needed for a payoff ratio
+pub fn kelly_stats(trades_out: &[TradeOut]) -> Option<(f64, f64)> {
+    let (wins, losses): (Vec<f64>, Vec<f64>) = trades_out
+        .iter()
+        .map(|trade| trade.profit)
+        .filter(|profit| *profit != 0.)
+        .partition(|profit| *profit > 0.);
+
+    if wins.is_empty() || losses.is_empty() {
+        return None;
+    }
+
+    let win_rate = wins.len() as f64 / (wins.len() + losses.len()) as f64;
+    let avg_win = wins.iter().sum::<f64>() / wins.len() as f64;
+    let avg_loss = losses.iter().map(|loss| loss.abs()).sum::<f64>() / losses.len() as f64;
+
+    Some((win_rate, avg_win / avg_loss))
+}
+
+pub fn trade_size(
+    index: usize,
+    instrument: &Instrument,
+    order_types: &[OrderType],
+    tick: &InstrumentTick,
+    account: &Account,
+    trades_out: &[TradeOut],
+    fx_rates: &FxRates,
+    config: &RsAlgoConfig,
+) -> Result<f64> {
+    let sizer = match kelly_stats(trades_out) {
+        Some((win_rate, payoff_ratio)) => {
+            PositionSizer::from_config(config).with_kelly_stats(win_rate, payoff_ratio)
+        }
+        None => PositionSizer::from_config(config),
+    };
+
+    if sizer.is_fixed() {
+        return Ok(config.order_size);
+    }
+
+    let date = match config.execution_mode.is_back_test() {
+        true => instrument.data().get(index + 1),
+        false => instrument.data().last(),
+    }
+    .map(|candle| candle.date())
+    .unwrap_or_else(Local::now);
+
+    let atr = stop_loss::current_atr(index, instrument);
+
+    let stop_distance = order_types.iter().find_map(|order_type| match order_type {
+        OrderType::StopLossLong(stop_loss_type, buy_price)
+        | OrderType::StopLossShort(stop_loss_type, buy_price) => {
+            let direction = match order_type {
+                OrderType::StopLossLong(_, _) => OrderDirection::Down,
+                _ => OrderDirection::Up,
+            };
+
+            stop_loss::create_stop_loss_order(
+                index,
+                *buy_price,
+                instrument,
+                &direction,
+                stop_loss_type,
+                tick,
+                config,
+            )
+            .map(|stop_loss: Order| (*buy_price, (buy_price - stop_loss.target_price).abs()))
+        }
+        _ => None,
+    });
+
+    match stop_distance {
+        Some((price, distance)) => {
+            sizer.size(account, price, Some(distance), atr, tick, fx_rates, date)
+        }
+        None => sizer.size(account, tick.ask(), None, atr, tick, fx_rates, date),
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PositionSizerBuilder {
+    mode: Option<SizingMode>,
+    order_size: Option<f64>,
+    order_size_limit: Option<f64>,
+    risk_per_trade: Option<f64>,
+    contract_size: Option<f64>,
+    leverage: Option<f64>,
+    atr_multiplier: Option<f64>,
+    kelly_fraction: Option<f64>,
+}
+
+impl PositionSizerBuilder {
+    pub fn new() -> Self {
+        Self {
+            mode: None,
+            order_size: None,
+            order_size_limit: None,
+            risk_per_trade: None,
+            contract_size: None,
+            leverage: None,
+            atr_multiplier: None,
+            kelly_fraction: None,
+        }
+    }
+
+    pub fn mode(mut self, val: SizingMode) -> Self {
+        self.mode = Some(val);
+        self
+    }
+
+    pub fn order_size(mut self, val: f64) -> Self {
+        self.order_size = Some(val);
+        self
+    }
+
+    pub fn order_size_limit(mut self, val: f64) -> Self {
+        self.order_size_limit = Some(val);
+        self
+    }
+
+    pub fn risk_per_trade(mut self, val: f64) -> Self {
+        self.risk_per_trade = Some(val);
+        self
+    }
+
+    pub fn contract_size(mut self, val: f64) -> Self {
+        self.contract_size = Some(val);
+        self
+    }
+
+    pub fn leverage(mut self, val: f64) -> Self {
+        self.leverage = Some(val);
+        self
+    }
+
+    pub fn atr_multiplier(mut self, val: f64) -> Self {
+        self.atr_multiplier = Some(val);
+        self
+    }
+
+    pub fn kelly_fraction(mut self, val: f64) -> Self {
+        self.kelly_fraction = Some(val);
+        self
+    }
+
+    pub fn build(self) -> std::result::Result<PositionSizer, &'static str> {
+        let mode = self.mode.ok_or("Sizing mode is required")?;
+        let order_size = self.order_size.ok_or("Order size is required")?;
+        let order_size_limit = self.order_size_limit.unwrap_or(order_size);
+        let leverage = self.leverage.ok_or("Leverage is required")?;
+
+        if mode != SizingMode::FixedLot && self.risk_per_trade.is_none() {
+            return Err("Risk per trade is required");
+        }
+
+        Ok(PositionSizer {
+            mode,
+            order_size,
+            order_size_limit,
+            risk_per_trade: self.risk_per_trade.unwrap_or_default(),
+            contract_size: self.contract_size.unwrap_or(100000.),
+            leverage,
+            atr_multiplier: self.atr_multiplier.unwrap_or(2.),
+            kelly_fraction: self.kelly_fraction.unwrap_or(0.5),
+            kelly_stats: None,
+        })
+    }
+}
+
+impl Default for PositionSizerBuilder {
+    fn default() -> Self {
+        PositionSizerBuilder::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::trade::TradeIn;
+
+    fn sizer(mode: SizingMode) -> PositionSizer {
+        PositionSizer::new()
+            .mode(mode)
+            .order_size(0.5)
+            .order_size_limit(5.)
+            .risk_per_trade(1.)
+            .leverage(30.)
+            .build()
+            .unwrap()
+    }
+
+    fn account(balance: f64) -> Account {
+        Account::new()
+            .balance(balance)
+            .leverage(30.)
+            .build()
+            .unwrap()
+    }
+
+    fn tick() -> InstrumentTick {
+        InstrumentTick::new()
+            .symbol("EURUSD".to_owned())
+            .ask(1.1)
+            .bid(1.1)
+            .high(1.1)
+            .low(1.1)
+            .spread(0.)
+            .pip_size(0.0001)
+            .time(0)
+            .build()
+            .unwrap()
+    }
+
+    fn size(sizer: &PositionSizer, account: &Account, stop_distance: Option<f64>) -> f64 {
+        let fx_rates = FxRates::new("USD", 100000.);
+        sizer
+            .size(
+                account,
+                1.1,
+                stop_distance,
+                0.001,
+                &tick(),
+                &fx_rates,
+                Local::now(),
+            )
+            .unwrap()
+    }
+
+    #[test]
+    fn sizes_each_mode() {
+        let account = account(10000.);
+
+        assert_eq!(
+            size(&sizer(SizingMode::FixedLot), &account, Some(0.002)),
+            0.5
+        );
+
+        // 1% of 10000 over a 20 pips stop at 10 USD per pip
+        let fixed_fractional = sizer(SizingMode::FixedFractional);
+        assert_eq!(size(&fixed_fractional, &account, Some(0.002)), 0.5);
+        assert_eq!(size(&fixed_fractional, &account, None), 0.5);
+
+        // Stop distance comes from 2 ATRs
+        assert_eq!(size(&sizer(SizingMode::Volatility), &account, None), 0.5);
+
+        // Half Kelly of a 50% win rate with 1.5 payoff risks 8.33%
+        let kelly = sizer(SizingMode::Kelly).with_kelly_stats(0.5, 1.5);
+        assert_eq!(size(&kelly, &account, Some(0.01)), 0.83);
+        assert_eq!(size(&sizer(SizingMode::Kelly), &account, Some(0.01)), 0.1);
+    }
+
+    #[test]
+    fn rounds_and_caps_the_size() {
+        let account = account(10000.);
+        let sizer = sizer(SizingMode::FixedFractional);
+
+        // 100 USD over 81 pips floors to the lot step
+        assert_eq!(size(&sizer, &account, Some(0.0081)), 0.12);
+
+        // Tight stops are capped by the margin, then by the order size limit
+        assert_eq!(size(&sizer, &account, Some(0.0001)), 2.73);
+
+        let limited = PositionSizer {
+            order_size_limit: 1.,
+            ..sizer
+        };
+        assert_eq!(size(&limited, &account, Some(0.0001)), 1.);
+    }
+
+    #[test]
+    fn caps_the_size_on_free_margin() {
+        let mut account = account(1000.);
+        let sizer = PositionSizer::new()
+            .mode(SizingMode::FixedFractional)
+            .order_size(0.5)
+            .order_size_limit(5.)
+            .risk_per_trade(10.)
+            .leverage(30.)
+            .build()
+            .unwrap();
+
+        // A lot of EURUSD takes 3663 USD of margin
+        assert_eq!(size(&sizer, &account, Some(0.002)), 0.27);
+
+        let trade_in = TradeIn {
+            id: 1,
+            size: 0.1,
+            price_in: 1.1,
+            ..TradeIn::default()
+        };
+        account
+            .open("EURUSD", &trade_in, &FxRates::new("USD", 100000.))
+            .unwrap();
+
+        assert_eq!(size(&sizer, &account, Some(0.002)), 0.17);
+    }
+
+    #[test]
+    fn skips_sizes_below_the_lot_step() {
+        let sizer = sizer(SizingMode::FixedFractional);
+
+        // 1 USD of risk can't cover the smallest lot
+        assert_eq!(size(&sizer, &account(100.), Some(0.002)), 0.);
+    }
+}
@@ -1,6 +1,9 @@
-use super::order::{self, Order, OrderDirection, OrderType};
+use super::market::MarketHours;
+use super::order::{self, MetaData, Order, OrderDirection, OrderType};
 use super::tick::InstrumentTick;
+use super::trade::{Position, TradeIn};
 
+use crate::config::RsAlgoConfig;
 use crate::helpers::{calc, date::*};
 use crate::indicators::Indicator;
 use crate::scanner::instrument::Instrument;
@@ -12,9 +15,41 @@ pub enum StopLossType {
     Atr(f64),
     Price(f64),
     Pips(f64),
+    Trailing { distance: f64, step: f64 },
+    TrailingAtr(f64),
     None,
 }
 
+impl StopLossType {
+    pub fn is_trailing(&self) -> bool {
+        matches!(
+            self,
+            StopLossType::Trailing { .. } | StopLossType::TrailingAtr(_)
+        )
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub enum BreakEvenTrigger {
+    Pips(f64),
+    RMultiple(f64),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub enum StopRule {
+    BreakEven {
+        trigger: BreakEvenTrigger,
+        buffer: f64,
+    },
+    MaxBars(i64),
+    CloseAt {
+        hour: u32,
+    },
+    WeekClose {
+        hour: u32,
+    },
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct StopLoss {
     pub stop_type: StopLossType,
@@ -25,6 +60,15 @@ pub struct StopLoss {
     pub valid_until: DbDateTime,
 }
 
+impl StopLoss {
+    pub fn amend(&mut self, price: f64) {
+        self.stop_type = StopLossType::Price(price);
+        self.price = price;
+        self.value = price;
+        self.updated_at = to_dbtime(Local::now());
+    }
+}
+
 pub fn init_stop_loss(stop_type: StopLossType, value: f64) -> StopLoss {
     StopLoss {
         price: 0.,
@@ -43,40 +87,41 @@ pub fn create_stop_loss_order(
     order_direction: &OrderDirection,
     stop_loss_type: &StopLossType,
     tick: &InstrumentTick,
-) -> Order {
-    let order_size = std::env::var("ORDER_SIZE").unwrap().parse::<f64>().unwrap();
+    config: &RsAlgoConfig,
+) -> Option<Order> {
+    let order_size = config.order_size;
 
-    let stop_loss_spread = std::env::var("STOP_LOSS_SPREAD")
-        .unwrap()
-        .parse::<bool>()
-        .unwrap();
+    let stop_loss_spread = config.stop_loss_spread;
 
     let spread_value = match stop_loss_spread {
         true => tick.spread(),
         false => 0.,
     };
 
-    let current_atr_value = match &instrument.indicators.atr {
-        Some(atr) => *atr.get_data_a().get(index).unwrap(),
-        None => 0.,
-    };
+    let current_atr_value = current_atr(index, instrument);
 
     let target_price = match stop_loss_type {
-        StopLossType::Atr(atr_stop_value) => match order_direction {
-            OrderDirection::Up => (buy_price + spread_value) + (atr_stop_value * current_atr_value),
-            OrderDirection::Down => {
-                (buy_price - spread_value) - (atr_stop_value * current_atr_value)
+        StopLossType::Atr(atr_stop_value) | StopLossType::TrailingAtr(atr_stop_value) => {
+            match order_direction {
+                OrderDirection::Up => {
+                    (buy_price + spread_value) + (atr_stop_value * current_atr_value)
+                }
+                OrderDirection::Down => {
+                    (buy_price - spread_value) - (atr_stop_value * current_atr_value)
+                }
             }
-        },
+        }
         StopLossType::Price(target_price) => match order_direction {
             OrderDirection::Up => *target_price,
             OrderDirection::Down => *target_price,
         },
-        StopLossType::Pips(pips) => match order_direction {
-            OrderDirection::Up => (buy_price + spread_value) + calc::to_pips(*pips, tick),
-            OrderDirection::Down => (buy_price - spread_value) - calc::to_pips(*pips, tick),
-        },
-        StopLossType::None => todo!(),
+        StopLossType::Pips(pips) | StopLossType::Trailing { distance: pips, .. } => {
+            match order_direction {
+                OrderDirection::Up => (buy_price + spread_value) + calc::to_pips(*pips, tick),
+                OrderDirection::Down => (buy_price - spread_value) - calc::to_pips(*pips, tick),
+            }
+        }
+        StopLossType::None => return None,
     };
 
     let stop_loss = match order_direction {
@@ -84,5 +129,255 @@ pub fn create_stop_loss_order(
         OrderDirection::Down => OrderType::StopLossLong(stop_loss_type.clone(), buy_price),
     };
 
-    order::create_order(index, instrument, &stop_loss, &target_price, &order_size)
+    let mut order = order::create_order(
+        index,
+        instrument,
+        &stop_loss,
+        &target_price,
+        &order_size,
+        config,
+    );
+
+    // Keeps the initial stop once trailing or break even move the target
+    order.meta = Some(MetaData {
+        sl: order.target_price,
+    });
+
+    Some(order)
+}
+
+pub fn update_trailing_stops(
+    index: usize,
+    orders: &mut Vec<Order>,
+    instrument: &Instrument,
+    tick: &InstrumentTick,
+    use_tick_price: bool,
+    config: &RsAlgoConfig,
+) -> Vec<Order> {
+    let execution_mode = &config.execution_mode;
+
+    let candle = match execution_mode.is_back_test() {
+        true => instrument.data().get(index).unwrap(),
+        false => instrument.data().last().unwrap(),
+    };
+
+    // Live candle stops only ratchet once the bar is closed, replayed bars always are
+    let is_closed = execution_mode.is_back_test() || candle.is_closed();
+
+    if !use_tick_price && !is_closed {
+        return vec![];
+    }
+
+    let date = match execution_mode.is_back_test() {
+        true => to_dbtime(candle.date()),
+        false => to_dbtime(Local::now()),
+    };
+
+    let spread_value = match config.stop_loss_spread {
+        true => tick.spread(),
+        false => 0.,
+    };
+
+    let current_atr_value = current_atr(index, instrument);
+    let mut trailed_orders = vec![];
+
+    for order in orders
+        .iter_mut()
+        .filter(|order| order.is_stop() && order.is_pending() && order.has_active_trade())
+    {
+        let stop_loss_type = match order.order_type.stop_loss_type() {
+            Some(stop_loss_type) if stop_loss_type.is_trailing() => stop_loss_type,
+            _ => continue,
+        };
+
+        let (distance, step) = match stop_loss_type {
+            StopLossType::Trailing { distance, step } => {
+                (calc::to_pips(*distance, tick), calc::to_pips(*step, tick))
+            }
+            StopLossType::TrailingAtr(multiplier) => (multiplier * current_atr_value, 0.),
+            _ => continue,
+        };
+
+        let direction = order.order_type.get_direction().clone();
+
+        let (target_price, improvement) = match direction {
+            OrderDirection::Down => {
+                let price = match use_tick_price {
+                    true => tick.bid(),
+                    false => candle.high(),
+                };
+                let target_price = price - spread_value - distance;
+                (target_price, target_price - order.target_price)
+            }
+            OrderDirection::Up => {
+                let price = match use_tick_price {
+                    true => tick.ask(),
+                    false => candle.low(),
+                };
+                let target_price = price + spread_value + distance;
+                (target_price, order.target_price - target_price)
+            }
+        };
+
+        if improvement > 0. && improvement >= step {
+            let target_price = calc::format_symbol_price(target_price, instrument.symbol());
+
+            log::info!(
+                "Trailing {:?} {} moved from {} to {}",
+                order.order_type,
+                order.trade_id,
+                order.target_price,
+                target_price
+            );
+
+            order.target_price = target_price;
+            order.set_updated_at(date);
+            trailed_orders.push(order.clone());
+        }
+    }
+
+    trailed_orders
+}
+
+pub fn resolve_stop_rules(
+    index: usize,
+    trade_in: &TradeIn,
+    orders: &mut Vec<Order>,
+    instrument: &Instrument,
+    market_hours: &MarketHours,
+    tick: &InstrumentTick,
+    use_tick_price: bool,
+    config: &RsAlgoConfig,
+) -> (Position, Vec<Order>) {
+    let execution_mode = &config.execution_mode;
+    let is_long = trade_in.trade_type.is_long();
+
+    let candle = match execution_mode.is_back_test() {
+        true => instrument.data().get(index).unwrap(),
+        false => instrument.data().last().unwrap(),
+    };
+
+    let current_date = match execution_mode.is_back_test() {
+        true => candle.date(),
+        false => Local::now(),
+    };
+
+    let price = match (use_tick_price, is_long) {
+        (true, true) => tick.bid(),
+        (true, false) => tick.ask(),
+        (false, true) => candle.high(),
+        (false, false) => candle.low(),
+    };
+
+    let price_in = trade_in.price_in;
+    let date_in = from_dbtime(&trade_in.date_in);
+    let profit = match is_long {
+        true => price - price_in,
+        false => price_in - price,
+    };
+
+    let mut position = Position::None;
+    let mut amended_orders = vec![];
+
+    for rule in &trade_in.stop_rules {
+        match rule {
+            StopRule::BreakEven { trigger, buffer } => {
+                let stop_loss = orders.iter_mut().find(|order| {
+                    order.trade_id == trade_in.id && order.is_stop() && order.is_pending()
+                });
+
+                let stop_loss = match stop_loss {
+                    Some(stop_loss) => stop_loss,
+                    None => continue,
+                };
+
+                // The R unit is the initial stop distance, trailing may have moved the stop since
+                let initial_stop = stop_loss.initial_stop().unwrap_or(stop_loss.target_price);
+                let risk = match is_long {
+                    true => price_in - initial_stop,
+                    false => initial_stop - price_in,
+                };
+
+                if risk <= 0. {
+                    continue;
+                }
+
+                let target_price = match is_long {
+                    true => price_in + calc::to_pips(*buffer, tick),
+                    false => price_in - calc::to_pips(*buffer, tick),
+                };
+                let target_price = calc::format_symbol_price(target_price, instrument.symbol());
+
+                // Stops already at or past the break even level are left alone
+                let is_protected = match is_long {
+                    true => stop_loss.target_price >= target_price,
+                    false => stop_loss.target_price <= target_price,
+                };
+
+                if is_protected {
+                    continue;
+                }
+
+                let is_triggered = match trigger {
+                    BreakEvenTrigger::Pips(pips) => profit >= calc::to_pips(*pips, tick),
+                    BreakEvenTrigger::RMultiple(multiple) => profit >= risk * multiple,
+                };
+
+                if is_triggered {
+                    log::info!(
+                        "Break even {:?} {} moved from {} to {}",
+                        stop_loss.order_type,
+                        trade_in.id,
+                        stop_loss.target_price,
+                        target_price
+                    );
+
+                    stop_loss.target_price = target_price;
+                    stop_loss.set_updated_at(to_dbtime(current_date));
+                    amended_orders.push(stop_loss.clone());
+                }
+            }
+            StopRule::MaxBars(max_bars) => {
+                // Candles since the entry, market closures don't count
+                let bars = match execution_mode.is_back_test() {
+                    true => index.saturating_sub(trade_in.index_in),
+                    false => instrument
+                        .data()
+                        .iter()
+                        .rev()
+                        .take_while(|candle| candle.date() > date_in)
+                        .count(),
+                };
+
+                if bars as i64 >= *max_bars {
+                    log::info!("Trade {} open for {} bars. Closing", trade_in.id, bars);
+                    position = Position::MarketOut(None);
+                }
+            }
+            StopRule::CloseAt { hour } => {
+                let opened_before =
+                    date_in.date_naive() < current_date.date_naive() || date_in.hour() < *hour;
+
+                if current_date.hour() >= *hour && opened_before {
+                    log::info!("Trade {} reached {}h. Closing", trade_in.id, hour);
+                    position = Position::MarketOut(None);
+                }
+            }
+            StopRule::WeekClose { hour } => {
+                if market_hours.is_week_close_at(current_date) && current_date.hour() >= *hour {
+                    log::info!("Trade {} open before the weekend. Closing", trade_in.id);
+                    position = Position::MarketOut(None);
+                }
+            }
+        }
+    }
+
+    (position, amended_orders)
+}
+
+pub fn current_atr(index: usize, instrument: &Instrument) -> f64 {
+    match &instrument.indicators.atr {
+        Some(atr) => *atr.get_data_a().get(index).unwrap(),
+        None => 0.,
+    }
 }
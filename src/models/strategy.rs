@@ -2,6 +2,9 @@ use crate::{helpers::calc::*, scanner::instrument::Instrument};
 
 use serde::{Deserialize, Serialize};
 
+use super::account::Account;
+use super::equity::EquityCurve;
+use super::order::Order;
 use super::trade::{TradeIn, TradeOut};
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -72,14 +75,46 @@ pub struct StrategyStats {
     pub stop_losses: usize,
     pub gross_profit: f64,
     pub commissions: f64,
+    #[serde(default)]
+    pub spread_costs: f64,
+    #[serde(default)]
+    pub swaps: f64,
     pub net_profit: f64,
     pub net_profit_per: f64,
     pub profitable_trades: f64,
     pub profit_factor: f64,
     pub max_runup: f64,
     pub max_drawdown: f64,
+    #[serde(default)]
+    pub max_drawdown_abs: f64,
+    #[serde(default)]
+    pub max_drawdown_duration: usize,
+    #[serde(default)]
+    pub max_drawdown_recovery: Option<usize>,
     pub buy_hold: f64,
     pub annual_return: f64,
+    #[serde(default)]
+    pub sharpe_ratio: f64,
+    #[serde(default)]
+    pub sortino_ratio: f64,
+    #[serde(default)]
+    pub calmar_ratio: f64,
+    #[serde(default)]
+    pub expectancy: f64,
+    #[serde(default)]
+    pub avg_r_multiple: f64,
+    #[serde(default)]
+    pub max_consecutive_wins: usize,
+    #[serde(default)]
+    pub max_consecutive_losses: usize,
+    #[serde(default)]
+    pub avg_holding_time: f64,
+    #[serde(default)]
+    pub exposure: f64,
+    #[serde(default)]
+    pub avg_margin_utilization: f64,
+    #[serde(default)]
+    pub max_margin_utilization: f64,
 }
 
 impl Default for StrategyStats {
@@ -99,14 +134,30 @@ impl StrategyStats {
             stop_losses: 0,
             gross_profit: 0.,
             commissions: 0.,
+            spread_costs: 0.,
+            swaps: 0.,
             net_profit: 0.,
             net_profit_per: 0.,
             profitable_trades: 0.,
             profit_factor: 0.,
             max_runup: 0.,
             max_drawdown: 0.,
+            max_drawdown_abs: 0.,
+            max_drawdown_duration: 0,
+            max_drawdown_recovery: None,
             buy_hold: 0.,
             annual_return: 0.,
+            sharpe_ratio: 0.,
+            sortino_ratio: 0.,
+            calmar_ratio: 0.,
+            expectancy: 0.,
+            avg_r_multiple: 0.,
+            max_consecutive_wins: 0,
+            max_consecutive_losses: 0,
+            avg_holding_time: 0.,
+            exposure: 0.,
+            avg_margin_utilization: 0.,
+            max_margin_utilization: 0.,
         }
     }
 }
@@ -115,8 +166,9 @@ pub fn calculate_strategy_stats(
     instrument: &Instrument,
     trades_in: &Vec<TradeIn>,
     trades_out: &Vec<TradeOut>,
-    equity: f64,
-    commission: f64,
+    orders: &[Order],
+    account: &Account,
+    equity_curve: &EquityCurve,
 ) -> StrategyStats {
     log::info!("Calculating Strategy stats");
     let data = &instrument.data;
@@ -134,9 +186,12 @@ pub fn calculate_strategy_stats(
         let stop_losses = trades_out.iter().filter(|x| x.trade_type.is_stop()).count();
         let gross_profits = total_gross(&w_trades);
         let gross_loses = total_gross(&l_trades);
-        let gross_profit = gross_profits + gross_loses;
-        let commissions = total_commissions(trades, commission);
-        let net_profit = gross_profit - commissions;
+        // Trade profits are net of their own commissions and swaps
+        let commissions: f64 = trades_out.iter().map(|trade| trade.commission).sum();
+        let swaps: f64 = trades_out.iter().map(|trade| trade.swap).sum();
+        let spread_costs: f64 = trades_out.iter().map(|trade| trade.spread_cost).sum();
+        let gross_profit = gross_profits + gross_loses + commissions - swaps;
+        let net_profit = gross_profit - commissions + swaps;
         let first = trades_in.first().unwrap();
 
         let initial_order_amount = (first.price_in * first.size).ceil();
@@ -150,9 +205,10 @@ pub fn calculate_strategy_stats(
         //     _ => total_drawdown(trades_out, equity),
         // };
 
-        let max_drawdown = total_drawdown(trades_out, equity);
+        let drawdown = equity_curve.drawdown();
+        let max_drawdown = drawdown.max_drawdown_per;
+        let max_runup = equity_curve.max_runup();
 
-        let max_runup = total_runup(trades_out, equity);
         let strategy_start_price = match instrument.data.first().map(|x| x.open) {
             Some(open) => open,
             _ => 0.,
@@ -160,7 +216,28 @@ pub fn calculate_strategy_stats(
 
         let buy_hold =
             calculate_buy_hold(strategy_start_price, initial_order_amount, current_price);
-        let annual_return = 100.;
+        let annual_return = match (data.first(), data.last()) {
+            (Some(first), Some(last)) => calculate_annual_return(
+                equity_curve.initial_equity(),
+                equity_curve.final_equity(),
+                first.date,
+                last.date,
+            ),
+            _ => 0.,
+        };
+
+        let returns = equity_curve.returns();
+        let periods_per_year = equity_curve.periods_per_year();
+        let sharpe_ratio = calculate_sharpe_ratio(&returns, periods_per_year);
+        let sortino_ratio = calculate_sortino_ratio(&returns, periods_per_year);
+        let calmar_ratio = calculate_calmar_ratio(annual_return, max_drawdown);
+        let expectancy = calculate_expectancy(trades_out);
+        let avg_r_multiple = calculate_avg_r_multiple(trades_out, orders);
+        let (max_consecutive_wins, max_consecutive_losses) = max_consecutive_trades(trades_out);
+        let avg_holding_time = avg_holding_time(trades_out);
+        let exposure = total_exposure(trades_in, trades_out, data.len());
+        let avg_margin_utilization = account.avg_margin_utilization();
+        let max_margin_utilization = account.max_margin_utilization();
 
         StrategyStats {
             trades,
@@ -171,14 +248,30 @@ pub fn calculate_strategy_stats(
             stop_losses,
             gross_profit,
             commissions,
+            spread_costs,
+            swaps,
             net_profit,
             net_profit_per,
             profitable_trades,
             profit_factor,
             max_runup,
             max_drawdown,
+            max_drawdown_abs: drawdown.max_drawdown,
+            max_drawdown_duration: drawdown.max_duration,
+            max_drawdown_recovery: drawdown.recovery,
             buy_hold,
             annual_return,
+            sharpe_ratio,
+            sortino_ratio,
+            calmar_ratio,
+            expectancy,
+            avg_r_multiple,
+            max_consecutive_wins,
+            max_consecutive_losses,
+            avg_holding_time,
+            exposure,
+            avg_margin_utilization,
+            max_margin_utilization,
         }
     } else {
         StrategyStats::new()
@@ -1,6 +1,6 @@
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct InstrumentSwap {
     symbol: String,
     enabled: bool,
@@ -13,6 +13,27 @@ impl InstrumentSwap {
     pub fn new() -> InstrumentSwapBuilder {
         InstrumentSwapBuilder::new()
     }
+
+    pub fn symbol(&self) -> &str {
+        &self.symbol
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn swap_long(&self) -> f64 {
+        self.swap_long
+    }
+
+    pub fn swap_short(&self) -> f64 {
+        self.swap_short
+    }
+
+    // Weekday of the triple rollover, 0 being Monday as in swap_rollover3days
+    pub fn swap_weekend(&self) -> f64 {
+        self.swap_weekend
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -0,0 +1,290 @@
+use super::market::MarketHour;
+
+use crate::config::RsAlgoConfig;
+use crate::error::{Result, RsAlgoError, RsAlgoErrorKind};
+use crate::helpers::symbols::specs;
+
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+
+// Writes swap in a new registry, lookups read a per thread snapshot that is
+// only refreshed after a write
+static REGISTRY: OnceLock<Mutex<Arc<SymbolRegistry>>> = OnceLock::new();
+static VERSION: AtomicUsize = AtomicUsize::new(1);
+
+thread_local! {
+    static SNAPSHOT: RefCell<(usize, Arc<SymbolRegistry>)> =
+        RefCell::new((0, Arc::new(SymbolRegistry::new())));
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SymbolSpec {
+    pub symbol: String,
+    pub digits: u32,
+    pub pip_size: f64,
+    pub tick_size: f64,
+    pub contract_size: f64,
+    pub lot_step: f64,
+    pub min_volume: f64,
+    pub max_volume: f64,
+    pub margin_rate: f64,
+    #[serde(default)]
+    pub base_currency: String,
+    pub quote_currency: String,
+    #[serde(default)]
+    pub trading_hours: Vec<MarketHour>,
+}
+
+impl SymbolSpec {
+    pub fn is_currency_pair(&self) -> bool {
+        !self.base_currency.is_empty() && self.base_currency != self.quote_currency
+    }
+
+    pub fn price_factor(&self) -> f64 {
+        1. / self.pip_size
+    }
+
+    pub fn leverage(&self) -> f64 {
+        match self.margin_rate > 0. {
+            true => 1. / self.margin_rate,
+            false => 1.,
+        }
+    }
+
+    pub fn round_price(&self, price: f64) -> f64 {
+        let factor = 10_f64.powi(self.digits as i32);
+        (price * factor).round() / factor
+    }
+
+    // Floors to the lot step and caps to the max volume. Sizes below the
+    // min volume can't be traded and round to zero
+    pub fn round_volume(&self, size: f64) -> f64 {
+        let size = match self.lot_step > 0. {
+            true => (size / self.lot_step + 1e-9).floor() * self.lot_step,
+            false => size,
+        };
+
+        if size <= 0. || size < self.min_volume - 1e-9 {
+            return 0.;
+        }
+
+        let size = match self.max_volume > 0. {
+            true => size.min(self.max_volume),
+            false => size,
+        };
+
+        // Drops the float noise left by the lot step multiplication
+        let decimals = match self.lot_step > 0. {
+            true => (-self.lot_step.log10()).ceil().max(0.) as i32,
+            false => 2,
+        };
+        let factor = 10_f64.powi(decimals);
+        (size * factor).round() / factor
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct SymbolRegistry {
+    specs: HashMap<String, Arc<SymbolSpec>>,
+}
+
+impl SymbolRegistry {
+    pub fn new() -> Self {
+        Self {
+            specs: HashMap::new(),
+        }
+    }
+
+    pub fn bundled() -> Self {
+        let mut registry = Self::new();
+        registry.extend(specs::get_specs());
+        registry
+    }
+
+    pub fn from_json(content: &str) -> Result<Self> {
+        let specs: Vec<SymbolSpec> = serde_json::from_str(content).map_err(|err| {
+            log::error!("Invalid symbol specs: {}", err);
+            RsAlgoError {
+                err: RsAlgoErrorKind::ParseError,
+            }
+        })?;
+
+        let mut registry = Self::new();
+        registry.extend(specs);
+        Ok(registry)
+    }
+
+    pub fn get(&self, symbol: &str) -> Option<&Arc<SymbolSpec>> {
+        self.specs.get(symbol)
+    }
+
+    // Broker specs don't carry the trading hours, the known ones are kept.
+    // None when the spec is already registered
+    fn merge(&self, mut spec: SymbolSpec) -> Option<SymbolSpec> {
+        if let Some(current) = self.specs.get(&spec.symbol) {
+            if spec.trading_hours.is_empty() {
+                spec.trading_hours = current.trading_hours.clone();
+            }
+            if **current == spec {
+                return None;
+            }
+        }
+        Some(spec)
+    }
+
+    pub fn insert(&mut self, spec: SymbolSpec) {
+        if let Some(spec) = self.merge(spec) {
+            self.specs.insert(spec.symbol.clone(), Arc::new(spec));
+        }
+    }
+
+    pub fn set_trading_hours(&mut self, symbol: &str, trading_hours: Vec<MarketHour>) {
+        if let Some(spec) = self.specs.get_mut(symbol) {
+            Arc::make_mut(spec).trading_hours = trading_hours;
+        }
+    }
+
+    pub fn extend(&mut self, specs: Vec<SymbolSpec>) {
+        for spec in specs {
+            self.insert(spec);
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.specs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.specs.is_empty()
+    }
+}
+
+fn registry() -> &'static Mutex<Arc<SymbolRegistry>> {
+    REGISTRY.get_or_init(|| Mutex::new(Arc::new(SymbolRegistry::bundled())))
+}
+
+fn snapshot() -> Arc<SymbolRegistry> {
+    let version = VERSION.load(Ordering::Acquire);
+
+    SNAPSHOT.with(|snapshot| {
+        let mut snapshot = snapshot.borrow_mut();
+        if snapshot.0 != version {
+            if let Ok(registry) = registry().lock() {
+                *snapshot = (version, registry.clone());
+            }
+        }
+        snapshot.1.clone()
+    })
+}
+
+fn update<F: FnOnce(&mut SymbolRegistry)>(change: F) {
+    if let Ok(mut registry) = registry().lock() {
+        let mut next = SymbolRegistry::clone(&registry);
+        change(&mut next);
+
+        *registry = Arc::new(next);
+        VERSION.fetch_add(1, Ordering::Release);
+    }
+}
+
+pub fn symbol_spec(symbol: &str) -> Option<Arc<SymbolSpec>> {
+    snapshot().get(symbol).cloned()
+}
+
+pub fn registered() -> usize {
+    snapshot().len()
+}
+
+// Called on every getSymbol tick, unchanged specs skip the registry update
+pub fn register(spec: SymbolSpec) {
+    if let Some(spec) = snapshot().merge(spec) {
+        update(|registry| registry.insert(spec));
+    }
+}
+
+pub fn register_all(specs: Vec<SymbolSpec>) {
+    update(|registry| registry.extend(specs));
+}
+
+pub fn set_trading_hours(symbol: &str, trading_hours: Vec<MarketHour>) {
+    update(|registry| registry.set_trading_hours(symbol, trading_hours));
+}
+
+pub fn load_file<P: AsRef<Path>>(path: P) -> Result<usize> {
+    let content = fs::read_to_string(path.as_ref()).map_err(|err| {
+        log::error!("Can't read {:?}: {}", path.as_ref(), err);
+        RsAlgoError {
+            err: RsAlgoErrorKind::WrongInstrumentConf,
+        }
+    })?;
+
+    let loaded = SymbolRegistry::from_json(&content)?;
+    let len = loaded.len();
+    register_all(
+        loaded
+            .specs
+            .into_values()
+            .map(|spec| SymbolSpec::clone(&spec))
+            .collect(),
+    );
+
+    log::info!("{} symbol specs loaded from {:?}", len, path.as_ref());
+    Ok(len)
+}
+
+// Loads the configured specs file on top of the bundled ones
+pub fn init(config: &RsAlgoConfig) -> Result<()> {
+    match config.symbol_specs_file.is_empty() {
+        true => Ok(()),
+        false => load_file(&config.symbol_specs_file).map(|_| ()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spec(symbol: &str, lot_step: f64) -> SymbolSpec {
+        SymbolSpec {
+            lot_step,
+            min_volume: lot_step,
+            symbol: symbol.to_owned(),
+            ..SymbolRegistry::bundled()
+                .get("EURUSD")
+                .unwrap()
+                .as_ref()
+                .clone()
+        }
+    }
+
+    #[test]
+    fn rounds_volume_to_the_lot_step() {
+        assert_eq!(spec("EURUSD", 0.01).round_volume(0.1234), 0.12);
+        assert_eq!(spec("BTCUSD", 0.001).round_volume(0.1234), 0.123);
+        assert_eq!(spec("BTCUSD", 0.0001).round_volume(0.00019), 0.0001);
+        assert_eq!(spec("BTCUSD", 0.0001).round_volume(0.00009), 0.);
+        assert_eq!(spec("US500", 0.5).round_volume(1.7), 1.5);
+    }
+
+    #[test]
+    fn keeps_trading_hours_on_broker_updates() {
+        let hours = vec![MarketHour {
+            day: 1,
+            from: 0,
+            to: 22,
+        }];
+
+        register(spec("TESTFX", 0.01));
+        set_trading_hours("TESTFX", hours.clone());
+        register(spec("TESTFX", 0.001));
+
+        let registered = symbol_spec("TESTFX").unwrap();
+        assert_eq!(registered.lot_step, 0.001);
+        assert_eq!(registered.trading_hours, hours);
+    }
+}
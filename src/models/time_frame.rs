@@ -1,7 +1,5 @@
-use super::{
-    mode::{self, ExecutionMode},
-    trade::TradeDirection,
-};
+use super::{mode::ExecutionMode, trade::TradeDirection};
+use crate::config::RsAlgoConfig;
 use crate::{
     helpers::{
         calc::get_prev_index,
@@ -11,7 +9,6 @@ use crate::{
 };
 
 use serde::{Deserialize, Serialize};
-use std::env;
 
 type DOHLC = (DateTime<Local>, f64, f64, f64, f64, f64);
 type DOHLCC = (DateTime<Local>, f64, f64, f64, f64, f64, bool);
@@ -352,8 +349,9 @@ fn get_htf_indexes<'a>(
     index: usize,
     instrument: &'a Instrument,
     htf_instrument: &'a HTFInstrument,
+    config: &RsAlgoConfig,
 ) -> (usize, usize, &'a Instrument) {
-    let execution_mode = mode::from_str(&env::var("EXECUTION_MODE").unwrap());
+    let execution_mode = config.execution_mode.clone();
 
     let base_date = &instrument.data.get(index).unwrap().date;
 
@@ -388,12 +386,13 @@ pub fn get_htf_data<F>(
     instrument: &Instrument,
     htf_instrument: &HTFInstrument,
     mut callback: F,
+    config: &RsAlgoConfig,
 ) -> bool
 where
     F: Send + FnMut((usize, usize, &Instrument)) -> bool,
 {
     let upper_tf_data: (usize, usize, &Instrument) =
-        get_htf_indexes(index, instrument, htf_instrument);
+        get_htf_indexes(index, instrument, htf_instrument, config);
 
     callback(upper_tf_data)
 }
@@ -403,12 +402,13 @@ pub fn get_htf_trading_direction<F>(
     instrument: &Instrument,
     htf_instrument: &HTFInstrument,
     mut callback: F,
+    config: &RsAlgoConfig,
 ) -> TradeDirection
 where
     F: Send + FnMut((usize, usize, &Instrument)) -> TradeDirection,
 {
     let upper_tf_data: (usize, usize, &Instrument) =
-        get_htf_indexes(index, instrument, htf_instrument);
+        get_htf_indexes(index, instrument, htf_instrument, config);
 
     callback(upper_tf_data)
 }
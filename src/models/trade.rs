@@ -1,8 +1,12 @@
-use std::env;
-
-use super::mode::{self, ExecutionMode};
+use super::account::Account;
+use super::costs::CostModel;
+use super::fx::FxRates;
+use super::mode::ExecutionMode;
 use super::order::{Order, OrderType};
+use super::stop_loss::StopRule;
 use super::tick::InstrumentTick;
+use crate::config::RsAlgoConfig;
+use crate::error::Result;
 use crate::helpers::calc::*;
 use crate::helpers::date::*;
 use crate::helpers::uuid;
@@ -19,6 +23,24 @@ pub enum TradeStatus {
     Rejected,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct TradeAttempt {
+    pub attempt: usize,
+    pub status: TradeStatus,
+    pub ask: f64,
+    pub bid: f64,
+    pub error_code: Option<String>,
+    pub message: Option<String>,
+    pub retryable: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct TradeAmend {
+    pub trade_in: TradeIn,
+    pub stop_loss: Option<f64>,
+    pub take_profit: Option<f64>,
+}
+
 pub trait Trade {
     fn get_id(&self) -> &usize;
     //fn get_key(&self) -> &usize;
@@ -220,6 +242,15 @@ pub struct TradeIn {
     pub ask: f64,
     pub spread: f64,
     pub date_in: DbDateTime,
+    #[serde(default)]
+    pub stop_rules: Vec<StopRule>,
+}
+
+impl TradeIn {
+    pub fn with_stop_rules(mut self, stop_rules: Vec<StopRule>) -> Self {
+        self.stop_rules = stop_rules;
+        self
+    }
 }
 
 impl Default for TradeIn {
@@ -235,6 +266,7 @@ impl Default for TradeIn {
             date_in: to_dbtime(Local::now()),
             trade_type: TradeType::MarketInLong,
             status: TradeStatus::default(),
+            stop_rules: vec![],
         }
     }
 }
@@ -304,6 +336,19 @@ pub struct TradeOut {
     pub run_up_per: f64,
     pub draw_down: f64,
     pub draw_down_per: f64,
+    #[serde(default)]
+    pub commission: f64,
+    #[serde(default)]
+    pub spread_cost: f64,
+    #[serde(default)]
+    pub swap: f64,
+}
+
+impl TradeOut {
+    // Price profit before commissions and swaps
+    pub fn gross_profit(&self) -> f64 {
+        self.profit + self.commission - self.swap
+    }
 }
 
 impl Trade for TradeOut {
@@ -366,14 +411,22 @@ pub fn resolve_trade_in(
     trade_type: &TradeType,
     order: Option<&Order>,
     tick: &InstrumentTick,
+    account: &Account,
+    fx_rates: &FxRates,
+    config: &RsAlgoConfig,
 ) -> TradeResult {
-    let execution_mode = mode::from_str(&env::var("EXECUTION_MODE").unwrap());
-    let order_engine = &env::var("ORDER_ENGINE").unwrap();
-    let activation_source = &env::var("ORDER_ACTIVATION_SOURCE").unwrap();
+    let execution_mode = config.execution_mode.clone();
+    let order_engine = &config.order_engine;
+    let activation_source = &config.order_activation_source;
 
     let symbol = instrument.symbol();
     let index = calculate_trade_index(index, order, &execution_mode);
-    let size = trade_size;
+
+    // Order entries were already sized when the orders were prepared
+    let size = match order {
+        Some(order) => order.size,
+        None => trade_size,
+    };
 
     if trade_type.is_entry() {
         let spread = tick.spread();
@@ -420,8 +473,15 @@ pub fn resolve_trade_in(
             false => id,
         };
 
+        // Backtest entries the account can't afford never get filled
         let status = match execution_mode.is_back_test() {
-            true => TradeStatus::Fulfilled,
+            true => match account.check_margin(symbol, price_in, size, fx_rates, current_date) {
+                Ok(_) => TradeStatus::Fulfilled,
+                Err(err) => {
+                    log::warn!("Trade {} rejected: {}", id, err);
+                    TradeStatus::Rejected
+                }
+            },
             false => TradeStatus::default(),
         };
 
@@ -436,6 +496,7 @@ pub fn resolve_trade_in(
             date_in: to_dbtime(current_date),
             trade_type: trade_type.clone(),
             status,
+            stop_rules: vec![],
         })
     } else {
         TradeResult::None
@@ -449,22 +510,21 @@ pub fn resolve_trade_out(
     trade_type: &TradeType,
     order: Option<&Order>,
     tick: &InstrumentTick,
-) -> TradeResult {
+    fx_rates: &FxRates,
+    config: &RsAlgoConfig,
+) -> Result<TradeResult> {
     let symbol = &instrument.symbol;
     let data = &instrument.data;
     let spread = tick.spread();
     let trade_in_type = &trade_in.trade_type;
     let index_in = trade_in.index_in;
     let spread_in = trade_in.spread;
-    let execution_mode = mode::from_str(&env::var("EXECUTION_MODE").unwrap());
-    let non_profitable_outs = &env::var("NON_PROFITABLE_OUTS")
-        .unwrap()
-        .parse::<bool>()
-        .unwrap();
-    let order_engine = &env::var("ORDER_ENGINE").unwrap();
-
-    let leverage = env::var("LEVERAGE").unwrap().parse::<f64>().unwrap();
-    let equity = env::var("EQUITY").unwrap().parse::<f64>().unwrap();
+    let execution_mode = config.execution_mode.clone();
+    let non_profitable_outs = &config.non_profitable_outs;
+    let order_engine = &config.order_engine;
+
+    let leverage = config.leverage;
+    let equity = config.equity;
     let size = trade_in.size;
 
     let index = calculate_trade_index(index, order, &execution_mode);
@@ -556,12 +616,14 @@ pub fn resolve_trade_out(
         };
 
         let profit = match execution_mode.is_back_test() {
-            true => calculate_trade_profit(size, price_in, price_out, leverage, trade_type, symbol),
-            false => 0.,
-        };
-
-        let profit_per = match execution_mode.is_back_test() {
-            true => calc::calculate_trade_profit_per(equity, profit, price_in, symbol),
+            true => fx_rates.trade_profit(
+                size,
+                price_in,
+                price_out,
+                trade_type,
+                symbol,
+                current_date,
+            )?,
             false => 0.,
         };
 
@@ -590,7 +652,7 @@ pub fn resolve_trade_out(
             false => TradeStatus::default(),
         };
 
-        TradeResult::TradeOut(TradeOut {
+        let mut trade_out = TradeOut {
             id: trade_in.id,
             index_in,
             price_in,
@@ -606,16 +668,27 @@ pub fn resolve_trade_out(
             spread_out: spread,
             date_out,
             profit,
-            profit_per,
+            profit_per: 0.,
             run_up,
             run_up_per,
             draw_down,
             draw_down_per,
             status,
-        })
+            commission: 0.,
+            spread_cost: 0.,
+            swap: 0.,
+        };
+
+        if execution_mode.is_back_test() {
+            CostModel::from_config(config, symbol).apply(&mut trade_out, symbol, fx_rates)?;
+            trade_out.profit_per =
+                calc::calculate_trade_profit_per(equity, trade_out.profit, price_in, symbol);
+        }
+
+        Ok(TradeResult::TradeOut(trade_out))
     } else {
         log::warn!("Non profitable {:?} exit", trade_type);
-        TradeResult::None
+        Ok(TradeResult::None)
     }
 }
 
@@ -623,20 +696,15 @@ pub fn wait_for_new_trade(
     index: usize,
     instrument: &Instrument,
     trades_out: &Vec<TradeOut>,
+    config: &RsAlgoConfig,
 ) -> bool {
-    let wait_for_new_entry = env::var("WAIT_FOR_NEW_ENTRY")
-        .unwrap()
-        .parse::<bool>()
-        .unwrap();
+    let wait_for_new_entry = config.wait_for_new_entry;
 
     match wait_for_new_entry {
         true => {
-            let execution_mode = mode::from_str(&env::var("EXECUTION_MODE").unwrap());
+            let execution_mode = config.execution_mode.clone();
 
-            let candles_until_new_operation = env::var("CANDLES_UNTIL_NEW_ENTRY")
-                .unwrap()
-                .parse::<i64>()
-                .unwrap();
+            let candles_until_new_operation = config.candles_until_new_entry;
 
             let time_frame = instrument.time_frame();
 
@@ -671,20 +739,19 @@ pub fn wait_for_new_trade(
     }
 }
 
-pub fn wait_for_closing_trade(index: usize, instrument: &Instrument, trade_in: &TradeIn) -> bool {
-    let wait_for_new_exit = env::var("WAIT_FOR_NEW_EXIT")
-        .unwrap()
-        .parse::<bool>()
-        .unwrap();
+pub fn wait_for_closing_trade(
+    index: usize,
+    instrument: &Instrument,
+    trade_in: &TradeIn,
+    config: &RsAlgoConfig,
+) -> bool {
+    let wait_for_new_exit = config.wait_for_new_exit;
 
     match wait_for_new_exit {
         true => {
-            let execution_mode = mode::from_str(&env::var("EXECUTION_MODE").unwrap());
+            let execution_mode = config.execution_mode.clone();
 
-            let candles_until_new_operation = env::var("CANDLES_UNTIL_NEW_ENTRY")
-                .unwrap()
-                .parse::<i64>()
-                .unwrap();
+            let candles_until_new_operation = config.candles_until_new_entry;
 
             let time_frame = instrument.time_frame();
 
@@ -725,13 +792,14 @@ pub fn calculate_trade_index(
 pub fn calculate_trade_stats(
     trade_in: &TradeIn,
     trade_out: &TradeOut,
+    symbol: &str,
     data: &Vec<Candle>,
-) -> TradeOut {
-    let symbol = &env::var("SYMBOL").unwrap();
-    let leverage = env::var("LEVERAGE").unwrap().parse::<f64>().unwrap();
-    let equity = env::var("EQUITY").unwrap().parse::<f64>().unwrap();
+    fx_rates: &FxRates,
+    config: &RsAlgoConfig,
+) -> Result<TradeOut> {
+    let equity = config.equity;
     //let equity = env::var("EQUITY").unwrap().parse::<f64>().unwrap();
-    let execution_mode = mode::from_str(&env::var("EXECUTION_MODE").unwrap());
+    let execution_mode = config.execution_mode.clone();
 
     log::info!("Calculating Trade stats in {:?} mode", &execution_mode);
 
@@ -742,18 +810,24 @@ pub fn calculate_trade_stats(
 
     let profit = match execution_mode.is_bot() {
         true => trade_out.profit,
-        false => calculate_trade_profit(size, price_in, price_out, leverage, trade_type, symbol),
+        false => fx_rates.trade_profit(
+            size,
+            price_in,
+            price_out,
+            trade_type,
+            symbol,
+            from_dbtime(&trade_out.date_out),
+        )?,
     };
 
     log::info!("Trade profit: {:?}", profit);
 
-    let profit_per = calculate_trade_profit_per(equity, profit, price_in, symbol);
     let run_up = calculate_trade_runup(data, price_in, trade_type);
     let run_up_per = calculate_trade_runup_per(run_up, price_in, trade_type);
     let draw_down = calculate_trade_drawdown(data, price_in, trade_type);
     let draw_down_per = calculate_trade_drawdown_per(draw_down, price_in, trade_type);
 
-    TradeOut {
+    let mut stats = TradeOut {
         id: trade_out.id,
         index_in: trade_in.index_in,
         price_in: trade_in.price_in,
@@ -769,13 +843,24 @@ pub fn calculate_trade_stats(
         spread_out: trade_out.spread_out,
         date_out: trade_out.date_out,
         profit,
-        profit_per,
+        profit_per: 0.,
         run_up,
         run_up_per,
         draw_down,
         draw_down_per,
         status: trade_out.status.clone(),
+        commission: trade_out.commission,
+        spread_cost: trade_out.spread_cost,
+        swap: trade_out.swap,
+    };
+
+    // Broker profits already come net of costs
+    if !execution_mode.is_bot() {
+        CostModel::from_config(config, symbol).apply(&mut stats, symbol, fx_rates)?;
     }
+
+    stats.profit_per = calculate_trade_profit_per(equity, stats.profit, price_in, symbol);
+    Ok(stats)
 }
 
 pub fn trade_exists<T: Trade>(trades: &[T], search_id: usize) -> bool {
@@ -1,50 +1,51 @@
 use super::highs_lows::*;
 use super::pattern::pattern_active_result;
+use crate::config::RsAlgoConfig;
 use crate::helpers::date::*;
 use crate::scanner::candle::Candle;
 use crate::scanner::pattern::{DataPoints, PatternActive, PatternType};
 use crate::scanner::prices::*;
 
-pub fn is_ascendant_top(data: &DataPoints) -> bool {
+pub fn is_ascendant_top(data: &DataPoints, config: &RsAlgoConfig) -> bool {
     is_upperhighs_top(data)
         && is_upperlows_bottom(data)
         //&& points_are_in_slope(data)
-        && bands_have_same_slope(data)
-        && are_parallel_lines(data)
-        && has_minimum_bars(data)
+        && bands_have_same_slope(data, config)
+        && are_parallel_lines(data, config)
+        && has_minimum_bars(data, config)
         //// && has_minimum_target(data)
         && data[0].1 > data[1].1 && data[2].1 > data[3].1
 }
 
-pub fn is_ascendant_bottom(data: &DataPoints) -> bool {
+pub fn is_ascendant_bottom(data: &DataPoints, config: &RsAlgoConfig) -> bool {
     is_upperhighs_bottom(data)
         && is_upperlows_top(data)
         //&& points_are_in_slope(data)
-        && bands_have_same_slope(data)
-        && are_parallel_lines(data)
-        && has_minimum_bars(data)
+        && bands_have_same_slope(data, config)
+        && are_parallel_lines(data, config)
+        && has_minimum_bars(data, config)
         // && has_minimum_target(data)
         && data[0].1 < data[1].1 && data[2].1 < data[3].1
 }
 
-pub fn is_descendant_top(data: &DataPoints) -> bool {
+pub fn is_descendant_top(data: &DataPoints, config: &RsAlgoConfig) -> bool {
     is_lower_highs_top(data)
         && is_lower_lows_bottom(data)
         //&& points_are_in_slope(data)
-        && bands_have_same_slope(data)
-       && are_parallel_lines(data)
-        && has_minimum_bars(data)
+        && bands_have_same_slope(data, config)
+       && are_parallel_lines(data, config)
+        && has_minimum_bars(data, config)
         // && has_minimum_target(data)
         && data[0].1 > data[1].1 && data[2].1 > data[3].1
 }
 
-pub fn is_descendant_bottom(data: &DataPoints) -> bool {
+pub fn is_descendant_bottom(data: &DataPoints, config: &RsAlgoConfig) -> bool {
     is_lower_highs_bottom(data)
         && is_lower_lows_top(data)
         //&& points_are_in_slope(data)
-        && bands_have_same_slope(data)
-        && are_parallel_lines(data)
-        && has_minimum_bars(data)
+        && bands_have_same_slope(data, config)
+        && are_parallel_lines(data, config)
+        && has_minimum_bars(data, config)
         // && has_minimum_target(data)
         && data[0].1 < data[1].1 && data[2].1 < data[3].1
 }
@@ -53,10 +54,11 @@ pub fn channel_descendant_top_active(
     data: &DataPoints,
     candles: &Vec<Candle>,
     pattern_type: PatternType,
+    config: &RsAlgoConfig,
 ) -> PatternActive {
     pattern_active_result(
         data,
-        price_is_upperupper_band_top(data, candles, &pattern_type),
+        price_is_upperupper_band_top(data, candles, &pattern_type, config),
         (false, 0, 0., to_dbtime(Local::now() - Duration::days(1000))),
     )
 }
@@ -65,10 +67,11 @@ pub fn channel_descendant_bottom_active(
     data: &DataPoints,
     candles: &Vec<Candle>,
     pattern_type: PatternType,
+    config: &RsAlgoConfig,
 ) -> PatternActive {
     pattern_active_result(
         data,
-        price_is_upperupper_band_bottom(data, candles, &pattern_type),
+        price_is_upperupper_band_bottom(data, candles, &pattern_type, config),
         (false, 0, 0., to_dbtime(Local::now() - Duration::days(1000))),
     )
 }
@@ -77,11 +80,12 @@ pub fn channel_ascendant_top_active(
     data: &DataPoints,
     candles: &Vec<Candle>,
     pattern_type: PatternType,
+    config: &RsAlgoConfig,
 ) -> PatternActive {
     pattern_active_result(
         data,
         (false, 0, 0., to_dbtime(Local::now() - Duration::days(1000))),
-        price_is_lower_low_band_bottom(data, candles, &pattern_type),
+        price_is_lower_low_band_bottom(data, candles, &pattern_type, config),
     )
 }
 
@@ -89,10 +93,11 @@ pub fn channel_ascendant_bottom_active(
     data: &DataPoints,
     candles: &Vec<Candle>,
     pattern_type: PatternType,
+    config: &RsAlgoConfig,
 ) -> PatternActive {
     pattern_active_result(
         data,
         (false, 0, 0., to_dbtime(Local::now() - Duration::days(1000))),
-        price_is_lower_low_band_top(data, candles, &pattern_type),
+        price_is_lower_low_band_top(data, candles, &pattern_type, config),
     )
 }
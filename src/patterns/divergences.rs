@@ -29,15 +29,9 @@ impl Divergences {
         candles: &Vec<Candle>,
         maxima_minima: &Vec<(usize, f64)>,
     ) {
-        let prominence = env::var("DIVERGENCE_MIN_PROMINENCE")
-            .unwrap()
-            .parse::<f64>()
-            .unwrap();
+        let prominence = config.divergence_min_prominence;
 
-        let min_distance = env::var("DIVERGENCE_PROMINENCE_MIN_DISTANCE")
-            .unwrap()
-            .parse::<usize>()
-            .unwrap();
+        let min_distance = config.divergence_prominence_min_distance;
 
         let data_indicators: [(IndicatorType, &Vec<f64>); 2] = [
             (IndicatorType::Rsi, indicators.rsi().get_data_a()),
@@ -77,20 +71,11 @@ impl Divergences {
         candles: &Vec<Candle>,
         maxima_minima: &Vec<(usize, f64)>,
     ) {
-        let local_max_points = env::var("PATTERNS_MAX_POINTS")
-            .unwrap()
-            .parse::<usize>()
-            .unwrap();
+        let local_max_points = config.patterns_max_points;
 
-        let min_points = env::var("DIVERGENCES_MIN_POINTS")
-            .unwrap()
-            .parse::<usize>()
-            .unwrap();
+        let min_points = config.divergences_min_points;
 
-        let window_size = env::var("DIVERGENCES_WINDOW_SIZE")
-            .unwrap()
-            .parse::<usize>()
-            .unwrap();
+        let window_size = config.divergences_window_size;
 
         let fake_date = Local::now() - Duration::days(1000);
 
@@ -1,18 +1,18 @@
 use super::highs_lows::*;
 use super::pattern::pattern_active_result;
+use crate::config::RsAlgoConfig;
 use crate::helpers::comp::*;
 use crate::scanner::candle::Candle;
 use crate::scanner::prices::*;
 
 use crate::scanner::pattern::{DataPoints, PatternActive, PatternType};
-use std::env;
 
-pub fn is_top(data: &DataPoints) -> bool {
-    let equal_threshold = env::var("EQUAL_THRESHOLD").unwrap().parse::<f64>().unwrap();
+pub fn is_top(data: &DataPoints, config: &RsAlgoConfig) -> bool {
+    let equal_threshold = config.equal_threshold;
     let threshold = percentage_change(data[1].1, data[0].1) * equal_threshold;
 
     is_equal(data[3].1, data[1].1, threshold)
-        && has_minimum_bars(data)
+        && has_minimum_bars(data, config)
         // && has_minimum_target(data)
         && data[0].1 < data[1].1
         && data[2].1 < data[1].1 && data[2].1 < data[3].1
@@ -22,20 +22,21 @@ pub fn top_active(
     data: &DataPoints,
     candles: &Vec<Candle>,
     pattern_type: PatternType,
+    config: &RsAlgoConfig,
 ) -> PatternActive {
     pattern_active_result(
         data,
-        price_is_upperlast_high_top(data, candles, &pattern_type),
-        price_is_lower_last_low_bottom(data, candles, &pattern_type),
+        price_is_upperlast_high_top(data, candles, &pattern_type, config),
+        price_is_lower_last_low_bottom(data, candles, &pattern_type, config),
     )
 }
 
-pub fn is_bottom(data: &DataPoints) -> bool {
-    let equal_threshold = env::var("EQUAL_THRESHOLD").unwrap().parse::<f64>().unwrap();
+pub fn is_bottom(data: &DataPoints, config: &RsAlgoConfig) -> bool {
+    let equal_threshold = config.equal_threshold;
     let threshold = percentage_change(data[1].1, data[0].1) * equal_threshold;
 
     is_equal(data[3].1, data[1].1, threshold)
-        && has_minimum_bars(data)
+        && has_minimum_bars(data, config)
         // && has_minimum_target(data)
         && data[0].1 > data[1].1
         && data[2].1 > data[1].1 && data[2].1 > data[3].1
@@ -45,10 +46,11 @@ pub fn bottom_active(
     data: &DataPoints,
     candles: &Vec<Candle>,
     pattern_type: PatternType,
+    config: &RsAlgoConfig,
 ) -> PatternActive {
     pattern_active_result(
         data,
-        price_is_upperlast_high_bottom(data, candles, &pattern_type),
-        price_is_lower_last_low_top(data, candles, &pattern_type),
+        price_is_upperlast_high_bottom(data, candles, &pattern_type, config),
+        price_is_lower_last_low_top(data, candles, &pattern_type, config),
     )
 }
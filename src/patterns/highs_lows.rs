@@ -1,3 +1,4 @@
+use crate::config::RsAlgoConfig;
 use crate::helpers::slope_intercept::slope_intercept;
 
 use super::pattern::pattern_active_result;
@@ -6,7 +7,6 @@ use crate::scanner::prices::*;
 
 use crate::helpers::comp::*;
 use crate::scanner::pattern::{DataPoints, PatternActive, PatternType};
-use std::env;
 
 pub fn is_upperhighs_upperlows_top(data: &DataPoints) -> bool {
     is_upperhighs_top(data) && is_upperlows_bottom(data)
@@ -20,11 +20,12 @@ pub fn ascendant_top_active(
     data: &DataPoints,
     candles: &Vec<Candle>,
     pattern_type: PatternType,
+    config: &RsAlgoConfig,
 ) -> PatternActive {
     pattern_active_result(
         data,
-        price_is_upperlast_high_top(data, candles, &pattern_type),
-        price_is_lower_last_low_bottom(data, candles, &pattern_type),
+        price_is_upperlast_high_top(data, candles, &pattern_type, config),
+        price_is_lower_last_low_bottom(data, candles, &pattern_type, config),
     )
 }
 
@@ -32,11 +33,12 @@ pub fn ascendant_bottom_active(
     data: &DataPoints,
     candles: &Vec<Candle>,
     pattern_type: PatternType,
+    config: &RsAlgoConfig,
 ) -> PatternActive {
     pattern_active_result(
         data,
-        price_is_upperlast_high_bottom(data, candles, &pattern_type),
-        price_is_lower_last_low_top(data, candles, &pattern_type),
+        price_is_upperlast_high_bottom(data, candles, &pattern_type, config),
+        price_is_lower_last_low_top(data, candles, &pattern_type, config),
     )
 }
 
@@ -52,11 +54,12 @@ pub fn descendant_top_active(
     data: &DataPoints,
     candles: &Vec<Candle>,
     pattern_type: PatternType,
+    config: &RsAlgoConfig,
 ) -> PatternActive {
     pattern_active_result(
         data,
-        price_is_lower_last_low_top(data, candles, &pattern_type),
-        price_is_upperlast_high_bottom(data, candles, &pattern_type),
+        price_is_lower_last_low_top(data, candles, &pattern_type, config),
+        price_is_upperlast_high_bottom(data, candles, &pattern_type, config),
     )
 }
 
@@ -64,11 +67,12 @@ pub fn descendant_bottom_active(
     data: &DataPoints,
     candles: &Vec<Candle>,
     pattern_type: PatternType,
+    config: &RsAlgoConfig,
 ) -> PatternActive {
     pattern_active_result(
         data,
-        price_is_lower_last_low_bottom(data, candles, &pattern_type),
-        price_is_upperlast_high_top(data, candles, &pattern_type),
+        price_is_lower_last_low_bottom(data, candles, &pattern_type, config),
+        price_is_upperlast_high_top(data, candles, &pattern_type, config),
     )
 }
 
@@ -108,48 +112,42 @@ pub fn is_lower_lows_bottom(data: &DataPoints) -> bool {
     data[1].1 > data[3].1
 }
 
-pub fn upper_band_is_equal_top(data: &DataPoints) -> bool {
-    let equal_threshold = env::var("EQUAL_THRESHOLD").unwrap().parse::<f64>().unwrap();
+pub fn upper_band_is_equal_top(data: &DataPoints, config: &RsAlgoConfig) -> bool {
+    let equal_threshold = config.equal_threshold;
     let threshold = percentage_change(data[2].1, data[0].1) * equal_threshold;
 
     is_equal(data[0].1, data[2].1, threshold)
 }
 
-pub fn upper_band_is_equal_bottom(data: &DataPoints) -> bool {
-    let equal_threshold = env::var("EQUAL_THRESHOLD").unwrap().parse::<f64>().unwrap();
+pub fn upper_band_is_equal_bottom(data: &DataPoints, config: &RsAlgoConfig) -> bool {
+    let equal_threshold = config.equal_threshold;
     let threshold = percentage_change(data[3].1, data[1].1) * equal_threshold;
 
     is_equal(data[3].1, data[1].1, threshold)
 }
 
-pub fn lower_band_is_equal_bottom(data: &DataPoints) -> bool {
-    let equal_threshold = env::var("EQUAL_THRESHOLD").unwrap().parse::<f64>().unwrap();
+pub fn lower_band_is_equal_bottom(data: &DataPoints, config: &RsAlgoConfig) -> bool {
+    let equal_threshold = config.equal_threshold;
     let threshold = percentage_change(data[3].1, data[1].1) * equal_threshold;
     is_equal(data[3].1, data[1].1, threshold)
 }
 
-pub fn lower_band_is_equal_top(data: &DataPoints) -> bool {
-    let equal_threshold = env::var("EQUAL_THRESHOLD").unwrap().parse::<f64>().unwrap();
+pub fn lower_band_is_equal_top(data: &DataPoints, config: &RsAlgoConfig) -> bool {
+    let equal_threshold = config.equal_threshold;
     let threshold = percentage_change(data[2].1, data[0].1) * equal_threshold;
     is_equal(data[0].1, data[2].1, threshold)
 }
 
-pub fn bands_have_same_slope(data: &DataPoints) -> bool {
-    let threshold = env::var("SLOPE_DEVIATION_THRESHOLD")
-        .unwrap()
-        .parse::<f64>()
-        .unwrap();
+pub fn bands_have_same_slope(data: &DataPoints, config: &RsAlgoConfig) -> bool {
+    let threshold = config.slope_deviation_threshold;
     let (slope_1, _y1) = slope_intercept(data[0].0 as f64, data[0].1, data[2].0 as f64, data[2].1);
     let (slope_2, _y2) = slope_intercept(data[1].0 as f64, data[1].1, data[3].0 as f64, data[3].1);
     let diff = (slope_1 / slope_2).abs();
     diff < threshold
 }
 
-pub fn are_parallel_lines(data: &DataPoints) -> bool {
-    let threshold = env::var("PARALLEL_LINES_THRESHOLD")
-        .unwrap()
-        .parse::<f64>()
-        .unwrap();
+pub fn are_parallel_lines(data: &DataPoints, config: &RsAlgoConfig) -> bool {
+    let threshold = config.parallel_lines_threshold;
     let (slope_1, _y1) = slope_intercept(data[0].0 as f64, data[0].1, data[2].0 as f64, data[2].1);
     let (slope_2, _y2) = slope_intercept(data[1].0 as f64, data[1].1, data[3].0 as f64, data[3].1);
 
@@ -157,19 +155,13 @@ pub fn are_parallel_lines(data: &DataPoints) -> bool {
     angle_degree <= threshold
 }
 
-pub fn is_valid_triangle(data: &DataPoints) -> bool {
-    let threshold = env::var("PARALLEL_LINES_THRESHOLD")
-        .unwrap()
-        .parse::<f64>()
-        .unwrap();
+pub fn is_valid_triangle(data: &DataPoints, config: &RsAlgoConfig) -> bool {
+    let threshold = config.parallel_lines_threshold;
     ((data[0].1 * data[3].1) / (data[2].1 * data[1].1)).abs() <= threshold
 }
 
-pub fn is_valid_broadening(data: &DataPoints) -> bool {
-    let _threshold = env::var("PARALLEL_LINES_THRESHOLD")
-        .unwrap()
-        .parse::<f64>()
-        .unwrap();
+pub fn is_valid_broadening(data: &DataPoints, config: &RsAlgoConfig) -> bool {
+    let _threshold = config.parallel_lines_threshold;
     let (slope_1, _y1) = slope_intercept(data[0].0 as f64, data[0].1, data[2].0 as f64, data[2].1);
     let (slope_2, _y2) = slope_intercept(data[1].0 as f64, data[1].1, data[3].0 as f64, data[3].1);
 
@@ -179,20 +171,14 @@ pub fn is_valid_broadening(data: &DataPoints) -> bool {
     true
 }
 
-pub fn has_minimum_bars(data: &DataPoints) -> bool {
-    let min_bars = env::var("MIN_PATTERN_BARS")
-        .unwrap()
-        .parse::<usize>()
-        .unwrap();
+pub fn has_minimum_bars(data: &DataPoints, config: &RsAlgoConfig) -> bool {
+    let min_bars = config.min_pattern_bars;
 
     data[2].0 - data[0].0 > min_bars && data[3].0 - data[1].0 > min_bars
 }
 
-pub fn has_minimum_target(data: &DataPoints) -> bool {
-    let min_target = env::var("MINIMUM_PATTERN_TARGET")
-        .unwrap()
-        .parse::<f64>()
-        .unwrap();
+pub fn has_minimum_target(data: &DataPoints, config: &RsAlgoConfig) -> bool {
+    let min_target = config.minimum_pattern_target;
     //true
     //FIXME
     percentage_change(data[0].1, data[1].1).abs() > min_target
@@ -1,5 +1,6 @@
 use super::peaks::Peaks;
 
+use crate::config::RsAlgoConfig;
 use crate::error::Result;
 use crate::helpers::comp::*;
 use crate::helpers::date::*;
@@ -7,7 +8,6 @@ use crate::scanner::horizontal_level::{HorizontalLevel, HorizontalLevelType};
 
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use std::env;
 /*
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum HorizontalLevelType {
@@ -63,18 +63,13 @@ impl HorizontalLevels {
         current_price: &f64,
         local_maxima: &Vec<(usize, f64)>,
         _peak_type: &Vec<f64>,
+        config: &RsAlgoConfig,
     ) -> Result<Vec<HorizontalLevel>> {
         let mut hash: HashMap<String, HorizontalLevel> = HashMap::new();
 
-        let min_ocurrences = env::var("MIN_HORIZONTAL_LEVELS_OCCURENCES")
-            .unwrap()
-            .parse::<usize>()
-            .unwrap();
+        let min_ocurrences = config.min_horizontal_levels_occurences;
 
-        let threshold = env::var("HORIZONTAL_LEVELS_THRESHOLD")
-            .unwrap()
-            .parse::<f64>()
-            .unwrap();
+        let threshold = config.horizontal_levels_threshold;
 
         for (peak_index, peak_price) in local_maxima {
             let price = *peak_price;
@@ -114,16 +109,26 @@ impl HorizontalLevels {
         Ok(result)
     }
 
-    pub fn calculate_horizontal_highs(&mut self, current_price: &f64, peaks: &Peaks) -> Result<()> {
+    pub fn calculate_horizontal_highs(
+        &mut self,
+        current_price: &f64,
+        peaks: &Peaks,
+        config: &RsAlgoConfig,
+    ) -> Result<()> {
         self.highs = self
-            .calculate_bands(current_price, peaks.local_maxima(), peaks.highs())
+            .calculate_bands(current_price, peaks.local_maxima(), peaks.highs(), config)
             .unwrap();
         Ok(())
     }
 
-    pub fn calculate_horizontal_lows(&mut self, current_price: &f64, peaks: &Peaks) -> Result<()> {
+    pub fn calculate_horizontal_lows(
+        &mut self,
+        current_price: &f64,
+        peaks: &Peaks,
+        config: &RsAlgoConfig,
+    ) -> Result<()> {
         self.lows = self
-            .calculate_bands(current_price, peaks.local_maxima(), peaks.lows())
+            .calculate_bands(current_price, peaks.local_maxima(), peaks.lows(), config)
             .unwrap();
         Ok(())
     }
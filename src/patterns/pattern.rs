@@ -1,3 +1,4 @@
+use crate::config::RsAlgoConfig;
 use crate::helpers::poly::poly_fit;
 use crate::helpers::slope_intercept::{add_next_bottom_points, add_next_top_points};
 use crate::patterns::*;
@@ -10,7 +11,6 @@ use crate::models::status::Status;
 use crate::scanner::pattern::*;
 
 use serde::{Deserialize, Serialize};
-use std::env;
 
 pub type PatternActiveResult = (bool, usize, f64, DbDateTime);
 
@@ -40,21 +40,13 @@ impl Patterns {
         maxima: &Vec<(usize, f64)>,
         minima: &Vec<(usize, f64)>,
         candles: &Vec<Candle>,
+        config: &RsAlgoConfig,
     ) {
-        let local_max_points = env::var("PATTERNS_MAX_POINTS")
-            .unwrap()
-            .parse::<usize>()
-            .unwrap();
+        let local_max_points = config.patterns_max_points;
 
-        let min_points = env::var("PATTERNS_MIN_POINTS")
-            .unwrap()
-            .parse::<usize>()
-            .unwrap();
+        let min_points = config.patterns_min_points;
 
-        let window_size = env::var("PATTERNS_WINDOW_SIZE")
-            .unwrap()
-            .parse::<usize>()
-            .unwrap();
+        let window_size = config.patterns_window_size;
 
         let mut max_start = 0;
         let mut max_end = 0;
@@ -91,19 +83,20 @@ impl Patterns {
                 match iter.next() {
                     Some(window) => {
                         let mut data_points = window.to_vec();
-                     
+
                         let last_index = data_points.last().unwrap().0;
                         let candle_date = candles.get(last_index).unwrap().date();
 
                         //let change = self.calculate_change(&data_points);
 
-                        if rectangle::is_renctangle_top(&data_points) {
+                        if rectangle::is_renctangle_top(&data_points, config) {
                             data_points = add_next_top_points(data_points);
 
                             let is_pattern_active = rectangle::rectangle_top_active(
                                 &data_points,
                                 candles,
                                 PatternType::Rectangle,
+                                config,
                             );
 
                             self.set_pattern(
@@ -115,13 +108,14 @@ impl Patterns {
                                 is_pattern_active,
                             );
                             not_founded = true;
-                        } else if rectangle::is_renctangle_bottom(&data_points) {
+                        } else if rectangle::is_renctangle_bottom(&data_points, config) {
                             data_points = add_next_bottom_points(data_points);
 
                             let is_pattern_active = rectangle::rectangle_bottom_active(
                                 &data_points,
                                 candles,
                                 PatternType::Rectangle,
+                                config,
                             );
 
                             self.set_pattern(
@@ -133,11 +127,15 @@ impl Patterns {
                                 is_pattern_active,
                             );
                             not_founded = true;
-                        } else if double::is_top(&data_points) {
+                        } else if double::is_top(&data_points, config) {
                             data_points = add_next_top_points(data_points);
 
-                            let is_pattern_active =
-                                double::top_active(&data_points, candles, PatternType::DoubleTop);
+                            let is_pattern_active = double::top_active(
+                                &data_points,
+                                candles,
+                                PatternType::DoubleTop,
+                                config,
+                            );
 
                             self.set_pattern(
                                 PatternType::DoubleTop,
@@ -148,13 +146,14 @@ impl Patterns {
                                 is_pattern_active,
                             );
                             not_founded = true;
-                        } else if double::is_bottom(&data_points) {
+                        } else if double::is_bottom(&data_points, config) {
                             data_points = add_next_bottom_points(data_points);
 
                             let is_pattern_active = double::top_active(
                                 &data_points,
                                 candles,
                                 PatternType::DoubleBottom,
+                                config,
                             );
 
                             self.set_pattern(
@@ -166,13 +165,14 @@ impl Patterns {
                                 is_pattern_active,
                             );
                             not_founded = true;
-                        } else if channel::is_ascendant_top(&data_points) {
+                        } else if channel::is_ascendant_top(&data_points, config) {
                             data_points = add_next_top_points(data_points);
 
                             let is_pattern_active = channel::channel_ascendant_top_active(
                                 &data_points,
                                 candles,
                                 PatternType::ChannelUp,
+                                config,
                             );
 
                             self.set_pattern(
@@ -184,13 +184,14 @@ impl Patterns {
                                 is_pattern_active,
                             );
                             not_founded = true;
-                        } else if channel::is_ascendant_bottom(&data_points) {
+                        } else if channel::is_ascendant_bottom(&data_points, config) {
                             data_points = add_next_bottom_points(data_points);
 
                             let is_pattern_active = channel::channel_ascendant_bottom_active(
                                 &data_points,
                                 candles,
                                 PatternType::ChannelUp,
+                                config,
                             );
 
                             self.set_pattern(
@@ -202,13 +203,14 @@ impl Patterns {
                                 is_pattern_active,
                             );
                             not_founded = true;
-                        } else if triangle::is_ascendant_top(&data_points) {
+                        } else if triangle::is_ascendant_top(&data_points, config) {
                             data_points = add_next_top_points(data_points);
 
                             let is_pattern_active = triangle::ascendant_top_active(
                                 &data_points,
                                 candles,
                                 PatternType::TriangleUp,
+                                config,
                             );
 
                             self.set_pattern(
@@ -220,13 +222,14 @@ impl Patterns {
                                 is_pattern_active,
                             );
                             not_founded = true;
-                        } else if triangle::is_ascendant_bottom(&data_points) {
+                        } else if triangle::is_ascendant_bottom(&data_points, config) {
                             data_points = add_next_bottom_points(data_points);
 
                             let is_pattern_active = triangle::ascendant_bottom_active(
                                 &data_points,
                                 candles,
                                 PatternType::TriangleUp,
+                                config,
                             );
 
                             self.set_pattern(
@@ -238,13 +241,14 @@ impl Patterns {
                                 is_pattern_active,
                             );
                             not_founded = true;
-                        } else if triangle::is_descendant_top(&data_points) {
+                        } else if triangle::is_descendant_top(&data_points, config) {
                             data_points = add_next_top_points(data_points);
 
                             let is_pattern_active = triangle::descendant_top_active(
                                 &data_points,
                                 candles,
                                 PatternType::TriangleDown,
+                                config,
                             );
 
                             self.set_pattern(
@@ -256,13 +260,14 @@ impl Patterns {
                                 is_pattern_active,
                             );
                             not_founded = true;
-                        } else if triangle::is_descendant_bottom(&data_points) {
+                        } else if triangle::is_descendant_bottom(&data_points, config) {
                             data_points = add_next_bottom_points(data_points);
 
                             let is_pattern_active = triangle::descendant_bottom_active(
                                 &data_points,
                                 candles,
                                 PatternType::TriangleDown,
+                                config,
                             );
 
                             self.set_pattern(
@@ -274,13 +279,14 @@ impl Patterns {
                                 is_pattern_active,
                             );
                             not_founded = true;
-                        } else if channel::is_descendant_top(&data_points) {
+                        } else if channel::is_descendant_top(&data_points, config) {
                             data_points = add_next_top_points(data_points);
 
                             let is_pattern_active = channel::channel_descendant_top_active(
                                 &data_points,
                                 candles,
                                 PatternType::ChannelDown,
+                                config,
                             );
 
                             self.set_pattern(
@@ -292,12 +298,13 @@ impl Patterns {
                                 is_pattern_active,
                             );
                             not_founded = true;
-                        } else if channel::is_descendant_bottom(&data_points) {
+                        } else if channel::is_descendant_bottom(&data_points, config) {
                             data_points = add_next_bottom_points(data_points);
                             let is_pattern_active = channel::channel_descendant_bottom_active(
                                 &data_points,
                                 candles,
                                 PatternType::ChannelDown,
+                                config,
                             );
 
                             self.set_pattern(
@@ -309,13 +316,14 @@ impl Patterns {
                                 is_pattern_active,
                             );
                             not_founded = true;
-                        } else if broadening::is_top(&data_points) {
+                        } else if broadening::is_top(&data_points, config) {
                             data_points = add_next_top_points(data_points);
 
                             let is_pattern_active = broadening::broadening_top_active(
                                 &data_points,
                                 candles,
                                 PatternType::Broadening,
+                                config,
                             );
 
                             self.set_pattern(
@@ -327,13 +335,14 @@ impl Patterns {
                                 is_pattern_active,
                             );
                             not_founded = true;
-                        } else if triangle::is_symmetrical_top(&data_points) {
+                        } else if triangle::is_symmetrical_top(&data_points, config) {
                             data_points = add_next_top_points(data_points);
 
                             let is_pattern_active = triangle::symetrical_top_active(
                                 &data_points,
                                 candles,
                                 PatternType::TriangleSym,
+                                config,
                             );
 
                             self.set_pattern(
@@ -345,13 +354,14 @@ impl Patterns {
                                 is_pattern_active,
                             );
                             not_founded = true;
-                        } else if triangle::is_symmetrical_bottom(&data_points) {
+                        } else if triangle::is_symmetrical_bottom(&data_points, config) {
                             data_points = add_next_bottom_points(data_points);
 
                             let is_pattern_active = triangle::symetrical_bottom_active(
                                 &data_points,
                                 candles,
                                 PatternType::TriangleSym,
+                                config,
                             );
 
                             self.set_pattern(
@@ -363,13 +373,14 @@ impl Patterns {
                                 is_pattern_active,
                             );
                             not_founded = true;
-                        } else if broadening::is_bottom(&data_points) {
+                        } else if broadening::is_bottom(&data_points, config) {
                             data_points = add_next_bottom_points(data_points);
 
                             let is_pattern_active = broadening::broadening_top_active(
                                 &data_points,
                                 candles,
                                 PatternType::Broadening,
+                                config,
                             );
 
                             self.set_pattern(
@@ -388,6 +399,7 @@ impl Patterns {
                                 &data_points,
                                 candles,
                                 PatternType::HigherHighsHigherLows,
+                                config,
                             );
 
                             self.set_pattern(
@@ -406,6 +418,7 @@ impl Patterns {
                                 &data_points,
                                 candles,
                                 PatternType::HigherHighsHigherLows,
+                                config,
                             );
 
                             self.set_pattern(
@@ -424,6 +437,7 @@ impl Patterns {
                                 &data_points,
                                 candles,
                                 PatternType::HigherHighsHigherLows,
+                                config,
                             );
 
                             self.set_pattern(
@@ -442,6 +456,7 @@ impl Patterns {
                                 &data_points,
                                 candles,
                                 PatternType::HigherHighsHigherLows,
+                                config,
                             );
 
                             self.set_pattern(
@@ -1,9 +1,9 @@
+use crate::config::RsAlgoConfig;
 use crate::error::Result;
 
 use crate::helpers::maxima_minima::maxima_minima;
 use crate::helpers::regression::kernel_regression;
 use serde::{Deserialize, Serialize};
-use std::env;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Peaks {
@@ -73,38 +73,25 @@ impl Peaks {
         &self.extrema_minima
     }
 
-    pub fn calculate_peaks(&mut self, max_price: &f64, min_price: &f64) -> Result<()> {
-        let mut local_prominence = env::var("LOCAL_MIN_PROMINENCE")
-            .unwrap()
-            .parse::<f64>()
-            .unwrap();
+    pub fn calculate_peaks(
+        &mut self,
+        max_price: &f64,
+        min_price: &f64,
+        config: &RsAlgoConfig,
+    ) -> Result<()> {
+        let mut local_prominence = config.local_min_prominence;
 
-        let _extrema_prominence = env::var("EXTREMA_MIN_PROMINENCE")
-            .unwrap()
-            .parse::<f64>()
-            .unwrap();
+        let _extrema_prominence = config.extrema_min_prominence;
 
-        let local_min_distance = env::var("LOCAL_PROMINENCE_MIN_DISTANCE")
-            .unwrap()
-            .parse::<usize>()
-            .unwrap();
+        let local_min_distance = config.local_prominence_min_distance;
 
-        let _extrema_min_distance = env::var("EXTREMA_PROMINENCE_MIN_DISTANCE")
-            .unwrap()
-            .parse::<usize>()
-            .unwrap();
+        let _extrema_min_distance = config.extrema_prominence_min_distance;
 
-        let price_smoothing = env::var("KERNEL_PRICE_SMOOTHING")
-            .unwrap()
-            .parse::<bool>()
-            .unwrap();
+        let price_smoothing = config.kernel_price_smoothing;
 
-        let mut kernel_bandwidth = env::var("KERNEL_REGRESSION_BANDWIDTH")
-            .unwrap()
-            .parse::<f64>()
-            .unwrap();
+        let mut kernel_bandwidth = config.kernel_regression_bandwidth;
 
-        let price_source = env::var("PRICE_SOURCE").unwrap();
+        let price_source = config.price_source.clone();
 
         let mut smooth_highs: Vec<f64> = vec![];
         let mut smooth_lows: Vec<f64> = vec![];
@@ -120,14 +107,16 @@ impl Peaks {
 
             for x in &self.close {
                 if price_source == "highs_lows" {
-                    let smoothed_high = kernel_regression(kernel_bandwidth, *x, &self.highs);
-                    let smoothed_low = kernel_regression(kernel_bandwidth, *x, &self.lows);
+                    let smoothed_high =
+                        kernel_regression(kernel_bandwidth, *x, &self.highs, config);
+                    let smoothed_low = kernel_regression(kernel_bandwidth, *x, &self.lows, config);
                     smooth_highs.push(smoothed_high.abs());
                     smooth_lows.push(smoothed_low.abs());
                     self.smooth_highs.push((candle_id, smoothed_high.abs()));
                     self.smooth_lows.push((candle_id, smoothed_low.abs()));
                 } else {
-                    let smoothed_close = kernel_regression(kernel_bandwidth, *x, &self.close);
+                    let smoothed_close =
+                        kernel_regression(kernel_bandwidth, *x, &self.close, config);
                     smooth_close.push(smoothed_close.abs());
                     self.smooth_close.push((candle_id, smoothed_close.abs()));
                 }
@@ -149,8 +138,13 @@ impl Peaks {
             },
         };
 
-        self.local_maxima =
-            maxima_minima(source.0, source.1, local_prominence, local_min_distance)?;
+        self.local_maxima = maxima_minima(
+            source.0,
+            source.1,
+            local_prominence,
+            local_min_distance,
+            config,
+        )?;
 
         self.local_maxima
             .sort_by(|(id_a, _indicator_value_a), (id_b, _indicator_value_b)| id_a.cmp(id_b));
@@ -160,6 +154,7 @@ impl Peaks {
             source.3,
             local_prominence,
             local_min_distance,
+            config,
         )?;
 
         self.local_minima
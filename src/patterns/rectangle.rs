@@ -1,42 +1,44 @@
 use super::highs_lows::*;
 use super::pattern::pattern_active_result;
+use crate::config::RsAlgoConfig;
 use crate::scanner::candle::Candle;
 use crate::scanner::prices::*;
 
 use crate::helpers::comp::*;
 use crate::scanner::pattern::{DataPoints, PatternActive, PatternType};
 
-use std::env;
-
-pub fn is_renctangle_top(data: &DataPoints) -> bool {
-    let equal_threshold = env::var("EQUAL_THRESHOLD").unwrap().parse::<f64>().unwrap();
+pub fn is_renctangle_top(data: &DataPoints, config: &RsAlgoConfig) -> bool {
+    let equal_threshold = config.equal_threshold;
     let _threshold = percentage_change(data[1].1, data[0].1) * equal_threshold;
 
-    upper_band_is_equal_top(data)
-        && lower_band_is_equal_bottom(data)
-        && bands_have_same_slope(data)
-        && are_parallel_lines(data) && has_minimum_bars(data)
+    upper_band_is_equal_top(data, config)
+        && lower_band_is_equal_bottom(data, config)
+        && bands_have_same_slope(data, config)
+        && are_parallel_lines(data, config)
+        && has_minimum_bars(data, config)
 }
 
-pub fn is_renctangle_bottom(data: &DataPoints) -> bool {
-    let equal_threshold = env::var("EQUAL_THRESHOLD").unwrap().parse::<f64>().unwrap();
+pub fn is_renctangle_bottom(data: &DataPoints, config: &RsAlgoConfig) -> bool {
+    let equal_threshold = config.equal_threshold;
     let _threshold = percentage_change(data[1].1, data[0].1) * equal_threshold;
 
-    upper_band_is_equal_bottom(data)
-        && lower_band_is_equal_top(data)
-        && bands_have_same_slope(data)
-        && are_parallel_lines(data) && has_minimum_bars(data)
+    upper_band_is_equal_bottom(data, config)
+        && lower_band_is_equal_top(data, config)
+        && bands_have_same_slope(data, config)
+        && are_parallel_lines(data, config)
+        && has_minimum_bars(data, config)
 }
 
 pub fn rectangle_top_active(
     data: &DataPoints,
     candles: &Vec<Candle>,
     pattern_type: PatternType,
+    config: &RsAlgoConfig,
 ) -> PatternActive {
     pattern_active_result(
         data,
-        price_is_upperupper_band_top(data, candles, &pattern_type),
-        price_is_lower_low_band_bottom(data, candles, &pattern_type),
+        price_is_upperupper_band_top(data, candles, &pattern_type, config),
+        price_is_lower_low_band_bottom(data, candles, &pattern_type, config),
     )
 }
 
@@ -44,10 +46,11 @@ pub fn rectangle_bottom_active(
     data: &DataPoints,
     candles: &Vec<Candle>,
     pattern_type: PatternType,
+    config: &RsAlgoConfig,
 ) -> PatternActive {
     pattern_active_result(
         data,
-        price_is_upperupper_band_bottom(data, candles, &pattern_type),
-        price_is_lower_low_band_top(data, candles, &pattern_type),
+        price_is_upperupper_band_bottom(data, candles, &pattern_type, config),
+        price_is_lower_low_band_top(data, candles, &pattern_type, config),
     )
 }
@@ -1,5 +1,4 @@
-use std::env;
-
+use crate::config::RsAlgoConfig;
 use crate::error::{Result, RsAlgoError, RsAlgoErrorKind};
 use crate::helpers::comp::percentage_change;
 use crate::helpers::date::*;
@@ -539,9 +538,9 @@ impl CandleBuilder {
         (open.floor() == close.floor()) || (open - close).abs() <= ((high - low) * 0.1)
     }
 
-    fn identify_candle_type(&self) -> CandleType {
-        let candle_types = env::var("CANDLE_TYPES").unwrap().parse::<bool>().unwrap();
-        let selected_candle_types_str = env::var("SELECTED_CANDLE_TYPES").unwrap_or_default();
+    fn identify_candle_type(&self, config: &RsAlgoConfig) -> CandleType {
+        let candle_types = config.candle_types;
+        let selected_candle_types_str = &config.selected_candle_types;
 
         let _selected_candle_types: HashMap<CandleType, bool> = selected_candle_types_str
             .split(',')
@@ -619,7 +618,7 @@ impl CandleBuilder {
         CandleType::Default
     }
 
-    pub fn build(self) -> Result<Candle> {
+    pub fn build(self, config: &RsAlgoConfig) -> Result<Candle> {
         if let (
             Some(date),
             Some(open),
@@ -642,7 +641,7 @@ impl CandleBuilder {
             self.logarithmic,
         ) {
             Ok(Candle {
-                candle_type: self.identify_candle_type(),
+                candle_type: self.identify_candle_type(config),
                 date,
                 open,
                 close,
@@ -1,3 +1,4 @@
+use crate::config::RsAlgoConfig;
 use crate::error::Result;
 use crate::helpers::date::*;
 use crate::helpers::maxima_minima::*;
@@ -5,7 +6,6 @@ use crate::indicators::Indicator;
 use crate::models::indicator::IndicatorType;
 use crate::scanner::pattern::{DataPoints, Pattern};
 use std::cmp::Ordering;
-use std::env;
 
 use serde::{Deserialize, Serialize};
 
@@ -61,16 +61,11 @@ impl Divergences {
         patterns: &Vec<Pattern>,
         candles: &Vec<Candle>,
         maxima_minima: &Vec<(usize, f64)>,
+        config: &RsAlgoConfig,
     ) {
-        let prominence = env::var("DIVERGENCE_MIN_PROMINENCE")
-            .unwrap()
-            .parse::<f64>()
-            .unwrap();
+        let prominence = config.divergence_min_prominence;
 
-        let min_distance = env::var("DIVERGENCE_PROMINENCE_MIN_DISTANCE")
-            .unwrap()
-            .parse::<usize>()
-            .unwrap();
+        let min_distance = config.divergence_prominence_min_distance;
 
         let data_indicators: [(IndicatorType, &Vec<f64>); 2] = [
             (IndicatorType::Rsi, indicators.rsi().get_data_a()),
@@ -98,6 +93,7 @@ impl Divergences {
                 patterns,
                 candles,
                 maxima_minima,
+                config,
             );
         }
     }
@@ -110,21 +106,13 @@ impl Divergences {
         patterns: &Vec<Pattern>,
         candles: &Vec<Candle>,
         maxima_minima: &Vec<(usize, f64)>,
+        config: &RsAlgoConfig,
     ) {
-        let local_max_points = env::var("PATTERNS_MAX_POINTS")
-            .unwrap()
-            .parse::<usize>()
-            .unwrap();
+        let local_max_points = config.patterns_max_points;
 
-        let min_points = env::var("DIVERGENCES_MIN_POINTS")
-            .unwrap()
-            .parse::<usize>()
-            .unwrap();
+        let min_points = config.divergences_min_points;
 
-        let window_size = env::var("DIVERGENCES_WINDOW_SIZE")
-            .unwrap()
-            .parse::<usize>()
-            .unwrap();
+        let window_size = config.divergences_window_size;
 
         let fake_date = Local::now() - Duration::days(1000);
 
@@ -9,6 +9,7 @@
 // pub mod stoch;
 // pub mod tema;
 
+use crate::config::RsAlgoConfig;
 use crate::error::Result;
 
 use crate::indicators::atr::Atr;
@@ -21,7 +22,6 @@ use crate::indicators::rsi::Rsi;
 //use crate::models::indicator::Indicator;
 use crate::indicators::Indicator;
 use serde::{Deserialize, Serialize};
-use std::env;
 
 // pub trait Indicator {
 //     fn new() -> Result<Self>
@@ -53,10 +53,10 @@ pub struct Indicators {
 }
 
 impl Indicators {
-    pub fn new() -> Result<Self> {
-        let ema_a = &env::var("EMA_A").unwrap().parse::<usize>().unwrap();
-        let ema_b = &env::var("EMA_B").unwrap().parse::<usize>().unwrap();
-        let ema_c = &env::var("EMA_C").unwrap().parse::<usize>().unwrap();
+    pub fn new(config: &RsAlgoConfig) -> Result<Self> {
+        let ema_a = &config.ema_a;
+        let ema_b = &config.ema_b;
+        let ema_c = &config.ema_c;
 
         Ok(Self {
             macd: Macd::new().unwrap(),
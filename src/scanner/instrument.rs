@@ -1,3 +1,4 @@
+use crate::config::RsAlgoConfig;
 use crate::error::{Result, RsAlgoError, RsAlgoErrorKind};
 use crate::helpers::comp::*;
 use crate::helpers::date::*;
@@ -5,8 +6,8 @@ use crate::indicators::{Indicator, Indicators};
 use crate::models::indicator::CompactIndicators;
 use crate::models::mode::ExecutionMode;
 
+use crate::models::market::*;
 use crate::models::time_frame::*;
-use crate::models::{market::*, mode};
 use crate::scanner::candle::{Candle, CandleType};
 use crate::scanner::divergence::{CompactDivergences, Divergences};
 use crate::scanner::horizontal_level::HorizontalLevels;
@@ -15,7 +16,6 @@ use crate::scanner::pattern::Patterns;
 use crate::scanner::peak::Peaks;
 
 use serde::{Deserialize, Serialize};
-use std::env;
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct CompactInstrument {
@@ -188,8 +188,9 @@ impl Instrument {
         data: &Vec<(DateTime<Local>, f64, f64, f64, f64, f64)>,
         adapted: (DateTime<Local>, f64, f64, f64, f64, f64, bool),
         logarithmic_scanner: bool,
+        config: &RsAlgoConfig,
     ) -> Candle {
-        let execution_mode = mode::from_str(&env::var("EXECUTION_MODE").unwrap());
+        let execution_mode = config.execution_mode.clone();
         let date = adapted.0;
         let volume = adapted.5;
         let is_closed = match execution_mode {
@@ -214,7 +215,7 @@ impl Instrument {
             .is_closed(is_closed)
             .previous_candles(vec![data[prev_0], data[prev_1], data[prev_2], data[prev_3]])
             .logarithmic(logarithmic_scanner)
-            .build()
+            .build(config)
             .unwrap()
     }
 
@@ -224,6 +225,7 @@ impl Instrument {
         data: (DateTime<Local>, f64, f64, f64, f64, f64, bool),
         candles: &Vec<Candle>,
         logarithmic_scanner: bool,
+        config: &RsAlgoConfig,
     ) -> Candle {
         let date = data.0;
         let volume = data.5;
@@ -286,43 +288,36 @@ impl Instrument {
             .is_closed(is_closed)
             .previous_candles(vec![data_0, data_1, data_2, data_3])
             .logarithmic(logarithmic_scanner)
-            .build()
+            .build(config)
             .unwrap()
     }
 
     pub fn set_data(
         &mut self,
         data: Vec<(DateTime<Local>, f64, f64, f64, f64, f64)>,
+        config: &RsAlgoConfig,
     ) -> Result<()> {
         let mut avg_volume = vec![];
-        let logarithmic_scanner = env::var("LOGARITHMIC_SCANNER")
-            .unwrap()
-            .parse::<bool>()
-            .unwrap();
+        let logarithmic_scanner = config.logarithmic_scanner;
 
-        let process_indicators = env::var("INDICATORS").unwrap().parse::<bool>().unwrap();
-        let process_patterns = env::var("PATTERNS").unwrap().parse::<bool>().unwrap();
-        let process_divergences = env::var("DIVERGENCES").unwrap().parse::<bool>().unwrap();
-        let process_horizontal_levels = env::var("HORIZONTAL_LEVELS")
-            .unwrap()
-            .parse::<bool>()
-            .unwrap();
+        let process_indicators = config.indicators;
+        let process_patterns = config.patterns;
+        let process_divergences = config.divergences;
+        let process_horizontal_levels = config.horizontal_levels;
 
-        let avg_volume_days = env::var("AVG_VOLUME_DAYS")
-            .unwrap()
-            .parse::<usize>()
-            .unwrap();
+        let avg_volume_days = config.avg_volume_days;
 
         //FIXME Instrument should be Optional
-        self.init();
+        self.init(config);
 
         let candles: Vec<Candle> = data
             .iter()
             .enumerate()
             .map(|(id, x)| {
                 let formated_dohlcc = format_open_until(*x, &self.time_frame, false);
-                let candle = self.process_candle(id, &data, formated_dohlcc, logarithmic_scanner);
-                let _num_bars = env::var("NUM_BARS").unwrap().parse::<usize>().unwrap();
+                let candle =
+                    self.process_candle(id, &data, formated_dohlcc, logarithmic_scanner, config);
+                let _num_bars = config.num_bars;
 
                 let low = candle.low();
                 let high = candle.high();
@@ -356,11 +351,16 @@ impl Instrument {
                         let delete_previous = false;
 
                         self.indicators
-                            .next(ohlc_indicators, delete_previous, &self.time_frame().clone())
+                            .next(
+                                ohlc_indicators,
+                                delete_previous,
+                                &self.time_frame().clone(),
+                                config,
+                            )
                             .unwrap();
                     } else {
                         self.indicators
-                            .init_indicators(&self.time_frame().clone(), false)
+                            .init_indicators(&self.time_frame().clone(), false, config)
                             .unwrap();
                     }
                 }
@@ -372,7 +372,7 @@ impl Instrument {
         if !candles.is_empty() {
             if process_patterns {
                 self.peaks
-                    .calculate_peaks(&self.max_price, &self.min_price, &0)
+                    .calculate_peaks(&self.max_price, &self.min_price, &0, config)
                     .unwrap();
 
                 let local_maxima = self.peaks.local_maxima();
@@ -385,6 +385,7 @@ impl Instrument {
                     local_maxima,
                     local_minima,
                     &candles,
+                    config,
                 );
 
                 // self.patterns.process_pattern(
@@ -397,11 +398,11 @@ impl Instrument {
 
             if process_horizontal_levels {
                 self.horizontal_levels
-                    .calculate_horizontal_highs(&self.current_price, &self.peaks)
+                    .calculate_horizontal_highs(&self.current_price, &self.peaks, config)
                     .unwrap();
 
                 self.horizontal_levels
-                    .calculate_horizontal_lows(&self.current_price, &self.peaks)
+                    .calculate_horizontal_lows(&self.current_price, &self.peaks, config)
                     .unwrap();
             }
 
@@ -451,39 +452,42 @@ impl Instrument {
         Ok(())
     }
 
-    pub fn next(&mut self, data: (DateTime<Local>, f64, f64, f64, f64, f64)) -> Result<Candle> {
-        let logarithmic_scanner = env::var("LOGARITHMIC_SCANNER")
-            .unwrap()
-            .parse::<bool>()
-            .unwrap();
+    pub fn next(
+        &mut self,
+        data: (DateTime<Local>, f64, f64, f64, f64, f64),
+        config: &RsAlgoConfig,
+    ) -> Result<Candle> {
+        let logarithmic_scanner = config.logarithmic_scanner;
 
         let next_id = self.data.len();
         let last_candle = &self.data().last().unwrap().clone();
 
         let time_frame = &self.time_frame.clone();
         let formated_dohlcc = format_open_until(data, &self.time_frame, true);
-        let formated_candle =
-            self.generate_candle(next_id, formated_dohlcc, &self.data, logarithmic_scanner);
+        let formated_candle = self.generate_candle(
+            next_id,
+            formated_dohlcc,
+            &self.data,
+            logarithmic_scanner,
+            config,
+        );
 
         if formated_candle.is_closed() {
             self.close_last_candle();
-            self.close_indicators(last_candle);
+            self.close_indicators(last_candle, config);
         } else {
             self.adapt_last_candle_tf(formated_candle.clone(), last_candle, time_frame);
             let updated_candle = &self.data.last().unwrap().clone();
-            self.update_tmp_indicators(updated_candle);
+            self.update_tmp_indicators(updated_candle, config);
         }
 
         Ok(formated_candle)
     }
 
-    pub fn close_indicators(&mut self, candle: &Candle) {
-        let logarithmic_scanner = env::var("LOGARITHMIC_SCANNER")
-            .unwrap()
-            .parse::<bool>()
-            .unwrap();
+    pub fn close_indicators(&mut self, candle: &Candle, config: &RsAlgoConfig) {
+        let logarithmic_scanner = config.logarithmic_scanner;
 
-        let process_indicators = env::var("INDICATORS").unwrap().parse::<bool>().unwrap();
+        let process_indicators = config.indicators;
 
         if process_indicators {
             let ohlc_indicators = self.get_scale_ohlc_indicators(candle, logarithmic_scanner);
@@ -493,8 +497,8 @@ impl Instrument {
         }
     }
 
-    pub fn update_tmp_indicators(&mut self, candle: &Candle) {
-        let process_indicators = env::var("INDICATORS").unwrap().parse::<bool>().unwrap();
+    pub fn update_tmp_indicators(&mut self, candle: &Candle, config: &RsAlgoConfig) {
+        let process_indicators = config.indicators;
         if process_indicators {
             self.indicators
                 .next_tmp_indicators(candle, &self.data)
@@ -502,23 +506,25 @@ impl Instrument {
         }
     }
 
-    pub fn next_peaks(&mut self, candle: &Candle) {
-        let _logarithmic_scanner = env::var("LOGARITHMIC_SCANNER")
-            .unwrap()
-            .parse::<bool>()
-            .unwrap();
-        let process_patterns = env::var("PATTERNS").unwrap().parse::<bool>().unwrap();
+    pub fn next_peaks(&mut self, candle: &Candle, config: &RsAlgoConfig) {
+        let _logarithmic_scanner = config.logarithmic_scanner;
+        let process_patterns = config.patterns;
         if process_patterns {
             //FIXME peaks next detection iterates the whole list
             self.peaks.update(candle);
             self.peaks
-                .calculate_peaks(&self.max_price, &self.min_price, &0)
+                .calculate_peaks(&self.max_price, &self.min_price, &0, config)
                 .unwrap();
             let local_maxima = self.peaks.local_maxima();
             let local_minima = self.peaks.local_minima();
             //Fixme CALCULATE ONLY LAST CHANGES clean first pattern
-            self.patterns
-                .update(PatternSize::Local, local_maxima, local_minima, &self.data);
+            self.patterns.update(
+                PatternSize::Local,
+                local_maxima,
+                local_minima,
+                &self.data,
+                config,
+            );
         }
     }
 
@@ -565,20 +571,19 @@ impl Instrument {
         &mut self,
         data: (DateTime<Local>, f64, f64, f64, f64, f64),
         time_frame: &Option<TimeFrameType>,
+        config: &RsAlgoConfig,
     ) {
-        let logarithmic_scanner = env::var("LOGARITHMIC_SCANNER")
-            .unwrap()
-            .parse::<bool>()
-            .unwrap();
+        let logarithmic_scanner = config.logarithmic_scanner;
 
-        let num_bars = env::var("NUM_BARS").unwrap().parse::<usize>().unwrap();
+        let num_bars = config.num_bars;
 
         let adapted = format_open_until(data, &self.time_frame, true);
         let open_from = get_open_from(data, &self.time_frame, true);
 
         let len = self.data.len();
 
-        let mut candle = self.generate_candle(len, adapted, &self.data, logarithmic_scanner);
+        let mut candle =
+            self.generate_candle(len, adapted, &self.data, logarithmic_scanner, config);
 
         candle.set_is_closed(false);
         candle.set_date(open_from);
@@ -592,12 +597,12 @@ impl Instrument {
         self.data.push(candle);
     }
 
-    pub fn init(&mut self) {
+    pub fn init(&mut self, config: &RsAlgoConfig) {
         self.data = vec![];
         self.peaks = Peaks::new();
         self.horizontal_levels = HorizontalLevels::new();
         self.patterns = Patterns::new();
-        self.indicators = Indicators::new().unwrap();
+        self.indicators = Indicators::new(config).unwrap();
         self.divergences = Divergences::new().unwrap();
         //self.set_data(data).unwrap();
     }
@@ -639,7 +644,7 @@ impl InstrumentBuilder {
         self
     }
 
-    pub fn build(self) -> Result<Instrument> {
+    pub fn build(self, config: &RsAlgoConfig) -> Result<Instrument> {
         if let (Some(symbol), Some(market), Some(time_frame)) =
             (self.symbol, self.market, self.time_frame)
         {
@@ -650,14 +655,14 @@ impl InstrumentBuilder {
                 current_price: 0.,
                 date: to_dbtime(Local::now()), //FIXME
                 current_candle: CandleType::Default,
-                min_price: env::var("MIN_PRICE").unwrap().parse::<f64>().unwrap(),
-                max_price: env::var("MIN_PRICE").unwrap().parse::<f64>().unwrap(),
+                min_price: config.min_price,
+                max_price: config.min_price,
                 avg_volume: 0.,
                 data: vec![],
                 peaks: Peaks::new(),
                 horizontal_levels: HorizontalLevels::new(),
                 patterns: Patterns::new(),
-                indicators: Indicators::new().unwrap(),
+                indicators: Indicators::new(config).unwrap(),
                 divergences: Divergences::new().unwrap(),
             })
         } else {
@@ -1,3 +1,4 @@
+use crate::config::RsAlgoConfig;
 use crate::helpers::slope_intercept::add_next_bottom_points;
 use crate::helpers::{poly::poly_fit, slope_intercept::add_next_top_points};
 
@@ -10,8 +11,6 @@ use crate::helpers::date::*;
 use crate::models::status::Status;
 use serde::{Deserialize, Serialize};
 
-use std::env;
-
 pub type PatternActiveResult = (bool, usize, f64, DbDateTime);
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -132,11 +131,9 @@ impl Patterns {
         maxima: &Vec<(usize, f64)>,
         minima: &Vec<(usize, f64)>,
         candles: &Vec<Candle>,
+        config: &RsAlgoConfig,
     ) {
-        let _pattern_prev_bars = env::var("MAX_PREVIOUS_BARS")
-            .unwrap()
-            .parse::<usize>()
-            .unwrap();
+        let _pattern_prev_bars = config.max_previous_bars;
         let _len = candles.len();
 
         // let last_candles = match len.cmp(&0) {
@@ -147,7 +144,7 @@ impl Patterns {
         //     _ => vec![],
         // };
 
-        self.detect_pattern(pattern_size, maxima, minima, candles);
+        self.detect_pattern(pattern_size, maxima, minima, candles, config);
     }
 
     pub fn update(
@@ -156,8 +153,9 @@ impl Patterns {
         maxima: &Vec<(usize, f64)>,
         minima: &Vec<(usize, f64)>,
         candles: &Vec<Candle>,
+        config: &RsAlgoConfig,
     ) {
-        self.detect_pattern(pattern_size, maxima, minima, candles);
+        self.detect_pattern(pattern_size, maxima, minima, candles, config);
     }
 
     pub fn detect_pattern(
@@ -166,21 +164,13 @@ impl Patterns {
         maxima: &Vec<(usize, f64)>,
         minima: &Vec<(usize, f64)>,
         candles: &Vec<Candle>,
+        config: &RsAlgoConfig,
     ) {
-        let local_max_points = env::var("PATTERNS_MAX_POINTS")
-            .unwrap()
-            .parse::<usize>()
-            .unwrap();
+        let local_max_points = config.patterns_max_points;
 
-        let min_points = env::var("PATTERNS_MIN_POINTS")
-            .unwrap()
-            .parse::<usize>()
-            .unwrap();
+        let min_points = config.patterns_min_points;
 
-        let _window_size = env::var("PATTERNS_WINDOW_SIZE")
-            .unwrap()
-            .parse::<usize>()
-            .unwrap();
+        let _window_size = config.patterns_window_size;
 
         let mut max_start = 0;
         let mut max_end = 0;
@@ -281,13 +271,14 @@ impl Patterns {
                         let last_index = data_points.last().unwrap().0;
                         let candle_date = candles.get(last_index).unwrap().date();
 
-                        if rectangle::is_renctangle_top(&data_points) {
+                        if rectangle::is_renctangle_top(&data_points, config) {
                             data_points = add_next_top_points(data_points);
 
                             let is_pattern_active = rectangle::rectangle_top_active(
                                 &data_points,
                                 candles,
                                 PatternType::Rectangle,
+                                config,
                             );
 
                             self.set_pattern(
@@ -299,13 +290,14 @@ impl Patterns {
                                 is_pattern_active,
                             );
                             not_found = true;
-                        } else if rectangle::is_renctangle_bottom(&data_points) {
+                        } else if rectangle::is_renctangle_bottom(&data_points, config) {
                             data_points = add_next_bottom_points(data_points);
 
                             let is_pattern_active = rectangle::rectangle_bottom_active(
                                 &data_points,
                                 candles,
                                 PatternType::Rectangle,
+                                config,
                             );
 
                             self.set_pattern(
@@ -317,11 +309,15 @@ impl Patterns {
                                 is_pattern_active,
                             );
                             not_found = true;
-                        } else if double::is_top(&data_points) {
+                        } else if double::is_top(&data_points, config) {
                             data_points = add_next_top_points(data_points);
 
-                            let is_pattern_active =
-                                double::top_active(&data_points, candles, PatternType::DoubleTop);
+                            let is_pattern_active = double::top_active(
+                                &data_points,
+                                candles,
+                                PatternType::DoubleTop,
+                                config,
+                            );
 
                             self.set_pattern(
                                 PatternType::DoubleTop,
@@ -332,13 +328,14 @@ impl Patterns {
                                 is_pattern_active,
                             );
                             not_found = true;
-                        } else if double::is_bottom(&data_points) {
+                        } else if double::is_bottom(&data_points, config) {
                             data_points = add_next_bottom_points(data_points);
 
                             let is_pattern_active = double::top_active(
                                 &data_points,
                                 candles,
                                 PatternType::DoubleBottom,
+                                config,
                             );
 
                             self.set_pattern(
@@ -350,13 +347,14 @@ impl Patterns {
                                 is_pattern_active,
                             );
                             not_found = true;
-                        } else if channel::is_ascendant_top(&data_points) {
+                        } else if channel::is_ascendant_top(&data_points, config) {
                             data_points = add_next_top_points(data_points);
 
                             let is_pattern_active = channel::channel_ascendant_top_active(
                                 &data_points,
                                 candles,
                                 PatternType::ChannelUp,
+                                config,
                             );
 
                             self.set_pattern(
@@ -368,13 +366,14 @@ impl Patterns {
                                 is_pattern_active,
                             );
                             not_found = true;
-                        } else if channel::is_ascendant_bottom(&data_points) {
+                        } else if channel::is_ascendant_bottom(&data_points, config) {
                             data_points = add_next_bottom_points(data_points);
 
                             let is_pattern_active = channel::channel_ascendant_bottom_active(
                                 &data_points,
                                 candles,
                                 PatternType::ChannelUp,
+                                config,
                             );
 
                             self.set_pattern(
@@ -386,13 +385,14 @@ impl Patterns {
                                 is_pattern_active,
                             );
                             not_found = true;
-                        } else if triangle::is_ascendant_top(&data_points) {
+                        } else if triangle::is_ascendant_top(&data_points, config) {
                             data_points = add_next_top_points(data_points);
 
                             let is_pattern_active = triangle::ascendant_top_active(
                                 &data_points,
                                 candles,
                                 PatternType::TriangleUp,
+                                config,
                             );
 
                             self.set_pattern(
@@ -404,13 +404,14 @@ impl Patterns {
                                 is_pattern_active,
                             );
                             not_found = true;
-                        } else if triangle::is_ascendant_bottom(&data_points) {
+                        } else if triangle::is_ascendant_bottom(&data_points, config) {
                             data_points = add_next_bottom_points(data_points);
 
                             let is_pattern_active = triangle::ascendant_bottom_active(
                                 &data_points,
                                 candles,
                                 PatternType::TriangleUp,
+                                config,
                             );
 
                             self.set_pattern(
@@ -422,13 +423,14 @@ impl Patterns {
                                 is_pattern_active,
                             );
                             not_found = true;
-                        } else if triangle::is_descendant_top(&data_points) {
+                        } else if triangle::is_descendant_top(&data_points, config) {
                             data_points = add_next_top_points(data_points);
 
                             let is_pattern_active = triangle::descendant_top_active(
                                 &data_points,
                                 candles,
                                 PatternType::TriangleDown,
+                                config,
                             );
 
                             self.set_pattern(
@@ -440,13 +442,14 @@ impl Patterns {
                                 is_pattern_active,
                             );
                             not_found = true;
-                        } else if triangle::is_descendant_bottom(&data_points) {
+                        } else if triangle::is_descendant_bottom(&data_points, config) {
                             data_points = add_next_bottom_points(data_points);
 
                             let is_pattern_active = triangle::descendant_bottom_active(
                                 &data_points,
                                 candles,
                                 PatternType::TriangleDown,
+                                config,
                             );
 
                             self.set_pattern(
@@ -458,13 +461,14 @@ impl Patterns {
                                 is_pattern_active,
                             );
                             not_found = true;
-                        } else if channel::is_descendant_top(&data_points) {
+                        } else if channel::is_descendant_top(&data_points, config) {
                             data_points = add_next_top_points(data_points);
 
                             let is_pattern_active = channel::channel_descendant_top_active(
                                 &data_points,
                                 candles,
                                 PatternType::ChannelDown,
+                                config,
                             );
 
                             self.set_pattern(
@@ -476,12 +480,13 @@ impl Patterns {
                                 is_pattern_active,
                             );
                             not_found = true;
-                        } else if channel::is_descendant_bottom(&data_points) {
+                        } else if channel::is_descendant_bottom(&data_points, config) {
                             data_points = add_next_bottom_points(data_points);
                             let is_pattern_active = channel::channel_descendant_bottom_active(
                                 &data_points,
                                 candles,
                                 PatternType::ChannelDown,
+                                config,
                             );
 
                             self.set_pattern(
@@ -493,13 +498,14 @@ impl Patterns {
                                 is_pattern_active,
                             );
                             not_found = true;
-                        } else if broadening::is_top(&data_points) {
+                        } else if broadening::is_top(&data_points, config) {
                             data_points = add_next_top_points(data_points);
 
                             let is_pattern_active = broadening::broadening_top_active(
                                 &data_points,
                                 candles,
                                 PatternType::Broadening,
+                                config,
                             );
 
                             self.set_pattern(
@@ -511,13 +517,14 @@ impl Patterns {
                                 is_pattern_active,
                             );
                             not_found = true;
-                        } else if triangle::is_symmetrical_top(&data_points) {
+                        } else if triangle::is_symmetrical_top(&data_points, config) {
                             data_points = add_next_top_points(data_points);
 
                             let is_pattern_active = triangle::symetrical_top_active(
                                 &data_points,
                                 candles,
                                 PatternType::TriangleSym,
+                                config,
                             );
 
                             self.set_pattern(
@@ -529,13 +536,14 @@ impl Patterns {
                                 is_pattern_active,
                             );
                             not_found = true;
-                        } else if triangle::is_symmetrical_bottom(&data_points) {
+                        } else if triangle::is_symmetrical_bottom(&data_points, config) {
                             data_points = add_next_bottom_points(data_points);
 
                             let is_pattern_active = triangle::symetrical_bottom_active(
                                 &data_points,
                                 candles,
                                 PatternType::TriangleSym,
+                                config,
                             );
 
                             self.set_pattern(
@@ -547,13 +555,14 @@ impl Patterns {
                                 is_pattern_active,
                             );
                             not_found = true;
-                        } else if broadening::is_bottom(&data_points) {
+                        } else if broadening::is_bottom(&data_points, config) {
                             data_points = add_next_bottom_points(data_points);
 
                             let is_pattern_active = broadening::broadening_top_active(
                                 &data_points,
                                 candles,
                                 PatternType::Broadening,
+                                config,
                             );
 
                             self.set_pattern(
@@ -572,6 +581,7 @@ impl Patterns {
                                 &data_points,
                                 candles,
                                 PatternType::HigherHighsHigherLows,
+                                config,
                             );
 
                             self.set_pattern(
@@ -590,6 +600,7 @@ impl Patterns {
                                 &data_points,
                                 candles,
                                 PatternType::HigherHighsHigherLows,
+                                config,
                             );
 
                             self.set_pattern(
@@ -608,6 +619,7 @@ impl Patterns {
                                 &data_points,
                                 candles,
                                 PatternType::HigherHighsHigherLows,
+                                config,
                             );
 
                             self.set_pattern(
@@ -626,6 +638,7 @@ impl Patterns {
                                 &data_points,
                                 candles,
                                 PatternType::HigherHighsHigherLows,
+                                config,
                             );
 
                             self.set_pattern(
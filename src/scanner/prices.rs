@@ -1,10 +1,10 @@
+use crate::config::RsAlgoConfig;
 use crate::scanner::candle::Candle;
 
 use crate::helpers::comp::percentage_change;
 use crate::helpers::date::*;
 use crate::helpers::slope_intercept::slope_intercept;
 use crate::scanner::pattern::*;
-use std::env;
 
 pub type PriceBreak = (bool, usize, f64, DbDateTime);
 
@@ -12,102 +12,112 @@ pub fn price_is_upperupper_band_top(
     data: &DataPoints,
     candles: &Vec<Candle>,
     _pattern_type: &PatternType,
+    config: &RsAlgoConfig,
 ) -> PriceBreak {
     let points = vec![data[2], data[4]];
     let break_price_comparator = |price: f64, price_break: f64| price > price_break;
-    search_price_break(points, candles, &break_price_comparator)
+    search_price_break(points, candles, &break_price_comparator, config)
 }
 
 pub fn price_is_upperupper_band_bottom(
     data: &DataPoints,
     candles: &Vec<Candle>,
     _pattern_type: &PatternType,
+    config: &RsAlgoConfig,
 ) -> PriceBreak {
     let points = vec![data[3], data[5]];
     let break_price_comparator = |price: f64, price_break: f64| price > price_break;
-    search_price_break(points, candles, &break_price_comparator)
+    search_price_break(points, candles, &break_price_comparator, config)
 }
 
 pub fn price_is_lower_low_band_bottom(
     data: &DataPoints,
     candles: &Vec<Candle>,
     _pattern_type: &PatternType,
+    config: &RsAlgoConfig,
 ) -> PriceBreak {
     let points = vec![data[3], data[5]];
     let bottom_break = |price: f64, price_break: f64| price < price_break;
-    search_price_break(points, candles, &bottom_break)
+    search_price_break(points, candles, &bottom_break, config)
 }
 
 pub fn price_is_lower_low_band_top(
     data: &DataPoints,
     candles: &Vec<Candle>,
     _pattern_type: &PatternType,
+    config: &RsAlgoConfig,
 ) -> PriceBreak {
     let points = vec![data[2], data[4]];
     let break_price_comparator = |price: f64, price_break: f64| price < price_break;
-    search_price_break(points, candles, &break_price_comparator)
+    search_price_break(points, candles, &break_price_comparator, config)
 }
 
 pub fn price_is_upperlast_high_top(
     data: &DataPoints,
     candles: &Vec<Candle>,
     _pattern_type: &PatternType,
+    config: &RsAlgoConfig,
 ) -> PriceBreak {
     let points = vec![data[2]];
     let break_price_comparator = |price: f64, price_break: f64| price > price_break;
-    search_price_break(points, candles, &break_price_comparator)
+    search_price_break(points, candles, &break_price_comparator, config)
 }
 
 pub fn price_is_upperlast_high_bottom(
     data: &DataPoints,
     candles: &Vec<Candle>,
     _pattern_type: &PatternType,
+    config: &RsAlgoConfig,
 ) -> PriceBreak {
     let points = vec![data[3]];
     let break_price_comparator = |price: f64, price_break: f64| price > price_break;
-    search_price_break(points, candles, &break_price_comparator)
+    search_price_break(points, candles, &break_price_comparator, config)
 }
 
 pub fn price_is_lower_last_low_top(
     data: &DataPoints,
     candles: &Vec<Candle>,
     _pattern_type: &PatternType,
+    config: &RsAlgoConfig,
 ) -> PriceBreak {
     let points = vec![data[3]];
     let break_price_comparator = |price: f64, price_break: f64| price > price_break;
-    search_price_break(points, candles, &break_price_comparator)
+    search_price_break(points, candles, &break_price_comparator, config)
 }
 
 pub fn price_is_lower_last_low_bottom(
     data: &DataPoints,
     candles: &Vec<Candle>,
     _pattern_type: &PatternType,
+    config: &RsAlgoConfig,
 ) -> PriceBreak {
     let points = vec![data[2]];
     let break_price_comparator = |price: f64, price_break: f64| price > price_break;
-    search_price_break(points, candles, &break_price_comparator)
+    search_price_break(points, candles, &break_price_comparator, config)
 }
 
 pub fn price_is_upperpeak(
     peak: (usize, f64),
     candles: &Vec<Candle>,
     _pattern_type: &PatternType,
+    config: &RsAlgoConfig,
 ) -> PriceBreak {
     let mut band = vec![];
     band.push(peak);
     let break_price_comparator = |price: f64, price_break: f64| price > price_break;
-    search_price_break(band, candles, &break_price_comparator)
+    search_price_break(band, candles, &break_price_comparator, config)
 }
 
 pub fn price_is_lower_peak(
     peak: (usize, f64),
     candles: &Vec<Candle>,
     _pattern_type: &PatternType,
+    config: &RsAlgoConfig,
 ) -> PriceBreak {
     let mut band = vec![];
     band.push(peak);
     let break_price_comparator = |price: f64, price_break: f64| price < price_break;
-    search_price_break(band, candles, &break_price_comparator)
+    search_price_break(band, candles, &break_price_comparator, config)
 }
 
 pub fn calculate_price_change(data_points: &DataPoints) -> f64 {
@@ -134,11 +144,9 @@ pub fn search_price_break(
     points: Vec<(usize, f64)>,
     candles: &Vec<Candle>,
     comparator: &dyn Fn(f64, f64) -> bool,
+    config: &RsAlgoConfig,
 ) -> PriceBreak {
-    let logarithmic = env::var("LOGARITHMIC_SCANNER")
-        .unwrap()
-        .parse::<bool>()
-        .unwrap();
+    let logarithmic = config.logarithmic_scanner;
 
     let len = points.len();
     if len > 1 {
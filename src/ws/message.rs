@@ -3,13 +3,14 @@ use bson::Uuid;
 pub use tungstenite::Message;
 
 use crate::broker::{DOHLC, VEC_DOHLC};
+use crate::error::{Result, RsAlgoError, RsAlgoErrorKind};
 use crate::models::bot::BotData;
 use crate::models::market::MarketHours;
 use crate::models::order::Order;
 use crate::models::strategy::StrategyType;
 use crate::models::tick::InstrumentTick;
 use crate::models::time_frame::TimeFrameType;
-use crate::models::trade::{PositionResult, TradeIn, TradeOut, TradeResult};
+use crate::models::trade::{PositionResult, TradeAttempt, TradeIn, TradeOut, TradeResult};
 
 use serde::{Deserialize, Serialize};
 
@@ -45,13 +46,17 @@ pub enum ResponseType {
     GetInstrumentSwap,
     GetMarketHours,
     GetActivePositions,
+    GetPendingOrders,
     IsMarketOpen,
     TradeInFulfilled,
     TradeOutFulfilled,
+    TradeModified,
     InitSession,
     SubscribeStream,
     SubscribeTickPrices,
     SubscribeTrades,
+    OrderUpdated,
+    AccountUpdated,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -60,6 +65,14 @@ pub struct ResponseBody<T> {
     pub payload: Option<T>,
 }
 
+impl<T> ResponseBody<T> {
+    pub fn into_payload(self) -> Result<T> {
+        self.payload.ok_or(RsAlgoError {
+            err: RsAlgoErrorKind::NoResponse,
+        })
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Payload<'a> {
     pub symbol: &'a str,
@@ -131,6 +144,8 @@ pub struct TradeResponse<T> {
     pub symbol: String,
     pub accepted: bool,
     pub data: T,
+    #[serde(default)]
+    pub attempts: Vec<TradeAttempt>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
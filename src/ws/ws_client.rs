@@ -12,13 +12,18 @@ pub struct WebSocket {
 }
 
 impl WebSocket {
-    pub fn connect(url: &str) -> Self {
-        let (socket, _) = connect(url).expect("Can't connect");
+    pub fn connect(url: &str) -> Result<Self> {
+        let (socket, _) = connect(url).map_err(|err| {
+            log::error!("Can't connect to {} ({})", url, err);
+            RsAlgoError {
+                err: RsAlgoErrorKind::ConnectionError,
+            }
+        })?;
         log::info!("Connected to {} !", &url);
-        Self {
+        Ok(Self {
             url: url.to_string(),
             socket,
-        }
+        })
     }
 
     pub async fn send(&mut self, msg: &str) -> Result<()> {
@@ -33,23 +38,19 @@ impl WebSocket {
         }
     }
 
-    pub async fn re_connect(&mut self) {
+    pub async fn re_connect(&mut self) -> Result<()> {
         log::info!("Reconnecting to the server...");
-        let url = self.url.to_owned();
-        let (socket, _response) = connect(url).expect("Can't connect");
-        self.socket = socket;
+        let socket = Self::connect(&self.url)?;
+        self.socket = socket.socket;
+        Ok(())
     }
 
-    pub async fn ping(&mut self, msg: &[u8]) {
-        self.socket
-            .write_message(Message::Ping(msg.to_vec()))
-            .unwrap();
+    pub async fn ping(&mut self, msg: &[u8]) -> Result<()> {
+        self.write(Message::Ping(msg.to_vec()))
     }
 
-    pub async fn pong(&mut self, msg: &[u8]) {
-        self.socket
-            .write_message(Message::Pong(msg.to_vec()))
-            .unwrap();
+    pub async fn pong(&mut self, msg: &[u8]) -> Result<()> {
+        self.write(Message::Pong(msg.to_vec()))
     }
 
     pub async fn read(&mut self) -> std::result::Result<tungstenite::Message, tungstenite::Error> {
@@ -86,7 +87,20 @@ impl WebSocket {
     }
 
     pub async fn disconnect(&mut self) -> Result<()> {
-        self.socket.close(None).unwrap();
-        Ok(())
+        self.socket.close(None).map_err(|err| {
+            log::error!("Error closing socket: {}", err);
+            RsAlgoError {
+                err: RsAlgoErrorKind::ConnectionError,
+            }
+        })
+    }
+
+    fn write(&mut self, msg: Message) -> Result<()> {
+        self.socket.write_message(msg).map_err(|err| {
+            log::error!("Error sending message: {}", err);
+            RsAlgoError {
+                err: RsAlgoErrorKind::SendingAfter,
+            }
+        })
     }
 }
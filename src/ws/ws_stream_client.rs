@@ -1,43 +1,89 @@
-use crate::error::Result;
+use crate::error::{Result, RsAlgoError, RsAlgoErrorKind};
 
-use futures_util::{
-    stream::{SplitSink, SplitStream},
-    Future, SinkExt, StreamExt,
-};
+use futures_util::{stream::SplitStream, SinkExt, StreamExt};
 use tokio_tungstenite::{connect_async, MaybeTlsStream, WebSocketStream};
 
-use std::env;
 use tokio::net::TcpStream;
+use tokio::sync::mpsc;
 use tungstenite::Message;
 
+// Writes go through a channel so the heartbeat task can share the stream
 #[derive(Debug)]
 pub struct WebSocket {
-    pub write: SplitSink<WebSocketStream<MaybeTlsStream<TcpStream>>, Message>,
+    url: String,
+    sender: mpsc::UnboundedSender<Message>,
     pub read: SplitStream<WebSocketStream<MaybeTlsStream<TcpStream>>>,
 }
 
 impl WebSocket {
-    pub async fn connect(url: &str) -> Self {
-        let (socket, response) = connect_async(url).await.expect("Can't connect");
+    pub async fn connect(url: &str) -> Result<Self> {
+        let (socket, _) = connect_async(url).await.map_err(|err| {
+            log::error!("Can't connect to {} ({})", url, err);
+            RsAlgoError {
+                err: RsAlgoErrorKind::ConnectionError,
+            }
+        })?;
 
         log::info!("Connected to the stream server");
-        //log::info!("[STREAM] Response HTTP code: {}", response.status());
 
-        let (write, read) = socket.split();
-        Self { write, read }
+        let (mut write, read) = socket.split();
+        let (sender, mut outgoing) = mpsc::unbounded_channel::<Message>();
+
+        tokio::spawn(async move {
+            while let Some(msg) = outgoing.recv().await {
+                let is_close = matches!(msg, Message::Close(_));
+
+                if let Err(err) = write.send(msg).await {
+                    log::error!("Error sending stream message: {}", err);
+                    break;
+                }
+
+                if is_close {
+                    break;
+                }
+            }
+        });
+
+        Ok(Self {
+            url: url.to_string(),
+            sender,
+            read,
+        })
     }
 
-    pub async fn send(&mut self, msg: &str) -> Result<()> {
-        self.write.send(Message::text(msg)).await.unwrap();
+    pub async fn re_connect(&mut self) -> Result<()> {
+        log::info!("Reconnecting to the stream server...");
+        self.disconnect().await.ok();
+        *self = Self::connect(&self.url).await?;
         Ok(())
     }
 
-    pub async fn ping(&mut self, msg: &[u8]) {
-        self.write.send(Message::Ping(msg.to_vec())).await.unwrap();
+    pub fn sender(&self) -> mpsc::UnboundedSender<Message> {
+        self.sender.clone()
+    }
+
+    pub async fn send(&mut self, msg: &str) -> Result<()> {
+        self.write(Message::text(msg))
+    }
+
+    pub async fn read(&mut self) -> Option<std::result::Result<Message, tungstenite::Error>> {
+        self.read.next().await
+    }
+
+    pub async fn ping(&mut self, msg: &[u8]) -> Result<()> {
+        self.write(Message::Ping(msg.to_vec()))
     }
 
     pub async fn disconnect(&mut self) -> Result<()> {
-        self.write.close().await.unwrap();
-        Ok(())
+        self.write(Message::Close(None))
+    }
+
+    fn write(&self, msg: Message) -> Result<()> {
+        self.sender.send(msg).map_err(|_| {
+            log::error!("Stream writer is closed");
+            RsAlgoError {
+                err: RsAlgoErrorKind::SendingAfter,
+            }
+        })
     }
 }
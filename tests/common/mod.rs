@@ -0,0 +1,15 @@
+#![allow(dead_code)]
+
+use rs_algo_shared::config::RsAlgoConfig;
+
+pub const FIXTURE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/config.toml");
+
+pub fn config(values: &[(&str, &str)]) -> RsAlgoConfig {
+    let mut builder = RsAlgoConfig::new().file(FIXTURE).unwrap();
+
+    for (key, value) in values {
+        builder = builder.set(key, value);
+    }
+
+    builder.build().unwrap()
+}
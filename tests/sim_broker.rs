@@ -0,0 +1,274 @@
+#![cfg(feature = "broker")]
+
+mod common;
+
+use rs_algo_shared::broker::{BrokerEvent, SimBroker, TradingBroker, VEC_DOHLC};
+use rs_algo_shared::helpers::date::{to_dbtime, Duration, Local, TimeZone};
+use rs_algo_shared::models::order::{Order, OrderType};
+use rs_algo_shared::models::stop_loss::StopLossType;
+use rs_algo_shared::models::trade::{TradeIn, TradeOut, TradeStatus, TradeType};
+use rs_algo_shared::ws::message::{TradeData, TradeOptions};
+
+const SYMBOL: &str = "EURUSD";
+const TREND: &str = "trend";
+const HEDGE: &str = "hedge";
+
+fn candles() -> VEC_DOHLC {
+    let start = Local.with_ymd_and_hms(2024, 1, 8, 10, 0, 0).unwrap();
+    let prices = [
+        (1.1000, 1.1010, 1.0990, 1.1000),
+        (1.1000, 1.1030, 1.0995, 1.1020),
+        (1.1020, 1.1025, 1.0970, 1.0980),
+        (1.0980, 1.1005, 1.0975, 1.1000),
+    ];
+
+    prices
+        .iter()
+        .enumerate()
+        .map(|(index, (open, high, low, close))| {
+            let date = start + Duration::hours(index as i64);
+            (date, *open, *high, *low, *close, 100.)
+        })
+        .collect()
+}
+
+fn trade_in(trade_type: TradeType) -> TradeData<TradeIn> {
+    TradeData::new(
+        SYMBOL,
+        "",
+        TradeIn {
+            size: 1.,
+            trade_type,
+            ..TradeIn::default()
+        },
+        TradeOptions {
+            non_profitable_out: true,
+        },
+    )
+}
+
+fn with_strategy<T>(mut trade: TradeData<T>, strategy_name: &str) -> TradeData<T> {
+    trade.strategy_name = strategy_name.to_owned();
+    trade
+}
+
+fn stop_loss(price: f64) -> Order {
+    Order {
+        order_type: OrderType::StopLossLong(StopLossType::Price(price), 1.1),
+        target_price: price,
+        size: 1.,
+        ..Order::default()
+    }
+}
+
+fn trade_out(trade_in: &TradeIn, trade_type: TradeType) -> TradeOut {
+    TradeOut {
+        id: trade_in.id,
+        trade_type,
+        status: TradeStatus::Pending,
+        index_in: trade_in.index_in,
+        price_in: trade_in.price_in,
+        size: trade_in.size,
+        ask: trade_in.ask,
+        spread_in: trade_in.spread,
+        date_in: trade_in.date_in,
+        index_out: 0,
+        price_origin: 0.,
+        price_out: 0.,
+        bid: 0.,
+        spread_out: 0.,
+        date_out: to_dbtime(Local::now()),
+        profit: 0.,
+        profit_per: 0.,
+        run_up: 0.,
+        run_up_per: 0.,
+        draw_down: 0.,
+        draw_down_per: 0.,
+        commission: 0.,
+        spread_cost: 0.,
+        swap: 0.,
+    }
+}
+
+#[tokio::test]
+async fn replays_a_strategy() {
+    let config = common::config(&[("EXECUTION_MODE", "bot")]);
+    let mut broker = SimBroker::new()
+        .config(config)
+        .candles(candles())
+        .spread(0.)
+        .build()
+        .unwrap();
+
+    broker.subscribe(SYMBOL).await.unwrap();
+
+    let mut bar = 0;
+    let mut hedge: Option<TradeIn> = None;
+    let mut closed: Vec<TradeOut> = vec![];
+
+    while !broker.is_finished() {
+        let event = match broker.next_event(SYMBOL, TREND).await.unwrap() {
+            Some(event) => event,
+            None => continue,
+        };
+
+        match event {
+            BrokerEvent::Candle(_) => {
+                match bar {
+                    // Trend goes long with a 20 pips stop
+                    0 => {
+                        let res = broker
+                            .enter_trade(
+                                with_strategy(trade_in(TradeType::MarketInLong), TREND),
+                                Some(vec![stop_loss(1.0980)]),
+                            )
+                            .await
+                            .unwrap();
+                        assert_eq!(res.data.price_in, 1.1000);
+                    }
+                    // A second position for the same strategy is refused,
+                    // other strategies can still open theirs
+                    1 => {
+                        let res = broker
+                            .enter_trade(
+                                with_strategy(trade_in(TradeType::MarketInLong), TREND),
+                                None,
+                            )
+                            .await;
+                        assert!(res.is_err());
+
+                        let res = broker
+                            .enter_trade(
+                                with_strategy(trade_in(TradeType::MarketInShort), HEDGE),
+                                None,
+                            )
+                            .await
+                            .unwrap();
+                        assert_eq!(res.data.price_in, 1.1020);
+                        assert_eq!(broker.open_positions(SYMBOL, TREND).await.unwrap().len(), 1);
+                        assert_eq!(broker.open_positions(SYMBOL, HEDGE).await.unwrap().len(), 1);
+                        hedge = Some(res.data);
+                    }
+                    3 => {
+                        let hedge = hedge.take().unwrap();
+                        let res = broker
+                            .exit_trade(with_strategy(
+                                TradeData::new(
+                                    SYMBOL,
+                                    "",
+                                    trade_out(&hedge, TradeType::MarketOutShort),
+                                    TradeOptions {
+                                        non_profitable_out: true,
+                                    },
+                                ),
+                                HEDGE,
+                            ))
+                            .await
+                            .unwrap();
+                        assert!(res.accepted);
+                        closed.push(res.data);
+                    }
+                    _ => {}
+                }
+                bar += 1;
+            }
+            BrokerEvent::TradeOut(res) => closed.push(res.data),
+            _ => {}
+        }
+    }
+
+    assert_eq!(bar, 4);
+    assert_eq!(closed.len(), 2);
+
+    let stop = &closed[0];
+    assert_eq!(stop.trade_type, TradeType::StopLossLong);
+    assert_eq!(stop.price_out, 1.0980);
+    assert!((stop.profit + 200.).abs() < 1e-6);
+
+    let hedge = &closed[1];
+    assert_eq!(hedge.price_out, 1.1000);
+    assert!((hedge.profit - 200.).abs() < 1e-6);
+
+    assert!(broker
+        .open_positions(SYMBOL, TREND)
+        .await
+        .unwrap()
+        .is_empty());
+    assert!(broker
+        .open_positions(SYMBOL, HEDGE)
+        .await
+        .unwrap()
+        .is_empty());
+    assert!((broker.balance() - 10000.).abs() < 1e-6);
+
+    let history = broker.trade_history(SYMBOL, HEDGE, 0).await.unwrap();
+    assert_eq!(history.len(), 1);
+    assert_eq!(history[0].close_price, 1.1000);
+    assert!((history[0].profit - 200.).abs() < 1e-6);
+}
+
+#[tokio::test]
+async fn fires_stops_for_every_strategy() {
+    let config = common::config(&[("EXECUTION_MODE", "bot")]);
+    let mut broker = SimBroker::new()
+        .config(config)
+        .candles(candles())
+        .spread(0.)
+        .build()
+        .unwrap();
+
+    broker.subscribe(SYMBOL).await.unwrap();
+
+    let mut bars = [0, 0];
+    let mut closed: Vec<(&str, TradeOut)> = vec![];
+
+    while !broker.is_finished() {
+        for (index, strategy_name) in [TREND, HEDGE].into_iter().enumerate() {
+            let event = match broker.next_event(SYMBOL, strategy_name).await {
+                Ok(Some(event)) => event,
+                _ => continue,
+            };
+
+            match event {
+                BrokerEvent::Candle(_) => {
+                    // Only the second strategy trades, with a 25 pips stop
+                    if strategy_name == HEDGE && bars[index] == 0 {
+                        let stop = Order {
+                            order_type: OrderType::StopLossShort(StopLossType::Price(1.1025), 1.1),
+                            target_price: 1.1025,
+                            size: 1.,
+                            ..Order::default()
+                        };
+                        broker
+                            .enter_trade(
+                                with_strategy(trade_in(TradeType::MarketInShort), HEDGE),
+                                Some(vec![stop]),
+                            )
+                            .await
+                            .unwrap();
+                    }
+                    bars[index] += 1;
+                }
+                BrokerEvent::TradeOut(res) => closed.push((strategy_name, res.data)),
+                _ => {}
+            }
+        }
+    }
+
+    // Both strategies see every bar, whichever of them steps the feed
+    assert_eq!(bars, [4, 4]);
+    assert_eq!(closed.len(), 1);
+
+    let (strategy_name, stop) = &closed[0];
+    assert_eq!(*strategy_name, HEDGE);
+    assert_eq!(stop.trade_type, TradeType::StopLossShort);
+    assert_eq!(stop.price_out, 1.1025);
+    assert!((stop.profit + 250.).abs() < 1e-6);
+
+    assert!(broker
+        .open_positions(SYMBOL, HEDGE)
+        .await
+        .unwrap()
+        .is_empty());
+    assert!((broker.balance() - 9750.).abs() < 1e-6);
+}
@@ -0,0 +1,615 @@
+#![cfg(feature = "mock")]
+
+mod common;
+
+use rs_algo_shared::broker::mock::{MockResponse, MockXtbServer};
+use rs_algo_shared::broker::rate_limiter::RateLimiter;
+use rs_algo_shared::broker::retry;
+use rs_algo_shared::broker::xtb_error;
+use rs_algo_shared::broker::xtb_socket::XtbSocket;
+use rs_algo_shared::broker::xtb_stream::{BrokerStream, Xtb};
+use rs_algo_shared::broker::{BrokerEvent, StreamTrade, TradingBroker};
+use rs_algo_shared::error::RsAlgoErrorKind;
+use rs_algo_shared::helpers::date::{to_dbtime, Local};
+use rs_algo_shared::models::market::MarketHour;
+use rs_algo_shared::models::order::OrderType;
+use rs_algo_shared::models::stop_loss::StopLossType;
+use rs_algo_shared::models::symbol_spec::symbol_spec;
+use rs_algo_shared::models::trade::{
+    PositionResult, TradeIn, TradeOut, TradeResult, TradeStatus, TradeType,
+};
+use rs_algo_shared::ws::message::{TradeData, TradeOptions};
+
+use serde_json::{json, Value};
+use std::time::{Duration, Instant};
+
+async fn connect(server: &MockXtbServer) -> Xtb {
+    connect_with(server, &[]).await
+}
+
+async fn connect_with(server: &MockXtbServer, values: &[(&str, &str)]) -> Xtb {
+    let url = server.url();
+    let stream_url = server.stream_url();
+    let mut values = values.to_vec();
+    values.push(("BROKER_URL", url.as_str()));
+    values.push(("BROKER_STREAM_URL", stream_url.as_str()));
+    let config = common::config(&values);
+
+    let mut xtb = Xtb::new(&config).await.unwrap();
+    xtb.login("user", "password").await.unwrap();
+    xtb
+}
+
+fn position(symbol: &str, strategy_name: &str, position: u64) -> Value {
+    let comment = json!({
+        "strategy_name": strategy_name,
+        "index_in": 10,
+        "sell_order_price": 1.1100,
+        "stop_loss_order_price": 1.0950,
+        "trade_type": "MarketInLong",
+        "spread": 0.0001,
+        "bid": 1.1000
+    });
+
+    json!({
+        "symbol": symbol,
+        "position": position,
+        "closed": false,
+        "volume": 0.5,
+        "open_price": 1.1001,
+        "sl": 1.0950,
+        "open_time": 1700000000000_i64,
+        "customComment": comment.to_string()
+    })
+}
+
+fn stream_trade(position_id: u64, volume: f64, closed: bool, trans_type: i64) -> StreamTrade {
+    let mut trade = position("EURUSD", "trend", position_id);
+    trade["volume"] = json!(volume);
+    trade["closed"] = json!(closed);
+    trade["type"] = json!(trans_type);
+    serde_json::from_value(trade).unwrap()
+}
+
+fn trade_data<T>(data: T) -> TradeData<T>
+where
+    for<'de> T: serde::Serialize + serde::Deserialize<'de>,
+{
+    TradeData::new(
+        "EURUSD",
+        "trend",
+        data,
+        TradeOptions {
+            non_profitable_out: true,
+        },
+    )
+}
+
+fn trade_out(trade_in: &TradeIn) -> TradeOut {
+    TradeOut {
+        id: trade_in.id,
+        trade_type: TradeType::MarketOutLong,
+        status: TradeStatus::Pending,
+        index_in: trade_in.index_in,
+        price_in: trade_in.price_in,
+        size: trade_in.size,
+        ask: trade_in.ask,
+        spread_in: trade_in.spread,
+        date_in: trade_in.date_in,
+        index_out: 0,
+        price_origin: 0.,
+        price_out: 0.,
+        bid: 0.,
+        spread_out: 0.,
+        date_out: to_dbtime(Local::now()),
+        profit: 0.,
+        profit_per: 0.,
+        run_up: 0.,
+        run_up_per: 0.,
+        draw_down: 0.,
+        draw_down_per: 0.,
+        commission: 0.,
+        spread_cost: 0.,
+        swap: 0.,
+    }
+}
+
+#[tokio::test]
+async fn logs_in_and_parses_candles() {
+    let server = MockXtbServer::new()
+        .respond(
+            "getChartLastRequest",
+            MockResponse::reply(json!({
+                "digits": 5,
+                "rateInfos": [
+                    { "ctm": 1700000000000_i64, "open": 110000.0, "high": 50.0, "low": -30.0, "close": 20.0, "vol": 2.0 },
+                    { "ctm": 1700003600000_i64, "open": 110020.0, "high": 10.0, "low": -10.0, "close": -5.0, "vol": 1.0 }
+                ]
+            })),
+        )
+        .start()
+        .unwrap();
+
+    let mut xtb = connect(&server).await;
+    assert_eq!(xtb.get_session_id(), "mock-stream-session");
+
+    let res = xtb.get_instrument_data("EURUSD", 60, 0).await.unwrap();
+    let candles = res.payload.unwrap().data;
+
+    assert_eq!(candles.len(), 2);
+    let (_, open, high, low, close, volume) = candles[0];
+    assert!((open - 1.1).abs() < 1e-9);
+    assert!((high - 1.1005).abs() < 1e-9);
+    assert!((low - 1.0997).abs() < 1e-9);
+    assert!((close - 1.1002).abs() < 1e-9);
+    assert_eq!(volume, 2000.);
+
+    let requests = server.requests();
+    assert_eq!(requests[0]["command"], "login");
+    assert_eq!(requests[1]["arguments"]["info"]["symbol"], "EURUSD");
+}
+
+#[tokio::test]
+async fn handles_scripted_responses() {
+    let server = MockXtbServer::new().start().unwrap();
+    let mut xtb = connect(&server).await;
+
+    let login = json!({ "status": true, "streamSessionId": "other-session" });
+    let res = xtb.handle_response::<()>(&login.to_string()).await.unwrap();
+    assert!(res.payload.unwrap().data.is_empty());
+    assert_eq!(xtb.get_session_id(), "other-session");
+
+    let unknown = json!({ "status": true, "returnData": {} });
+    let res = xtb
+        .handle_response::<()>(&unknown.to_string())
+        .await
+        .unwrap();
+    assert!(res.payload.is_none());
+
+    let error = json!({ "status": false, "errorCode": "BE006", "errorDescr": "Market closed" });
+    let err = xtb
+        .handle_response::<()>(&error.to_string())
+        .await
+        .unwrap_err();
+    assert!(matches!(err.err, RsAlgoErrorKind::MarketClosed(..)));
+
+    let disabled =
+        json!({ "status": false, "errorCode": "BE018", "errorDescr": "Trading disabled" });
+    let err = xtb
+        .handle_response::<()>(&disabled.to_string())
+        .await
+        .unwrap_err();
+    assert!(matches!(err.err, RsAlgoErrorKind::TradingDisabled(..)));
+    assert!(!retry::is_retryable_error(&err.err));
+
+    let err = xtb.handle_response::<()>("not json").await.unwrap_err();
+    assert!(matches!(err.err, RsAlgoErrorKind::ParseError));
+
+    let candles = json!({ "status": true, "returnData": { "digits": 5 } });
+    let err = xtb
+        .handle_response::<()>(&candles.to_string())
+        .await
+        .unwrap_err();
+    assert!(matches!(err.err, RsAlgoErrorKind::ParseError));
+}
+
+#[tokio::test]
+async fn maps_broker_errors() {
+    let server = MockXtbServer::new()
+        .respond(
+            "getSymbol",
+            MockResponse::error("BE094", "Symbol not found"),
+        )
+        .start()
+        .unwrap();
+    let mut xtb = connect(&server).await;
+
+    let err = xtb.get_instrument_tick("NOPE").await.unwrap_err();
+    assert!(matches!(err.err, RsAlgoErrorKind::InvalidSymbol(..)));
+    assert!(!retry::is_retryable_error(&err.err));
+
+    let invalid_stop = xtb_error::error_kind("BE002", "Invalid S/L or T/P");
+    assert!(matches!(invalid_stop, RsAlgoErrorKind::InvalidStop(..)));
+    assert!(!retry::is_retryable_error(&invalid_stop));
+
+    let closed = xtb_error::error_kind("BE101", "Cannot close already closed order");
+    assert!(matches!(closed, RsAlgoErrorKind::OrderNotFound(..)));
+
+    // Unmapped codes are retried on transient descriptions only
+    assert!(retry::is_retryable_error(&xtb_error::error_kind(
+        "BE999", "Requote"
+    )));
+    assert!(!retry::is_retryable_error(&xtb_error::error_kind(
+        "BE999", "Unknown"
+    )));
+
+    // Unscripted commands fall back to the default replies
+    let tick = xtb.get_instrument_tick("EURUSD").await.unwrap();
+    assert_eq!(tick.payload.unwrap().bid(), 1.1);
+}
+
+#[tokio::test]
+async fn waits_for_delayed_replies() {
+    let delay = Duration::from_millis(200);
+    let server = MockXtbServer::new()
+        .respond(
+            "getSymbol",
+            MockResponse::delayed(delay, MockResponse::error("BE006", "Market closed")),
+        )
+        .start()
+        .unwrap();
+    let mut xtb = connect(&server).await;
+
+    let started = Instant::now();
+    let err = xtb.get_instrument_tick("EURUSD").await.unwrap_err();
+
+    assert!(started.elapsed() >= delay);
+    assert!(matches!(err.err, RsAlgoErrorKind::MarketClosed(..)));
+    assert!(retry::is_retryable_error(&err.err));
+}
+
+#[tokio::test]
+async fn throttles_commands_past_the_burst() {
+    let interval = Duration::from_millis(50);
+    let server = MockXtbServer::new().start().unwrap();
+    let socket = XtbSocket::connect(&server.url(), RateLimiter::new(interval, 2))
+        .await
+        .unwrap();
+
+    let started = Instant::now();
+    let requests: Vec<_> = (0..6)
+        .map(|_| {
+            let socket = socket.clone();
+            tokio::spawn(async move {
+                socket.request(&json!({ "command": "ping" })).await.unwrap();
+                started.elapsed()
+            })
+        })
+        .collect();
+
+    let mut replied = vec![];
+    for request in requests {
+        replied.push(request.await.unwrap());
+    }
+    replied.sort();
+
+    // Two commands go out in the burst, the rest one interval apart
+    assert!(replied[1] < interval);
+    assert!(replied[5] >= interval * 4);
+    for pair in replied[2..].windows(2) {
+        assert!(pair[1] - pair[0] >= interval / 2);
+    }
+
+    let metrics = socket.metrics();
+    assert_eq!(server.requests().len(), 6);
+    assert_eq!(metrics.sent, 6);
+    assert_eq!(metrics.throttled, 4);
+    assert_eq!(metrics.queue_depth, 0);
+    assert!(metrics.max_queue_depth >= 2);
+    assert!(metrics.max_wait >= interval * 3);
+    assert!(metrics.avg_wait() >= interval);
+}
+
+#[tokio::test]
+async fn fails_when_the_server_closes() {
+    let server = MockXtbServer::new()
+        .respond("getSymbol", MockResponse::Close)
+        .start()
+        .unwrap();
+    let mut xtb = connect(&server).await;
+
+    assert!(xtb.get_instrument_tick("EURUSD").await.is_err());
+}
+
+#[tokio::test]
+async fn parses_executed_order_ids() {
+    let server = MockXtbServer::new().start().unwrap();
+    let mut xtb = connect(&server).await;
+
+    let executed = json!({ "status": true, "returnData": { "order": 4242 } });
+    assert_eq!(
+        xtb.get_order_id_executed(&executed.to_string()).unwrap(),
+        (true, 4242)
+    );
+
+    let no_order = json!({ "status": true, "returnData": {} });
+    assert_eq!(
+        xtb.get_order_id_executed(&no_order.to_string()).unwrap(),
+        (false, 0)
+    );
+
+    let rejected = json!({ "status": false, "errorCode": "BE009", "errorDescr": "No money" });
+    let err = xtb
+        .get_order_id_executed(&rejected.to_string())
+        .unwrap_err();
+    assert!(matches!(err.err, RsAlgoErrorKind::InsufficientMargin(..)));
+}
+
+#[tokio::test]
+async fn parses_active_positions() {
+    let trades = json!([
+        position("GBPUSD", "trend", 1),
+        position("EURUSD", "other", 2),
+        position("EURUSD", "trend", 3)
+    ]);
+    let server = MockXtbServer::new()
+        .respond("getTrades", MockResponse::reply(trades.clone()))
+        .start()
+        .unwrap();
+    let mut xtb = connect(&server).await;
+
+    let res = xtb.get_active_positions("EURUSD", "trend").await.unwrap();
+
+    match res.payload.unwrap() {
+        PositionResult::MarketIn(TradeResult::TradeIn(trade_in), Some(orders)) => {
+            assert_eq!(trade_in.id, 3);
+            assert_eq!(trade_in.size, 0.5);
+            assert_eq!(trade_in.index_in, 10);
+            assert_eq!(trade_in.trade_type, TradeType::MarketInLong);
+
+            assert_eq!(orders.len(), 2);
+            assert_eq!(
+                orders[0].order_type,
+                OrderType::StopLossLong(StopLossType::Price(1.095), 1.1001)
+            );
+            assert_eq!(orders[1].order_type, OrderType::SellOrderLong(0.5, 1.11));
+        }
+        result => panic!("Unexpected position {:?}", result),
+    }
+
+    let reply = json!({ "status": true, "returnData": trades });
+    let result = xtb
+        .parse_active_positions_data(reply.to_string(), "EURUSD", "missing")
+        .unwrap();
+    assert!(matches!(result, PositionResult::None));
+}
+
+#[tokio::test]
+async fn rejects_incomplete_replies() {
+    let server = MockXtbServer::new()
+        .respond(
+            "getSymbol",
+            MockResponse::reply(json!({ "symbol": "EURUSD", "ask": 1.1001 })),
+        )
+        .start()
+        .unwrap();
+    let mut xtb = connect(&server).await;
+
+    let err = xtb.get_instrument_tick("EURUSD").await.unwrap_err();
+    assert!(matches!(err.err, RsAlgoErrorKind::ParseError));
+
+    let status = json!({ "status": true, "returnData": { "order": 4242 } });
+    let err = xtb.parse_trade_status_data(status.to_string()).unwrap_err();
+    assert!(matches!(err.err, RsAlgoErrorKind::ParseError));
+
+    // Manually opened positions have no strategy comments and are skipped
+    let manual = json!({ "status": true, "returnData": [
+        { "symbol": "EURUSD", "position": 7, "customComment": "" },
+        position("EURUSD", "trend", 3)
+    ]});
+    let result = xtb
+        .parse_active_positions_data(manual.to_string(), "EURUSD", "trend")
+        .unwrap();
+    assert!(matches!(result, PositionResult::MarketIn(..)));
+}
+
+#[tokio::test]
+async fn tracks_stream_positions() {
+    let server = MockXtbServer::new()
+        .respond(
+            "getTrades",
+            MockResponse::reply(json!([position("EURUSD", "trend", 3)])),
+        )
+        .start()
+        .unwrap();
+    let mut xtb = connect(&server).await;
+
+    let positions = xtb.open_positions("EURUSD", "trend").await.unwrap();
+    assert_eq!(positions.len(), 1);
+
+    // Already known from the broker, the stream echo is skipped
+    let event = xtb.track_stream_trade(stream_trade(3, 0.5, false, 0), "EURUSD", "trend");
+    assert!(event.is_none());
+
+    // Partial close, then the remaining size of the original trade
+    match xtb.track_stream_trade(stream_trade(3, 0.2, true, 2), "EURUSD", "trend") {
+        Some(BrokerEvent::TradeOut(res)) => assert_eq!(res.data.size, 0.2),
+        event => panic!("Unexpected event {:?}", event),
+    }
+    match xtb.track_stream_trade(stream_trade(3, 0.3, false, 3), "EURUSD", "trend") {
+        Some(BrokerEvent::TradeIn(res)) => {
+            assert_eq!(res.data.id, 3);
+            assert_eq!(res.data.size, 0.3);
+        }
+        event => panic!("Unexpected event {:?}", event),
+    }
+    match xtb.track_stream_trade(stream_trade(3, 0.3, true, 2), "EURUSD", "trend") {
+        Some(BrokerEvent::TradeOut(res)) => assert_eq!(res.data.size, 0.3),
+        event => panic!("Unexpected event {:?}", event),
+    }
+
+    // Positions opened outside the bot flow are still reported
+    let event = xtb.track_stream_trade(stream_trade(4, 0.5, false, 0), "EURUSD", "trend");
+    assert!(matches!(event, Some(BrokerEvent::TradeIn(..))));
+    let event = xtb.track_stream_trade(stream_trade(4, 0.5, false, 0), "EURUSD", "trend");
+    assert!(event.is_none());
+}
+
+#[tokio::test]
+async fn opens_and_closes_trades() {
+    let mut opened = position("EURUSD", "trend", 7);
+    opened["close_price"] = json!(1.1001);
+
+    let mut closed = position("EURUSD", "trend", 7);
+    closed["closed"] = json!(true);
+    closed["close_price"] = json!(1.1051);
+    closed["profit"] = json!(25.0);
+    closed["storage"] = json!(-1.0);
+    closed["commission"] = json!(-2.0);
+
+    // The first attempt hits a closed market and is retried
+    let server = MockXtbServer::new()
+        .respond(
+            "tradeTransaction",
+            MockResponse::error("BE006", "Market closed"),
+        )
+        .respond("getTrades", MockResponse::reply(json!([opened])))
+        .respond("getTradesHistory", MockResponse::reply(json!([closed])))
+        .start()
+        .unwrap();
+    let mut xtb = connect_with(&server, &[("ENV", "production")]).await;
+
+    let trade_in = TradeIn {
+        size: 0.5,
+        trade_type: TradeType::MarketInLong,
+        ..TradeIn::default()
+    };
+    let res = xtb.enter_trade(trade_data(trade_in), None).await.unwrap();
+
+    assert!(res.accepted);
+    assert_eq!(res.data.id, 7);
+    assert_eq!(res.data.price_in, 1.1001);
+    assert_eq!(res.data.status, TradeStatus::Fulfilled);
+    assert_eq!(res.attempts.len(), 2);
+    assert!(res.attempts[0].retryable);
+    assert_eq!(res.attempts[0].error_code.as_deref(), Some("BE006"));
+    assert_eq!(res.attempts[1].status, TradeStatus::Fulfilled);
+
+    // Entries without a stop loss order are sent without one
+    let transactions: Vec<Value> = server
+        .requests()
+        .into_iter()
+        .filter(|request| request["command"] == "tradeTransaction")
+        .collect();
+    assert_eq!(transactions.len(), 2);
+    assert_eq!(transactions[1]["arguments"]["tradeTransInfo"]["sl"], 0.0);
+    assert_eq!(
+        transactions[1]["arguments"]["tradeTransInfo"]["volume"],
+        0.5
+    );
+
+    let res = xtb
+        .exit_trade(trade_data(trade_out(&res.data)))
+        .await
+        .unwrap();
+
+    assert!(res.accepted);
+    assert_eq!(res.attempts.len(), 1);
+    assert_eq!(res.data.price_out, 1.1051);
+    assert!((res.data.profit - 22.).abs() < 1e-9);
+    assert_eq!(res.data.commission, 2.);
+    assert_eq!(res.data.swap, -1.);
+
+    let close = &server
+        .requests()
+        .into_iter()
+        .filter(|request| request["command"] == "tradeTransaction")
+        .last()
+        .unwrap()["arguments"]["tradeTransInfo"];
+    assert_eq!(close["order"], 7);
+    assert_eq!(close["type"], 2);
+}
+
+#[tokio::test]
+async fn rejects_non_retryable_trades() {
+    let server = MockXtbServer::new()
+        .respond("tradeTransaction", MockResponse::error("BE009", "No money"))
+        .start()
+        .unwrap();
+    let mut xtb = connect_with(&server, &[("ENV", "production")]).await;
+
+    let trade_in = TradeIn {
+        size: 0.5,
+        trade_type: TradeType::MarketInShort,
+        ..TradeIn::default()
+    };
+    let res = xtb.enter_trade(trade_data(trade_in), None).await.unwrap();
+
+    assert!(!res.accepted);
+    assert_eq!(res.data.status, TradeStatus::Rejected);
+    assert_eq!(res.attempts.len(), 1);
+    assert!(!res.attempts[0].retryable);
+}
+
+#[tokio::test]
+async fn reads_trading_hours() {
+    let server = MockXtbServer::new().start().unwrap();
+    let mut xtb = connect(&server).await;
+
+    let hours = xtb.market_hours("EURUSD").await.unwrap();
+    let monday = MarketHour {
+        day: 1,
+        from: 0,
+        to: 24,
+    };
+
+    assert_eq!(hours.data().len(), 5);
+    assert_eq!(hours.data()[0], monday);
+
+    // The registered spec keeps the broker hours
+    let spec = symbol_spec("EURUSD").unwrap();
+    assert_eq!(spec.trading_hours.len(), 5);
+    assert_eq!(spec.trading_hours[0], monday);
+}
+
+#[tokio::test]
+async fn streams_broker_events() {
+    let server = MockXtbServer::new().start().unwrap();
+    let mut xtb = connect(&server).await;
+    xtb.subscribe("EURUSD").await.unwrap();
+
+    // Stream writes are queued, wait for the server to see the subscriptions
+    let started = Instant::now();
+    let subscriptions = loop {
+        let subscriptions: Vec<Value> = server
+            .stream_requests()
+            .into_iter()
+            .map(|request| request["command"].clone())
+            .collect();
+
+        if subscriptions.contains(&json!("getBalance")) || started.elapsed().as_secs() > 1 {
+            break subscriptions;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    };
+    for command in ["getCandles", "getTickPrices", "getTrades", "getBalance"] {
+        assert!(subscriptions.contains(&json!(command)));
+    }
+
+    let mut trade = position("EURUSD", "trend", 9);
+    trade["cmd"] = json!(0);
+    trade["type"] = json!(0);
+
+    server.push_stream(json!({ "command": "candle", "data": {
+        "symbol": "EURUSD", "ctm": 1700000000000_i64,
+        "open": 1.1, "high": 1.1005, "low": 1.0995, "close": 1.1002, "vol": 2.0
+    }}));
+    server.push_stream(json!({ "command": "tickPrices", "data": {
+        "symbol": "EURUSD", "ask": 1.1003, "bid": 1.1002, "high": 1.1005,
+        "low": 1.0995, "spreadRaw": 0.0001, "timestamp": 1700000000000_i64
+    }}));
+    server.push_stream(json!({ "command": "trade", "data": trade }));
+
+    match xtb.next_event("EURUSD", "trend").await.unwrap() {
+        Some(BrokerEvent::Candle((_, open, _, _, close, volume))) => {
+            assert_eq!(open, 1.1);
+            assert_eq!(close, 1.1002);
+            assert_eq!(volume, 2000.);
+        }
+        event => panic!("Unexpected event {:?}", event),
+    }
+    match xtb.next_event("EURUSD", "trend").await.unwrap() {
+        Some(BrokerEvent::Tick(tick)) => {
+            assert_eq!(tick.ask(), 1.1003);
+            assert_eq!(tick.bid(), 1.1002);
+        }
+        event => panic!("Unexpected event {:?}", event),
+    }
+    match xtb.next_event("EURUSD", "trend").await.unwrap() {
+        Some(BrokerEvent::TradeIn(res)) => {
+            assert_eq!(res.data.id, 9);
+            assert_eq!(res.data.size, 0.5);
+        }
+        event => panic!("Unexpected event {:?}", event),
+    }
+}